@@ -32,6 +32,15 @@ impl <R: BufferedReader<C>, C> Bzip<R, C> {
                 BzDecoder::new(reader), None, cookie),
         }
     }
+
+    /// Returns the underlying reader.
+    ///
+    /// Unlike `BufferedReader::into_inner`, this preserves the type
+    /// of the underlying reader.  Any data that has been decompressed
+    /// but not yet consumed is discarded.
+    pub fn into_parts(self) -> R {
+        self.reader.reader.into_inner()
+    }
 }
 
 impl<R: BufferedReader<C>, C> io::Read for Bzip<R, C> {
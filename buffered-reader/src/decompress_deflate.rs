@@ -32,6 +32,15 @@ impl <R: BufferedReader<C>, C> Deflate<R, C> {
                 DeflateDecoder::new(reader), None, cookie),
         }
     }
+
+    /// Returns the underlying reader.
+    ///
+    /// Unlike `BufferedReader::into_inner`, this preserves the type
+    /// of the underlying reader.  Any data that has been decompressed
+    /// but not yet consumed is discarded.
+    pub fn into_parts(self) -> R {
+        self.reader.reader.into_inner()
+    }
 }
 
 impl<R: BufferedReader<C>, C> io::Read for Deflate<R, C> {
@@ -154,6 +163,15 @@ impl <R: BufferedReader<C>, C> Zlib<R, C> {
                 ZlibDecoder::new(reader), None, cookie),
         }
     }
+
+    /// Returns the underlying reader.
+    ///
+    /// Unlike `BufferedReader::into_inner`, this preserves the type
+    /// of the underlying reader.  Any data that has been decompressed
+    /// but not yet consumed is discarded.
+    pub fn into_parts(self) -> R {
+        self.reader.reader.into_inner()
+    }
 }
 
 impl<R: BufferedReader<C>, C> io::Read for Zlib<R, C> {
@@ -254,6 +272,28 @@ impl<R: BufferedReader<C>, C> BufferedReader<C>
 mod test {
     use super::*;
 
+    #[test]
+    fn into_parts() {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        use std::io::prelude::*;
+
+        let mut input = Vec::new();
+        {
+            let mut encoder =
+                ZlibEncoder::new(&mut input, Compression::default());
+            encoder.write_all(b"Hello, world.").unwrap();
+            encoder.try_finish().unwrap();
+        }
+
+        let mut reader = Zlib::new(Memory::new(&input[..]));
+        assert_eq!(reader.steal_eof().unwrap(), b"Hello, world.");
+
+        // The concrete type of the underlying reader is preserved.
+        let inner: Memory<()> = reader.into_parts();
+        assert_eq!(inner.total_out(), input.len());
+    }
+
     // Test that buffer() returns the same data as data().
     #[test]
     fn buffer_test() {
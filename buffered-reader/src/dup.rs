@@ -64,6 +64,13 @@ impl<'a, C> Dup<'a, C> {
     }
 
     /// Resets the cursor to the beginning of the stream.
+    ///
+    /// This makes it possible to peek at the start of a stream, and
+    /// then hand the whole stream, including the data that has been
+    /// peeked at, to another parser.  Note: as nothing is consumed
+    /// from the underlying reader, it has to buffer everything that
+    /// has been read so far.  Hence, `Dup` should not be used to
+    /// read large amounts of data.
     pub fn rewind(&mut self) {
         self.cursor = 0;
     }
@@ -76,7 +83,7 @@ impl<'a, C> io::Read for Dup<'a, C> {
         let data = &data[self.cursor..];
 
         let amount = cmp::min(buf.len(), data.len());
-        buf.copy_from_slice(&data[..amount]);
+        buf[..amount].copy_from_slice(&data[..amount]);
 
         self.cursor += amount;
 
@@ -175,6 +182,37 @@ mod test {
         buffered_reader_test_data_check(&mut reader);
     }
 
+    #[test]
+    fn rewind() {
+        use std::io::Read;
+
+        let data : &[u8] = include_bytes!("buffered-reader-test.txt");
+        let mut reader = Dup::new(Box::new(Memory::new(data)));
+
+        // Peek at the start, and dispatch on it.
+        assert_eq!(reader.data_consume_hard(4).unwrap()[..4], data[..4]);
+        assert_eq!(reader.total_out(), 4);
+        reader.rewind();
+        assert_eq!(reader.total_out(), 0);
+
+        // Reading returns everything, including the peeked data.
+        // Use a buffer larger than the data to exercise short reads.
+        let mut output = vec![0; data.len() + 1];
+        let mut read = 0;
+        loop {
+            let n = reader.read(&mut output[read..]).unwrap();
+            if n == 0 {
+                break;
+            }
+            read += n;
+        }
+        assert_eq!(&output[..read], data);
+
+        // And we can rewind again.
+        reader.rewind();
+        assert_eq!(reader.steal_eof().unwrap(), data);
+    }
+
     // Test that buffer() returns the same data as data().
     #[test]
     fn buffer_test() {
@@ -0,0 +1,212 @@
+use std::io;
+use std::fmt;
+
+use super::*;
+
+/// A hash function that can be fed incrementally.
+///
+/// This is implemented by the digests used with `Hash`.
+pub trait Digest {
+    /// Feeds `data` into the hash function.
+    fn update(&mut self, data: &[u8]);
+}
+
+/// Hashes the data read from the underlying `BufferedReader`.
+///
+/// Only data that is consumed is hashed.  Data that is merely peeked
+/// at, e.g. using `data()`, does not influence the digest until it
+/// is consumed.
+pub struct Hash<'a, D: Digest, C> {
+    reader: Box<'a + BufferedReader<C>>,
+
+    // The digest of the data consumed so far.
+    digest: D,
+
+    // The number of bytes that have been hashed.
+    hashed: u64,
+
+    // The user settable cookie.
+    cookie: C,
+}
+
+impl<'a, D: Digest, C> fmt::Display for Hash<'a, D, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Hash ({} bytes hashed)", self.hashed)
+    }
+}
+
+impl<'a, D: Digest, C> fmt::Debug for Hash<'a, D, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hash")
+            .field("hashed", &self.hashed)
+            .field("reader", &self.reader)
+            .finish()
+    }
+}
+
+impl<'a, D: Digest> Hash<'a, D, ()> {
+    /// Instantiates a new hashing buffered reader.
+    ///
+    /// `reader` is the source to wrap.  The consumed data is fed
+    /// into `digest`.
+    pub fn new(reader: Box<'a + BufferedReader<()>>, digest: D) -> Self {
+        Self::with_cookie(reader, digest, ())
+    }
+}
+
+impl<'a, D: Digest, C> Hash<'a, D, C> {
+    /// Like `new()`, but sets a cookie.
+    ///
+    /// The cookie can be retrieved using the `cookie_ref` and
+    /// `cookie_mut` methods, and set using the `cookie_set` method.
+    pub fn with_cookie(reader: Box<'a + BufferedReader<C>>, digest: D,
+                       cookie: C)
+                       -> Self {
+        Hash {
+            reader: reader,
+            digest: digest,
+            hashed: 0,
+            cookie: cookie,
+        }
+    }
+
+    /// Returns a reference to the digest.
+    pub fn digest(&self) -> &D {
+        &self.digest
+    }
+
+    /// Returns the number of bytes that have been hashed.
+    pub fn hashed(&self) -> u64 {
+        self.hashed
+    }
+
+    /// Returns the digest, discarding the reader.
+    pub fn into_digest(self) -> D {
+        self.digest
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.digest.update(data);
+        self.hashed += data.len() as u64;
+    }
+}
+
+impl<'a, D: Digest, C> io::Read for Hash<'a, D, C> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        let amount = self.reader.read(buf)?;
+        self.update(&buf[..amount]);
+        Ok(amount)
+    }
+}
+
+impl<'a, D: Digest, C> BufferedReader<C> for Hash<'a, D, C> {
+    fn buffer(&self) -> &[u8] {
+        self.reader.buffer()
+    }
+
+    fn data(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        self.reader.data(amount)
+    }
+
+    fn data_hard(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        self.reader.data_hard(amount)
+    }
+
+    fn consume(&mut self, amount: usize) -> &[u8] {
+        let data = self.reader.consume(amount);
+        assert!(data.len() >= amount);
+        self.digest.update(&data[..amount]);
+        self.hashed += amount as u64;
+        data
+    }
+
+    fn data_consume(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        let data = self.reader.data_consume(amount)?;
+        let amount = cmp::min(amount, data.len());
+        self.digest.update(&data[..amount]);
+        self.hashed += amount as u64;
+        Ok(data)
+    }
+
+    fn data_consume_hard(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        let data = self.reader.data_consume_hard(amount)?;
+        self.digest.update(&data[..amount]);
+        self.hashed += amount as u64;
+        Ok(data)
+    }
+
+    fn consummated(&mut self) -> bool {
+        self.reader.consummated()
+    }
+
+    fn get_mut(&mut self) -> Option<&mut BufferedReader<C>> {
+        Some(&mut self.reader)
+    }
+
+    fn get_ref(&self) -> Option<&BufferedReader<C>> {
+        Some(&self.reader)
+    }
+
+    fn into_inner<'b>(self: Box<Self>) -> Option<Box<BufferedReader<C> + 'b>>
+        where Self: 'b {
+        Some(self.reader)
+    }
+
+    fn cookie_set(&mut self, cookie: C) -> C {
+        use std::mem;
+
+        mem::replace(&mut self.cookie, cookie)
+    }
+
+    fn cookie_ref(&self) -> &C {
+        &self.cookie
+    }
+
+    fn cookie_mut(&mut self) -> &mut C {
+        &mut self.cookie
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    // Collects the hashed data, so that we can see what was hashed.
+    impl Digest for Vec<u8> {
+        fn update(&mut self, data: &[u8]) {
+            self.extend_from_slice(data);
+        }
+    }
+
+    #[test]
+    fn hash_consumed() {
+        let data = b"0123456789";
+        let mut reader = Hash::new(Box::new(Memory::new(data)), Vec::new());
+
+        // Peeking does not hash anything.
+        assert_eq!(reader.data(5).unwrap(), &data[..]);
+        assert!(reader.digest().is_empty());
+
+        reader.consume(2);
+        assert_eq!(reader.digest(), b"01");
+
+        assert_eq!(reader.data_consume(3).unwrap(), &data[2..]);
+        assert_eq!(reader.digest(), b"01234");
+
+        reader.data_consume_hard(1).unwrap();
+        assert_eq!(reader.digest(), b"012345");
+        assert!(reader.data_consume_hard(10).is_err());
+
+        let mut buf = [0; 2];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(reader.digest(), b"01234567");
+
+        // Asking for more than there is only hashes what is there.
+        assert_eq!(reader.data_consume(10).unwrap(), b"89");
+        assert_eq!(reader.data_consume(10).unwrap(), b"");
+        assert!(reader.eof());
+        assert_eq!(reader.hashed(), data.len() as u64);
+        assert_eq!(reader.into_digest(), data);
+    }
+}
@@ -240,6 +240,8 @@ mod limitor;
 mod reserve;
 mod dup;
 mod eof;
+mod hash;
+mod throttle;
 #[cfg(feature = "compression-deflate")]
 mod decompress_deflate;
 #[cfg(feature = "compression-bzip2")]
@@ -251,6 +253,8 @@ pub use self::limitor::Limitor;
 pub use self::reserve::Reserve;
 pub use self::dup::Dup;
 pub use self::eof::EOF;
+pub use self::hash::{Hash, Digest};
+pub use self::throttle::Throttle;
 #[cfg(feature = "compression-deflate")]
 pub use self::decompress_deflate::Deflate;
 #[cfg(feature = "compression-deflate")]
@@ -379,6 +383,42 @@ pub trait BufferedReader<C> : io::Read + fmt::Debug + fmt::Display {
         return result;
     }
 
+    /// Like `data()`, but also returns whether EOF has been reached.
+    ///
+    /// The returned flag is `true` if there are at most `amount`
+    /// bytes left, i.e. if the reader is exhausted once `amount`
+    /// bytes have been consumed.  Unlike checking for a short read
+    /// after calling `data()`, this also detects the case where
+    /// exactly `amount` bytes are left.
+    ///
+    /// The default implementation peeks one byte beyond `amount`.
+    /// Readers that know how much data is left should override it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # f(); fn f() -> Result<(), std::io::Error> {
+    /// use buffered_reader;
+    /// use buffered_reader::BufferedReader;
+    ///
+    /// let mut br = buffered_reader::Memory::new(&b"0123456789"[..]);
+    ///
+    /// let (data, eof) = br.data_with_eof(5)?;
+    /// assert!(data.len() >= 5);
+    /// assert!(! eof);
+    ///
+    /// let (data, eof) = br.data_with_eof(10)?;
+    /// assert_eq!(data.len(), 10);
+    /// assert!(eof);
+    /// # Ok(()) }
+    /// ```
+    fn data_with_eof(&mut self, amount: usize)
+                     -> Result<(&[u8], bool), io::Error> {
+        // Peek one byte beyond `amount` to see if there is more.
+        let len = self.data(amount.saturating_add(1))?.len();
+        Ok((self.buffer(), len <= amount))
+    }
+
     /// Returns all of the data until EOF.  Like `data()`, this does not
     /// actually consume the data that is read.
     ///
@@ -878,6 +918,11 @@ impl <'a, C> BufferedReader<C> for Box<BufferedReader<C> + 'a> {
         return self.as_mut().data_hard(amount);
     }
 
+    fn data_with_eof(&mut self, amount: usize)
+                     -> Result<(&[u8], bool), io::Error> {
+        return self.as_mut().data_with_eof(amount);
+    }
+
     fn data_eof(&mut self) -> Result<&[u8], io::Error> {
         return self.as_mut().data_eof();
     }
@@ -1085,6 +1130,22 @@ mod test {
         }
     }
 
+    #[test]
+    fn data_with_eof() {
+        let data : &[u8] = b"0123456789";
+        let mut bio = Generic::new(data, None);
+
+        assert!(! bio.data_with_eof(9).unwrap().1);
+        // Exactly as much data as requested is left.
+        assert!(bio.data_with_eof(10).unwrap().1);
+        assert!(bio.data_with_eof(11).unwrap().1);
+
+        bio.consume(10);
+        let (buffer, eof) = bio.data_with_eof(1).unwrap();
+        assert_eq!(buffer.len(), 0);
+        assert!(eof);
+    }
+
     #[test]
     fn drop_until() {
         let data : &[u8] = &b"abcd"[..];
@@ -50,6 +50,29 @@ impl<'a, C> Limitor<'a, C> {
             cookie: cookie,
         }
     }
+
+    /// Returns the wrapped reader.
+    ///
+    /// Unlike `BufferedReader::into_inner`, this does not require
+    /// boxing the limitor, and cannot fail.
+    pub fn into_parts(self) -> Box<'a + BufferedReader<C>> {
+        self.reader
+    }
+
+    /// Returns the number of bytes that can still be read.
+    ///
+    /// Note: the underlying reader may hit EOF before that.
+    pub fn remaining(&self) -> u64 {
+        self.limit
+    }
+
+    /// Returns whether the limit has been reached.
+    ///
+    /// Unlike `BufferedReader::consummated`, this does not require a
+    /// mutable reference.
+    pub fn is_exhausted(&self) -> bool {
+        self.limit == 0
+    }
 }
 
 impl<'a, C> io::Read for Limitor<'a, C> {
@@ -87,6 +110,17 @@ impl<'a, C> BufferedReader<C> for Limitor<'a, C> {
         }
     }
 
+    fn data_with_eof(&mut self, amount: usize)
+                     -> Result<(&[u8], bool), io::Error> {
+        if amount as u64 >= self.limit {
+            // We know that there is nothing beyond the limit.
+            Ok((self.data(amount)?, true))
+        } else {
+            let len = self.data(amount + 1)?.len();
+            Ok((self.buffer(), len <= amount))
+        }
+    }
+
     fn consume(&mut self, amount: usize) -> &[u8] {
         assert!(amount as u64 <= self.limit);
         self.limit -= amount as u64;
@@ -156,6 +190,86 @@ impl<'a, C> BufferedReader<C> for Limitor<'a, C> {
 mod test {
     use super::*;
 
+    #[test]
+    fn into_parts() {
+        let data : &[u8] = b"01234567890123456789";
+
+        let mut l = Limitor::new(Box::new(Memory::new(data)), 5);
+        assert_eq!(l.data_consume_hard(5).unwrap(), &b"01234"[..]);
+        assert_eq!(l.data(1).unwrap().len(), 0);
+
+        let mut inner = l.into_parts();
+        assert_eq!(inner.steal_eof().unwrap(), &data[5..]);
+    }
+
+    #[test]
+    fn remaining() {
+        use std::io::Read;
+
+        let data : &[u8] = b"01234567890123456789";
+        let mut l = Limitor::new(Box::new(Memory::new(data)), 10);
+        assert_eq!(l.remaining(), 10);
+
+        // Peeking does not change the remaining capacity.
+        assert_eq!(l.data(20).unwrap(), &b"0123456789"[..]);
+        assert_eq!(l.remaining(), 10);
+
+        l.consume(2);
+        assert_eq!(l.remaining(), 8);
+        l.data_consume_hard(3).unwrap();
+        assert_eq!(l.remaining(), 5);
+        l.data_consume(1).unwrap();
+        assert_eq!(l.remaining(), 4);
+        let mut buf = [0; 1];
+        assert_eq!(l.read(&mut buf).unwrap(), 1);
+        assert_eq!(l.remaining(), 3);
+
+        // Trying to read past the limit fails without consuming
+        // anything.
+        assert!(l.data_consume_hard(4).is_err());
+        assert_eq!(l.remaining(), 3);
+        assert!(! l.is_exhausted());
+
+        assert_eq!(l.data_consume_hard(3).unwrap(), &b"789"[..]);
+        assert_eq!(l.remaining(), 0);
+        assert!(l.is_exhausted());
+
+        // Nothing more can be consumed.
+        assert_eq!(l.data_consume(1).unwrap(), &b""[..]);
+        assert!(l.data_consume_hard(1).is_err());
+        assert_eq!(l.remaining(), 0);
+
+        // The limit may exceed the underlying data.
+        let mut l = Limitor::new(Box::new(Memory::new(data)), 30);
+        l.drop_eof().unwrap();
+        assert_eq!(l.remaining(), 10);
+        assert!(! l.is_exhausted());
+    }
+
+    #[test]
+    fn data_with_eof() {
+        let data : &[u8] = b"01234567890123456789";
+        let mut bio = Limitor::new(Box::new(Memory::new(data)), 5);
+
+        {
+            let (buffer, eof) = bio.data_with_eof(3).unwrap();
+            assert_eq!(buffer, &b"01234"[..]);
+            assert!(! eof);
+        }
+        {
+            // EOF is known from the limit, even though the
+            // underlying reader has more data.
+            let (buffer, eof) = bio.data_with_eof(5).unwrap();
+            assert_eq!(buffer, &b"01234"[..]);
+            assert!(eof);
+        }
+
+        bio.consume(3);
+        let (buffer, eof) = bio.data_with_eof(2).unwrap();
+        assert_eq!(buffer, &b"34"[..]);
+        assert!(eof);
+    }
+
     #[test]
     fn buffered_reader_limitor_test() {
         let data : &[u8] = b"01234567890123456789";
@@ -63,6 +63,22 @@ impl<'a, C> Memory<'a, C> {
     pub fn total_out(&self) -> usize {
         return self.cursor;
     }
+
+    /// Returns the position of the cursor in the buffer.
+    ///
+    /// This is the offset of the next byte to be read, and is the
+    /// same as `total_out()`.
+    pub fn position(&self) -> usize {
+        self.cursor
+    }
+
+    /// Rewinds the reader to the start of the buffer.
+    ///
+    /// As the whole buffer is kept in memory, any consumed data can
+    /// be read again.  This is useful for speculative parsing.
+    pub fn reset(&mut self) {
+        self.cursor = 0;
+    }
 }
 
 impl<'a, C> io::Read for Memory<'a, C> {
@@ -146,6 +162,31 @@ mod test {
         buffered_reader_test_data_check(&mut bio);
     }
 
+    #[test]
+    fn position_and_reset() {
+        let data = b"0123456789";
+        let mut reader = Memory::new(&data[..]);
+        assert_eq!(reader.position(), 0);
+
+        reader.data_consume_hard(4).unwrap();
+        assert_eq!(reader.position(), 4);
+        assert_eq!(reader.buffer(), b"456789");
+
+        // Peeking doesn't move the cursor.
+        reader.data(2).unwrap();
+        assert_eq!(reader.position(), 4);
+
+        reader.reset();
+        assert_eq!(reader.position(), 0);
+        assert_eq!(reader.data_eof().unwrap(), &data[..]);
+
+        reader.data_consume_hard(data.len()).unwrap();
+        assert!(reader.eof());
+        reader.reset();
+        assert!(! reader.eof());
+        assert_eq!(reader.total_out(), 0);
+    }
+
     // Test that buffer() returns the same data as data().
     #[test]
     fn buffer_test() {
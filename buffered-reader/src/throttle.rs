@@ -0,0 +1,192 @@
+use std::io;
+use std::cmp;
+
+use super::*;
+
+/// Limits the amount of data returned by each call.
+///
+/// `BufferedReader`s usually return as much data as they have
+/// buffered, which is often more than was requested.  A `Throttle`
+/// returns at most `max_per_call` bytes per call, unless the caller
+/// explicitly requests more, or more was already returned (and not
+/// yet consumed).  Likewise, `read` returns at most `max_per_call`
+/// bytes.
+///
+/// This is useful for testing code that processes trickle-fed input,
+/// e.g., to exercise the short read and buffering paths of filters.
+pub struct Throttle<'a, C> {
+    reader: Box<'a + BufferedReader<C>>,
+    max_per_call: usize,
+    // The amount of data that has been returned, but not yet
+    // consumed.
+    exposed: usize,
+
+    cookie: C,
+}
+
+impl<'a, C> fmt::Display for Throttle<'a, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Throttle ({} bytes per call)", self.max_per_call)
+    }
+}
+
+impl<'a, C> fmt::Debug for Throttle<'a, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Throttle")
+            .field("max_per_call", &self.max_per_call)
+            .field("exposed", &self.exposed)
+            .field("reader", &self.reader)
+            .finish()
+    }
+}
+
+impl<'a> Throttle<'a, ()> {
+    /// Instantiates a new throttle.
+    ///
+    /// `reader` is the source to wrap.  `max_per_call` is the maximum
+    /// number of bytes that are returned per call, unless more are
+    /// explicitly requested.
+    pub fn new(reader: Box<'a + BufferedReader<()>>, max_per_call: usize)
+               -> Self {
+        Self::with_cookie(reader, max_per_call, ())
+    }
+}
+
+impl<'a, C> Throttle<'a, C> {
+    /// Like `new()`, but sets a cookie.
+    ///
+    /// The cookie can be retrieved using the `cookie_ref` and
+    /// `cookie_mut` methods, and set using the `cookie_set` method.
+    pub fn with_cookie(reader: Box<'a + BufferedReader<C>>,
+                       max_per_call: usize, cookie: C)
+                       -> Throttle<'a, C> {
+        Throttle {
+            reader: reader,
+            max_per_call: max_per_call,
+            exposed: 0,
+            cookie: cookie,
+        }
+    }
+}
+
+impl<'a, C> io::Read for Throttle<'a, C> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        let len = cmp::min(self.max_per_call, buf.len());
+        let amount = self.reader.read(&mut buf[..len])?;
+        self.exposed = self.exposed.saturating_sub(amount);
+        Ok(amount)
+    }
+}
+
+impl<'a, C> BufferedReader<C> for Throttle<'a, C> {
+    fn buffer(&self) -> &[u8] {
+        let buf = self.reader.buffer();
+        &buf[..cmp::min(buf.len(), self.exposed)]
+    }
+
+    fn data(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        // Never return less than was requested, and never shrink
+        // what was previously returned.
+        let cap = cmp::max(amount, cmp::max(self.max_per_call, self.exposed));
+        let buffer = self.reader.data(amount)?;
+        let buffer = &buffer[..cmp::min(buffer.len(), cap)];
+        self.exposed = buffer.len();
+        Ok(buffer)
+    }
+
+    fn consume(&mut self, amount: usize) -> &[u8] {
+        assert!(amount <= self.exposed,
+                "Attempt to consume {} bytes, but only {} were returned",
+                amount, self.exposed);
+        self.exposed -= amount;
+        let data = self.reader.consume(amount);
+        &data[..cmp::min(data.len(), amount + self.exposed)]
+    }
+
+    fn data_consume(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        let amount = cmp::min(amount, self.data(amount)?.len());
+        Ok(self.consume(amount))
+    }
+
+    fn data_consume_hard(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        self.data_hard(amount)?;
+        Ok(self.consume(amount))
+    }
+
+    fn get_mut(&mut self) -> Option<&mut BufferedReader<C>> {
+        Some(&mut self.reader)
+    }
+
+    fn get_ref(&self) -> Option<&BufferedReader<C>> {
+        Some(&self.reader)
+    }
+
+    fn into_inner<'b>(self: Box<Self>) -> Option<Box<BufferedReader<C> + 'b>>
+        where Self: 'b {
+        Some(self.reader)
+    }
+
+    fn cookie_set(&mut self, cookie: C) -> C {
+        use std::mem;
+
+        mem::replace(&mut self.cookie, cookie)
+    }
+
+    fn cookie_ref(&self) -> &C {
+        &self.cookie
+    }
+
+    fn cookie_mut(&mut self) -> &mut C {
+        &mut self.cookie
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn throttle() {
+        let data : &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+        let mut bio = Throttle::new(Box::new(Memory::new(data)), 3);
+
+        // At most three bytes are returned, unless more are
+        // requested.
+        assert_eq!(bio.data(1).unwrap(), &b"012"[..]);
+        assert_eq!(bio.data(0).unwrap(), &b"012"[..]);
+        assert_eq!(bio.buffer(), &b"012"[..]);
+        assert_eq!(bio.data(5).unwrap(), &b"01234"[..]);
+
+        // What was returned is not taken back.
+        assert_eq!(bio.data(1).unwrap(), &b"01234"[..]);
+        assert_eq!(bio.buffer(), &b"01234"[..]);
+
+        assert_eq!(&bio.consume(2)[..2], &b"01"[..]);
+        assert_eq!(bio.buffer(), &b"234"[..]);
+        assert_eq!(bio.data_consume(2).unwrap(), &b"234"[..]);
+        assert_eq!(bio.data_consume_hard(4).unwrap(), &b"4567"[..]);
+
+        // Reads are short.
+        let mut buf = [0u8; 10];
+        assert_eq!(bio.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], &b"89a"[..]);
+
+        // But everything is eventually returned.
+        let mut rest = Vec::new();
+        bio.read_to_end(&mut rest).unwrap();
+        assert_eq!(&rest[..], &data[11..]);
+        assert_eq!(bio.data(1).unwrap().len(), 0);
+        assert!(bio.data_hard(1).is_err());
+    }
+
+    #[test]
+    fn throttle_steal_eof() {
+        let data : &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        let mut bio = Throttle::new(Box::new(Memory::new(data)), 1);
+        assert_eq!(&bio.data_consume_hard(2).unwrap()[..2], &b"01"[..]);
+        assert_eq!(&bio.steal_eof().unwrap()[..], &data[2..]);
+        assert!(bio.eof());
+    }
+}
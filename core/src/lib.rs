@@ -19,6 +19,7 @@
 /// ```
 
 extern crate dirs;
+extern crate rand;
 extern crate tempdir;
 #[macro_use]
 extern crate failure;
@@ -26,8 +27,13 @@ extern crate failure;
 use std::fmt;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use tempdir::TempDir;
 
+mod retry;
+pub use retry::{RetryPolicy, Delays};
+
 /// A `Context` for Sequoia.
 ///
 /// # Example
@@ -65,6 +71,9 @@ pub struct Context {
     ipc_policy: IPCPolicy,
     ephemeral: bool,
     cleanup: bool,
+    keyserver: Option<String>,
+    update_overdue_after: Option<Duration>,
+    logger: Option<Logger>,
 }
 
 impl Clone for Context {
@@ -76,6 +85,9 @@ impl Clone for Context {
             ipc_policy: self.ipc_policy,
             ephemeral: self.ephemeral,
             cleanup: false, // Prevent cleanup.
+            keyserver: self.keyserver.clone(),
+            update_overdue_after: self.update_overdue_after,
+            logger: self.logger.clone(),
         }
     }
 }
@@ -115,6 +127,9 @@ impl Context {
             ipc_policy: IPCPolicy::Robust,
             ephemeral: false,
             cleanup: false,
+            keyserver: None,
+            update_overdue_after: None,
+            logger: None,
         })
     }
 
@@ -142,6 +157,147 @@ impl Context {
     pub fn ephemeral(&self) -> bool {
         self.ephemeral
     }
+
+    /// Returns the URI of the default keyserver, if any.
+    ///
+    /// The default keyserver is used for background updates of
+    /// stores, and whenever no keyserver is given explicitly.  If
+    /// none is configured, the SKS keyserver pool is used.
+    pub fn keyserver(&self) -> Option<&str> {
+        self.keyserver.as_ref().map(|s| s.as_str())
+    }
+
+    /// Returns the time after which keys are considered overdue.
+    ///
+    /// If a key has not been updated successfully for this long, the
+    /// store logs a warning for every binding of the key.  If none is
+    /// configured, twice the interval after which all keys are
+    /// refreshed is used.
+    pub fn update_overdue_after(&self) -> Option<Duration> {
+        self.update_overdue_after
+    }
+
+    /// Returns the logger, if any.
+    pub fn logger(&self) -> Option<&Logger> {
+        self.logger.as_ref()
+    }
+
+    /// Sets the logger.
+    ///
+    /// The logger is invoked for every request the store client makes
+    /// to the background service, for every keyserver and WKD
+    /// operation, and for background updates of keys.
+    /// Clones of this context, and objects created from it, share
+    /// the logger.  Objects that have been created before this call
+    /// are not affected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use sequoia_core::{Context, LogEvent, Result};
+    /// # use std::sync::{Arc, Mutex};
+    /// # f().unwrap();
+    /// # fn f() -> Result<()> {
+    /// let events = Arc::new(Mutex::new(Vec::new()));
+    /// let mut c = Context::configure().ephemeral().build()?;
+    /// let sink = events.clone();
+    /// c.set_logger(move |e| sink.lock().unwrap().push(e));
+    ///
+    /// c.log(LogEvent::StoreRequest {
+    ///     operation: "store.lookup".into(),
+    ///     duration: Default::default(),
+    ///     error: None,
+    /// });
+    /// assert_eq!(events.lock().unwrap().len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_logger<F>(&mut self, logger: F) -> Option<Logger>
+        where F: Fn(LogEvent) + Send + Sync + 'static
+    {
+        ::std::mem::replace(&mut self.logger, Some(Arc::new(logger)))
+    }
+
+    /// Passes `event` to the logger, if any.
+    pub fn log(&self, event: LogEvent) {
+        if let Some(ref logger) = self.logger {
+            logger(event);
+        }
+    }
+}
+
+/// Receives `LogEvent`s.
+///
+/// See `Context::set_logger`.
+pub type Logger = Arc<Fn(LogEvent) + Send + Sync>;
+
+/// An operation reported to the logger.
+///
+/// See `Context::set_logger`.
+#[derive(Clone, Debug)]
+pub enum LogEvent {
+    /// The store client made a request to the background service.
+    StoreRequest {
+        /// The name of the operation, e.g. `store.lookup`.
+        operation: String,
+        /// The time it took to complete the request.
+        duration: Duration,
+        /// The error, if the request failed.
+        error: Option<String>,
+    },
+
+    /// A key was requested from a keyserver.
+    KeyserverGet {
+        /// The URI of the keyserver.
+        uri: String,
+        /// The requested key handle.
+        handle: String,
+        /// The error, if the request failed.
+        error: Option<String>,
+    },
+
+    /// Keys were sent to a keyserver.
+    KeyserverSend {
+        /// The URI of the keyserver.
+        uri: String,
+        /// The number of keys sent.
+        keys: usize,
+        /// The error, if the request failed.
+        error: Option<String>,
+    },
+
+    /// A key was requested from a Web Key Directory.
+    WkdGet {
+        /// The URI the key was requested from.
+        uri: String,
+        /// The requested email address.
+        email: String,
+        /// The error, if the request failed.
+        error: Option<String>,
+    },
+
+    /// The background service updated a key.
+    ///
+    /// Updates are only reported if the background service runs in
+    /// the same process, i.e. if it shares the context.
+    UpdateSucceeded {
+        /// The fingerprint of the key.
+        fingerprint: String,
+        /// The URI of the keyserver the key was retrieved from.
+        uri: String,
+    },
+
+    /// The background service failed to update a key.
+    ///
+    /// See `LogEvent::UpdateSucceeded`.
+    UpdateFailed {
+        /// The fingerprint of the key.
+        fingerprint: String,
+        /// The URI of the keyserver the key was requested from.
+        uri: String,
+        /// The error.
+        error: String,
+    },
 }
 
 /// Represents a `Context` configuration.
@@ -262,6 +418,40 @@ impl Config {
     pub fn set_ephemeral(&mut self) -> bool {
         ::std::mem::replace(&mut self.0.ephemeral, true)
     }
+
+    /// Sets the URI of the default keyserver.
+    pub fn keyserver<S: Into<String>>(mut self, uri: S) -> Self {
+        self.set_keyserver(uri);
+        self
+    }
+
+    /// Sets the URI of the default keyserver.
+    pub fn set_keyserver<S: Into<String>>(&mut self, uri: S)
+                                          -> Option<String> {
+        ::std::mem::replace(&mut self.0.keyserver, Some(uri.into()))
+    }
+
+    /// Sets the time after which keys are considered overdue.
+    pub fn update_overdue_after(mut self, after: Duration) -> Self {
+        self.set_update_overdue_after(after);
+        self
+    }
+
+    /// Sets the time after which keys are considered overdue.
+    pub fn set_update_overdue_after(&mut self, after: Duration)
+                                    -> Option<Duration> {
+        ::std::mem::replace(&mut self.0.update_overdue_after, Some(after))
+    }
+
+    /// Sets the logger.
+    ///
+    /// See `Context::set_logger`.
+    pub fn logger<F>(mut self, logger: F) -> Self
+        where F: Fn(LogEvent) + Send + Sync + 'static
+    {
+        self.0.set_logger(logger);
+        self
+    }
 }
 
 /* Error handling.  */
@@ -0,0 +1,203 @@
+//! Retrying failed operations.
+//!
+//! Network operations may fail transiently.  A `RetryPolicy`
+//! describes how often, and after what delay, such an operation is
+//! retried.  It is used by the keyserver client in `sequoia-net`, and
+//! by the background updater of the public key store.
+
+use std::cmp;
+use std::time::Duration;
+
+use rand::distributions::{Distribution, Uniform};
+use rand::thread_rng;
+
+/// Controls how failed operations are retried.
+///
+/// The delay before the `n`th retry is `base_delay * 2^(n - 1)`,
+/// capped at `max_delay`.  If `jitter` is set, the delay is drawn
+/// uniformly from the interval `[delay / 2, delay]` to avoid many
+/// clients retrying in lockstep.
+///
+/// # Example
+///
+/// ```
+/// # use sequoia_core::RetryPolicy;
+/// # use std::time::Duration;
+/// let p = RetryPolicy {
+///     jitter: false,
+///     .. RetryPolicy::exponential()
+/// };
+/// let delays: Vec<Duration> = p.delays().collect();
+/// assert_eq!(delays, [Duration::from_secs(1), Duration::from_secs(2)]);
+/// ```
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one.
+    ///
+    /// A value of `0` or `1` disables retrying.
+    pub max_attempts: u32,
+
+    /// Delay before the first retry.
+    pub base_delay: Duration,
+
+    /// Upper bound for the delay between two attempts.
+    pub max_delay: Duration,
+
+    /// Whether to randomize delays.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::exponential()
+    }
+}
+
+impl RetryPolicy {
+    /// Returns a policy that does not retry.
+    pub fn never() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::from_secs(0),
+            max_delay: Duration::from_secs(0),
+            jitter: false,
+        }
+    }
+
+    /// Returns a policy with exponential backoff.
+    ///
+    /// Makes up to three attempts, waiting one second before the
+    /// first retry, and doubling the delay up to one minute.
+    pub fn exponential() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: true,
+        }
+    }
+
+    /// Returns the nominal delay before the given retry, ignoring
+    /// jitter.
+    ///
+    /// `retry` counts from `1`, i.e., `delay(1)` is the delay between
+    /// the first and the second attempt.  Returns `None` if the
+    /// policy does not allow for another attempt.
+    pub fn delay(&self, retry: u32) -> Option<Duration> {
+        if retry == 0 || retry >= self.max_attempts {
+            return None;
+        }
+
+        // Saturate instead of overflowing for large retry counts.
+        let factor = 1u32.checked_shl(retry - 1).unwrap_or(u32::max_value());
+        let delay = self.base_delay.checked_mul(factor)
+            .unwrap_or(self.max_delay);
+        Some(cmp::min(delay, self.max_delay))
+    }
+
+    /// Returns the delay to wait before the given retry.
+    ///
+    /// Like `delay`, but applies jitter if the policy requests it.
+    pub fn jittered_delay(&self, retry: u32) -> Option<Duration> {
+        self.delay(retry).map(|d| {
+            if ! self.jitter || d == Duration::from_secs(0) {
+                return d;
+            }
+
+            let max = d.as_secs() * 1000 + d.subsec_millis() as u64;
+            let ms = Uniform::from(max / 2..max + 1)
+                .sample(&mut thread_rng());
+            Duration::from_millis(ms)
+        })
+    }
+
+    /// Returns the nominal delays between all attempts.
+    pub fn delays(&self) -> Delays {
+        Delays {
+            policy: *self,
+            retry: 0,
+        }
+    }
+}
+
+/// Iterates over the nominal delays between attempts.
+///
+/// Returned by `RetryPolicy::delays`.
+#[derive(Debug)]
+pub struct Delays {
+    policy: RetryPolicy,
+    retry: u32,
+}
+
+impl Iterator for Delays {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.retry = self.retry.saturating_add(1);
+        self.policy.delay(self.retry)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn secs(s: &[u64]) -> Vec<Duration> {
+        s.iter().map(|&s| Duration::from_secs(s)).collect()
+    }
+
+    #[test]
+    fn never() {
+        let p = RetryPolicy::never();
+        assert_eq!(p.delay(1), None);
+        assert_eq!(p.delays().count(), 0);
+
+        let p = RetryPolicy { max_attempts: 0, .. RetryPolicy::exponential() };
+        assert_eq!(p.delays().count(), 0);
+    }
+
+    #[test]
+    fn exponential() {
+        let p = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: false,
+        };
+        assert_eq!(p.delay(0), None);
+        assert_eq!(p.delays().collect::<Vec<_>>(),
+                   secs(&[1, 2, 4, 8, 16, 32, 60, 60, 60]));
+        assert_eq!(p.delay(10), None);
+    }
+
+    #[test]
+    fn saturates() {
+        let p = RetryPolicy {
+            max_attempts: u32::max_value(),
+            base_delay: Duration::from_secs(u64::max_value() / 2),
+            max_delay: Duration::from_secs(3600),
+            jitter: false,
+        };
+        assert_eq!(p.delay(1), Some(Duration::from_secs(3600)));
+        assert_eq!(p.delay(40), Some(Duration::from_secs(3600)));
+        assert_eq!(p.delay(u32::max_value() - 1),
+                   Some(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn jitter() {
+        let p = RetryPolicy {
+            max_attempts: 6,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter: true,
+        };
+        for _ in 0..100 {
+            for (i, d) in p.delays().enumerate() {
+                let j = p.jittered_delay(i as u32 + 1).unwrap();
+                assert!(j >= d / 2 && j <= d);
+            }
+        }
+        assert_eq!(p.jittered_delay(6), None);
+    }
+}
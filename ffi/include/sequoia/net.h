@@ -58,6 +58,18 @@ pgp_tpk_t sq_keyserver_get (sq_context_t ctx,
 			   sq_keyserver_t ks,
 			   const pgp_keyid_t id);
 
+/*/
+/// Retrieves the key with the given fingerprint.
+///
+/// The full fingerprint is used to look up the key.  If the server
+/// returns a key with a different fingerprint, this fails.
+///
+/// Returns `NULL` on errors.
+/*/
+pgp_tpk_t sq_keyserver_get_by_fingerprint (sq_context_t ctx,
+					  sq_keyserver_t ks,
+					  const pgp_fingerprint_t fp);
+
 /*/
 /// Sends the given key to the server.
 ///
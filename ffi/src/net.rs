@@ -38,6 +38,7 @@ use sequoia_net::KeyServer;
 
 use super::error::Status;
 use super::core::Context;
+use ::openpgp::fingerprint::Fingerprint;
 use ::openpgp::keyid::KeyID;
 use ::openpgp::tpk::TPK;
 use ::RefRaw;
@@ -124,6 +125,25 @@ fn sq_keyserver_get(ctx: *mut Context,
     ks.get(&id).move_into_raw(Some(ctx.errp()))
 }
 
+/// Retrieves the key with the given fingerprint.
+///
+/// The full fingerprint is used to look up the key.  If the server
+/// returns a key with a different fingerprint, this fails.
+///
+/// Returns `NULL` on errors.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_keyserver_get_by_fingerprint(ctx: *mut Context,
+                                   ks: *mut KeyServer,
+                                   fp: *const Fingerprint)
+                                   -> Maybe<TPK> {
+    let ctx = ffi_param_ref_mut!(ctx);
+    ffi_make_fry_from_ctx!(ctx);
+    let ks = ffi_param_ref_mut!(ks);
+    let fp = fp.ref_raw();
+
+    ks.get_by_fingerprint(&fp).move_into_raw(Some(ctx.errp()))
+}
+
 /// Sends the given key to the server.
 ///
 /// Returns != 0 on errors.
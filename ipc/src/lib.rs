@@ -70,6 +70,7 @@ use std::process::{Command, Stdio};
 use std::os::unix::io::AsRawFd;
 
 use std::thread;
+use std::time::Duration;
 
 extern crate sequoia_core;
 
@@ -131,22 +132,8 @@ impl Descriptor {
     pub fn connect_with_policy(&self, handle: &tokio_core::reactor::Handle,
                                policy: core::IPCPolicy)
                    -> Result<RpcSystem<Side>> {
-        let do_connect =
-            move |cookie: Cookie, mut s: TcpStream| -> Result<RpcSystem<Side>> {
-            cookie.send(&mut s)?;
-
-            /* Tokioize.  */
-            let stream = net::TcpStream::from_stream(s, &handle)?;
-            stream.set_nodelay(true)?;
-            let (reader, writer) = stream.split();
-
-            let network =
-                Box::new(twoparty::VatNetwork::new(reader, writer,
-                                                   Side::Client,
-                                                   Default::default()));
-            let rpc_system = RpcSystem::new(network, None);
-
-            Ok(rpc_system)
+        let do_connect = |cookie: Cookie, s: TcpStream| {
+            Self::bootstrap(cookie, s, handle)
         };
 
         fs::create_dir_all(self.ctx.home())?;
@@ -225,6 +212,48 @@ impl Descriptor {
         }
     }
 
+    /// Connects to a running server.
+    ///
+    /// Unlike `connect`, this never starts a server.  Instead, it
+    /// fails if the rendezvous point does not exist, is malformed,
+    /// or does not point to a usable server.  This can be used to
+    /// connect to a server managed by someone else.
+    pub fn connect_existing(&self, handle: &tokio_core::reactor::Handle)
+                            -> Result<RpcSystem<Side>> {
+        let mut file = fs::File::open(&self.rendezvous)?;
+        file.lock_shared()?;
+
+        let mut c = vec![];
+        file.read_to_end(&mut c)?;
+        drop(file);
+
+        let (cookie, a) = Cookie::extract(c).ok_or_else(
+            || format_err!("Malformed rendezvous point {:?}",
+                           self.rendezvous))?;
+        let addr: SocketAddr = String::from_utf8_lossy(&a).parse()?;
+        Self::bootstrap(cookie, TcpStream::connect(addr)?, handle)
+    }
+
+    /// Authenticates using `cookie` and sets up an RPC system on `s`.
+    fn bootstrap(cookie: Cookie, mut s: TcpStream,
+                 handle: &tokio_core::reactor::Handle)
+                 -> Result<RpcSystem<Side>> {
+        cookie.send(&mut s)?;
+
+        /* Tokioize.  */
+        let stream = net::TcpStream::from_stream(s, handle)?;
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.split();
+
+        let network =
+            Box::new(twoparty::VatNetwork::new(reader, writer,
+                                               Side::Client,
+                                               Default::default()));
+        let rpc_system = RpcSystem::new(network, None);
+
+        Ok(rpc_system)
+    }
+
     /// Try to create a TCP socket, bind it to a random port on
     /// localhost.
     fn listen(&self) -> Result<TcpListener> {
@@ -259,13 +288,21 @@ impl Descriptor {
         let fd = l.as_raw_fd();
         ::std::mem::forget(l);
 
-        Command::new(&self.executable.clone().into_os_string())
+        let mut command =
+            Command::new(&self.executable.clone().into_os_string());
+        command
             .arg("--home")
             .arg(self.ctx.home().to_string_lossy().into_owned())
             .arg("--lib")
             .arg(self.ctx.home().to_string_lossy().into_owned())
             .arg("--ephemeral")
-            .arg(format!("{}", self.ctx.ephemeral()))
+            .arg(format!("{}", self.ctx.ephemeral()));
+        if let Some(after) = self.ctx.update_overdue_after() {
+            command
+                .arg("--update-overdue-after")
+                .arg(format!("{}", after.as_secs()));
+        }
+        command
             // l will be closed here if the exec fails.
             .stdin(unsafe { Stdio::from_raw_fd(fd) })
             .spawn()?;
@@ -304,11 +341,13 @@ impl Server {
         use std::env::args;
         let args: Vec<String> = args().collect();
 
-        if args.len() != 7 || args[1] != "--home"
-            || args[3] != "--lib" || args[5] != "--ephemeral" {
+        if (args.len() != 7 && args.len() != 9) || args[1] != "--home"
+            || args[3] != "--lib" || args[5] != "--ephemeral"
+            || (args.len() == 9 && args[7] != "--update-overdue-after") {
                 return Err(format_err!(
                     "Usage: {} --home <HOMEDIR> --lib <LIBDIR> \
-                     --ephemeral true|false", args[0]));
+                     --ephemeral true|false \
+                     [--update-overdue-after <SECONDS>]", args[0]));
             }
 
         let mut cfg = core::Context::configure()
@@ -324,6 +363,13 @@ impl Server {
                 args[6]));
         }
 
+        if args.len() == 9 {
+            let after = args[8].parse().map_err(|_| format_err!(
+                "Expected a number of seconds for --update-overdue-after, \
+                 got: {}", args[8]))?;
+            cfg.set_update_overdue_after(Duration::from_secs(after));
+        }
+
         cfg.build()
     }
 
@@ -0,0 +1,227 @@
+//! For parsing responses of HKP servers.
+//!
+//! The `index` and `vindex` operations of [HKP] return a listing of
+//! the keys matching a query.  If the `mr` option is given, the
+//! listing uses a machine-readable, colon-delimited format.  This
+//! module parses that format.  See `KeyServer::search` for querying
+//! a keyserver.
+//!
+//! [HKP]: https://tools.ietf.org/html/draft-shaw-openpgp-hkp-00#section-5.2
+
+use percent_encoding::percent_decode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use openpgp::{Fingerprint, KeyID, TPK};
+use openpgp::constants::PublicKeyAlgorithm;
+
+use super::{Error, KeyServer, Result};
+
+/// Describes a key listed by a keyserver.
+///
+/// Servers may omit all fields but the KeyID, hence most fields are
+/// optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// The KeyID of the key.
+    pub keyid: KeyID,
+
+    /// The fingerprint of the key, if the server returned one.
+    pub fingerprint: Option<Fingerprint>,
+
+    /// The public key algorithm.
+    pub algo: Option<PublicKeyAlgorithm>,
+
+    /// The length of the key in bits.
+    pub bits: Option<u32>,
+
+    /// The creation time of the key.
+    pub creation_time: Option<SystemTime>,
+
+    /// The expiration time of the key.
+    pub expiration_time: Option<SystemTime>,
+
+    /// Whether the key is revoked.
+    pub revoked: bool,
+
+    /// Whether the key is disabled.
+    pub disabled: bool,
+
+    /// Whether the key is expired.
+    pub expired: bool,
+
+    /// The user IDs of the key.
+    pub userids: Vec<String>,
+}
+
+impl IndexEntry {
+    fn new(keyid: KeyID) -> Self {
+        IndexEntry {
+            keyid: keyid,
+            fingerprint: None,
+            algo: None,
+            bits: None,
+            creation_time: None,
+            expiration_time: None,
+            revoked: false,
+            disabled: false,
+            expired: false,
+            userids: Vec::new(),
+        }
+    }
+
+    /// Retrieves the key described by this entry from `ks`.
+    ///
+    /// If the server gave the fingerprint of the key, the key is
+    /// retrieved using `KeyServer::get_by_fingerprint`.  Otherwise,
+    /// it is retrieved by KeyID.  In either case, if the server
+    /// returns a different key, `Error::UnexpectedKey` or
+    /// `Error::UnexpectedKeyID` is returned.
+    pub fn fetch(&self, ks: &mut KeyServer) -> Result<TPK> {
+        if let Some(ref fp) = self.fingerprint {
+            return ks.get_by_fingerprint(fp);
+        }
+
+        let tpk = ks.get(&self.keyid)?;
+        if self.keyid.is_suffix_of(&tpk.fingerprint()) {
+            Ok(tpk)
+        } else {
+            Err(Error::UnexpectedKeyID {
+                expected: self.keyid.clone(),
+                found: tpk.fingerprint(),
+            }.into())
+        }
+    }
+}
+
+/// Parses a field containing seconds since the epoch.
+fn parse_time(field: Option<&str>) -> Option<SystemTime> {
+    field.and_then(|f| f.trim().parse::<u64>().ok())
+        .map(|t| UNIX_EPOCH + Duration::from_secs(t))
+}
+
+/// Parses a machine-readable index.
+///
+/// Unknown records and fields are ignored.  Keys the server only
+/// gives a short, 32-bit KeyID for are included, but such KeyIDs
+/// are prone to collisions.
+///
+/// # Example
+///
+/// ```
+/// # extern crate sequoia_net;
+/// # use sequoia_net::hkp::parse_index;
+/// # fn main() { f().unwrap(); }
+/// # fn f() -> sequoia_net::Result<()> {
+/// let index = parse_index(b"info:1:1
+/// pub:3E8877C877274692975189F5D03F6F865226FE8B:1:2048:1511355130::
+/// uid:Testy McTestface <testy%40example.org>:1511355130::
+/// ")?;
+/// assert_eq!(index.len(), 1);
+/// assert_eq!(index[0].bits, Some(2048));
+/// assert_eq!(index[0].userids, ["Testy McTestface <testy@example.org>"]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_index(data: &[u8]) -> Result<Vec<IndexEntry>> {
+    let data = String::from_utf8_lossy(data);
+    let mut entries: Vec<IndexEntry> = Vec::new();
+
+    for line in data.lines() {
+        let line = line.trim_right();
+        let mut fields = line.split(':');
+        match fields.next() {
+            Some("pub") => {
+                let id = fields.next().map(|f| f.trim()).unwrap_or("");
+                let keyid = KeyID::from_hex(id)
+                    .map_err(|_| Error::MalformedResponse)?;
+                let mut entry = IndexEntry::new(keyid);
+                if id.len() == 40 {
+                    entry.fingerprint = Fingerprint::from_hex(id).ok();
+                }
+                entry.algo = fields.next()
+                    .and_then(|f| f.trim().parse::<u8>().ok())
+                    .map(|a| a.into());
+                entry.bits = fields.next()
+                    .and_then(|f| f.trim().parse().ok());
+                entry.creation_time = parse_time(fields.next());
+                entry.expiration_time = parse_time(fields.next());
+                if let Some(flags) = fields.next() {
+                    entry.revoked = flags.contains('r');
+                    entry.disabled = flags.contains('d');
+                    entry.expired = flags.contains('e');
+                }
+                entries.push(entry);
+            },
+            Some("uid") => {
+                // User IDs preceding the first key are ignored.
+                if let (Some(entry), Some(uid)) =
+                    (entries.last_mut(), fields.next())
+                {
+                    entry.userids.push(
+                        percent_decode(uid.as_bytes())
+                            .decode_utf8_lossy().into_owned());
+                }
+            },
+            // Including "info", which we don't need.
+            _ => (),
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index() {
+        let index = parse_index(b"info:1:3
+pub:3E8877C877274692975189F5D03F6F865226FE8B:1:2048:1511355130:1574427130:
+uid:Testy McTestface <testy%40example.org>:1511355130::
+uid:Testy %3A-) <testy@example.com>:::r
+pub:0123456789ABCDEF:22::::re
+pub:01234567
+uid:Short
+").unwrap();
+        assert_eq!(index.len(), 3);
+
+        let testy = &index[0];
+        assert_eq!(testy.keyid, KeyID::from_hex("D03F6F865226FE8B").unwrap());
+        assert_eq!(testy.fingerprint,
+                   Some(Fingerprint::from_hex(
+                       "3E8877C877274692975189F5D03F6F865226FE8B").unwrap()));
+        assert_eq!(testy.algo, Some(PublicKeyAlgorithm::RSAEncryptSign));
+        assert_eq!(testy.bits, Some(2048));
+        assert_eq!(testy.creation_time,
+                   Some(UNIX_EPOCH + Duration::from_secs(1511355130)));
+        assert_eq!(testy.expiration_time,
+                   Some(UNIX_EPOCH + Duration::from_secs(1574427130)));
+        assert!(! testy.revoked && ! testy.disabled && ! testy.expired);
+        assert_eq!(testy.userids, ["Testy McTestface <testy@example.org>",
+                                   "Testy :-) <testy@example.com>"]);
+
+        // Optional fields may be empty.
+        let other = &index[1];
+        assert_eq!(other.fingerprint, None);
+        assert_eq!(other.algo, Some(PublicKeyAlgorithm::EdDSA));
+        assert_eq!(other.bits, None);
+        assert_eq!(other.creation_time, None);
+        assert!(other.revoked && ! other.disabled && other.expired);
+        assert!(other.userids.is_empty());
+
+        // Or missing altogether.
+        let short = &index[2];
+        assert_eq!(short.keyid, KeyID::from_hex("01234567").unwrap());
+        assert_eq!(short.algo, None);
+        assert_eq!(short.userids, ["Short"]);
+    }
+
+    #[test]
+    fn malformed_index() {
+        assert_eq!(parse_index(b"").unwrap(), vec![]);
+        assert_eq!(parse_index(b"info:1:0\nuid:Nobody:::\n").unwrap(),
+                   vec![]);
+        assert!(parse_index(b"pub:not hex:1:2048:::\n").is_err());
+    }
+}
@@ -1,8 +1,10 @@
 //! For accessing keys over the network.
 //!
-//! Currently, this module provides access to keyservers providing the [HKP] protocol.
+//! Currently, this module provides access to keyservers providing the [HKP] protocol,
+//! and to keys published in a [Web Key Directory].
 //!
 //! [HKP]: https://tools.ietf.org/html/draft-shaw-openpgp-hkp-00
+//! [Web Key Directory]: https://tools.ietf.org/html/draft-koch-openpgp-webkey-service-07
 //!
 //! # Example
 //!
@@ -35,6 +37,7 @@
 extern crate sequoia_openpgp as openpgp;
 extern crate sequoia_core;
 
+extern crate base64;
 #[macro_use]
 extern crate failure;
 extern crate futures;
@@ -48,20 +51,23 @@ extern crate tokio_io;
 extern crate percent_encoding;
 extern crate url;
 
-use hyper::client::{ResponseFuture, HttpConnector};
-use hyper::{Client, Request, Body};
-use hyper_tls::HttpsConnector;
 use native_tls::Certificate;
 use std::convert::From;
+use std::fmt;
+use std::time::Duration;
 use tokio_core::reactor::Core;
-use url::Url;
 
+use openpgp::Fingerprint;
 use openpgp::KeyID;
 use openpgp::TPK;
-use sequoia_core::Context;
+use sequoia_core::{Context, RetryPolicy};
 
 pub mod async;
-use async::url2uri;
+pub use async::{CancelToken, SendReport, Transport};
+pub mod hkp;
+pub use hkp::IndexEntry;
+mod proxy;
+pub mod wkd;
 
 /// For accessing keyservers using HKP.
 pub struct KeyServer {
@@ -77,6 +83,26 @@ impl KeyServer {
         Ok(KeyServer{core: core, ks: ks})
     }
 
+    /// Returns a handle for the given URI using an HTTP proxy.
+    ///
+    /// See [`async::KeyServer::with_proxy`](async/struct.KeyServer.html#method.with_proxy).
+    pub fn with_proxy(ctx: &Context, uri: &str, proxy: &str) -> Result<Self> {
+        let core = Core::new()?;
+        let ks = async::KeyServer::with_proxy(ctx, uri, proxy,
+                                              &core.handle())?;
+        Ok(KeyServer{core: core, ks: ks})
+    }
+
+    /// Returns a handle for the given URI caching retrieved keys.
+    ///
+    /// Keys retrieved using `get` are remembered for `ttl`.  During
+    /// that time, retrieving them again does not contact the server.
+    pub fn with_cache(ctx: &Context, uri: &str, ttl: Duration) -> Result<Self> {
+        let core = Core::new()?;
+        let ks = async::KeyServer::with_cache(ctx, uri, ttl, &core.handle())?;
+        Ok(KeyServer{core: core, ks: ks})
+    }
+
     /// Returns a handle for the given URI.
     ///
     /// `cert` is used to authenticate the server.
@@ -98,6 +124,59 @@ impl KeyServer {
         Self::with_cert(ctx, uri, cert)
     }
 
+    /// Returns a handle for the context's default keyserver.
+    ///
+    /// If the context does not configure a keyserver, the SKS
+    /// keyserver pool is used.  See `Context::keyserver`.
+    pub fn from_context(ctx: &Context) -> Result<Self> {
+        let core = Core::new()?;
+        let ks = async::KeyServer::from_context(ctx, &core.handle())?;
+        Ok(KeyServer{core: core, ks: ks})
+    }
+
+    /// Returns a handle for the given URI using `transport`.
+    ///
+    /// See [`async::KeyServer::with_transport`](async/struct.KeyServer.html#method.with_transport).
+    pub fn with_transport(ctx: &Context, uri: &str,
+                          transport: Box<Transport>) -> Result<Self> {
+        let core = Core::new()?;
+        let ks = async::KeyServer::with_transport(ctx, uri, transport,
+                                                  &core.handle())?;
+        Ok(KeyServer{core: core, ks: ks})
+    }
+
+    /// Returns the normalized URI of the server.
+    ///
+    /// The scheme is either `http` or `https`, and the port is
+    /// explicit.
+    pub fn uri(&self) -> &str {
+        self.ks.uri().as_str()
+    }
+
+    /// Returns the retry policy.
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        self.ks.retry_policy()
+    }
+
+    /// Sets the retry policy.
+    ///
+    /// See [`async::KeyServer::set_retry_policy`](async/struct.KeyServer.html#method.set_retry_policy).
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) -> RetryPolicy {
+        self.ks.set_retry_policy(policy)
+    }
+
+    /// Returns the request timeout.
+    pub fn timeout(&self) -> Duration {
+        self.ks.timeout()
+    }
+
+    /// Sets the request timeout.
+    ///
+    /// See [`async::KeyServer::set_timeout`](async/struct.KeyServer.html#method.set_timeout).
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.ks.set_timeout(timeout)
+    }
+
     /// Retrieves the key with the given `keyid`.
     pub fn get(&mut self, keyid: &KeyID) -> Result<TPK> {
         self.core.run(
@@ -105,34 +184,126 @@ impl KeyServer {
         )
     }
 
+    /// Retrieves the keys with the given `ids`.
+    ///
+    /// The lookups are issued concurrently.  The results are returned
+    /// in the same order as `ids`.  See
+    /// [`async::KeyServer::get_many`](async/struct.KeyServer.html#method.get_many).
+    pub fn get_many(&mut self, ids: &[KeyID]) -> Vec<Result<TPK>> {
+        self.core.run(
+            self.ks.get_many(ids)
+        ).unwrap_or_else(|()| unreachable!())
+    }
+
+    /// Searches the server for keys matching `query`.
+    ///
+    /// See [`async::KeyServer::search`](async/struct.KeyServer.html#method.search).
+    pub fn search(&mut self, query: &str) -> Result<Vec<IndexEntry>> {
+        self.core.run(
+            self.ks.search(query)
+        )
+    }
+
+    /// Retrieves the key with the given fingerprint.
+    ///
+    /// See [`async::KeyServer::get_by_fingerprint`](async/struct.KeyServer.html#method.get_by_fingerprint).
+    pub fn get_by_fingerprint(&mut self, fp: &Fingerprint) -> Result<TPK> {
+        self.core.run(
+            self.ks.get_by_fingerprint(fp)
+        )
+    }
+
+    /// Retrieves the key with the given `keyid`, unless cancelled.
+    ///
+    /// If `token` is cancelled, e.g. from another thread, before the
+    /// key has been retrieved, `Error::Cancelled` is returned.  See
+    /// [`CancelToken`](struct.CancelToken.html) for details.
+    pub fn get_cancellable(&mut self, keyid: &KeyID, token: &CancelToken)
+                           -> Result<TPK> {
+        self.core.run(
+            self.ks.get_cancellable(keyid, token)
+        )
+    }
+
+    /// Checks whether the server is reachable.
+    ///
+    /// Issues a cheap index request and returns the round-trip time.
+    /// As the server answered, a 404 response is considered a success.
+    /// Like all other requests, this is subject to the network policy
+    /// of the context used to create the handle.
+    pub fn ping(&mut self) -> Result<Duration> {
+        self.core.run(
+            self.ks.ping()
+        )
+    }
+
     /// Sends the given key to the server.
     pub fn send(&mut self, key: &TPK) -> Result<()> {
         self.core.run(
             self.ks.send(key)
         )
     }
+
+    /// Sends the given keys to the server in a single request.
+    ///
+    /// See [`async::KeyServer::send_many`](async/struct.KeyServer.html#method.send_many).
+    pub fn send_many(&mut self, keys: &[&TPK]) -> Result<SendReport> {
+        self.core.run(
+            self.ks.send_many(keys)
+        )
+    }
+
+    /// Sends the given key to the server, reporting progress.
+    ///
+    /// `progress` is invoked with the number of bytes sent so far and
+    /// the total number of bytes to send.
+    pub fn send_with_progress<F>(&mut self, key: &TPK, progress: F)
+                                 -> Result<()>
+        where F: Fn(u64, u64)
+    {
+        self.core.run(
+            self.ks.send_with_progress(key, progress)
+        )
+    }
 }
 
-trait AClient {
-    fn do_get(&mut self, uri: Url) -> ResponseFuture;
-    fn do_request(&mut self, request: Request<Body>) -> ResponseFuture;
+/// For retrieving keys from a Web Key Directory.
+///
+/// See the [`wkd`](wkd/index.html) module for details.
+pub struct Wkd {
+    core: Core,
+    wkd: async::Wkd,
 }
 
-impl AClient for Client<HttpConnector> {
-    fn do_get(&mut self, uri: Url) -> ResponseFuture {
-        self.get(url2uri(uri))
-    }
-    fn do_request(&mut self, request: Request<Body>) -> ResponseFuture {
-        self.request(request)
+impl Wkd {
+    /// Returns a handle for retrieving keys from Web Key Directories.
+    ///
+    /// As WKD uses https, the network policy of `ctx` must allow
+    /// encrypted connections.
+    pub fn new(ctx: &Context) -> Result<Self> {
+        let core = Core::new()?;
+        let wkd = async::Wkd::new(ctx, &core.handle())?;
+        Ok(Wkd{core: core, wkd: wkd})
     }
-}
 
-impl AClient for Client<HttpsConnector<HttpConnector>> {
-    fn do_get(&mut self, uri: Url) -> ResponseFuture {
-        self.get(url2uri(uri))
+    /// Retrieves the key for the given email address.
+    ///
+    /// This is a convenience function for one-off lookups.  It
+    /// creates a handle honoring the network policy of `ctx`, and
+    /// retrieves the key using [`Wkd::get_by_email`].
+    ///
+    ///   [`Wkd::get_by_email`]: #method.get_by_email
+    pub fn get(ctx: &Context, email: &str) -> Result<TPK> {
+        Self::new(ctx)?.get_by_email(email)
     }
-    fn do_request(&mut self, request: Request<Body>) -> ResponseFuture {
-        self.request(request)
+
+    /// Retrieves the key for the given email address.
+    ///
+    /// See [`async::Wkd::get_by_email`](async/struct.Wkd.html#method.get_by_email).
+    pub fn get_by_email(&mut self, email: &str) -> Result<TPK> {
+        self.core.run(
+            self.wkd.get_by_email(email)
+        )
     }
 }
 
@@ -148,15 +319,18 @@ pub enum Error {
     /// A given keyserver URI was malformed.
     #[fail(display = "Malformed URI; expected hkp: or hkps:")]
     MalformedUri,
+    /// A given email address was malformed.
+    #[fail(display = "Malformed email address {:?}", _0)]
+    MalformedEmail(String),
     /// The server provided malformed data.
     #[fail(display = "Malformed response from server")]
     MalformedResponse,
     /// A communication partner violated the protocol.
     #[fail(display = "Protocol violation")]
     ProtocolViolation,
-    /// Encountered an unexpected low-level http status.
-    #[fail(display = "Error communicating with server")]
-    HttpStatus(hyper::StatusCode),
+    /// The server redirected us too many times.
+    #[fail(display = "Too many redirects")]
+    TooManyRedirects,
     /// A `hyper::error::UriError` occurred.
     #[fail(display = "URI Error")]
     UriError(url::ParseError),
@@ -169,6 +343,78 @@ pub enum Error {
     /// A `native_tls::Error` occurred.
     #[fail(display = "TLS Error")]
     TlsError(native_tls::Error),
+    /// The request was cancelled.
+    #[fail(display = "Request cancelled")]
+    Cancelled,
+    /// The request timed out.
+    #[fail(display = "Request timed out")]
+    Timeout,
+    /// The keyserver rejected the request.
+    #[fail(display = "{}", _0)]
+    KeyServer(KeyServerError),
+    /// The server returned a key other than the requested one.
+    #[fail(display = "Expected key {}, but the server returned {}",
+           expected, found)]
+    UnexpectedKey {
+        /// The fingerprint of the requested key.
+        expected: Fingerprint,
+        /// The fingerprint of the returned key.
+        found: Fingerprint,
+    },
+    /// The server returned a key not matching the requested KeyID.
+    #[fail(display = "Expected key {}, but the server returned {}",
+           expected, found)]
+    UnexpectedKeyID {
+        /// The requested KeyID.
+        expected: KeyID,
+        /// The fingerprint of the returned key.
+        found: Fingerprint,
+    },
+}
+
+/// Details about a request that a keyserver rejected.
+///
+/// Returned as `Error::KeyServer` when the server answers a request
+/// with an unexpected status code.  This allows callers to implement
+/// server-specific handling, e.g. rate limiting.
+#[derive(Debug, Clone)]
+pub struct KeyServerError {
+    /// The status code of the response.
+    pub status: hyper::StatusCode,
+    /// The headers of the response.
+    pub headers: hyper::HeaderMap,
+}
+
+impl KeyServerError {
+    /// Captures the status and headers of `response`.
+    pub(crate) fn from_response<B>(response: &hyper::Response<B>) -> Self {
+        KeyServerError {
+            status: response.status(),
+            headers: response.headers().clone(),
+        }
+    }
+
+    /// Returns the delay requested using the `Retry-After` header.
+    ///
+    /// Only delays given in seconds are supported, HTTP dates are
+    /// ignored.
+    pub fn retry_after(&self) -> Option<Duration> {
+        retry_after(&self.headers)
+    }
+}
+
+impl fmt::Display for KeyServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Keyserver responded with {}", self.status)
+    }
+}
+
+/// Parses the `Retry-After` header, if it is given in seconds.
+pub(crate) fn retry_after(headers: &hyper::HeaderMap) -> Option<Duration> {
+    headers.get(hyper::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
 }
 
 impl From<http::Error> for Error {
@@ -211,4 +457,43 @@ mod tests {
         assert!(KeyServer::new(&ctx, "hkp://keys.openpgp.org").is_err());
         assert!(KeyServer::new(&ctx, "hkps://keys.openpgp.org").is_ok());
     }
+
+    #[test]
+    fn wkd_requires_encryption() {
+        let ctx = Context::configure()
+            .network_policy(sequoia_core::NetworkPolicy::Offline)
+            .build().unwrap();
+        assert!(Wkd::new(&ctx).is_err());
+        assert!(Wkd::get(&ctx, "joe@example.org").is_err());
+
+        let ctx = Context::configure()
+            .network_policy(sequoia_core::NetworkPolicy::Encrypted)
+            .build().unwrap();
+        assert!(Wkd::new(&ctx).is_ok());
+    }
+
+    #[test]
+    fn uri_normalization() {
+        let ctx = Context::configure()
+            .network_policy(sequoia_core::NetworkPolicy::Insecure)
+            .build().unwrap();
+
+        let n = |uri| KeyServer::new(&ctx, uri).unwrap().ks.uri().to_string();
+
+        assert_eq!(n("keys.openpgp.org"), "https://keys.openpgp.org/");
+        assert_eq!(n("hkp://keys.openpgp.org"),
+                   "http://keys.openpgp.org:11371/");
+        assert_eq!(n("hkp://keys.openpgp.org/"),
+                   "http://keys.openpgp.org:11371/");
+        assert_eq!(n("hkp://Keys.OpenPGP.org:80"),
+                   "http://keys.openpgp.org/");
+        assert_eq!(n("hkps://keys.openpgp.org"),
+                   "https://keys.openpgp.org/");
+        assert_eq!(n("hkps://keys.openpgp.org:8443/"),
+                   "https://keys.openpgp.org:8443/");
+        assert_eq!(n("hkps://example.org/keyserver"),
+                   "https://example.org/keyserver/");
+        assert_eq!(n("hkps://example.org/keyserver/"),
+                   "https://example.org/keyserver/");
+    }
 }
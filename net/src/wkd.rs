@@ -0,0 +1,157 @@
+//! For retrieving keys from a Web Key Directory.
+//!
+//! A [Web Key Directory] (WKD) publishes keys at well-known https
+//! URLs derived from email addresses.  This module computes these
+//! URLs.  See `Wkd` for retrieving keys.
+//!
+//! [Web Key Directory]: https://tools.ietf.org/html/draft-koch-openpgp-webkey-service-07
+
+use url::Url;
+
+use openpgp::TPK;
+use openpgp::constants::HashAlgorithm;
+
+use super::{Error, Result};
+
+/// The z-base-32 alphabet.
+const ZBASE32: &'static [u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+/// Encodes `data` using z-base-32.
+fn zbase32(data: &[u8]) -> String {
+    let mut s = String::with_capacity((data.len() * 8 + 4) / 5);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for &b in data {
+        buffer = (buffer << 8) | b as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            s.push(ZBASE32[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        s.push(ZBASE32[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    s
+}
+
+/// Splits `email` into the local part and the lowercased domain.
+fn split_email(email: &str) -> Result<(&str, String)> {
+    let at = email.rfind('@')
+        .ok_or_else(|| Error::MalformedEmail(email.into()))?;
+    let (local_part, domain) = (&email[..at], &email[at + 1..]);
+    if local_part.is_empty() || domain.is_empty() {
+        return Err(Error::MalformedEmail(email.into()).into());
+    }
+    Ok((local_part, domain.to_lowercase()))
+}
+
+/// Returns the hashed local part of an email address.
+///
+/// The local part is lowercased, hashed using SHA-1, and encoded
+/// using z-base-32.
+///
+/// # Example
+///
+/// ```
+/// # extern crate sequoia_net;
+/// # use sequoia_net::wkd::encode_local_part;
+/// assert_eq!(encode_local_part("Joe.Doe"),
+///            "iy9q119eutrkn8s1mk4r39qejnbu3n5q");
+/// ```
+pub fn encode_local_part(local_part: &str) -> String {
+    let mut hash = HashAlgorithm::SHA1.context()
+        .expect("SHA-1 is supported");
+    hash.update(local_part.to_lowercase().as_bytes());
+    let mut digest = vec![0; hash.digest_size()];
+    hash.digest(&mut digest);
+    zbase32(&digest)
+}
+
+/// Returns the URLs to retrieve the key for `email` from.
+///
+/// The first URL uses the advanced method, which uses the
+/// `openpgpkey` subdomain, the second one uses the direct method.
+/// Clients should try them in this order.
+///
+/// # Example
+///
+/// ```
+/// # extern crate sequoia_net;
+/// # use sequoia_net::wkd::urls;
+/// # fn main() { f().unwrap(); }
+/// # fn f() -> sequoia_net::Result<()> {
+/// let [advanced, direct] = urls("Joe.Doe@Example.ORG")?;
+/// assert_eq!(advanced.as_str(),
+///            "https://openpgpkey.example.org/.well-known/openpgpkey/\
+///             example.org/hu/iy9q119eutrkn8s1mk4r39qejnbu3n5q?l=Joe.Doe");
+/// assert_eq!(direct.as_str(),
+///            "https://example.org/.well-known/openpgpkey/\
+///             hu/iy9q119eutrkn8s1mk4r39qejnbu3n5q?l=Joe.Doe");
+/// # Ok(())
+/// # }
+/// ```
+pub fn urls(email: &str) -> Result<[Url; 2]> {
+    let (local_part, domain) = split_email(email)?;
+    let hash = encode_local_part(local_part);
+
+    let mut advanced: Url = format!(
+        "https://openpgpkey.{}/.well-known/openpgpkey/{}/hu/{}",
+        domain, domain, hash).parse()?;
+    advanced.query_pairs_mut().append_pair("l", local_part);
+    let mut direct: Url = format!(
+        "https://{}/.well-known/openpgpkey/hu/{}",
+        domain, hash).parse()?;
+    direct.query_pairs_mut().append_pair("l", local_part);
+    Ok([advanced, direct])
+}
+
+/// Returns whether `tpk` has a user ID with the given address.
+///
+/// Keys published in a WKD must be ignored unless they carry a user
+/// ID matching the address that was looked up.
+pub(crate) fn tpk_matches(tpk: &TPK, email: &str) -> bool {
+    let (local_part, domain) = match split_email(email) {
+        Ok(parts) => parts,
+        Err(_) => return false,
+    };
+    let email = format!("{}@{}", local_part.to_lowercase(), domain);
+    tpk.userids().any(|uidb| {
+        uidb.userid().address_normalized().ok()
+            .and_then(|a| a)
+            .map(|a| a == email)
+            .unwrap_or(false)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zbase32_encoding() {
+        assert_eq!(zbase32(b""), "");
+        assert_eq!(zbase32(&[0]), "yy");
+        assert_eq!(zbase32(&[0xff]), "9h");
+        assert_eq!(zbase32(b"\xf0\xbf\xc7"), "6n9hq");
+    }
+
+    #[test]
+    fn matching_userids() {
+        use openpgp::parse::Parse;
+
+        let tpk = TPK::from_bytes(
+            include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
+        assert!(tpk_matches(&tpk, "testy@example.org"));
+        assert!(tpk_matches(&tpk, "Testy@Example.ORG"));
+        assert!(! tpk_matches(&tpk, "nesty@example.org"));
+        assert!(! tpk_matches(&tpk, "testy@example.com"));
+    }
+
+    #[test]
+    fn malformed_emails() {
+        assert!(urls("example.org").is_err());
+        assert!(urls("@example.org").is_err());
+        assert!(urls("joe@").is_err());
+    }
+}
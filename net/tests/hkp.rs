@@ -1,3 +1,4 @@
+extern crate failure;
 extern crate futures;
 extern crate http;
 extern crate hyper;
@@ -14,9 +15,14 @@ use hyper::service::service_fn;
 use hyper::{Method, StatusCode};
 use rand::RngCore;
 use rand::rngs::OsRng;
+use url::Url;
+use std::cell::RefCell;
 use std::io::Cursor;
 use std::net::{SocketAddr, IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
+use std::time::Duration;
 
 extern crate sequoia_openpgp as openpgp;
 extern crate sequoia_core;
@@ -26,8 +32,9 @@ use openpgp::armor::Reader;
 use openpgp::TPK;
 use openpgp::{Fingerprint, KeyID};
 use openpgp::parse::Parse;
-use sequoia_core::{Context, NetworkPolicy};
-use sequoia_net::KeyServer;
+use openpgp::tpk::TPKParser;
+use sequoia_core::{Context, NetworkPolicy, RetryPolicy};
+use sequoia_net::{Error, KeyServer, Transport, hkp};
 
 const RESPONSE: &'static str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
 
@@ -64,6 +71,19 @@ Pu1xwz57O4zo1VYf6TqHJzVC3OMvMUM2hhdecMUe5x6GorNaj6g=
 const FP: &'static str = "3E8877C877274692975189F5D03F6F865226FE8B";
 const ID: &'static str = "D03F6F865226FE8B";
 
+/// Fingerprints of the other keys the server accepts.
+const OTHER_FPS: &[&'static str] = &[
+    // neal.pgp
+    "8F17777118A33DDA9BA48E62AACB3243630052D9",
+    // dennis-simon-anton.pgp
+    "5BFBCD2A23E6866B77198C1147606B18E3D45CE9",
+];
+
+const INDEX: &'static str = "info:1:1
+pub:3E8877C877274692975189F5D03F6F865226FE8B:1:2048:1511355130::
+uid:Testy McTestface <testy%40example.org>:1511355130::
+";
+
 fn service(req: Request<Body>)
            -> Box<Future<Item=Response<Body>, Error=hyper::Error> + Send> {
     let (parts, body) = req.into_parts();
@@ -72,9 +92,41 @@ fn service(req: Request<Body>)
             if let Some(args) = parts.uri.query() {
                 for (key, value) in url::form_urlencoded::parse(args.as_bytes()) {
                     match key.clone().into_owned().as_ref() {
+                        "op" if value == "index" => {
+                            let search = url::form_urlencoded::parse(
+                                args.as_bytes())
+                                .find(|&(ref k, _)| k == "search")
+                                .map(|(_, v)| v.into_owned());
+                            if search.as_ref().map(|s| s.as_str())
+                                == Some("testy@example.org")
+                            {
+                                return Box::new(futures::future::ok(
+                                    Response::new(Body::from(INDEX))));
+                            }
+
+                            // Ping, or no such key.
+                            return Box::new(futures::future::ok(
+                                Response::builder()
+                                    .status(StatusCode::NOT_FOUND)
+                                    .body(Body::from("Not found")).unwrap()));
+                        },
                         "op" => assert_eq!(value, "get"),
                         "options" => assert_eq!(value, "mr"),
-                        "search" => assert_eq!(value, "0xD03F6F865226FE8B"),
+                        "search" if value == "0x0000000000000000" => {
+                            // There is no such key.
+                            return Box::new(futures::future::ok(
+                                Response::builder()
+                                    .status(StatusCode::NOT_FOUND)
+                                    .body(Body::from("Not found")).unwrap()));
+                        },
+                        // Fingerprints are accepted as well, but the
+                        // server always returns the same key.  So
+                        // does this KeyID, which is not Testy's.
+                        "search" => assert!(value == "0xD03F6F865226FE8B"
+                                            || value == "0x0123456789ABCDEF"
+                                            || (value.starts_with("0x")
+                                                && value.len() == 42),
+                                            "Bad search: {}", value),
                         _ => panic!("Bad query: {}:{}", key, value),
                     }
                 }
@@ -91,13 +143,20 @@ fn service(req: Request<Body>)
                         for (key, value) in url::form_urlencoded::parse(b.as_ref()) {
                             match key.clone().into_owned().as_ref() {
                                 "keytext" => {
-			            let key = TPK::from_reader(
+                                    // This may be a keyring.
+			            let keys = TPKParser::from_reader(
                                         Reader::new(Cursor::new(value.into_owned()),
-                                                    None)).unwrap();
-                                    assert_eq!(
-                                        key.fingerprint(),
-                                        Fingerprint::from_hex(FP)
-                                            .unwrap());
+                                                    None)).unwrap()
+                                        .collect::<Vec<_>>();
+                                    assert!(! keys.is_empty());
+                                    for key in keys {
+                                        let fp = key.unwrap().fingerprint()
+                                            .to_hex();
+                                        assert!(fp == FP
+                                                || OTHER_FPS.contains(
+                                                    &fp.as_str()),
+                                                "Unexpected key {}", fp);
+                                    }
                                 },
                                 _ => panic!("Bad post: {}:{}", key, value),
                             }
@@ -106,6 +165,25 @@ fn service(req: Request<Body>)
                         Response::new(Body::from("Ok"))
                     }))
         },
+        (Method::GET, "/redirect/pks/lookup") => {
+            // Moved to the root.
+            let location = format!("/pks/lookup?{}",
+                                   parts.uri.query().unwrap_or(""));
+            Box::new(futures::future::ok(
+                Response::builder()
+                    .status(StatusCode::MOVED_PERMANENTLY)
+                    .header("Location", location)
+                    .body(Body::empty()).unwrap()))
+        },
+        (Method::GET, "/loop/pks/lookup") => {
+            // Redirects to itself.
+            let location = format!("{}", parts.uri);
+            Box::new(futures::future::ok(
+                Response::builder()
+                    .status(StatusCode::FOUND)
+                    .header("Location", location)
+                    .body(Body::empty()).unwrap()))
+        },
         _ => {
             Box::new(futures::future::ok(Response::builder()
                                          .status(StatusCode::NOT_FOUND)
@@ -119,6 +197,11 @@ fn service(req: Request<Body>)
 /// Returns the address, a channel to drop() to kill the server, and
 /// the thread handle to join the server thread.
 fn start_server() -> SocketAddr {
+    start_counting_server(Arc::new(AtomicUsize::new(0)))
+}
+
+/// Starts a server on a random port, counting the requests.
+fn start_counting_server(requests: Arc<AtomicUsize>) -> SocketAddr {
     let (tx, rx) = oneshot::channel::<SocketAddr>();
     thread::spawn(move || {
         let (addr, server) = loop {
@@ -132,7 +215,13 @@ fn start_server() -> SocketAddr {
 
         tx.send(addr).unwrap();
         hyper::rt::run(server
-                       .serve(|| service_fn(service))
+                       .serve(move || {
+                           let requests = requests.clone();
+                           service_fn(move |req| {
+                               requests.fetch_add(1, Ordering::SeqCst);
+                               service(req)
+                           })
+                       })
                        .map_err(|e| panic!("{}", e)));
     });
 
@@ -140,6 +229,55 @@ fn start_server() -> SocketAddr {
     addr
 }
 
+/// Serves requests by calling `service` directly, counting them.
+struct CannedTransport {
+    requests: Arc<AtomicUsize>,
+}
+
+impl Transport for CannedTransport {
+    fn get(&mut self, uri: Url)
+           -> Box<Future<Item=Response<Body>, Error=failure::Error>> {
+        self.post(Request::get(uri.as_str()).body(Body::empty()).unwrap())
+    }
+
+    fn post(&mut self, request: Request<Body>)
+            -> Box<Future<Item=Response<Body>, Error=failure::Error>> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        Box::new(service(request).from_err())
+    }
+}
+
+/// Fails the first `failures` requests with the given status.
+struct FlakyTransport {
+    failures: usize,
+    status: StatusCode,
+    retry_after: Option<&'static str>,
+    inner: CannedTransport,
+}
+
+impl Transport for FlakyTransport {
+    fn get(&mut self, uri: Url)
+           -> Box<Future<Item=Response<Body>, Error=failure::Error>> {
+        self.post(Request::get(uri.as_str()).body(Body::empty()).unwrap())
+    }
+
+    fn post(&mut self, request: Request<Body>)
+            -> Box<Future<Item=Response<Body>, Error=failure::Error>> {
+        if self.failures > 0 {
+            self.failures -= 1;
+            self.inner.requests.fetch_add(1, Ordering::SeqCst);
+            let mut response = Response::builder();
+            response.status(self.status);
+            if let Some(retry_after) = self.retry_after {
+                response.header("Retry-After", retry_after);
+            }
+            return Box::new(futures::future::ok(
+                response.body(Body::empty()).unwrap()));
+        }
+        self.inner.post(request)
+    }
+}
+
 #[test]
 fn get() {
     let ctx = Context::configure()
@@ -159,6 +297,292 @@ fn get() {
                Fingerprint::from_hex(FP).unwrap());
 }
 
+#[test]
+fn get_by_fingerprint() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // Start server.
+    let addr = start_server();
+
+    let mut keyserver =
+        KeyServer::new(&ctx, &format!("hkp://{}", addr)).unwrap();
+    let fp = Fingerprint::from_hex(FP).unwrap();
+    let key = keyserver.get_by_fingerprint(&fp).unwrap();
+    assert_eq!(key.fingerprint(), fp);
+
+    // The server returns the wrong key.
+    let other = Fingerprint::from_hex(
+        "0000000000000000000000000000000000000000").unwrap();
+    match keyserver.get_by_fingerprint(&other).unwrap_err()
+        .downcast::<Error>()
+    {
+        Ok(Error::UnexpectedKey { expected, found }) => {
+            assert_eq!(expected, other);
+            assert_eq!(found, fp);
+        },
+        e => panic!("Expected UnexpectedKey, got {:?}", e),
+    }
+}
+
+#[test]
+fn get_from_context() {
+    // Start server.
+    let addr = start_server();
+
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .keyserver(format!("hkp://{}", addr))
+        .build().unwrap();
+
+    let mut keyserver = KeyServer::from_context(&ctx).unwrap();
+    let keyid = KeyID::from_hex(ID).unwrap();
+    let key = keyserver.get(&keyid).unwrap();
+
+    assert_eq!(key.fingerprint(),
+               Fingerprint::from_hex(FP).unwrap());
+}
+
+#[test]
+fn get_many() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // Start server.
+    let requests = Arc::new(AtomicUsize::new(0));
+    let addr = start_counting_server(requests.clone());
+
+    let mut keyserver =
+        KeyServer::new(&ctx, &format!("hkp://{}", addr)).unwrap();
+    let keyid = KeyID::from_hex(ID).unwrap();
+    let missing = KeyID::from_hex("0000000000000000").unwrap();
+    let keys = keyserver.get_many(&[keyid.clone(), missing, keyid]);
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+    assert_eq!(keys.len(), 3);
+    assert_eq!(keys[0].as_ref().unwrap().fingerprint(),
+               Fingerprint::from_hex(FP).unwrap());
+    match keys[1].as_ref().unwrap_err().downcast_ref::<Error>() {
+        Some(Error::NotFound) => (),
+        e => panic!("Expected NotFound, got {:?}", e),
+    }
+    assert_eq!(keys[2].as_ref().unwrap().fingerprint(),
+               Fingerprint::from_hex(FP).unwrap());
+
+    assert!(keyserver.get_many(&[]).is_empty());
+}
+
+#[test]
+fn search() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // Start server.
+    let addr = start_server();
+
+    let mut keyserver =
+        KeyServer::new(&ctx, &format!("hkp://{}", addr)).unwrap();
+    let index = keyserver.search("testy@example.org").unwrap();
+    assert_eq!(index.len(), 1);
+    assert_eq!(index[0].keyid, KeyID::from_hex(ID).unwrap());
+    assert_eq!(index[0].fingerprint, Some(Fingerprint::from_hex(FP).unwrap()));
+    assert_eq!(index[0].userids, ["Testy McTestface <testy@example.org>"]);
+
+    // No match.
+    assert!(keyserver.search("nobody@example.org").unwrap().is_empty());
+}
+
+#[test]
+fn fetch() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // Start server.
+    let addr = start_server();
+
+    let mut keyserver =
+        KeyServer::new(&ctx, &format!("hkp://{}", addr)).unwrap();
+    let index = keyserver.search("testy@example.org").unwrap();
+    let tpk = index[0].fetch(&mut keyserver).unwrap();
+    assert_eq!(tpk.fingerprint(), Fingerprint::from_hex(FP).unwrap());
+
+    // Entries without fingerprint are fetched by KeyID.
+    let index = hkp::parse_index(
+        format!("pub:{}:1:2048:::\n", ID).as_bytes()).unwrap();
+    let tpk = index[0].fetch(&mut keyserver).unwrap();
+    assert_eq!(tpk.fingerprint(), Fingerprint::from_hex(FP).unwrap());
+
+    // The server returns Testy for other keys, too.
+    let index = hkp::parse_index(b"\
+pub:0123456789ABCDEF0123456789ABCDEF01234567:1:2048:::
+pub:0123456789ABCDEF:1:2048:::
+").unwrap();
+    match index[0].fetch(&mut keyserver).unwrap_err().downcast::<Error>() {
+        Ok(Error::UnexpectedKey { .. }) => (),
+        e => panic!("Expected UnexpectedKey, got {:?}", e),
+    }
+    match index[1].fetch(&mut keyserver).unwrap_err().downcast::<Error>() {
+        Ok(Error::UnexpectedKeyID { .. }) => (),
+        e => panic!("Expected UnexpectedKeyID, got {:?}", e),
+    }
+}
+
+/// Starts a CONNECT proxy on a random port.
+///
+/// Every request is recorded in `requests`.
+fn start_proxy(requests: Arc<std::sync::Mutex<Vec<String>>>) -> SocketAddr {
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind((IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for client in listener.incoming() {
+            let mut client = client.unwrap();
+            let mut reader = BufReader::new(client.try_clone().unwrap());
+            let mut request = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                request.push_str(&line);
+            }
+
+            let target = request.split(' ').nth(1).unwrap().to_string();
+            assert!(request.starts_with("CONNECT "), "{}", request);
+            requests.lock().unwrap().push(request);
+
+            let server = TcpStream::connect(&target).unwrap();
+            client.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .unwrap();
+
+            let mut c = client.try_clone().unwrap();
+            let mut s = server.try_clone().unwrap();
+            thread::spawn(move || io::copy(&mut reader, &mut s));
+            let mut server = server;
+            thread::spawn(move || io::copy(&mut server, &mut c));
+        }
+    });
+    addr
+}
+
+#[test]
+fn get_via_proxy() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // Start server and proxy.
+    let addr = start_server();
+    let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let proxy = start_proxy(requests.clone());
+
+    let mut keyserver =
+        KeyServer::with_proxy(&ctx, &format!("hkp://{}", addr),
+                              &format!("http://alice:secret@{}", proxy))
+        .unwrap();
+    let keyid = KeyID::from_hex(ID).unwrap();
+    let key = keyserver.get(&keyid).unwrap();
+    assert_eq!(key.fingerprint(),
+               Fingerprint::from_hex(FP).unwrap());
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].starts_with(&format!("CONNECT {} HTTP/1.1\r\n", addr)));
+    // "alice:secret", base64 encoded.
+    assert!(requests[0].contains(
+        "Proxy-Authorization: Basic YWxpY2U6c2VjcmV0\r\n"));
+}
+
+#[test]
+fn get_cached() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // Start server.
+    let requests = Arc::new(AtomicUsize::new(0));
+    let addr = start_counting_server(requests.clone());
+
+    let mut keyserver =
+        KeyServer::with_cache(&ctx, &format!("hkp://{}", addr),
+                              Duration::from_secs(60)).unwrap();
+    let keyid = KeyID::from_hex(ID).unwrap();
+    let key = keyserver.get(&keyid).unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    // The second request is served from the cache.
+    let cached = keyserver.get(&keyid).unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    assert_eq!(key, cached);
+}
+
+#[test]
+fn get_redirected() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // Start server.
+    let addr = start_server();
+
+    let mut keyserver =
+        KeyServer::new(&ctx, &format!("hkp://{}/redirect", addr)).unwrap();
+    let keyid = KeyID::from_hex(ID).unwrap();
+    let key = keyserver.get(&keyid).unwrap();
+
+    assert_eq!(key.fingerprint(),
+               Fingerprint::from_hex(FP).unwrap());
+}
+
+#[test]
+fn get_redirect_loop() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // Start server.
+    let addr = start_server();
+
+    let mut keyserver =
+        KeyServer::new(&ctx, &format!("hkp://{}/loop/", addr)).unwrap();
+    let keyid = KeyID::from_hex(ID).unwrap();
+    match keyserver.get(&keyid).unwrap_err().downcast::<Error>() {
+        Ok(Error::TooManyRedirects) => (),
+        e => panic!("Expected TooManyRedirects, got {:?}", e),
+    }
+
+    // Retrying does not help, hence we fail right away.
+    keyserver.set_retry_policy(RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_secs(10),
+        max_delay: Duration::from_secs(10),
+        jitter: false,
+    });
+    let start = ::std::time::Instant::now();
+    match keyserver.get(&keyid).unwrap_err().downcast::<Error>() {
+        Ok(Error::TooManyRedirects) => (),
+        e => panic!("Expected TooManyRedirects, got {:?}", e),
+    }
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
 #[test]
 fn send() {
     let ctx = Context::configure()
@@ -175,3 +599,325 @@ fn send() {
                                            None)).unwrap();
     keyserver.send(&key).unwrap();
 }
+
+#[test]
+fn send_with_progress() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // Start server.
+    let addr = start_server();
+    let mut keyserver =
+        KeyServer::new(&ctx, &format!("hkp://{}", addr)).unwrap();
+    let key = TPK::from_reader(Reader::new(Cursor::new(RESPONSE),
+                                           None)).unwrap();
+
+    let calls = RefCell::new(Vec::new());
+    keyserver.send_with_progress(&key, |sent, total| {
+        calls.borrow_mut().push((sent, total));
+    }).unwrap();
+
+    let calls = calls.into_inner();
+    assert!(!calls.is_empty());
+    let total = calls[0].1;
+    let mut last = 0;
+    for &(sent, t) in calls.iter() {
+        assert_eq!(t, total);
+        assert!(sent > last);
+        last = sent;
+    }
+    assert_eq!(last, total);
+}
+
+#[test]
+fn send_many() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // Start server.
+    let requests = Arc::new(AtomicUsize::new(0));
+    let addr = start_counting_server(requests.clone());
+    let mut keyserver =
+        KeyServer::new(&ctx, &format!("hkp://{}", addr)).unwrap();
+    let blobs: &[&[u8]] = &[
+        include_bytes!("../../openpgp/tests/data/keys/testy.pgp"),
+        include_bytes!("../../openpgp/tests/data/keys/neal.pgp"),
+        include_bytes!("../../openpgp/tests/data/keys/dennis-simon-anton.pgp"),
+    ];
+    let keys: Vec<TPK> =
+        blobs.iter().map(|b| TPK::from_bytes(b).unwrap()).collect();
+    let report = keyserver.send_many(&keys.iter().collect::<Vec<_>>())
+        .unwrap();
+    assert_eq!(report.fingerprints,
+               keys.iter().map(|k| k.fingerprint()).collect::<Vec<_>>());
+    assert!(report.bytes
+            > blobs.iter().map(|b| b.len() as u64).sum::<u64>());
+    // All keys are sent in one request.
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    // Sending no keys is an error, and does not hit the server.
+    match keyserver.send_many(&[]).unwrap_err()
+        .downcast::<openpgp::Error>()
+    {
+        Ok(openpgp::Error::InvalidArgument(_)) => (),
+        e => panic!("Expected InvalidArgument, got {:?}", e),
+    }
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn get_cancellable() {
+    use std::net::TcpListener;
+    use sequoia_net::CancelToken;
+
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // A cancelled token fails requests right away.
+    let addr = start_server();
+    let mut keyserver =
+        KeyServer::new(&ctx, &format!("hkp://{}", addr)).unwrap();
+    let keyid = KeyID::from_hex(ID).unwrap();
+    let token = CancelToken::new();
+    keyserver.get_cancellable(&keyid, &token).unwrap();
+    token.cancel();
+    match keyserver.get_cancellable(&keyid, &token).unwrap_err()
+        .downcast::<Error>()
+    {
+        Ok(Error::Cancelled) => (),
+        e => panic!("Expected Cancelled, got {:?}", e),
+    }
+
+    // A server that never answers.
+    let listener = TcpListener::bind((IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .unwrap();
+    let mut keyserver = KeyServer::new(
+        &ctx, &format!("hkp://{}", listener.local_addr().unwrap())).unwrap();
+    let token = CancelToken::new();
+    let t = token.clone();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        t.cancel();
+    });
+    match keyserver.get_cancellable(&keyid, &token).unwrap_err()
+        .downcast::<Error>()
+    {
+        Ok(Error::Cancelled) => (),
+        e => panic!("Expected Cancelled, got {:?}", e),
+    }
+    canceller.join().unwrap();
+    drop(listener);
+}
+
+#[test]
+fn timeout() {
+    use std::net::TcpListener;
+
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // A server that never answers.
+    let listener = TcpListener::bind((IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .unwrap();
+    let mut keyserver = KeyServer::new(
+        &ctx, &format!("hkp://{}", listener.local_addr().unwrap())).unwrap();
+    assert_eq!(keyserver.timeout(), Duration::from_secs(30));
+    keyserver.set_timeout(Duration::from_millis(100));
+
+    let keyid = KeyID::from_hex(ID).unwrap();
+    match keyserver.get(&keyid).unwrap_err().downcast::<Error>() {
+        Ok(Error::Timeout) => (),
+        e => panic!("Expected Timeout, got {:?}", e),
+    }
+
+    let key = TPK::from_reader(Reader::new(Cursor::new(RESPONSE),
+                                           None)).unwrap();
+    match keyserver.send(&key).unwrap_err().downcast::<Error>() {
+        Ok(Error::Timeout) => (),
+        e => panic!("Expected Timeout, got {:?}", e),
+    }
+    drop(listener);
+}
+
+#[test]
+fn logged() {
+    use std::sync::Mutex;
+    use sequoia_core::LogEvent;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .logger(move |e| sink.lock().unwrap().push(e))
+        .build().unwrap();
+
+    // Start server.
+    let addr = start_server();
+
+    let mut keyserver =
+        KeyServer::new(&ctx, &format!("hkp://{}", addr)).unwrap();
+    let key = keyserver.get(&KeyID::from_hex(ID).unwrap()).unwrap();
+    keyserver.send(&key).unwrap();
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 2);
+    match events[0] {
+        LogEvent::KeyserverGet { ref handle, error: None, .. } =>
+            assert_eq!(handle, ID),
+        ref e => panic!("unexpected event: {:?}", e),
+    }
+    match events[1] {
+        LogEvent::KeyserverSend { keys: 1, error: None, .. } => (),
+        ref e => panic!("unexpected event: {:?}", e),
+    }
+}
+
+#[test]
+fn ping() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    // Start server.
+    let addr = start_server();
+
+    let mut keyserver =
+        KeyServer::new(&ctx, &format!("hkp://{}", addr)).unwrap();
+    keyserver.ping().unwrap();
+}
+
+#[test]
+fn get_with_transport() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let mut keyserver =
+        KeyServer::with_transport(&ctx, "hkp://keys.example.org",
+                                  Box::new(CannedTransport {
+                                      requests: requests.clone(),
+                                  })).unwrap();
+    let keyid = KeyID::from_hex(ID).unwrap();
+    let key = keyserver.get(&keyid).unwrap();
+    assert_eq!(key.fingerprint(),
+               Fingerprint::from_hex(FP).unwrap());
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    keyserver.send(&key).unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn with_transport_checks_uri() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Encrypted)
+        .build().unwrap();
+    let transport = || Box::new(CannedTransport {
+        requests: Arc::new(AtomicUsize::new(0)),
+    });
+
+    assert!(KeyServer::with_transport(&ctx, "ftp://keys.example.org",
+                                      transport()).is_err());
+    // The network policy is enforced.
+    assert!(KeyServer::with_transport(&ctx, "hkp://keys.example.org",
+                                      transport()).is_err());
+}
+
+#[test]
+fn get_retries() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+    let keyid = KeyID::from_hex(ID).unwrap();
+    let keyserver = |failures, requests: &Arc<AtomicUsize>| {
+        let mut ks = KeyServer::with_transport(
+            &ctx, "hkp://keys.example.org",
+            Box::new(FlakyTransport {
+                failures: failures,
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                retry_after: None,
+                inner: CannedTransport { requests: requests.clone() },
+            })).unwrap();
+        ks.set_retry_policy(RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(20),
+            jitter: false,
+        });
+        ks
+    };
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let key = keyserver(2, &requests).get(&keyid).unwrap();
+    assert_eq!(key.fingerprint(), Fingerprint::from_hex(FP).unwrap());
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+    // The last error is reported once the attempts are exhausted.
+    let requests = Arc::new(AtomicUsize::new(0));
+    match keyserver(3, &requests).get(&keyid).unwrap_err()
+        .downcast::<Error>().unwrap()
+    {
+        Error::KeyServer(ref e) if e.status == StatusCode::SERVICE_UNAVAILABLE
+            => (),
+        e => panic!("Unexpected error: {:?}", e),
+    }
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn rate_limited() {
+    use std::time::Instant;
+
+    let ctx = Context::configure()
+        .ephemeral()
+        .network_policy(NetworkPolicy::Insecure)
+        .build().unwrap();
+    let keyid = KeyID::from_hex(ID).unwrap();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let mut ks = KeyServer::with_transport(
+        &ctx, "hkp://keys.example.org",
+        Box::new(FlakyTransport {
+            failures: 2,
+            status: StatusCode::TOO_MANY_REQUESTS,
+            retry_after: Some("1"),
+            inner: CannedTransport { requests: requests.clone() },
+        })).unwrap();
+
+    // Without retrying, the server's hint is surfaced.
+    match ks.get(&keyid).unwrap_err().downcast::<Error>().unwrap() {
+        Error::KeyServer(e) => {
+            assert_eq!(e.status, StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(e.headers.get("Retry-After").unwrap(), "1");
+            assert_eq!(e.retry_after(), Some(Duration::from_secs(1)));
+        },
+        e => panic!("Unexpected error: {:?}", e),
+    }
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    // With retrying, the hint overrides shorter delays.
+    ks.set_retry_policy(RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(10),
+        max_delay: Duration::from_secs(10),
+        jitter: false,
+    });
+    let start = Instant::now();
+    let key = ks.get(&keyid).unwrap();
+    assert_eq!(key.fingerprint(), Fingerprint::from_hex(FP).unwrap());
+    assert!(start.elapsed() >= Duration::from_secs(1));
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+}
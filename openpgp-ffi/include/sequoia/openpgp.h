@@ -56,6 +56,13 @@ char *pgp_keyid_debug (const pgp_keyid_t fp);
 /*/
 char *pgp_keyid_to_hex (const pgp_keyid_t keyid);
 
+/*/
+/// Converts the KeyID to an abbreviated hexadecimal number.
+///
+/// Only the first and the last four digits are kept.
+/*/
+char *pgp_keyid_to_hex_abbreviated (const pgp_keyid_t keyid);
+
 /*/
 /// Compares KeyIDs.
 /*/
@@ -74,6 +81,12 @@ pgp_fingerprint_t pgp_fingerprint_from_bytes (const uint8_t *buf, size_t len);
 /*/
 pgp_fingerprint_t pgp_fingerprint_from_hex (const char *hex);
 
+/*/
+/// Reads a hexadecimal fingerprint, reporting errors.
+/*/
+pgp_fingerprint_t pgp_fingerprint_from_hex_detailed (pgp_error_t *errp,
+                                                     const char *hex);
+
 /*/
 /// Frees a pgp_fingerprint_t.
 /*/
@@ -113,6 +126,21 @@ char *pgp_fingerprint_debug (const pgp_fingerprint_t fp);
 /*/
 char *pgp_fingerprint_to_hex (const pgp_fingerprint_t fp);
 
+/*/
+/// Converts the fingerprint to a compact hexadecimal number.
+///
+/// Like `pgp_fingerprint_to_hex`, but using lowercase digits.
+/*/
+char *pgp_fingerprint_to_hex_compact (const pgp_fingerprint_t fp);
+
+/*/
+/// Converts the fingerprint to an abbreviated hexadecimal number.
+///
+/// Only the first and the last four digits are kept.  This is useful
+/// for log output, but must not be used to identify keys.
+/*/
+char *pgp_fingerprint_to_hex_abbreviated (const pgp_fingerprint_t fp);
+
 /*/
 /// Converts the fingerprint to a key ID.
 /*/
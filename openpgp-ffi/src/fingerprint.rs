@@ -17,6 +17,7 @@ extern crate sequoia_openpgp as openpgp;
 use super::keyid::KeyID;
 use Maybe;
 use MoveIntoRaw;
+use MoveResultIntoRaw;
 use RefRaw;
 
 /// Holds a fingerprint.
@@ -73,6 +74,37 @@ fn pgp_fingerprint_from_hex(hex: *const c_char)
     openpgp::Fingerprint::from_hex(&hex).ok().move_into_raw()
 }
 
+/// Reads a hexadecimal fingerprint, reporting errors.
+///
+/// Like `pgp_fingerprint_from_hex`, but on failure, an error
+/// describing the problem is stored in `errp`.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <stdlib.h>
+/// #include <sequoia/openpgp.h>
+///
+/// pgp_error_t err = NULL;
+/// pgp_fingerprint_t fp = pgp_fingerprint_from_hex_detailed (
+///     &err, "D2F2 C5D4 5BE9 FDE6 A4EE  0AAF 3185 5247 6038 31FD");
+/// assert (fp);
+/// pgp_fingerprint_free (fp);
+///
+/// fp = pgp_fingerprint_from_hex_detailed (&err, "D2F2 C5D4");
+/// assert (! fp);
+/// assert (err);
+/// pgp_error_free (err);
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_fingerprint_from_hex_detailed(errp: Option<&mut *mut ::error::Error>,
+                                     hex: *const c_char)
+                                     -> Maybe<Fingerprint> {
+    let hex = ffi_param_cstr!(hex).to_string_lossy();
+    openpgp::Fingerprint::from_hex(&hex).move_into_raw(errp)
+}
+
 /// Returns a reference to the raw Fingerprint.
 ///
 /// This returns a reference to the internal buffer that is valid as
@@ -95,6 +127,25 @@ fn pgp_fingerprint_to_hex(fp: *const Fingerprint)
     ffi_return_string!(fp.ref_raw().to_hex())
 }
 
+/// Converts the fingerprint to a compact hexadecimal number.
+///
+/// Like `pgp_fingerprint_to_hex`, but using lowercase digits.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_fingerprint_to_hex_compact(fp: *const Fingerprint)
+                                  -> *mut c_char {
+    ffi_return_string!(fp.ref_raw().to_hex_compact())
+}
+
+/// Converts the fingerprint to an abbreviated hexadecimal number.
+///
+/// Only the first and the last four digits are kept.  This is useful
+/// for log output, but must not be used to identify keys.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_fingerprint_to_hex_abbreviated(fp: *const Fingerprint)
+                                      -> *mut c_char {
+    ffi_return_string!(fp.ref_raw().to_hex_abbreviated())
+}
+
 /// Converts the fingerprint to a key ID.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
 fn pgp_fingerprint_to_keyid(fp: *const Fingerprint)
@@ -88,3 +88,11 @@ fn pgp_keyid_from_hex(id: *const c_char) -> Maybe<KeyID> {
 fn pgp_keyid_to_hex(id: *const KeyID) -> *mut c_char {
     ffi_return_string!(id.ref_raw().to_hex())
 }
+
+/// Converts the KeyID to an abbreviated hexadecimal number.
+///
+/// Only the first and the last four digits are kept.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_keyid_to_hex_abbreviated(id: *const KeyID) -> *mut c_char {
+    ffi_return_string!(id.ref_raw().to_hex_abbreviated())
+}
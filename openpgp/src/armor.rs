@@ -45,8 +45,42 @@ use serialize::SerializeInto;
 /// 64.
 const LINE_LENGTH: usize = 64;
 
+/// The maximum line length permitted by [RFC 4880, section 6.3].
+///
+/// [RFC 4880, section 6.3]: https://tools.ietf.org/html/rfc4880#section-6.3
+const MAX_LINE_LENGTH: usize = 76;
+
 const LINE_ENDING: &str = "\n";
 
+const LINE_ENDING_CRLF: &str = "\r\n";
+
+/// Controls the formatting of ASCII Armor produced by `Writer`.
+///
+/// The default is compliant with [RFC 4880, section 6.3], and
+/// produces lines of 64 characters terminated by a bare line feed.
+/// Some legacy tools, notably on Windows, expect different line
+/// lengths or CRLF line endings.
+///
+/// [RFC 4880, section 6.3]: https://tools.ietf.org/html/rfc4880#section-6.3
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ArmorOptions {
+    /// The number of base64 characters per line.
+    ///
+    /// Must be a positive multiple of four not exceeding 76.
+    pub line_length: usize,
+    /// Whether to terminate lines with CRLF instead of LF.
+    pub crlf: bool,
+}
+
+impl Default for ArmorOptions {
+    fn default() -> Self {
+        ArmorOptions {
+            line_length: LINE_LENGTH,
+            crlf: false,
+        }
+    }
+}
+
 /// Specifies the type of data (see [RFC 4880, section 6.2]).
 ///
 /// [RFC 4880, section 6.2]: https://tools.ietf.org/html/rfc4880#section-6.2
@@ -157,6 +191,8 @@ pub struct Writer<W: Write> {
     epilogue: Vec<u8>,
     dirty: bool,
     finalized: bool,
+    line_length: usize,
+    line_ending: &'static str,
 }
 
 impl<W: Write> Writer<W> {
@@ -191,6 +227,56 @@ impl<W: Write> Writer<W> {
     /// # }
     /// ```
     pub fn new(inner: W, kind: Kind, headers: &[(&str, &str)]) -> Result<Self> {
+        Self::with_options(inner, kind, headers, Default::default())
+    }
+
+    /// Constructs a new filter with the given formatting options.
+    ///
+    /// Returns an error of kind `ErrorKind::InvalidInput` if the line
+    /// length is not a positive multiple of four, or exceeds the
+    /// maximum of 76 characters permitted by RFC 4880.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # use openpgp::armor::{ArmorOptions, Writer, Kind};
+    /// # use std::io::{self, Result};
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// let mut buffer = io::Cursor::new(vec![]);
+    /// {
+    ///     let mut writer = Writer::with_options(
+    ///         &mut buffer, Kind::File, &[],
+    ///         ArmorOptions { line_length: 8, crlf: true })?;
+    ///     writer.write_all(b"Hello world!")?;
+    /// }
+    /// assert_eq!(
+    ///     String::from_utf8_lossy(buffer.get_ref()),
+    ///     "-----BEGIN PGP ARMORED FILE-----\r\n\
+    ///      \r\n\
+    ///      SGVsbG8g\r\n\
+    ///      d29ybGQh\r\n\
+    ///      =s4Gu\r\n\
+    ///      -----END PGP ARMORED FILE-----\r\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_options(inner: W, kind: Kind, headers: &[(&str, &str)],
+                        options: ArmorOptions)
+                        -> Result<Self> {
+        if options.line_length == 0
+            || options.line_length % 4 != 0
+            || options.line_length > MAX_LINE_LENGTH
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid line length: {}", options.line_length)));
+        }
+
+        let line_ending =
+            if options.crlf { LINE_ENDING_CRLF } else { LINE_ENDING };
         let mut w = Writer {
             sink: inner,
             kind: kind,
@@ -200,18 +286,20 @@ impl<W: Write> Writer<W> {
             epilogue: Vec::with_capacity(128),
             dirty: false,
             finalized: false,
+            line_length: options.line_length,
+            line_ending: line_ending,
         };
 
         {
             let mut cur = Cursor::new(&mut w.epilogue);
-            write!(&mut cur, "{}{}", kind.begin(), LINE_ENDING)?;
+            write!(&mut cur, "{}{}", kind.begin(), line_ending)?;
 
             for h in headers {
-                write!(&mut cur, "{}: {}{}", h.0, h.1, LINE_ENDING)?;
+                write!(&mut cur, "{}: {}{}", h.0, h.1, line_ending)?;
             }
 
             // A blank line separates the headers from the body.
-            write!(&mut cur, "{}", LINE_ENDING)?;
+            write!(&mut cur, "{}", line_ending)?;
         }
 
         Ok(w)
@@ -252,7 +340,7 @@ impl<W: Write> Writer<W> {
         }
         self.linebreak()?;
         if self.column > 0 {
-            write!(self.sink, "{}", LINE_ENDING)?;
+            write!(self.sink, "{}", self.line_ending)?;
         }
 
         let crc = self.crc.finalize();
@@ -265,7 +353,7 @@ impl<W: Write> Writer<W> {
         // CRC and footer.
         write!(self.sink, "={}{}{}{}",
                base64::encode_config(&bytes, base64::STANDARD_NO_PAD),
-               LINE_ENDING, self.kind.end(), LINE_ENDING)?;
+               self.line_ending, self.kind.end(), self.line_ending)?;
 
         self.finalized = true;
         Ok(())
@@ -273,9 +361,9 @@ impl<W: Write> Writer<W> {
 
     /// Inserts a line break if necessary.
     fn linebreak(&mut self) -> Result<()> {
-        assert!(self.column <= LINE_LENGTH);
-        if self.column == LINE_LENGTH {
-            write!(self.sink, "{}", LINE_ENDING)?;
+        assert!(self.column <= self.line_length);
+        if self.column == self.line_length {
+            write!(self.sink, "{}", self.line_ending)?;
             self.column = 0;
         }
         Ok(())
@@ -340,7 +428,7 @@ impl<W: Write> Write for Writer<W> {
         written += input.len();
         let mut enc = encoded.as_bytes();
         while enc.len() > 0 {
-            let n = min(LINE_LENGTH - self.column, enc.len());
+            let n = min(self.line_length - self.column, enc.len());
             self.sink.write_all(&enc[..n])?;
             enc = &enc[n..];
             self.column += n;
@@ -401,6 +489,7 @@ pub struct Reader<'a> {
     buffer: Vec<u8>,
     crc: CRC,
     expect_crc: Option<u32>,
+    verify_crc: bool,
     initialized: bool,
     headers: Vec<(String, String)>,
     finalized: bool,
@@ -534,12 +623,55 @@ impl<'a> Reader<'a> {
             buffer: Vec::<u8>::with_capacity(1024),
             crc: CRC::new(),
             expect_crc: None,
+            verify_crc: true,
             headers: Vec::new(),
             initialized: false,
             finalized: false,
         }
     }
 
+    /// Constructs a new filter that ignores the checksum.
+    ///
+    /// The checksum in the armor footer is optional, and some
+    /// implementations are known to emit wrong checksums.  The
+    /// filter returned by `Reader::new` returns an `io::Error` if
+    /// the checksum does not match.  The filter returned by this
+    /// function accepts the data nevertheless.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Read;
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # use openpgp::armor::{Reader, ReaderMode, Kind};
+    /// # use std::io::{self, Result};
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// let data =
+    ///     "-----BEGIN PGP ARMORED FILE-----
+    ///
+    ///      SGVsbG8gd29ybGQh
+    ///      =AAAA
+    ///      -----END PGP ARMORED FILE-----";
+    ///
+    /// let mut reader = Reader::new_lenient(
+    ///     io::Cursor::new(&data), ReaderMode::Tolerant(Some(Kind::File)));
+    ///
+    /// let mut content = String::new();
+    /// reader.read_to_string(&mut content)?;
+    /// assert_eq!(content, "Hello world!");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_lenient<R, M>(inner: R, mode: M) -> Self
+        where R: 'a + Read,
+              M: Into<Option<ReaderMode>>
+    {
+        let mut reader = Self::new(inner, mode);
+        reader.verify_crc = false;
+        reader
+    }
+
     /// Returns the kind of data this reader is for.
     ///
     /// Useful if the kind of data is not known in advance.  If the
@@ -561,6 +693,65 @@ impl<'a> Reader<'a> {
         Ok(&self.headers[..])
     }
 
+    /// Returns the data following the armored block.
+    ///
+    /// The reader reads ahead, so once the footer has been
+    /// encountered, its internal buffer may contain data that follows
+    /// the armored block.  The reader never consumes data past the
+    /// footer, and this function returns the buffered data, minus the
+    /// rest of the footer line.  Hence, the data following the
+    /// armored block is the returned data followed by whatever
+    /// remains in the underlying reader.  This allows processing
+    /// inputs where armored data is followed by other data.
+    ///
+    /// This should only be called after the armored data has been
+    /// read until EOF.  Otherwise, the returned data includes the
+    /// rest of the armored data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Read;
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # use openpgp::armor::{Reader, ReaderMode, Kind};
+    /// # use std::io::{self, Result};
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// let data =
+    ///     "-----BEGIN PGP ARMORED FILE-----
+    ///
+    ///      SGVsbG8gd29ybGQh
+    ///      =s4Gu
+    ///      -----END PGP ARMORED FILE-----
+    ///      Goodbye!";
+    ///
+    /// let mut reader = Reader::new(io::Cursor::new(&data),
+    ///                              ReaderMode::Tolerant(Some(Kind::File)));
+    ///
+    /// let mut content = String::new();
+    /// reader.read_to_string(&mut content)?;
+    /// assert_eq!(content, "Hello world!");
+    /// assert_eq!(reader.into_unread(), b"     Goodbye!");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_unread(mut self) -> Vec<u8> {
+        if self.finalized {
+            // Skip the rest of the footer line.
+            let n = {
+                let line = self.source.read_to(b'\n').unwrap_or(&[]);
+                if line.iter().all(|c| c.is_ascii_whitespace()) {
+                    line.len()
+                } else {
+                    0
+                }
+            };
+            self.source.consume(n);
+        }
+
+        self.source.buffer().to_vec()
+    }
+
     /// Consumes the header if not already done.
     fn initialize(&mut self) -> Result<()> {
         if self.initialized { return Ok(()) }
@@ -1056,7 +1247,7 @@ impl<'a> Read for Reader<'a> {
 
         /* If we are finalized, we may have found a crc sum.  */
         if let Some(crc) = self.expect_crc {
-            if self.crc.finalize() != crc {
+            if self.verify_crc && self.crc.finalize() != crc {
                 return Err(Error::new(ErrorKind::InvalidInput, "Bad CRC sum."));
             }
         }
@@ -1099,9 +1290,16 @@ impl CRC {
     }
 }
 
+impl buffered_reader::Digest for CRC {
+    fn update(&mut self, data: &[u8]) {
+        CRC::update(self, data);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::io::{Cursor, Read, Write};
+    use super::ArmorOptions;
     use super::CRC;
     use super::Kind;
     use super::Writer;
@@ -1125,6 +1323,13 @@ mod test {
         for len in 0..b.len() + 1 {
             assert_eq!(CRC::new().update(&b[..len]).finalize(), crcs[len]);
         }
+
+        // The CRC can also be computed while reading.
+        use buffered_reader::{BufferedReader, Hash, Memory};
+        let mut reader = Hash::new(Box::new(Memory::new(&b[..])), CRC::new());
+        reader.data_consume_hard(3).unwrap();
+        reader.steal_eof().unwrap();
+        assert_eq!(reader.into_digest().finalize(), crcs[b.len()]);
     }
 
     macro_rules! t {
@@ -1263,6 +1468,35 @@ mod test {
         assert!(e.is_err());
     }
 
+    #[test]
+    fn dearmor_corrupted_crc() {
+        let good = "-----BEGIN PGP ARMORED FILE-----
+
+                    SGVsbG8gd29ybGQh
+                    =s4Gu
+                    -----END PGP ARMORED FILE-----";
+        // Flip the last character of the checksum.
+        let bad = good.replace("=s4Gu", "=s4Gv");
+
+        let mut r = Reader::new(
+            Cursor::new(&good), ReaderMode::Tolerant(Some(Kind::File)));
+        let mut content = String::new();
+        r.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "Hello world!");
+
+        let mut r = Reader::new(
+            Cursor::new(&bad), ReaderMode::Tolerant(Some(Kind::File)));
+        let mut content = String::new();
+        let e = r.read_to_string(&mut content).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+
+        let mut r = Reader::new_lenient(
+            Cursor::new(&bad), ReaderMode::Tolerant(Some(Kind::File)));
+        let mut content = String::new();
+        r.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "Hello world!");
+    }
+
     #[test]
     fn dearmor_wrong_footer() {
         let mut r = Reader::new(
@@ -1409,4 +1643,92 @@ mod test {
             payload == recovered && payload == recovered_any
         }
     }
+
+    #[test]
+    fn dearmor_trailing_data() {
+        let mut armored = Vec::new();
+        {
+            let mut w = Writer::new(&mut armored, Kind::File, &[]).unwrap();
+            w.write_all(b"Hello world!").unwrap();
+        }
+
+        // Enough trailing data so that not all of it is buffered.
+        let trailing = (0..64 * 1024).map(|i| b'a' + (i % 26) as u8)
+            .collect::<Vec<u8>>();
+
+        for crlf in [false, true].iter() {
+            let mut input = armored.clone();
+            if *crlf {
+                let l = input.len();
+                input[l - 1] = b'\r';
+                input.push(b'\n');
+            }
+            input.extend_from_slice(&trailing);
+
+            let mut cursor = Cursor::new(&input);
+            let unread = {
+                let mut r = Reader::new(&mut cursor,
+                                        ReaderMode::Tolerant(None));
+                let mut content = Vec::new();
+                r.read_to_end(&mut content).unwrap();
+                assert_eq!(content, b"Hello world!");
+                r.into_unread()
+            };
+
+            let mut rest = unread;
+            cursor.read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, trailing);
+        }
+    }
+
+    quickcheck! {
+        fn roundtrip_with_options(kind: Kind, payload: Vec<u8>,
+                                  line_length: u8, crlf: bool) -> bool {
+            if payload.is_empty() {
+                return true;
+            }
+
+            let options = ArmorOptions {
+                line_length: (line_length as usize % 19 + 1) * 4,
+                crlf: crlf,
+            };
+            let mut encoded = Vec::new();
+            Writer::with_options(&mut encoded, kind, &[], options).unwrap()
+                .write_all(&payload)
+                .unwrap();
+
+            // Check the formatting.
+            assert!(encoded.ends_with(b"\n"));
+            for line in encoded[..encoded.len() - 1].split(|&b| b == b'\n') {
+                if line.ends_with(b"\r") != crlf {
+                    return false;
+                }
+                let line = if crlf { &line[..line.len() - 1] } else { line };
+                // Except for the header, footer, and checksum lines,
+                // lines must not exceed the requested length.
+                if ! (line.starts_with(b"-----") || line.starts_with(b"="))
+                    && line.len() > options.line_length
+                {
+                    return false;
+                }
+            }
+
+            let mut recovered = Vec::new();
+            Reader::new(Cursor::new(&encoded),
+                        ReaderMode::Tolerant(Some(kind)))
+                .read_to_end(&mut recovered)
+                .unwrap();
+
+            payload == recovered
+        }
+    }
+
+    #[test]
+    fn enarmor_invalid_line_length() {
+        for &line_length in [0, 3, 5, 80].iter() {
+            let options = ArmorOptions { line_length: line_length, crlf: false };
+            assert!(Writer::with_options(Vec::new(), Kind::File, &[], options)
+                    .is_err());
+        }
+    }
 }
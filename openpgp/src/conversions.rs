@@ -130,6 +130,34 @@ pub mod hex {
             }
         }
 
+        /// Creates a new dumper starting at the given offset.
+        ///
+        /// This is like `new`, but the first byte written is shown at
+        /// `offset`, and lines are aligned accordingly.  This is
+        /// useful to dump a part of a larger stream.
+        ///
+        /// # Example
+        ///
+        /// ```rust
+        ///  use sequoia_openpgp::conversions::hex;
+        ///
+        /// let mut dumper = hex::Dumper::with_offset(Vec::new(), "", 0x12);
+        /// dumper.write(&[0x04], "version").unwrap();
+        ///
+        /// let buf = dumper.into_inner();
+        /// assert_eq!(
+        ///     ::std::str::from_utf8(&buf[..]).unwrap(),
+        ///     "00000012        04                                           version\n");
+        /// ```
+        pub fn with_offset<I: AsRef<str>>(inner: W, indent: I, offset: usize)
+                                          -> Self {
+            Dumper {
+                inner: inner,
+                indent: indent.as_ref().into(),
+                offset: offset,
+            }
+        }
+
         /// Returns the inner writer.
         pub fn into_inner(self) -> W {
             self.inner
@@ -206,6 +234,19 @@ pub(crate) fn to_hex(s: &[u8], pretty: bool) -> String {
     result
 }
 
+/// Abbreviates a hexadecimal string to its first and last four digits.
+///
+/// The elided digits are replaced by an ellipsis, e.g. `D2F2…31FD`.
+/// Strings that are too short to benefit from that are returned as
+/// is.
+pub(crate) fn abbreviate_hex(hex: &str) -> String {
+    if hex.len() <= 8 {
+        hex.into()
+    } else {
+        format!("{}\u{2026}{}", &hex[..4], &hex[hex.len() - 4..])
+    }
+}
+
 /// A helpful function for converting a hexadecimal string to binary.
 /// This function skips whitespace if `pretty` is set.
 pub(crate) fn from_hex(hex: &str, pretty: bool) -> Result<Vec<u8>> {
@@ -1,5 +1,6 @@
 use std::fmt;
 
+use Error;
 use Fingerprint;
 use KeyID;
 use Result;
@@ -32,7 +33,9 @@ impl Fingerprint {
 
     /// Reads a hexadecimal fingerprint.
     ///
-    /// This function ignores whitespace.
+    /// This function ignores whitespace.  Anything but exactly 40
+    /// hexadecimal digits, i.e. a version 4 fingerprint, is
+    /// rejected.
     ///
     /// # Example
     ///
@@ -45,7 +48,13 @@ impl Fingerprint {
     /// assert_eq!(fp.unwrap().to_hex(), hex);
     /// ```
     pub fn from_hex(hex: &str) -> Result<Fingerprint> {
-        Ok(Fingerprint::from_bytes(&::conversions::from_hex(hex, true)?[..]))
+        let bytes = ::conversions::from_hex(hex, true)?;
+        if bytes.len() != 20 {
+            return Err(Error::InvalidArgument(
+                format!("Fingerprint must be 20 bytes long, got {}",
+                        bytes.len())).into());
+        }
+        Ok(Fingerprint::from_bytes(&bytes[..]))
     }
 
     /// Returns a reference to the raw Fingerprint.
@@ -68,6 +77,45 @@ impl Fingerprint {
         self.convert_to_string(false)
     }
 
+    /// Converts the fingerprint to a compact hexadecimal number.
+    ///
+    /// Like `to_hex`, but using lowercase digits, which is more
+    /// convenient in URLs and file names.
+    pub fn to_hex_compact(&self) -> String {
+        self.to_hex().to_lowercase()
+    }
+
+    /// Converts the fingerprint to an abbreviated hexadecimal number.
+    ///
+    /// Only the first and the last four digits are kept, e.g.
+    /// `D2F2…31FD`.  This is useful for log output, where the full
+    /// fingerprint is too unwieldy.  Note that abbreviated
+    /// fingerprints must not be used to identify keys.
+    pub fn to_hex_abbreviated(&self) -> String {
+        ::conversions::abbreviate_hex(&self.to_hex())
+    }
+
+    /// Converts the fingerprint to a hexadecimal number in groups.
+    ///
+    /// Every `group_size` digits are separated by a space.  A
+    /// `group_size` of zero results in a single group.
+    pub fn to_hex_grouped(&self, group_size: usize) -> String {
+        let hex = self.to_hex();
+        if group_size == 0 {
+            return hex;
+        }
+
+        let mut output =
+            String::with_capacity(hex.len() + hex.len() / group_size);
+        for (i, c) in hex.chars().enumerate() {
+            if i > 0 && i % group_size == 0 {
+                output.push(' ');
+            }
+            output.push(c);
+        }
+        output
+    }
+
     /// Common code for the above functions.
     fn convert_to_string(&self, pretty: bool) -> String {
         let raw = match self {
@@ -187,4 +235,52 @@ Charlie Delta Echo Foxtrot Zero One Two Three Four Five Six Seven";
 
         assert_eq!(fpr.to_icao(), expected);
     }
+
+    #[test]
+    fn hex_formats() {
+        let fpr = Fingerprint::from_hex(
+            "0123 4567 89AB CDEF 0123 4567 89AB CDEF 0123 4567").unwrap();
+        assert_eq!(fpr.to_hex_compact(),
+                   "0123456789abcdef0123456789abcdef01234567");
+        assert_eq!(fpr.to_hex_grouped(8),
+                   "01234567 89ABCDEF 01234567 89ABCDEF 01234567");
+        assert_eq!(fpr.to_hex_grouped(0), fpr.to_hex());
+        assert_eq!(fpr.to_hex_abbreviated(), "0123\u{2026}4567");
+        assert_eq!(fpr.to_string(),
+                   "0123 4567 89AB CDEF 0123  4567 89AB CDEF 0123 4567");
+    }
+
+    #[test]
+    fn from_hex_lenient() {
+        let fpr = Fingerprint::from_hex(
+            "3E8877C877274692975189F5D03F6F865226FE8B").unwrap();
+
+        // What to_string produces can be parsed again.
+        assert_eq!(Fingerprint::from_hex(&fpr.to_string()).unwrap(), fpr);
+        assert_eq!(Fingerprint::from_hex(
+            "3E88 77C8 7727 4692 9751  89F5 D03F 6F86 5226 FE8B").unwrap(),
+                   fpr);
+        assert_eq!(Fingerprint::from_hex(
+            "0x3e8877c877274692975189F5D03F6F865226FE8B").unwrap(), fpr);
+        assert_eq!(Fingerprint::from_hex(
+            " 3e88\t77c8\n7727 4692 9751 89f5 d03f 6f86 5226 fe8b ").unwrap(),
+                   fpr);
+
+        assert!(Fingerprint::from_hex(
+            "3E88 77C8 7727 4692 9751  89F5 D03F 6F86 5226 FE8").is_err());
+        assert!(Fingerprint::from_hex("3E88 77C8 xx").is_err());
+    }
+
+    #[test]
+    fn from_hex_wrong_length() {
+        // Only V4 fingerprints are accepted.
+        assert!(Fingerprint::from_hex("").is_err());
+        assert!(Fingerprint::from_hex("D03F6F865226FE8B").is_err());
+        assert!(Fingerprint::from_hex(
+            "0123456789ABCDEF0123456789ABCDEF").is_err());
+        assert!(Fingerprint::from_hex(
+            "3E8877C877274692975189F5D03F6F865226FE8B00").is_err());
+        assert!(Fingerprint::from_hex(
+            "3E8877C877274692975189F5D03F6F865226FE8B3E88").is_err());
+    }
 }
@@ -77,16 +77,24 @@ impl KeyID {
     }
 
     /// Reads a hex-encoded Key ID.
+    ///
+    /// A fingerprint may be given instead, and is converted to a Key
+    /// ID.  4 byte short key IDs are returned as `KeyID::Invalid`.
+    /// They cannot be converted using `as_u64`, but can be matched
+    /// against fingerprints using `is_suffix_of`.  Inputs of any
+    /// other length are rejected.
     pub fn from_hex(hex: &str) -> Result<KeyID> {
         let bytes = ::conversions::from_hex(hex, true)?;
 
-        // A KeyID is exactly 8 bytes long.
-        if bytes.len() == 8 {
-            Ok(KeyID::from_bytes(&bytes[..]))
-        } else {
-            // Maybe a fingerprint was given.  Try to parse it and
-            // convert it to a KeyID.
-            Ok(Fingerprint::from_hex(hex)?.to_keyid())
+        match bytes.len() {
+            // Short key IDs are kept as they are, so that they can be
+            // matched against fingerprints using `is_suffix_of`.
+            4 | 8 => Ok(KeyID::from_bytes(&bytes[..])),
+            // A fingerprint was given.  Convert it to a KeyID.
+            20 => Ok(Fingerprint::from_bytes(&bytes[..]).to_keyid()),
+            n => Err(Error::InvalidArgument(
+                format!("KeyID must be 4 or 8 bytes long, got {}", n))
+                     .into()),
         }
     }
 
@@ -98,6 +106,19 @@ impl KeyID {
         }
     }
 
+    /// Returns whether this key ID identifies the key with the given
+    /// fingerprint.
+    ///
+    /// A key ID consists of the low-order bytes of the fingerprint.
+    /// This also works for short key IDs, but note that it is easy
+    /// to create keys with a given short key ID.  Hence, a short key
+    /// ID matching a fingerprint must not be taken as evidence that
+    /// this is the intended key.
+    pub fn is_suffix_of(&self, fp: &Fingerprint) -> bool {
+        let id = self.as_slice();
+        ! id.is_empty() && fp.as_slice().ends_with(id)
+    }
+
     /// Returns the wildcard KeyID.
     pub fn wildcard() -> Self {
         Self::from_bytes(&[0u8; 8][..])
@@ -120,6 +141,14 @@ impl KeyID {
         self.convert_to_string(false)
     }
 
+    /// Converts the key ID to an abbreviated hexadecimal number.
+    ///
+    /// Like `Fingerprint::to_hex_abbreviated`, only the first and
+    /// the last four digits are kept.
+    pub fn to_hex_abbreviated(&self) -> String {
+        ::conversions::abbreviate_hex(&self.to_hex())
+    }
+
     /// Common code for the above functions.
     fn convert_to_string(&self, pretty: bool) -> String {
         let raw = match self {
@@ -182,4 +211,55 @@ mod test {
             KeyID::new(id).as_u64().unwrap() == id
         }
     }
+
+    #[test]
+    fn is_suffix_of() {
+        let fp = Fingerprint::from_hex(
+            "3E88 77C8 7727 4692 9751  89F5 D03F 6F86 5226 FE8B").unwrap();
+        assert!(fp.to_keyid().is_suffix_of(&fp));
+        assert!(KeyID::from_hex("D03F6F865226FE8B").unwrap().is_suffix_of(&fp));
+        assert!(KeyID::from_hex("5226FE8B").unwrap().is_suffix_of(&fp));
+
+        assert!(! KeyID::from_hex("D03F6F865226FE8C").unwrap()
+                .is_suffix_of(&fp));
+        assert!(! KeyID::from_hex("3E8877C8").unwrap().is_suffix_of(&fp));
+        assert!(! KeyID::from_bytes(&[]).is_suffix_of(&fp));
+    }
+
+    #[test]
+    fn to_hex_abbreviated() {
+        let keyid = KeyID::new(0xd03f6f865226fe8b);
+        assert_eq!(keyid.to_hex_abbreviated(), "D03F\u{2026}FE8B");
+        let short = KeyID::from_hex("5226FE8B").unwrap();
+        assert_eq!(short.to_hex_abbreviated(), "5226FE8B");
+    }
+
+    #[test]
+    fn from_hex_lenient() {
+        let keyid = KeyID::new(0xd03f6f865226fe8b);
+        assert_eq!(KeyID::from_hex("0xd03f6f865226fe8b").unwrap(), keyid);
+        assert_eq!(KeyID::from_hex("D03F6F865226FE8B").unwrap(), keyid);
+        assert_eq!(KeyID::from_hex("d03f 6F86 5226 fe8b").unwrap(), keyid);
+        assert_eq!(KeyID::from_hex(&keyid.to_string()).unwrap(), keyid);
+
+        // A fingerprint may be given instead.
+        assert_eq!(KeyID::from_hex(
+            "3E88 77C8 7727 4692 9751  89F5 D03F 6F86 5226 FE8B").unwrap(),
+                   keyid);
+
+        // Short key IDs are preserved, but are not valid key IDs.
+        let short = KeyID::from_hex("5226FE8B").unwrap();
+        assert_eq!(short.as_slice(), &[0x52, 0x26, 0xfe, 0x8b][..]);
+        assert!(short.as_u64().is_err());
+        assert_match!(KeyID::Invalid(_) = short);
+
+        // Other lengths are rejected.
+        assert!(KeyID::from_hex("").is_err());
+        assert!(KeyID::from_hex("0x").is_err());
+        assert!(KeyID::from_hex("26FE8B").is_err());
+        assert!(KeyID::from_hex("865226FE8B").is_err());
+        assert!(KeyID::from_hex("D03F6F865226FE8B00").is_err());
+        assert!(KeyID::from_hex("0xd03f6f865226fe8").is_err());
+        assert!(KeyID::from_hex("0xd03f6f86 5226fezz").is_err());
+    }
 }
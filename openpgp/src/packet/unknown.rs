@@ -1,6 +1,7 @@
 use failure;
 use std::hash::{Hash, Hasher};
 
+use packet::Header;
 use packet::Tag;
 use packet;
 use Packet;
@@ -19,6 +20,17 @@ pub struct Unknown {
     tag: Tag,
     /// Error that caused parsing or processing to abort.
     error: failure::Error,
+    /// The packet's original header and its encoding, if known.
+    ///
+    /// This is used to serialize a parsed packet exactly as it was
+    /// encountered.
+    pub(crate) header: Option<(Header, Vec<u8>)>,
+    /// The lengths of the original partial body chunks, if any.
+    ///
+    /// Runs of chunks of the same length are stored as a single
+    /// `(length, count)` pair.  The last chunk is the one with a
+    /// definite length.
+    pub(crate) chunks: Vec<(u32, u32)>,
 }
 
 impl Eq for Unknown {}
@@ -42,6 +54,8 @@ impl Clone for Unknown {
             common: self.common.clone(),
             tag: self.tag,
             error: failure::err_msg(format!("{}", self.error)),
+            header: self.header.clone(),
+            chunks: self.chunks.clone(),
         }
     }
 }
@@ -54,6 +68,8 @@ impl Unknown {
             common: Default::default(),
             tag: tag,
             error: error,
+            header: None,
+            chunks: Vec::new(),
         }
     }
 
@@ -67,6 +83,16 @@ impl Unknown {
         ::std::mem::replace(&mut self.tag, tag)
     }
 
+    /// Gets the unknown packet's original header.
+    ///
+    /// If the packet was parsed, this is the header that framed it.
+    /// When serializing the packet, the original CTB and length
+    /// encoding are used, provided that they are still consistent
+    /// with the packet's tag and body.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref().map(|&(ref h, _)| h)
+    }
+
     /// Gets the unknown packet's error.
     ///
     /// This is the error that caused parsing or processing to abort.
@@ -0,0 +1,148 @@
+use std;
+use std::io;
+
+use buffered_reader::{buffered_reader_generic_read_impl, BufferedReader};
+use parse::Cookie;
+
+/// A `BufferedReader` that counts the number of bytes consumed.
+///
+/// The `PacketParserBuilder` pushes this on top of the external
+/// `BufferedReader`.  The count is kept in the cookie's `consumed`
+/// field, so that it can be read using `Cookie::position` without
+/// knowing the `BufferedReader`'s type.
+pub(crate) struct BufferedReaderCounter<T: BufferedReader<Cookie>> {
+    // The underlying reader.
+    reader: T,
+
+    // The user-defined cookie.
+    cookie: Cookie,
+}
+
+impl<T: BufferedReader<Cookie>> std::fmt::Display
+        for BufferedReaderCounter<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "BufferedReaderCounter ({} bytes consumed)",
+               self.cookie.consumed)
+    }
+}
+
+impl<T: BufferedReader<Cookie>> std::fmt::Debug
+        for BufferedReaderCounter<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("BufferedReaderCounter")
+            .field("consumed", &self.cookie.consumed)
+            .field("reader", &self.reader)
+            .finish()
+    }
+}
+
+impl<T: BufferedReader<Cookie>> BufferedReaderCounter<T> {
+    /// Creates a new `BufferedReaderCounter` object.
+    pub fn with_cookie(reader: T, mut cookie: Cookie) -> Self {
+        cookie.consumed = 0;
+        BufferedReaderCounter {
+            reader: reader,
+            cookie: cookie,
+        }
+    }
+}
+
+impl<T: BufferedReader<Cookie>> io::Read for BufferedReaderCounter<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        return buffered_reader_generic_read_impl(self, buf);
+    }
+}
+
+impl<T: BufferedReader<Cookie>> BufferedReader<Cookie>
+        for BufferedReaderCounter<T> {
+    fn buffer(&self) -> &[u8] {
+        self.reader.buffer()
+    }
+
+    fn data(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        self.reader.data(amount)
+    }
+
+    fn data_hard(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        self.reader.data_hard(amount)
+    }
+
+    fn consume(&mut self, amount: usize) -> &[u8] {
+        self.cookie.consumed += amount as u64;
+        self.reader.consume(amount)
+    }
+
+    fn data_consume(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        let data = self.reader.data_consume(amount)?;
+        self.cookie.consumed += std::cmp::min(amount, data.len()) as u64;
+        Ok(data)
+    }
+
+    fn data_consume_hard(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        let data = self.reader.data_consume_hard(amount)?;
+        self.cookie.consumed += amount as u64;
+        Ok(data)
+    }
+
+    fn consummated(&mut self) -> bool {
+        self.reader.consummated()
+    }
+
+    fn get_mut(&mut self) -> Option<&mut BufferedReader<Cookie>> {
+        Some(&mut self.reader)
+    }
+
+    fn get_ref(&self) -> Option<&BufferedReader<Cookie>> {
+        Some(&self.reader)
+    }
+
+    fn into_inner<'b>(self: Box<Self>) -> Option<Box<BufferedReader<Cookie> + 'b>>
+            where Self: 'b {
+        Some(Box::new(self.reader))
+    }
+
+    fn cookie_set(&mut self, cookie: Cookie) -> Cookie {
+        use std::mem;
+
+        let consumed = self.cookie.consumed;
+        let old = mem::replace(&mut self.cookie, cookie);
+        self.cookie.consumed = consumed;
+        old
+    }
+
+    fn cookie_ref(&self) -> &Cookie {
+        &self.cookie
+    }
+
+    fn cookie_mut(&mut self) -> &mut Cookie {
+        &mut self.cookie
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_consumed_bytes() {
+        let data = b"0123456789abcdef";
+        let mut reader = BufferedReaderCounter::with_cookie(
+            buffered_reader::Memory::with_cookie(&data[..], Cookie::default()),
+            Cookie::default());
+
+        reader.data(10).unwrap();
+        assert_eq!(reader.cookie_ref().consumed, 0);
+        reader.consume(3);
+        assert_eq!(reader.cookie_ref().consumed, 3);
+        reader.data_consume_hard(5).unwrap();
+        assert_eq!(reader.cookie_ref().consumed, 8);
+        // Asking for more than there is only counts what is there.
+        reader.data_consume(100).unwrap();
+        assert_eq!(reader.cookie_ref().consumed, 16);
+
+        let mut rest = Vec::new();
+        io::Read::read_to_end(&mut reader, &mut rest).unwrap();
+        assert_eq!(rest.len(), 0);
+        assert_eq!(reader.cookie_ref().consumed, 16);
+    }
+}
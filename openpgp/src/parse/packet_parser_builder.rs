@@ -12,6 +12,7 @@ use parse::PacketParserSettings;
 use parse::ParserResult;
 use parse::Parse;
 use parse::Cookie;
+use parse::BufferedReaderCounter;
 use armor;
 use packet;
 
@@ -29,6 +30,12 @@ pub enum Dearmor {
     Auto,
 }
 
+/// When parsing leniently, how far to look for an armor header.
+const ARMOR_PROBE_SIZE: usize = 32 * 1024;
+
+/// The start of an armor header line.
+const ARMOR_MARKER: &[u8] = b"-----BEGIN PGP ";
+
 /// A builder for configuring a `PacketParser`.
 ///
 /// Since the default settings are usually appropriate, this mechanism
@@ -95,6 +102,19 @@ impl<'a> PacketParserBuilder<'a> {
         self
     }
 
+    /// Sets the maximum number of packets to parse.
+    ///
+    /// Once more than `value` packets (including packets nested in
+    /// containers) have been encountered, the `PacketParser` returns
+    /// an `Error::MalformedPacket`.  This protects against inputs
+    /// consisting of a huge number of tiny packets, which can make
+    /// further processing pathologically slow.  By default, there is
+    /// no limit.
+    pub fn max_packets(mut self, value: usize) -> Self {
+        self.settings.max_packets = Some(value);
+        self
+    }
+
     /// Causes `PacketParser::finish()` to buffer any unread content.
     ///
     /// The unread content is stored in the `Packet::content` Option.
@@ -118,6 +138,51 @@ impl<'a> PacketParserBuilder<'a> {
         self
     }
 
+    /// Controls lenient parsing.
+    ///
+    /// When enabled, the `PacketParser` tries hard to recover from
+    /// corrupted input.  If a packet's header cannot be parsed, or
+    /// does not look like a well-formed packet, the parser skips to
+    /// the next position that looks like a well-formed packet header,
+    /// and returns the skipped bytes as an `Unknown` packet with the
+    /// `Reserved` tag.  Unlike in the default mode, this is also done
+    /// for the first packet, and there is no limit on the number of
+    /// bytes that are skipped.
+    ///
+    /// This is useful for inspecting damaged messages.  It should not
+    /// be used to process messages, as the result may be quite
+    /// different from what the sender intended.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # use openpgp::Result;
+    /// # use openpgp::packet::Tag;
+    /// # use openpgp::parse::{
+    /// #     Parse, PacketParserResult, PacketParserBuilder
+    /// # };
+    /// # f();
+    /// #
+    /// # fn f() -> Result<()> {
+    /// // Some junk followed by a marker packet.
+    /// let msg = b"\xff\xff\xa8\x03PGP";
+    /// let mut tags = Vec::new();
+    /// let mut ppr = PacketParserBuilder::from_bytes(&msg[..])?
+    ///     .lenient(true).finalize()?;
+    /// while let PacketParserResult::Some(pp) = ppr {
+    ///     tags.push(pp.packet.tag());
+    ///     ppr = pp.recurse()?.1;
+    /// }
+    /// assert_eq!(tags, [Tag::Reserved, Tag::Marker]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn lenient(mut self, enable: bool) -> Self {
+        self.settings.lenient = enable;
+        self
+    }
+
     /// How to treat the input stream.
     pub fn dearmor(mut self, mode: Dearmor) -> Self {
         self.dearmor = mode;
@@ -156,8 +221,21 @@ impl<'a> PacketParserBuilder<'a> {
                 let mut reader = buffered_reader::Dup::with_cookie(
                     self.bio, Cookie::default());
                 let header = packet::Header::parse(&mut reader);
+                let lenient = self.settings.lenient;
+                let armored = lenient && {
+                    reader.rewind();
+                    let data = reader.data(ARMOR_PROBE_SIZE)?;
+                    data.windows(ARMOR_MARKER.len())
+                        .any(|w| w == ARMOR_MARKER)
+                };
                 self.bio = Box::new(reader).into_inner().unwrap();
-                if let Ok(header) = header {
+                if lenient {
+                    // When looking at corrupted data, the first
+                    // header is likely broken, but that doesn't mean
+                    // that the data is armored.  Look for the armor
+                    // header line instead.
+                    armored
+                } else if let Ok(header) = header {
                     if let Err(_) = header.valid(false) {
                         // Invalid header: better try an ASCII armor
                         // decoder.
@@ -180,8 +258,13 @@ impl<'a> PacketParserBuilder<'a> {
                 Default::default()));
         }
 
+        // Keep track of the position in the input so that we can
+        // report the offsets of top-level packets.
+        let bio = BufferedReaderCounter::with_cookie(
+            self.bio, Default::default());
+
         // Parse the first packet.
-        match PacketParser::parse(Box::new(self.bio), state, vec![ 0 ])? {
+        match PacketParser::parse(Box::new(bio), state, vec![ 0 ])? {
             ParserResult::Success(mut pp) => {
                 // We successfully parsed the first packet's header.
                 pp.state.message_validator.push(pp.packet.tag(), &[0]);
@@ -46,6 +46,8 @@ use message::MessageValidator;
 
 mod partial_body;
 use self::partial_body::BufferedReaderPartialBodyFilter;
+mod counter;
+pub(crate) use self::counter::BufferedReaderCounter;
 
 use packet::signature::subpacket::SubpacketArea;
 
@@ -104,6 +106,13 @@ pub trait Parse<'a, T> {
 /// So, this should be more than enough.
 const MAX_RECURSION_DEPTH : u8 = 16;
 
+/// How much of a packet's body we look at when checking whether it
+/// is truncated in lenient mode.
+///
+/// This bounds the amount of data buffered for every candidate
+/// offset when scanning garbage for the next packet.
+const LENIENT_LOOKAHEAD : usize = 64 * 1024;
+
 // Used to parse an OpenPGP packet's header (note: in this case, the
 // header means a Packet's fixed data, not the OpenPGP framing
 // information, such as the CTB, and length information).
@@ -441,6 +450,29 @@ pub(crate) struct Cookie {
     /// When set, buffered_reader_pop_stack will return early when it
     /// encounters a fake EOF at the level it is popping to.
     fake_eof: bool,
+
+    /// The number of bytes consumed from the input.
+    ///
+    /// This is only maintained by the `BufferedReaderCounter` that
+    /// the `PacketParserBuilder` pushes on top of the external
+    /// `BufferedReader`.  See `Cookie::position`.
+    consumed: u64,
+
+    /// The lengths of the partial body chunks read so far.
+    ///
+    /// This is only maintained by the
+    /// `BufferedReaderPartialBodyFilter`.  Runs of chunks of the same
+    /// length are stored as a single `(length, count)` pair.  This is
+    /// used to serialize unknown packets using their original
+    /// framing.
+    partial_body_chunks: Vec<(u32, u32)>,
+
+    /// The number of decoded bytes consumed from a partial body.
+    ///
+    /// This is only maintained by the
+    /// `BufferedReaderPartialBodyFilter`, and is `None` for all other
+    /// `BufferedReader`s.  See `PacketParser::partial_body_decoded`.
+    partial_body_decoded: Option<u64>,
 }
 
 /// Contains hashes for consecutive one pass signature packets ending
@@ -495,6 +527,9 @@ impl Default for Cookie {
             sig_groups_max_len: 1,
             hash_stash: None,
             fake_eof: false,
+            consumed: 0,
+            partial_body_chunks: Vec::new(),
+            partial_body_decoded: None,
         }
     }
 }
@@ -510,6 +545,9 @@ impl Cookie {
             sig_groups_max_len: 1,
             hash_stash: None,
             fake_eof: false,
+            consumed: 0,
+            partial_body_chunks: Vec::new(),
+            partial_body_decoded: None,
         }
     }
 
@@ -586,6 +624,24 @@ impl Cookie {
         }
     }
 
+    // Returns the number of bytes consumed from the input.
+    //
+    // This walks down the stack to the first `BufferedReader` without
+    // a level, i.e., the `BufferedReaderCounter` pushed by the
+    // `PacketParserBuilder`.  Only when parsing a top-level packet are
+    // there no buffering filters above it, so the result is only
+    // meaningful at that point.
+    fn position(reader: &BufferedReader<Cookie>) -> u64 {
+        let mut reader : Option<&BufferedReader<Cookie>> = Some(reader);
+        while let Some(r) = reader {
+            if r.cookie_ref().level.is_none() {
+                return r.cookie_ref().consumed;
+            }
+            reader = r.get_ref();
+        }
+        0
+    }
+
     // A helpful debugging aid to pretty print a Buffered Reader
     // stack.
     #[allow(dead_code)]
@@ -677,12 +733,30 @@ struct PacketParserSettings {
     // then a read from the reader pipeline could blow the stack.
     max_recursion_depth: u8,
 
+    // The maximum number of packets to parse.
+    //
+    // A message or TPK consisting of a huge number of tiny packets
+    // (e.g., millions of signatures) can make subsequent processing
+    // pathologically slow.  If set, parsing fails once this many
+    // packets have been seen.
+    max_packets: Option<usize>,
+
     // Whether a packet's contents should be buffered or dropped when
     // the next packet is retrieved.
     buffer_unread_content: bool,
 
     // Whether or not to create a map.
     map: bool,
+
+    // Whether to try harder to recover from corrupted input.
+    //
+    // Normally, we only try to recover from junk between packets if
+    // we are not looking at the first packet, the junk is followed
+    // by a plausible key or signature packet, and there is not too
+    // much of it.  In lenient mode, we resynchronize at the next
+    // packet that looks well-formed, no matter where we are and how
+    // much junk we have to skip.
+    lenient: bool,
 }
 
 // The default `PacketParser` settings.
@@ -690,8 +764,10 @@ impl Default for PacketParserSettings {
     fn default() -> Self {
         PacketParserSettings {
             max_recursion_depth: MAX_RECURSION_DEPTH,
+            max_packets: None,
             buffer_unread_content: false,
             map: false,
+            lenient: false,
         }
     }
 }
@@ -772,7 +848,11 @@ impl Unknown {
                  -> Result<PacketParser<'a>>
     {
         let tag = php.header.ctb.tag;
-        php.ok(Packet::Unknown(Unknown::new(tag, error)))
+        let mut unknown = Unknown::new(tag, error);
+        // Remember the framing so that we can serialize the packet
+        // exactly as we found it.
+        unknown.header = Some((php.header.clone(), php.header_bytes.clone()));
+        php.ok(Packet::Unknown(unknown))
             .map(|pp| pp.set_decrypted(false))
     }
 }
@@ -787,23 +867,29 @@ impl Unknown {
 #[cfg(test)]
 pub(crate) fn to_unknown_packet<R: Read>(reader: R) -> Result<Unknown>
 {
-    let mut reader = buffered_reader::Generic::with_cookie(
+    let reader = buffered_reader::Generic::with_cookie(
         reader, None, Cookie::default());
+    let mut reader = buffered_reader::Dup::with_cookie(
+        Box::new(reader), Cookie::default());
     let header = Header::parse(&mut reader)?;
+    let consumed = reader.total_out();
+    let mut reader = Box::new(reader).into_inner().unwrap();
+    let header_bytes = reader.data_consume_hard(consumed)?[..consumed].to_vec();
 
     let reader : Box<BufferedReader<Cookie>>
         = match header.length {
             BodyLength::Full(len) =>
                 Box::new(buffered_reader::Limitor::with_cookie(
-                    Box::new(reader), len as u64, Cookie::default())),
+                    reader, len as u64, Cookie::default())),
             BodyLength::Partial(len) =>
                 Box::new(BufferedReaderPartialBodyFilter::with_cookie(
                     reader, len, true, Cookie::default())),
-            _ => Box::new(reader),
+            _ => reader,
     };
 
     let parser = PacketHeaderParser::new(
-        reader, PacketParserState::new(Default::default()), vec![ 0 ], header, Vec::new());
+        reader, PacketParserState::new(Default::default()), vec![ 0 ], header,
+        header_bytes);
     let mut pp =
         Unknown::parse(parser,
                        failure::err_msg("explicit conversion to unknown"))?;
@@ -2436,6 +2522,13 @@ struct PacketParserState {
 
     // Whether this is the first packet in the packet sequence.
     first_packet: bool,
+
+    // The number of packets seen so far.
+    packets: usize,
+
+    // The offset of the current top-level packet in the input, or,
+    // once we hit EOF, the number of bytes read.
+    offset: u64,
 }
 
 impl PacketParserState {
@@ -2446,6 +2539,8 @@ impl PacketParserState {
             keyring_validator: Default::default(),
             tpk_validator: Default::default(),
             first_packet: true,
+            packets: 0,
+            offset: 0,
         }
     }
 }
@@ -2635,6 +2730,14 @@ impl PacketParserEOF {
             Some(self.last_path.len() as isize - 1)
         }
     }
+
+    /// Returns the number of bytes that were read.
+    ///
+    /// This is the offset just past the last top-level packet.  See
+    /// `PacketParser::offset` for details.
+    pub fn offset(&self) -> u64 {
+        self.state.offset
+    }
 }
 
 /// The return type of `PacketParser::next`() and
@@ -2966,11 +3069,41 @@ impl <'a> PacketParser<'a> {
         }
     }
 
+    /// Checks whether `header` is a plausible packet header when
+    /// parsing leniently.
+    ///
+    /// This is less strict than `plausible`, which only recovers keys
+    /// and signatures: any known packet type with a valid header is
+    /// accepted as long as its body is not truncated.  For large
+    /// packets, we only check that the first `LENIENT_LOOKAHEAD`
+    /// bytes of the body are present.
+    fn plausible_lenient(bio: &mut buffered_reader::Dup<Cookie>,
+                         header: &Header) -> Result<()> {
+        match header.ctb.tag {
+            Tag::Reserved | Tag::Unknown(_) | Tag::Private(_) =>
+                return Err(Error::MalformedPacket(
+                    "Looks like garbage".into()).into()),
+            _ => (),
+        }
+
+        header.valid(true)?;
+
+        if let BodyLength::Full(len) = header.length {
+            let len = cmp::min(len as usize, LENIENT_LOOKAHEAD);
+            if bio.data(len)?.len() < len {
+                return Err(Error::MalformedPacket(
+                    "Truncated packet".into()).into());
+            }
+        }
+
+        Ok(())
+    }
+
     /// Returns a `PacketParser` for the next OpenPGP packet in the
     /// stream.  If there are no packets left, this function returns
     /// `bio`.
     fn parse(mut bio: Box<BufferedReader<Cookie> + 'a>,
-             state: PacketParserState,
+             mut state: PacketParserState,
              path: Vec<usize>)
         -> Result<ParserResult<'a>>
     {
@@ -2982,6 +3115,10 @@ impl <'a> PacketParser<'a> {
 
         let recursion_depth = path.len() as isize - 1;
 
+        if recursion_depth == 0 {
+            state.offset = Cookie::position(&*bio);
+        }
+
         // When header encounters an EOF, it returns an error.  But,
         // we want to return None.  Try a one byte read.
         if bio.data(1)?.len() == 0 {
@@ -2989,6 +3126,15 @@ impl <'a> PacketParser<'a> {
             return Ok(ParserResult::EOF((bio, state, path)));
         }
 
+        state.packets += 1;
+        if let Some(max) = state.settings.max_packets {
+            if state.packets > max {
+                t!("Maximum packet count ({}) exceeded.", max);
+                return Err(Error::MalformedPacket(
+                    format!("More than {} packets", max)).into());
+            }
+        }
+
         // When computing a hash for a signature, most of the
         // signature packet should not be included in the hash.  That
         // is:
@@ -3011,18 +3157,37 @@ impl <'a> PacketParser<'a> {
         // Read the header.
         let mut skip = 0;
         let mut orig_error : Option<failure::Error> = None;
+        let lenient = state.settings.lenient;
         loop {
             bio.rewind();
+
+            if lenient && skip > 0 && bio.data(skip + 1)?.len() <= skip {
+                // We hit EOF without finding another packet.  Turn
+                // the rest into junk (see below).
+                t!("No packet after {} bytes of junk (EOF).", skip);
+                header = Header {
+                    ctb: CTB::new(Tag::Reserved),
+                    length: BodyLength::Full(skip as u32),
+                };
+                break;
+            }
+
             bio.data_consume_hard(skip)?;
 
             match Header::parse(&mut bio) {
                 Ok(header_) => {
-                    if skip == 0 {
-                        header = header_;
-                        break;
-                    }
+                    // When parsing leniently, we also check the
+                    // first header, so that we can skip junk that
+                    // happens to parse as a header.
+                    let plausible = if lenient {
+                        Self::plausible_lenient(&mut bio, &header_)
+                    } else if skip == 0 {
+                        Ok(())
+                    } else {
+                        Self::plausible(&mut bio, &header_)
+                    };
 
-                    match Self::plausible(&mut bio, &header_) {
+                    match plausible {
                         Ok(()) => {
                             header = header_;
                             break;
@@ -3035,10 +3200,13 @@ impl <'a> PacketParser<'a> {
                         orig_error = Some(err.into());
                     }
 
-                    if state.first_packet || skip > 32 * 1024 {
+                    if ! lenient
+                        && (state.first_packet || skip > 32 * 1024)
+                    {
                         // Limit the search space.  This should be
                         // enough to find a reasonable recovery point
-                        // in a TPK.
+                        // in a TPK.  In lenient mode, we keep looking
+                        // until EOF.
                         return Err(orig_error.unwrap());
                     }
                 }
@@ -3492,6 +3660,14 @@ impl <'a> PacketParser<'a> {
             }
         }
 
+        // Remember the partial body chunks' boundaries so that we can
+        // serialize unknown packets using their original framing.
+        if let BodyLength::Partial(_) = self.header.length {
+            if let Packet::Unknown(ref mut u) = self.packet {
+                u.chunks = self.reader.cookie_ref().partial_body_chunks.clone();
+            }
+        }
+
         self.finished = true;
 
         Ok(&mut self.packet)
@@ -3502,6 +3678,104 @@ impl <'a> PacketParser<'a> {
         &self.header
     }
 
+    /// Returns the offset of the current packet in the input.
+    ///
+    /// The offset is the position of the packet's header.  If the
+    /// input is ASCII armored, the offset is relative to the
+    /// dearmored data.  Packets inside of a container are read from a
+    /// decompressed or decrypted stream, and have no meaningful
+    /// offset in the input.  For these, this returns `None`.
+    ///
+    /// A top-level packet's total length, i.e., the length of the
+    /// header plus the body, including any partial body length
+    /// headers, is the difference between its offset and the offset
+    /// of the next top-level packet, or `PacketParserEOF::offset`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # use openpgp::Result;
+    /// # use openpgp::parse::{Parse, PacketParser, PacketParserResult};
+    /// # f();
+    /// #
+    /// # fn f() -> Result<()> {
+    /// let msg = b"\xca\x03PGP\xcb\x12t\x00\x00\x00\x00\x00Hello world.";
+    /// let mut offsets = Vec::new();
+    /// let mut ppr = PacketParser::from_bytes(&msg[..])?;
+    /// while let PacketParserResult::Some(pp) = ppr {
+    ///     offsets.push(pp.offset());
+    ///     ppr = pp.recurse()?.1;
+    /// }
+    /// assert_eq!(offsets, [Some(0), Some(5)]);
+    /// if let PacketParserResult::EOF(eof) = ppr {
+    ///     assert_eq!(eof.offset(), msg.len() as u64);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn offset(&self) -> Option<u64> {
+        if self.recursion_depth() == 0 {
+            Some(self.state.offset)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of decoded bytes of the current packet's
+    /// body that have been consumed.
+    ///
+    /// This is only available if the packet's body uses partial body
+    /// lengths, otherwise `None` is returned.  It does not include
+    /// the partial body length headers, nor any data that has been
+    /// buffered, but not yet consumed.  This can be used to report
+    /// progress when streaming large packets.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # use std::io::Read;
+    /// # use openpgp::{Result, Packet};
+    /// # use openpgp::parse::{Parse, PacketParserResult, PacketParser};
+    /// # f(include_bytes!("../../tests/data/messages/literal-mode-t-partial-body.gpg"));
+    /// #
+    /// # fn f(message_data: &[u8]) -> Result<()> {
+    /// let mut ppr = PacketParser::from_bytes(message_data)?;
+    /// while let PacketParserResult::Some(mut pp) = ppr {
+    ///     if let Packet::Literal(_) = pp.packet {
+    ///         let start = pp.partial_body_decoded().unwrap();
+    ///         let mut buf = [0; 10];
+    ///         pp.read_exact(&mut buf)?;
+    ///         assert_eq!(pp.partial_body_decoded(), Some(start + 10));
+    ///     }
+    ///
+    ///     ppr = pp.recurse()?.1;
+    /// }
+    /// # return Ok(());
+    /// # }
+    /// ```
+    pub fn partial_body_decoded(&self) -> Option<u64> {
+        if let BodyLength::Partial(_) = self.header.length {
+            // Look for the partial body filter, which is at the
+            // current packet's level.
+            let level = self.recursion_depth();
+            let mut reader : Option<&BufferedReader<Cookie>> =
+                Some(&*self.reader);
+            while let Some(r) = reader {
+                let cookie = r.cookie_ref();
+                if cookie.level != Some(level) {
+                    break;
+                }
+                if cookie.partial_body_decoded.is_some() {
+                    return cookie.partial_body_decoded;
+                }
+                reader = r.get_ref();
+            }
+        }
+        None
+    }
+
     /// Returns a reference to the map (if any is written).
     pub fn map(&self) -> Option<&map::Map> {
         self.map.as_ref()
@@ -4365,4 +4639,190 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn max_packets() {
+        use serialize::Serialize;
+
+        // testy.pgp consists of a primary key, a user id, a subkey,
+        // and two signatures.
+        let key = ::tests::key("testy.pgp");
+
+        let mut ppr = PacketParserBuilder::from_bytes(key).unwrap()
+            .max_packets(5)
+            .finalize().unwrap();
+        let mut count = 0;
+        while let PacketParserResult::Some(pp) = ppr {
+            count += 1;
+            ppr = pp.recurse().unwrap().1;
+        }
+        assert_eq!(count, 5);
+
+        // Now append a lot of copies of the binding signature to
+        // simulate a flooded key.
+        let mut flooded = key.to_vec();
+        let sig = {
+            let mut ppr = PacketParser::from_bytes(key).unwrap();
+            let mut sig = None;
+            while let PacketParserResult::Some(pp) = ppr {
+                let (packet, ppr_) = pp.recurse().unwrap();
+                ppr = ppr_;
+                if let Packet::Signature(s) = packet {
+                    sig = Some(s);
+                }
+            }
+            sig.unwrap()
+        };
+        for _ in 0..1000 {
+            Packet::Signature(sig.clone()).serialize(&mut flooded).unwrap();
+        }
+
+        let mut ppr = PacketParserBuilder::from_bytes(&flooded[..]).unwrap()
+            .max_packets(100)
+            .finalize().unwrap();
+        let mut count = 0;
+        let err = loop {
+            if let PacketParserResult::Some(pp) = ppr {
+                count += 1;
+                match pp.recurse() {
+                    Ok((_, ppr_)) => ppr = ppr_,
+                    Err(err) => break err,
+                }
+            } else {
+                panic!("Packet count limit not enforced");
+            }
+        };
+        assert_eq!(count, 100);
+        assert_match!(Some(&Error::MalformedPacket(_)) = err.downcast_ref());
+
+        // Without a limit, all packets are parsed.
+        let mut ppr = PacketParser::from_bytes(&flooded[..]).unwrap();
+        let mut count = 0;
+        while let PacketParserResult::Some(pp) = ppr {
+            count += 1;
+            ppr = pp.recurse().unwrap().1;
+        }
+        assert_eq!(count, 1005);
+    }
+
+    #[test]
+    fn lenient() {
+        // testy.pgp consists of a primary key, a user id, a subkey,
+        // and two signatures.
+        let key = ::tests::key("testy.pgp");
+
+        let parse = |data: &[u8], lenient: bool| -> Result<Vec<Tag>> {
+            let mut ppr = PacketParserBuilder::from_bytes(data)?
+                .dearmor(packet_parser_builder::Dearmor::Disabled)
+                .lenient(lenient)
+                .finalize()?;
+            let mut tags = Vec::new();
+            while let PacketParserResult::Some(pp) = ppr {
+                tags.push(pp.packet.tag());
+                ppr = pp.recurse()?.1;
+            }
+            Ok(tags)
+        };
+
+        let expected = parse(key, false).unwrap();
+        assert_eq!(expected.len(), 5);
+        assert_eq!(parse(key, true).unwrap(), expected);
+
+        // Find the end of the primary key.
+        let ppr = PacketParser::from_bytes(key).unwrap();
+        let pp = ppr.unwrap();
+        let (_, ppr) = pp.recurse().unwrap();
+        let end = ppr.unwrap().offset().unwrap() as usize;
+
+        // Put junk in front of the key and after the primary key.
+        let mut corrupted = b"\x00\x01junk".to_vec();
+        corrupted.extend_from_slice(&key[..end]);
+        corrupted.extend_from_slice(b"\x00more junk");
+        corrupted.extend_from_slice(&key[end..]);
+        // And a truncated packet at the end.
+        corrupted.extend_from_slice(&key[..3]);
+
+        assert!(parse(&corrupted, false).is_err());
+
+        let mut with_junk = vec![ Tag::Reserved, expected[0], Tag::Reserved ];
+        with_junk.extend_from_slice(&expected[1..]);
+        with_junk.push(Tag::Reserved);
+        assert_eq!(parse(&corrupted, true).unwrap(), with_junk);
+
+        // The junk is preserved.
+        let mut ppr = PacketParserBuilder::from_bytes(&corrupted[..]).unwrap()
+            .dearmor(packet_parser_builder::Dearmor::Disabled)
+            .lenient(true)
+            .buffer_unread_content()
+            .finalize().unwrap();
+        let mut junk = Vec::new();
+        while let PacketParserResult::Some(pp) = ppr {
+            let (packet, ppr_) = pp.recurse().unwrap();
+            ppr = ppr_;
+            if let Packet::Unknown(u) = packet {
+                junk.push(u.body().unwrap().to_vec());
+            }
+        }
+        assert_eq!(junk, vec![ b"\x00\x01junk".to_vec(),
+                               b"\x00more junk".to_vec(),
+                               key[..3].to_vec() ]);
+
+        // Junk that looks like the header of a huge packet is
+        // skipped without reading the whole body.
+        let mut huge = b"\x00\xcb\xff\xff\xff\xff\xff".to_vec();
+        huge.extend_from_slice(key);
+        let mut with_junk = vec![ Tag::Reserved ];
+        with_junk.extend_from_slice(&expected);
+        assert_eq!(parse(&huge, true).unwrap(), with_junk);
+    }
+
+    #[test]
+    fn offsets() {
+        for (name, expected) in [
+            // One pass sig, literal (partial body), sig.
+            ("signed-2-partial-body.gpg", &[0, 15][..]),
+            // Compressed data packet, whose content has no offsets.
+            ("compressed-data-algo-1.gpg", &[0][..]),
+            // The armor is stripped.
+            ("a-cypherpunks-manifesto.txt.ed25519.sig", &[0][..]),
+        ].iter() {
+            let msg = ::tests::message(name);
+            let mut offsets = Vec::new();
+            let mut lengths = Vec::new();
+            let mut ppr = PacketParser::from_bytes(msg).unwrap();
+            while let PacketParserResult::Some(pp) = ppr {
+                if let Some(offset) = pp.offset() {
+                    assert_eq!(pp.recursion_depth(), 0);
+                    offsets.push(offset);
+                    lengths.push(match pp.header().length {
+                        BodyLength::Full(l) => Some(l as u64),
+                        _ => None,
+                    });
+                } else {
+                    assert!(pp.recursion_depth() > 0);
+                }
+                ppr = pp.recurse().unwrap().1;
+            }
+            let end = if let PacketParserResult::EOF(eof) = ppr {
+                eof.offset()
+            } else {
+                unreachable!()
+            };
+
+            assert_eq!(&offsets[..expected.len()], *expected);
+            if ! name.ends_with(".sig") {
+                assert_eq!(end, msg.len() as u64);
+            }
+
+            // The offsets partition the input.
+            offsets.push(end);
+            for (i, length) in lengths.iter().enumerate() {
+                let total = offsets[i + 1] - offsets[i];
+                if let Some(length) = length {
+                    // Header and body.
+                    assert!(total > *length && total <= *length + 6);
+                }
+            }
+        }
+    }
 }
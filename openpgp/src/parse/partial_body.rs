@@ -53,6 +53,7 @@ impl<T: BufferedReader<Cookie>> std::fmt::Debug
         f.debug_struct("BufferedReaderPartialBodyFilter")
             .field("partial_body_length", &self.partial_body_length)
             .field("last", &self.last)
+            .field("decoded", &self.cookie.partial_body_decoded)
             .field("hash headers", &self.hash_headers)
             .field("buffer (bytes left)",
                    &if let Some(ref buffer) = self.buffer {
@@ -70,7 +71,9 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
     /// `partial_body_length` is the amount of data in the initial
     /// partial body chunk.
     pub fn with_cookie(reader: T, partial_body_length: u32,
-                       hash_headers: bool, cookie: Cookie) -> Self {
+                       hash_headers: bool, mut cookie: Cookie) -> Self {
+        cookie.partial_body_chunks = vec![(partial_body_length, 1)];
+        cookie.partial_body_decoded = Some(0);
         BufferedReaderPartialBodyFilter {
             reader: reader,
             partial_body_length: partial_body_length,
@@ -82,6 +85,28 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
         }
     }
 
+    /// Returns the underlying reader.
+    ///
+    /// Unlike `BufferedReader::into_inner`, this preserves the type
+    /// of the underlying reader.  Any data that has been buffered,
+    /// but not yet consumed is discarded.
+    #[allow(dead_code)]
+    pub fn into_parts(self) -> T {
+        self.reader
+    }
+
+    // Records the length of the next chunk in the cookie.
+    fn record_chunk(&mut self, len: u32) {
+        let chunks = &mut self.cookie.partial_body_chunks;
+        if let Some(&mut (l, ref mut n)) = chunks.last_mut() {
+            if l == len {
+                *n += 1;
+                return;
+            }
+        }
+        chunks.push((len, 1));
+    }
+
     // Make sure that the local buffer contains `amount` bytes.
     fn do_fill_buffer (&mut self, amount: usize) -> Result<(), std::io::Error> {
         if TRACE {
@@ -136,12 +161,19 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
                         amount_buffered += did_read;
                         self.partial_body_length -= did_read as u32;
 
-                        if did_read < to_read {
-                            // Short read => EOF.  We're done.
-                            // (Although the underlying message is
-                            // probably corrupt.)
+                        if did_read == 0 {
+                            // EOF.  We're done.  (Although the
+                            // underlying message is probably
+                            // corrupt.)
                             break;
                         }
+
+                        if did_read < to_read {
+                            // Short read.  Read the rest of the
+                            // chunk before looking for the next
+                            // chunk's header.
+                            continue;
+                        }
                     },
                     Err(e) => {
                         if TRACE {
@@ -187,10 +219,12 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
                     //println!("Last chunk: {} bytes", len);
                     self.last = true;
                     self.partial_body_length = len;
+                    self.record_chunk(len);
                 },
                 Ok(BodyLength::Partial(len)) => {
                     //println!("Next chunk: {} bytes", len);
                     self.partial_body_length = len;
+                    self.record_chunk(len);
                 },
                 Ok(BodyLength::Indeterminate) => {
                     // A new format packet can't return Indeterminate.
@@ -269,8 +303,10 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
                                                   "unexpected EOF"));
                         } else {
                             if and_consume {
-                                self.partial_body_length -=
-                                    cmp::min(amount, amount_buffered) as u32;
+                                let consumed = cmp::min(amount, amount_buffered);
+                                self.partial_body_length -= consumed as u32;
+                                *self.cookie.partial_body_decoded
+                                    .get_or_insert(0) += consumed as u64;
                             }
                             return Ok(&buffer[..amount_buffered]);
                         }
@@ -308,7 +344,10 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
             return Err(Error::new(ErrorKind::UnexpectedEof, "unexpected EOF"));
         }
         if and_consume {
-            self.cursor += cmp::min(amount, buffer.len());
+            let consumed = cmp::min(amount, buffer.len());
+            self.cursor += consumed;
+            *self.cookie.partial_body_decoded.get_or_insert(0) +=
+                consumed as u64;
         }
         return Ok(buffer);
     }
@@ -346,6 +385,7 @@ impl<T: BufferedReader<Cookie>> BufferedReader<Cookie>
     }
 
     fn consume(&mut self, amount: usize) -> &[u8] {
+        *self.cookie.partial_body_decoded.get_or_insert(0) += amount as u64;
         if let Some(ref buffer) = self.buffer {
             // We have a local buffer.
 
@@ -402,3 +442,162 @@ impl<T: BufferedReader<Cookie>> BufferedReader<Cookie>
         &mut self.cookie
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+    use buffered_reader::Memory;
+    use serialize::PartialBodyWriter;
+
+    #[test]
+    fn roundtrip() {
+        // Make the buffer threshold small so that we get a bunch of
+        // partial body chunks of varying sizes.
+        for &(threshold, max_chunk_size) in &[(4, 4), (4, 16),
+                                              (16, 16), (64, 1 << 30)] {
+            for &len in &[0, 1, 3, 4, 5, 16, 17, 100, 1000] {
+                let input: Vec<u8> = (0..len).map(|i| i as u8).collect();
+
+                let mut buf = Vec::new();
+                {
+                    let mut w = PartialBodyWriter::with_limits(
+                        &mut buf, threshold, max_chunk_size).unwrap();
+                    // Write in odd-sized pieces.
+                    for chunk in input.chunks(7) {
+                        w.write_all(chunk).unwrap();
+                    }
+                    w.finalize().unwrap();
+                }
+
+                let mut reader = Memory::with_cookie(
+                    &buf[..], Cookie::default());
+                let output = match BodyLength::parse_new_format(&mut reader)
+                    .unwrap()
+                {
+                    BodyLength::Full(l) => {
+                        assert_eq!(l as usize, input.len());
+                        reader.steal_eof().unwrap()
+                    },
+                    BodyLength::Partial(l) => {
+                        let mut filter =
+                            BufferedReaderPartialBodyFilter::with_cookie(
+                                reader, l, true, Cookie::default());
+                        let mut output = Vec::new();
+                        filter.read_to_end(&mut output).unwrap();
+                        output
+                    },
+                    BodyLength::Indeterminate => unreachable!(),
+                };
+                assert_eq!(input, output);
+            }
+        }
+    }
+
+    #[test]
+    fn into_parts() {
+        let input: Vec<u8> = (0..100).map(|i| i as u8).collect();
+
+        let mut buf = Vec::new();
+        {
+            let mut w = PartialBodyWriter::with_limits(
+                &mut buf, 16, 16).unwrap();
+            w.write_all(&input).unwrap();
+            w.finalize().unwrap();
+        }
+        buf.extend_from_slice(b"trailer");
+
+        let mut reader = Memory::with_cookie(&buf[..], Cookie::default());
+        let l = match BodyLength::parse_new_format(&mut reader).unwrap() {
+            BodyLength::Partial(l) => l,
+            l => panic!("Expected a partial body, got {:?}", l),
+        };
+        let mut filter = BufferedReaderPartialBodyFilter::with_cookie(
+            reader, l, true, Cookie::default());
+        let mut output = Vec::new();
+        filter.read_to_end(&mut output).unwrap();
+        assert_eq!(input, output);
+
+        // We get back a Memory, positioned after the body.
+        let mut reader: Memory<Cookie> = filter.into_parts();
+        assert_eq!(reader.total_out(), buf.len() - b"trailer".len());
+        assert_eq!(reader.steal_eof().unwrap(), b"trailer");
+    }
+
+    #[test]
+    fn decoded_count() {
+        let input: Vec<u8> = (0..100).map(|i| i as u8).collect();
+
+        let mut buf = Vec::new();
+        {
+            let mut w = PartialBodyWriter::with_limits(
+                &mut buf, 4, 8).unwrap();
+            w.write_all(&input).unwrap();
+            w.finalize().unwrap();
+        }
+
+        let mut reader = Memory::with_cookie(&buf[..], Cookie::default());
+        let l = match BodyLength::parse_new_format(&mut reader).unwrap() {
+            BodyLength::Partial(l) => l,
+            l => panic!("Expected a partial body, got {:?}", l),
+        };
+        let mut filter = BufferedReaderPartialBodyFilter::with_cookie(
+            reader, l, true, Cookie::default());
+        assert_eq!(filter.cookie_ref().partial_body_decoded, Some(0));
+
+        // Buffered data is not counted, even if it straddles chunks.
+        assert_eq!(filter.data_hard(20).unwrap()[..20], input[..20]);
+        assert_eq!(filter.cookie_ref().partial_body_decoded, Some(0));
+
+        // Consumed data is, whether it is double buffered or not.
+        assert_eq!(filter.consume(3)[..3], input[..3]);
+        assert_eq!(filter.cookie_ref().partial_body_decoded, Some(3));
+        assert_eq!(filter.data_consume_hard(30).unwrap()[..30], input[3..33]);
+        assert_eq!(filter.cookie_ref().partial_body_decoded, Some(33));
+        assert_eq!(filter.data_consume(2).unwrap()[..2], input[33..35]);
+        assert_eq!(filter.cookie_ref().partial_body_decoded, Some(35));
+
+        let rest = filter.steal_eof().unwrap();
+        assert_eq!(rest, &input[35..]);
+        assert_eq!(filter.cookie_ref().partial_body_decoded,
+                   Some(input.len() as u64));
+    }
+
+    #[test]
+    fn throttled() {
+        // Trickle-feed the filter to exercise the short read paths.
+        for &max_per_call in &[1, 2, 3, 5] {
+            for &len in &[0, 1, 3, 4, 5, 16, 17, 100] {
+                let input: Vec<u8> = (0..len).map(|i| i as u8).collect();
+
+                let mut buf = Vec::new();
+                {
+                    let mut w = PartialBodyWriter::with_limits(
+                        &mut buf, 4, 4).unwrap();
+                    w.write_all(&input).unwrap();
+                    w.finalize().unwrap();
+                }
+
+                let mut reader = buffered_reader::Throttle::with_cookie(
+                    Box::new(Memory::with_cookie(&buf[..], Cookie::default())),
+                    max_per_call, Cookie::default());
+                let output = match BodyLength::parse_new_format(&mut reader)
+                    .unwrap()
+                {
+                    BodyLength::Full(l) => {
+                        assert_eq!(l as usize, input.len());
+                        reader.steal_eof().unwrap()
+                    },
+                    BodyLength::Partial(l) => {
+                        let mut filter =
+                            BufferedReaderPartialBodyFilter::with_cookie(
+                                reader, l, true, Cookie::default());
+                        filter.steal_eof().unwrap()
+                    },
+                    BodyLength::Indeterminate => unreachable!(),
+                };
+                assert_eq!(input, output);
+            }
+        }
+    }
+}
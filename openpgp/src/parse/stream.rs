@@ -47,6 +47,7 @@ use {
 };
 use parse::{
     Cookie,
+    Parse,
     PacketParser,
     PacketParserBuilder,
     PacketParserResult,
@@ -1015,6 +1016,221 @@ impl DetachedVerifier {
     }
 }
 
+/// Describes how a message is encrypted.
+///
+/// This summarizes the encrypted session key packets that precede
+/// the encryption container.  It can be obtained without decrypting
+/// the message, which is useful to tell the user why a message could
+/// not be decrypted.
+///
+/// # Example
+///
+/// ```
+/// # extern crate sequoia_openpgp as openpgp;
+/// # use openpgp::Result;
+/// use openpgp::parse::Parse;
+/// use openpgp::parse::stream::EncryptionInfo;
+///
+/// # f().unwrap();
+/// # fn f() -> Result<()> {
+/// let message =
+///    b"-----BEGIN PGP MESSAGE-----
+///
+///      wy4ECQMIY5Zs8RerVcXp85UgoUKjKkevNPX3WfcS5eb7rkT9I6kw6N2eEc5PJUDh
+///      0j0B9mnPKeIwhp2kBHpLX/en6RfNqYauX9eSeia7aqsd/AOLbO9WMCLZS5d2LTxN
+///      rwwb8Aggyukj13Mi0FF5
+///      =OB/8
+///      -----END PGP MESSAGE-----";
+///
+/// let info = EncryptionInfo::from_bytes(message)?;
+/// assert!(info.recipients().is_empty());
+/// assert_eq!(info.skesks(), 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptionInfo {
+    recipients: Vec<KeyID>,
+    skesks: usize,
+}
+
+impl EncryptionInfo {
+    /// Summarizes the given encrypted session key packets.
+    pub fn new(pkesks: &[PKESK], skesks: &[SKESK]) -> Self {
+        EncryptionInfo {
+            recipients: pkesks.iter().map(|p| p.recipient().clone()).collect(),
+            skesks: skesks.len(),
+        }
+    }
+
+    /// Returns the KeyIDs of the keys the message is encrypted to.
+    ///
+    /// Note that the sender may hide the recipients, in which case
+    /// the wildcard KeyID is returned.
+    pub fn recipients(&self) -> &[KeyID] {
+        &self.recipients
+    }
+
+    /// Returns the number of password-encrypted session keys.
+    ///
+    /// If this is non-zero, the message can be decrypted using a
+    /// password.
+    pub fn skesks(&self) -> usize {
+        self.skesks
+    }
+}
+
+impl<'a> Parse<'a, EncryptionInfo> for EncryptionInfo {
+    /// Reads the encrypted session key packets from the given reader.
+    ///
+    /// Parsing stops at the encryption container, which is not
+    /// decrypted.  If the message is not encrypted,
+    /// `Error::MalformedMessage` is returned.
+    fn from_reader<R: 'a + io::Read>(reader: R) -> Result<Self> {
+        let mut pkesks = Vec::new();
+        let mut skesks = Vec::new();
+
+        let mut ppr = PacketParser::from_reader(reader)?;
+        while let PacketParserResult::Some(pp) = ppr {
+            match pp.packet {
+                Packet::SEIP(_) | Packet::AED(_) =>
+                    return Ok(EncryptionInfo::new(&pkesks, &skesks)),
+                _ => (),
+            }
+
+            let (packet, ppr_) = pp.next()?;
+            ppr = ppr_;
+
+            match packet {
+                Packet::PKESK(p) => pkesks.push(p),
+                Packet::SKESK(s) => skesks.push(s),
+                _ => (),
+            }
+        }
+
+        Err(Error::MalformedMessage("Message is not encrypted".into()).into())
+    }
+}
+
+/// Reads the content of a literal data packet along with its metadata.
+///
+/// This is the counterpart to [`LiteralWriter`].  The message is
+/// parsed up to the first literal data packet, descending into
+/// compressed data containers.  The packet's format, filename, and
+/// date are available immediately, and the content can be read from
+/// the `LiteralReader` in a streaming fashion.
+///
+/// Note that neither the metadata nor the content is authenticated:
+/// signatures are not checked and encrypted messages are not
+/// supported.  Use [`Verifier`] or [`Decryptor`] for that.
+///
+///   [`LiteralWriter`]: ../../serialize/stream/struct.LiteralWriter.html
+///   [`Verifier`]: struct.Verifier.html
+///   [`Decryptor`]: struct.Decryptor.html
+///
+/// # Example
+///
+/// ```
+/// extern crate sequoia_openpgp as openpgp;
+/// use std::io::Read;
+/// use openpgp::constants::DataFormat;
+/// use openpgp::parse::Parse;
+/// use openpgp::parse::stream::LiteralReader;
+/// # use openpgp::Result;
+/// # f().unwrap();
+/// # fn f() -> Result<()> {
+///
+/// let message = b"\xcb\x12t\x00\x00\x00\x00\x00Hello world.";
+/// let mut r = LiteralReader::from_bytes(message)?;
+/// assert_eq!(r.format(), DataFormat::Text);
+/// assert_eq!(r.filename(), None);
+///
+/// let mut content = Vec::new();
+/// r.read_to_end(&mut content)?;
+/// assert_eq!(content, b"Hello world.");
+/// # Ok(())
+/// # }
+/// ```
+pub struct LiteralReader<'a> {
+    literal: Literal,
+    pp: PacketParser<'a>,
+}
+
+impl<'a> LiteralReader<'a> {
+    /// Reads the first literal data packet from `ppr`.
+    fn from_packet_parser_result(mut ppr: PacketParserResult<'a>)
+                                 -> Result<Self> {
+        while let PacketParserResult::Some(pp) = ppr {
+            match pp.packet {
+                Packet::Literal(ref l) => {
+                    let literal = l.clone();
+                    return Ok(LiteralReader { literal: literal, pp: pp });
+                },
+                Packet::SEIP(_) | Packet::AED(_) =>
+                    return Err(Error::MalformedMessage(
+                        "Message is encrypted".into()).into()),
+                _ => (),
+            }
+
+            ppr = pp.recurse()?.1;
+        }
+
+        Err(Error::MalformedMessage(
+            "No literal data packet found".into()).into())
+    }
+
+    /// Returns the literal data packet's header.
+    ///
+    /// The returned packet does not contain the content.
+    pub fn literal(&self) -> &Literal {
+        &self.literal
+    }
+
+    /// Returns the format of the content.
+    pub fn format(&self) -> DataFormat {
+        self.literal.format()
+    }
+
+    /// Returns the filename, if any.
+    pub fn filename(&self) -> Option<&[u8]> {
+        self.literal.filename()
+    }
+
+    /// Returns the date, if any.
+    pub fn date(&self) -> Option<&time::Tm> {
+        self.literal.date()
+    }
+
+    /// Returns a `BufferedReader` over the content.
+    pub fn reader(&mut self) -> &mut BufferedReader<Cookie> {
+        &mut self.pp
+    }
+}
+
+impl<'a> Parse<'a, LiteralReader<'a>> for LiteralReader<'a> {
+    /// Reads the first literal data packet from the given reader.
+    ///
+    /// If the message does not contain a literal data packet, or it
+    /// is encrypted, `Error::MalformedMessage` is returned.
+    fn from_reader<R: 'a + io::Read>(reader: R) -> Result<Self> {
+        Self::from_packet_parser_result(PacketParser::from_reader(reader)?)
+    }
+
+    /// Reads the first literal data packet from the given slice.
+    ///
+    /// If the message does not contain a literal data packet, or it
+    /// is encrypted, `Error::MalformedMessage` is returned.
+    fn from_bytes(data: &'a [u8]) -> Result<Self> {
+        Self::from_packet_parser_result(PacketParser::from_bytes(data)?)
+    }
+}
+
+impl<'a> io::Read for LiteralReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.pp.read(buf)
+    }
+}
+
 /// Decrypts and verifies an encrypted and optionally signed OpenPGP
 /// message.
 ///
@@ -1541,7 +1757,6 @@ impl<'a, H: VerificationHelper + DecryptionHelper> io::Read for Decryptor<'a, H>
 mod test {
     use failure;
     use super::*;
-    use parse::Parse;
 
     #[derive(Debug, PartialEq)]
     struct VHelper {
@@ -1615,6 +1830,25 @@ mod test {
         }
     }
 
+    #[test]
+    fn encryption_info() {
+        let testy = TPK::from_bytes(::tests::key("testy.pgp")).unwrap();
+        let info = EncryptionInfo::from_bytes(
+            ::tests::message("encrypted-to-testy.gpg")).unwrap();
+        assert_eq!(info.recipients().len(), 1);
+        assert!(testy.subkeys()
+                .any(|skb| skb.subkey().keyid() == info.recipients()[0]));
+        assert_eq!(info.skesks(), 0);
+
+        let info = EncryptionInfo::from_bytes(
+            ::tests::message("encrypted-aes256-password-123.gpg")).unwrap();
+        assert!(info.recipients().is_empty());
+        assert_eq!(info.skesks(), 1);
+
+        assert!(EncryptionInfo::from_bytes(
+            ::tests::message("signed-1.gpg")).is_err());
+    }
+
     #[test]
     fn verifier() {
         let keys = [
@@ -1791,6 +2025,40 @@ mod test {
         assert_eq!(reference, &content[..]);
     }
 
+    #[test]
+    fn literal_reader() {
+        use constants::CompressionAlgorithm;
+        use serialize::stream::{Compressor, LiteralWriter, Message};
+        use std::io::Write;
+
+        let date = time::at_utc(time::Timespec::new(1554542220, 0));
+        let mut buf = vec![];
+        {
+            let m = Message::new(&mut buf);
+            let c = Compressor::new(m, CompressionAlgorithm::Zip).unwrap();
+            let mut w = LiteralWriter::new(c, DataFormat::Text,
+                                           Some(&b"hello.txt"[..]),
+                                           Some(date)).unwrap();
+            w.write_all(b"Hello world.").unwrap();
+            w.finalize().unwrap();
+        }
+
+        let mut r = LiteralReader::from_bytes(&buf).unwrap();
+        assert_eq!(r.format(), DataFormat::Text);
+        assert_eq!(r.filename(), Some(&b"hello.txt"[..]));
+        assert_eq!(r.date().map(|d| d.to_timespec()),
+                   Some(date.to_timespec()));
+        assert_eq!(r.reader().data_eof().unwrap(), &b"Hello world."[..]);
+
+        let mut content = Vec::new();
+        r.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"Hello world.");
+
+        // Encrypted messages are not supported.
+        let encrypted = ::tests::message("encrypted-to-testy.gpg");
+        assert!(LiteralReader::from_bytes(encrypted).is_err());
+    }
+
     #[test]
     fn verify_long_message() {
         use constants::DataFormat;
@@ -19,6 +19,7 @@ mod partial_body;
 mod tpk;
 pub use self::tpk::TSK;
 use self::partial_body::PartialBodyFilter;
+pub use self::partial_body::PartialBodyWriter;
 pub mod writer;
 pub mod stream;
 use crypto::s2k::S2K;
@@ -834,7 +835,67 @@ impl SerializeInto for S2K {
     }
 }
 
+impl Unknown {
+    /// Returns the original header's encoding, if it can be reused.
+    ///
+    /// The original header can only be reused if it still matches
+    /// the packet's tag and, for packets with a definite length, the
+    /// body's length.
+    fn original_header(&self) -> Option<(&Header, &[u8])> {
+        let body_len = self.body().unwrap_or(&b""[..]).len();
+        match self.header {
+            Some((ref header, ref bytes)) if header.ctb.tag == self.tag() =>
+                match header.length {
+                    BodyLength::Full(l) if l as usize == body_len =>
+                        Some((header, &bytes[..])),
+                    BodyLength::Full(_) => None,
+                    BodyLength::Partial(_) | BodyLength::Indeterminate =>
+                        Some((header, &bytes[..])),
+                },
+            _ => None,
+        }
+    }
+
+    /// Returns the lengths of the partial body chunks to emit.
+    ///
+    /// If the original chunks' lengths are known and still match the
+    /// body, they are reused.  Otherwise, the body is split into
+    /// chunks of size `chunk`.  The last chunk is the one with a
+    /// definite length.
+    fn partial_body_chunks(&self, chunk: u32) -> Vec<(u32, u32)> {
+        let body_len = self.body().unwrap_or(&b""[..]).len() as u64;
+
+        let original: u64 = self.chunks.iter()
+            .map(|&(len, count)| len as u64 * count as u64)
+            .sum();
+        if original == body_len
+            && self.chunks.first().map(|&(len, _)| len) == Some(chunk)
+        {
+            return self.chunks.clone();
+        }
+
+        // Re-chunk the body.
+        let chunk = chunk as u64;
+        let full = if body_len == 0 { 0 } else { (body_len - 1) / chunk };
+        let mut chunks = Vec::new();
+        if full > 0 {
+            chunks.push((chunk as u32, full as u32));
+        }
+        chunks.push(((body_len - full * chunk) as u32, 1));
+        chunks
+    }
+}
+
 impl Serialize for Unknown {
+    /// Writes a serialized version of the packet to `o`.
+    ///
+    /// If the packet was parsed, and neither its tag nor its body
+    /// have been changed in a way that invalidates the original
+    /// header, the original CTB and length encoding are emitted,
+    /// including the boundaries of any partial body chunks.  This
+    /// makes parsing and serializing an unknown packet
+    /// byte-identical, as long as the lengths were encoded using
+    /// the shortest possible encoding.
     fn serialize(&self, o: &mut dyn std::io::Write) -> Result<()> {
         let body = if let Some(ref body) = self.common.body {
             &body[..]
@@ -842,9 +903,37 @@ impl Serialize for Unknown {
             &b""[..]
         };
 
-        CTB::new(self.tag()).serialize(o)?;
-        BodyLength::Full(body.len() as u32).serialize(o)?;
-        o.write_all(&body[..])?;
+        match self.original_header() {
+            Some((&Header { length: BodyLength::Partial(chunk), .. },
+                  bytes)) => {
+                // Reuse the CTB, and chunk the body.
+                o.write_all(&bytes[..1])?;
+                let chunks = self.partial_body_chunks(chunk);
+                let last = chunks.len() - 1;
+                let mut body = body;
+                for (i, &(len, count)) in chunks.iter().enumerate() {
+                    for j in 0..count {
+                        let len = len as usize;
+                        if i == last && j == count - 1 {
+                            BodyLength::Full(len as u32).serialize(o)?;
+                        } else {
+                            BodyLength::Partial(len as u32).serialize(o)?;
+                        }
+                        o.write_all(&body[..len])?;
+                        body = &body[len..];
+                    }
+                }
+            },
+            Some((_, bytes)) => {
+                o.write_all(bytes)?;
+                o.write_all(body)?;
+            },
+            None => {
+                CTB::new(self.tag()).serialize(o)?;
+                BodyLength::Full(body.len() as u32).serialize(o)?;
+                o.write_all(&body[..])?;
+            },
+        }
 
         Ok(())
     }
@@ -858,7 +947,22 @@ impl NetLength for Unknown {
 
 impl SerializeInto for Unknown {
     fn serialized_len(&self) -> usize {
-        self.gross_len()
+        let net = self.net_len();
+        match self.original_header() {
+            Some((&Header { length: BodyLength::Partial(chunk), .. }, _)) => {
+                let chunks = self.partial_body_chunks(chunk);
+                let &(last, _) = chunks.last().unwrap();
+                // Each chunk but the last is preceded by a one octet
+                // partial body length.
+                let headers: usize =
+                    chunks.iter().map(|&(_, count)| count as usize).sum();
+                1 + (headers - 1)
+                    + BodyLength::Full(last).serialized_len()
+                    + net
+            },
+            Some((_, bytes)) => bytes.len() + net,
+            None => self.gross_len(),
+        }
     }
 
     fn serialize_into(&self, buf: &mut [u8]) -> Result<usize> {
@@ -2312,10 +2416,94 @@ mod test {
             // reserialized content is identical to the original data.
             packets_bitwise_compare(filename, &Packet::Unknown(u),
                                     &data[..], &data2[..]);
+
+            // 5. In fact, unknown packets preserve the original
+            // framing, so the data must be identical.
+            assert_eq!(&data[..], &data2[..]);
         }
 
     }
 
+    #[test]
+    fn serialize_unknown_exact() {
+        // Parsing and serializing unknown packets must be
+        // byte-identical, irrespective of the CTB format and the
+        // length encoding.
+        let mut data = Vec::new();
+        // Start with a well-formed packet so that the input is
+        // recognized as binary OpenPGP data.
+        data.extend_from_slice(&[0xca, 3, b'P', b'G', b'P']);
+        // Old format, tag 15, one octet length.
+        data.extend_from_slice(&[0xbc, 3, 1, 2, 3]);
+        // Old format, tag 15, two octet length.
+        data.extend_from_slice(&[0xbd, 0, 3, 1, 2, 3]);
+        // Old format, tag 15, four octet length.
+        data.extend_from_slice(&[0xbe, 0, 0, 0, 3, 1, 2, 3]);
+        // New format, tag 60, five octet length.
+        data.extend_from_slice(&[0xfc, 0xff, 0, 0, 0, 3, 1, 2, 3]);
+        // New format, tag 60, two octet length.
+        data.extend_from_slice(&[0xfc, 192, 8]);
+        data.extend_from_slice(&[0x2a; 200][..]);
+        // New format, tag 60, partial body lengths.
+        data.extend_from_slice(&[0xfc, 0xe9]);
+        data.extend_from_slice(&[0x2b; 512][..]);
+        data.push(0xe9);
+        data.extend_from_slice(&[0x2c; 512][..]);
+        data.push(100);
+        data.extend_from_slice(&[0x2d; 100][..]);
+        // New format, tag 60, irregular partial body lengths.
+        data.extend_from_slice(&[0xfc, 0xe9]);
+        data.extend_from_slice(&[0x2e; 512][..]);
+        data.push(0xe1);
+        data.extend_from_slice(&[0x2f; 2][..]);
+        data.push(0xea);
+        data.extend_from_slice(&[0x30; 1024][..]);
+        data.push(0xe0);
+        data.push(0x31);
+        data.push(0xe0);
+        data.push(0x32);
+        data.push(7);
+        data.extend_from_slice(&[0x33; 7][..]);
+        // Another marker packet.
+        data.extend_from_slice(&[0xca, 3, b'P', b'G', b'P']);
+        // Old format, tag 15, indeterminate length.
+        data.extend_from_slice(&[0xbf, 1, 2, 3, 4, 5]);
+
+        let pile = PacketParserBuilder::from_bytes(&data[..]).unwrap()
+            .buffer_unread_content()
+            .into_packet_pile().unwrap();
+        assert_eq!(pile.children().count(), 10);
+        assert_eq!(pile.children()
+                   .filter(|p| if let Packet::Unknown(_) = p { true }
+                                else { false })
+                   .count(), 8);
+
+        let mut data2 = Vec::new();
+        for p in pile.children() {
+            let buf = p.to_vec().unwrap();
+            assert_eq!(buf.len(), p.serialized_len());
+            data2.extend_from_slice(&buf[..]);
+        }
+        assert_eq!(binary_pp(&data[..]), binary_pp(&data2[..]));
+
+        // If the body changes, the original length encoding is no
+        // longer used.
+        let mut u = to_unknown_packet(&[0xbd, 0, 3, 1, 2, 3][..]).unwrap();
+        assert_eq!(&u.to_vec().unwrap()[..], &[0xbd, 0, 3, 1, 2, 3][..]);
+        u.set_body(vec![1, 2]);
+        assert_eq!(&u.to_vec().unwrap()[..], &[0xcf, 2, 1, 2][..]);
+
+        // Likewise for the partial body chunks' boundaries.
+        let mut u = to_unknown_packet(
+            &[0xfc, 0xe0, 1, 0xe1, 2, 3, 0xe0, 4, 1, 5][..]).unwrap();
+        assert_eq!(&u.to_vec().unwrap()[..],
+                   &[0xfc, 0xe0, 1, 0xe1, 2, 3, 0xe0, 4, 1, 5][..]);
+        u.set_body(vec![1, 2, 3]);
+        let buf = u.to_vec().unwrap();
+        assert_eq!(buf.len(), u.serialized_len());
+        assert_eq!(&buf[..], &[0xfc, 0xe0, 1, 0xe0, 2, 1, 3][..]);
+    }
+
     #[cfg(feature = "compression-deflate")]
     #[test]
     fn serialize_test_2() {
@@ -9,6 +9,7 @@ use Error;
 use Result;
 use ::BodyLength;
 use super::{writer, write_byte, Serialize};
+use super::stream::Cookie;
 
 pub struct PartialBodyFilter<'a, C: 'a> {
     // The underlying writer.
@@ -230,6 +231,101 @@ impl<'a, C: 'a> writer::Stackable<'a, C> for PartialBodyFilter<'a, C> {
     }
 }
 
+/// Encodes a byte stream using OpenPGP's partial body encoding.
+///
+/// This writer wraps an arbitrary `io::Write`r and emits everything
+/// written to it using partial body length chunks.  This makes it
+/// possible to stream data of unknown length, e.g. when encrypting a
+/// message, without buffering the whole message.  When the writer
+/// is finalized, the remaining data is written using a full length
+/// header, as required by the standard.
+///
+/// # Example
+///
+/// ```
+/// extern crate sequoia_openpgp as openpgp;
+/// use std::io::Write;
+/// use openpgp::serialize::PartialBodyWriter;
+/// # use openpgp::Result;
+/// # f().unwrap();
+/// # fn f() -> Result<()> {
+/// let mut o = vec![];
+/// {
+///     let mut w = PartialBodyWriter::with_limits(&mut o, 4, 4)?;
+///     w.write_all(b"Hello world.")?;
+///     w.finalize()?;
+/// }
+/// assert_eq!(b"\xe2Hell\xe2o wo\x04rld.", o.as_slice());
+/// # Ok(())
+/// # }
+/// ```
+pub struct PartialBodyWriter<'a> {
+    inner: Option<writer::Stack<'a, Cookie>>,
+}
+
+impl<'a> PartialBodyWriter<'a> {
+    /// Returns a new partial body encoder writing to `inner`.
+    pub fn new<W: 'a + io::Write>(inner: W) -> Self {
+        PartialBodyWriter {
+            inner: Some(PartialBodyFilter::new(
+                writer::Generic::new(inner, Default::default()),
+                Default::default())),
+        }
+    }
+
+    /// Returns a new partial body encoder with the given limits.
+    ///
+    /// `buffer_threshold` is the amount of data that is buffered
+    /// before a chunk is emitted, and `max_chunk_size` is the
+    /// largest chunk that will be emitted.  Both must be powers of
+    /// two, and `max_chunk_size` must not exceed 1 GB.
+    pub fn with_limits<W: 'a + io::Write>(inner: W,
+                                          buffer_threshold: usize,
+                                          max_chunk_size: usize)
+                                          -> Result<Self> {
+        Ok(PartialBodyWriter {
+            inner: Some(PartialBodyFilter::with_limits(
+                writer::Generic::new(inner, Default::default()),
+                Default::default(), buffer_threshold, max_chunk_size)?),
+        })
+    }
+
+    /// Flushes any buffered data and writes the final chunk.
+    ///
+    /// This is done implicitly when the writer is dropped, but
+    /// errors are only reported when finalizing explicitly.
+    pub fn finalize(mut self) -> Result<()> {
+        if let Some(inner) = self.inner.take() {
+            inner.finalize()?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> io::Write for PartialBodyWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let writer = self.inner.as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe,
+                                          "Writer is finalized."))?;
+        writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let writer = self.inner.as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe,
+                                          "Writer is finalized."))?;
+        writer.flush()
+    }
+}
+
+impl<'a> fmt::Debug for PartialBodyWriter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PartialBodyWriter")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::io::Write;
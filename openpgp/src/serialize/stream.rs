@@ -20,6 +20,7 @@ use {
     Error,
     Fingerprint,
     HashAlgorithm,
+    KeyID,
     Result,
     crypto::Password,
     crypto::SessionKey,
@@ -830,6 +831,132 @@ pub enum EncryptionMode {
     ForTransport,
 }
 
+/// Specifies the order of the session key packets.
+///
+/// Some implementations only consider the first session key packets
+/// of a message, or only those of a certain kind.  This controls
+/// whether the PKESK packets or the SKESK packets come first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecipientOrder {
+    /// Emit the PKESK packets before the SKESK packets.
+    ///
+    /// This is the default.
+    KeysFirst,
+
+    /// Emit the SKESK packets before the PKESK packets.
+    PasswordsFirst,
+}
+
+impl Default for RecipientOrder {
+    fn default() -> Self {
+        RecipientOrder::KeysFirst
+    }
+}
+
+/// Configures an `Encryptor`.
+///
+/// This is an alternative to `Encryptor::new` that allows adding
+/// recipients and passwords one at a time, and controlling the order
+/// of the resulting session key packets.  All session key packets
+/// protect the same session key.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Write;
+/// extern crate sequoia_openpgp as openpgp;
+/// use openpgp::constants::DataFormat;
+/// use openpgp::serialize::stream::{
+///     Message, EncryptorBuilder, EncryptionMode, LiteralWriter,
+///     RecipientOrder,
+/// };
+/// # use openpgp::Result;
+/// # fn main() { f().unwrap(); }
+/// # fn f() -> Result<()> {
+/// let password = "совершенно секретно".into();
+///
+/// let mut o = vec![];
+/// let message = Message::new(&mut o);
+/// let encryptor = EncryptorBuilder::new(EncryptionMode::AtRest)
+///     .add_password(&password)
+///     .order(RecipientOrder::PasswordsFirst)
+///     .build(message)?;
+/// let mut w = LiteralWriter::new(encryptor, DataFormat::Text, None, None)?;
+/// w.write_all(b"Hello world.")?;
+/// w.finalize()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct EncryptorBuilder<'b> {
+    passwords: Vec<&'b Password>,
+    tpks: Vec<&'b TPK>,
+    encryption_mode: EncryptionMode,
+    cipher_algo: Option<SymmetricAlgorithm>,
+    order: RecipientOrder,
+    hidden_recipients: bool,
+}
+
+impl<'b> EncryptorBuilder<'b> {
+    /// Returns a new builder for the given encryption mode.
+    pub fn new(encryption_mode: EncryptionMode) -> Self {
+        EncryptorBuilder {
+            passwords: Vec::new(),
+            tpks: Vec::new(),
+            encryption_mode: encryption_mode,
+            cipher_algo: None,
+            order: Default::default(),
+            hidden_recipients: false,
+        }
+    }
+
+    /// Encrypts the session key for all suitable keys of `tpk`.
+    pub fn add_recipient(mut self, tpk: &'b TPK) -> Self {
+        self.tpks.push(tpk);
+        self
+    }
+
+    /// Encrypts the session key using `password`.
+    pub fn add_password(mut self, password: &'b Password) -> Self {
+        self.passwords.push(password);
+        self
+    }
+
+    /// Sets the symmetric algorithm.
+    ///
+    /// Unless otherwise specified, the stream is encrypted using
+    /// AES256.
+    pub fn cipher_algo(mut self, algo: SymmetricAlgorithm) -> Self {
+        self.cipher_algo = Some(algo);
+        self
+    }
+
+    /// Sets the order of the session key packets.
+    pub fn order(mut self, order: RecipientOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Hides the recipients' key IDs.
+    ///
+    /// If enabled, the PKESK packets use the wildcard key ID instead
+    /// of the recipients' key IDs, so that the message does not
+    /// reveal whom it is encrypted to.  The recipients have to try
+    /// all of their secret keys to decrypt it.
+    pub fn hidden_recipients(mut self, hidden: bool) -> Self {
+        self.hidden_recipients = hidden;
+        self
+    }
+
+    /// Creates the encryptor, writing the session key packets to
+    /// `inner`.
+    pub fn build<'a>(self, inner: writer::Stack<'a, Cookie>)
+                     -> Result<writer::Stack<'a, Cookie>> {
+        Encryptor::make(inner, &self.passwords, &self.tpks,
+                        self.encryption_mode, self.cipher_algo, None,
+                        self.order, self.hidden_recipients)
+    }
+}
+
 impl<'a> Encryptor<'a> {
     /// Creates a new encryptor.
     ///
@@ -903,18 +1030,67 @@ impl<'a> Encryptor<'a> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn new<C>(mut inner: writer::Stack<'a, Cookie>,
+    pub fn new<C>(inner: writer::Stack<'a, Cookie>,
                   passwords: &[&Password], tpks: &[&TPK],
                   encryption_mode: EncryptionMode,
                   cipher_algo: C)
                   -> Result<writer::Stack<'a, Cookie>>
         where C: Into<Option<SymmetricAlgorithm>>
+    {
+        Self::make(inner, passwords, tpks, encryption_mode,
+                   cipher_algo.into(), None, RecipientOrder::KeysFirst,
+                   false)
+    }
+
+    /// Creates a new encryptor using the given session key.
+    ///
+    /// This is like `Encryptor::new`, but instead of generating a
+    /// fresh session key, the stream is encrypted using
+    /// `session_key`, which is then encrypted using the given
+    /// passwords and TPKs.  This is useful if the session key has to
+    /// be known in advance, e.g. to escrow it, or to re-encrypt a
+    /// message to additional recipients.
+    ///
+    /// The length of `session_key` must match the key size of
+    /// `cipher_algo`, otherwise `Error::InvalidArgument` is
+    /// returned.
+    pub fn with_session_key(inner: writer::Stack<'a, Cookie>,
+                            passwords: &[&Password], tpks: &[&TPK],
+                            encryption_mode: EncryptionMode,
+                            cipher_algo: SymmetricAlgorithm,
+                            session_key: SessionKey)
+                            -> Result<writer::Stack<'a, Cookie>>
+    {
+        Self::make(inner, passwords, tpks, encryption_mode,
+                   Some(cipher_algo), Some(session_key),
+                   RecipientOrder::KeysFirst, false)
+    }
+
+    /// Common code for `new`, `with_session_key`, and
+    /// `EncryptorBuilder::build`.
+    fn make(mut inner: writer::Stack<'a, Cookie>,
+            passwords: &[&Password], tpks: &[&TPK],
+            encryption_mode: EncryptionMode,
+            cipher_algo: Option<SymmetricAlgorithm>,
+            session_key: Option<SessionKey>,
+            order: RecipientOrder,
+            hidden_recipients: bool)
+            -> Result<writer::Stack<'a, Cookie>>
     {
         if tpks.len() + passwords.len() == 0 {
             return Err(Error::InvalidArgument(
                 "Neither recipient keys nor passwords given".into()).into());
         }
 
+        let algo = cipher_algo.unwrap_or(SymmetricAlgorithm::AES256);
+        if let Some(ref sk) = session_key {
+            if sk.len() != algo.key_size()? {
+                return Err(Error::InvalidArgument(
+                    format!("Session key has length {}, but {} requires {}",
+                            sk.len(), algo, algo.key_size()?)).into());
+            }
+        }
+
         let mut rng = Yarrow::default();
 
         struct AEADParameters {
@@ -940,12 +1116,20 @@ impl<'a> Encryptor<'a> {
         };
 
         let level = inner.as_ref().cookie_ref().level + 1;
-        let algo = cipher_algo.into().unwrap_or(SymmetricAlgorithm::AES256);
 
-        // Generate a session key.
-        let sk = SessionKey::new(&mut rng, algo.key_size()?);
+        // Generate a session key, unless one was given.  It is
+        // shared by all PKESK and SKESK packets.
+        let sk = match session_key {
+            Some(sk) => sk,
+            None => SessionKey::new(&mut rng, algo.key_size()?),
+        };
+
+        // Collect the PKESK and SKESK packets separately, so that we
+        // can emit them in the requested order.
+        let mut pkesks = Vec::new();
+        let mut skesks = Vec::new();
 
-        // Write the PKESK packet(s).
+        // Serialize the PKESK packet(s).
         for tpk in tpks {
             // We need to find all applicable encryption (sub)keys.
             let can_encrypt = |key: &Key, sig: Option<&Signature>| -> bool {
@@ -987,8 +1171,11 @@ impl<'a> Encryptor<'a> {
 
             let mut count = 0;
             for key in keys {
-                if let Ok(pkesk) = PKESK3::for_recipient(algo, &sk, key) {
-                    pkesk.serialize(&mut inner)?;
+                if let Ok(mut pkesk) = PKESK3::for_recipient(algo, &sk, key) {
+                    if hidden_recipients {
+                        pkesk.set_recipient(KeyID::wildcard());
+                    }
+                    pkesk.serialize(&mut pkesks)?;
                     count += 1;
                 }
             }
@@ -1000,20 +1187,32 @@ impl<'a> Encryptor<'a> {
             }
         }
 
-        // Write the SKESK packet(s).
+        // Serialize the SKESK packet(s).
         for password in passwords {
             if let Some(aead) = aead.as_ref() {
                 let skesk = SKESK5::with_password(algo, aead.algo,
                                                   Default::default(),
                                                   &sk, password).unwrap();
-                skesk.serialize(&mut inner)?;
+                skesk.serialize(&mut skesks)?;
             } else {
                 let skesk = SKESK4::with_password(algo, Default::default(),
                                                   &sk, password).unwrap();
-                skesk.serialize(&mut inner)?;
+                skesk.serialize(&mut skesks)?;
             }
         }
 
+        // Write the session key packets.
+        match order {
+            RecipientOrder::KeysFirst => {
+                inner.write_all(&pkesks)?;
+                inner.write_all(&skesks)?;
+            },
+            RecipientOrder::PasswordsFirst => {
+                inner.write_all(&skesks)?;
+                inner.write_all(&pkesks)?;
+            },
+        }
+
         let encryptor = if let Some(aead) = aead {
             // Write the AED packet.
             CTB::new(Tag::AED).serialize(&mut inner)?;
@@ -1331,6 +1530,45 @@ mod test {
         assert!(o.len() < 1024);
     }
 
+    #[test]
+    fn compressor_roundtrip() {
+        let text = "Hello, compression!\n".repeat(1000);
+
+        for algo in &[CompressionAlgorithm::Uncompressed,
+                      CompressionAlgorithm::Zip,
+                      CompressionAlgorithm::Zlib,
+                      CompressionAlgorithm::BZip2] {
+            if ! algo.is_supported() {
+                continue;
+            }
+
+            let mut o = vec![];
+            {
+                let m = Message::new(&mut o);
+                let c = Compressor::new(m, *algo).unwrap();
+                let mut ls = LiteralWriter::new(c, T, None, None).unwrap();
+                ls.write_all(text.as_bytes()).unwrap();
+                ls.finalize().unwrap();
+            }
+            if *algo != CompressionAlgorithm::Uncompressed {
+                assert!(o.len() < text.len());
+            }
+
+            let pile = PacketPile::from_bytes(&o).unwrap();
+            assert_eq!(pile.children().count(), 1);
+            match pile.path_ref(&[0]) {
+                Some(Packet::CompressedData(ref cd)) =>
+                    assert_eq!(cd.algorithm(), *algo),
+                p => panic!("Expected a compressed data packet, got {:?}", p),
+            }
+            match pile.path_ref(&[0, 0]) {
+                Some(Packet::Literal(ref l)) =>
+                    assert_eq!(l.body(), Some(text.as_bytes())),
+                p => panic!("Expected a literal data packet, got {:?}", p),
+            }
+        }
+    }
+
     #[test]
     fn signature() {
         use crypto::KeyPair;
@@ -1498,4 +1736,182 @@ mod test {
             assert_eq!(state, State::Done);
         }
     }
+
+    #[test]
+    fn encryptor_builder_order() {
+        use packet::key::SecretKey;
+        use TPK;
+
+        let tpk = TPK::from_bytes(::tests::key("testy-private.pgp")).unwrap();
+        let password: Password = "streng geheim".into();
+        let message = b"Hello world.";
+
+        for order in &[RecipientOrder::KeysFirst,
+                       RecipientOrder::PasswordsFirst] {
+            let mut o = vec![];
+            {
+                let m = Message::new(&mut o);
+                let encryptor =
+                    EncryptorBuilder::new(EncryptionMode::ForTransport)
+                    .add_recipient(&tpk)
+                    .add_password(&password)
+                    .order(*order)
+                    .build(m).unwrap();
+                let mut literal =
+                    LiteralWriter::new(encryptor, DataFormat::Binary,
+                                       None, None).unwrap();
+                literal.write_all(message).unwrap();
+            }
+
+            // Recover the session key from both packets.
+            let mut kinds = Vec::new();
+            let mut session_keys = Vec::new();
+            let mut ppr = PacketParser::from_bytes(&o).unwrap();
+            while let PacketParserResult::Some(mut pp) = ppr {
+                match pp.packet {
+                    Packet::PKESK(ref pkesk) => {
+                        kinds.push(Tag::PKESK);
+                        let key = tpk.keys_all()
+                            .map(|(_, _, key)| key)
+                            .find(|key| &key.keyid() == pkesk.recipient())
+                            .unwrap();
+                        let sec = match key.secret() {
+                            Some(SecretKey::Unencrypted { ref mpis }) => mpis,
+                            _ => unreachable!(),
+                        };
+                        session_keys.push(pkesk.decrypt(key, sec).unwrap());
+                    },
+                    Packet::SKESK(ref skesk) => {
+                        kinds.push(Tag::SKESK);
+                        session_keys.push(skesk.decrypt(&password).unwrap());
+                    },
+                    Packet::SEIP(_) => {
+                        assert_eq!(session_keys.len(), 2);
+                        assert_eq!(session_keys[0], session_keys[1]);
+                        let (algo, ref key) = session_keys[0];
+                        pp.decrypt(algo, key).unwrap();
+                    },
+                    Packet::Literal(_) => {
+                        let mut body = Vec::new();
+                        pp.read_to_end(&mut body).unwrap();
+                        assert_eq!(&body, message);
+                    },
+                    _ => (),
+                }
+
+                ppr = pp.recurse().unwrap().1;
+            }
+
+            assert_eq!(kinds, match order {
+                RecipientOrder::KeysFirst => vec![Tag::PKESK, Tag::SKESK],
+                RecipientOrder::PasswordsFirst => vec![Tag::SKESK, Tag::PKESK],
+            });
+        }
+    }
+
+    #[test]
+    fn encryptor_hidden_recipients() {
+        use packet::key::SecretKey;
+        use TPK;
+
+        let tpk = TPK::from_bytes(::tests::key("testy-private.pgp")).unwrap();
+        let message = b"Hello world.";
+
+        let mut o = vec![];
+        {
+            let m = Message::new(&mut o);
+            let encryptor =
+                EncryptorBuilder::new(EncryptionMode::ForTransport)
+                .add_recipient(&tpk)
+                .hidden_recipients(true)
+                .build(m).unwrap();
+            let mut literal =
+                LiteralWriter::new(encryptor, DataFormat::Binary,
+                                   None, None).unwrap();
+            literal.write_all(message).unwrap();
+        }
+
+        // The recipient is not revealed, but we can decrypt the
+        // message by trying all keys.
+        let mut pkesks = 0;
+        let mut session_key = None;
+        let mut ppr = PacketParser::from_bytes(&o).unwrap();
+        while let PacketParserResult::Some(mut pp) = ppr {
+            match pp.packet {
+                Packet::PKESK(ref pkesk) => {
+                    pkesks += 1;
+                    assert!(pkesk.recipient().is_wildcard());
+                    session_key = tpk.keys_all()
+                        .filter_map(|(_, _, key)| match key.secret() {
+                            Some(SecretKey::Unencrypted { ref mpis }) =>
+                                pkesk.decrypt(key, mpis).ok(),
+                            _ => None,
+                        })
+                        .next();
+                },
+                Packet::SEIP(_) => {
+                    let (algo, ref key) = session_key.clone().unwrap();
+                    pp.decrypt(algo, key).unwrap();
+                },
+                Packet::Literal(_) => {
+                    let mut body = Vec::new();
+                    pp.read_to_end(&mut body).unwrap();
+                    assert_eq!(&body, message);
+                },
+                _ => (),
+            }
+
+            ppr = pp.recurse().unwrap().1;
+        }
+        assert_eq!(pkesks, 1);
+        assert!(session_key.is_some());
+    }
+
+    #[test]
+    fn encryptor_with_session_key() {
+        let password: Password = "streng geheim".into();
+        let message = b"Hello world.";
+        let algo = SymmetricAlgorithm::AES128;
+        let sk: SessionKey = vec![0x42; algo.key_size().unwrap()].into();
+
+        // A session key of the wrong length is rejected.
+        let mut o = vec![];
+        let bad: SessionKey = vec![0x42; 7].into();
+        assert!(Encryptor::with_session_key(
+            Message::new(&mut o), &[&password], &[],
+            EncryptionMode::ForTransport, algo, bad).is_err());
+
+        let mut o = vec![];
+        {
+            let encryptor = Encryptor::with_session_key(
+                Message::new(&mut o), &[&password], &[],
+                EncryptionMode::ForTransport, algo, sk.clone()).unwrap();
+            let mut literal =
+                LiteralWriter::new(encryptor, DataFormat::Binary,
+                                   None, None).unwrap();
+            literal.write_all(message).unwrap();
+        }
+
+        let mut ppr = PacketParser::from_bytes(&o).unwrap();
+        while let PacketParserResult::Some(mut pp) = ppr {
+            match pp.packet {
+                Packet::SKESK(ref skesk) => {
+                    let (a, ref key) = skesk.decrypt(&password).unwrap();
+                    assert_eq!(a, algo);
+                    assert_eq!(key, &sk);
+                },
+                Packet::SEIP(_) => {
+                    pp.decrypt(algo, &sk).unwrap();
+                },
+                Packet::Literal(_) => {
+                    let mut body = Vec::new();
+                    pp.read_to_end(&mut body).unwrap();
+                    assert_eq!(&body, message);
+                },
+                _ => (),
+            }
+
+            ppr = pp.recurse().unwrap().1;
+        }
+    }
 }
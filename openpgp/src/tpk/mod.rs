@@ -1671,6 +1671,21 @@ impl TPK {
         }
     }
 
+    /// Returns the key's preferred keyserver, if any.
+    ///
+    /// The preferred keyserver is read from the Preferred Key Server
+    /// subpacket of the primary key's current self-signature (see
+    /// `TPK::primary_key_signature`).  If there is no such subpacket,
+    /// this returns `None`.
+    ///
+    /// Note: the subpacket is under the control of the key holder.
+    /// Using it to update the key lets the key holder observe who is
+    /// interested in the key.
+    pub fn preferred_keyserver(&self) -> Option<&[u8]> {
+        self.primary_key_signature()
+            .and_then(|sig| sig.preferred_key_server())
+    }
+
     /// The self-signatures.
     ///
     /// All self-signatures have been validated, and the newest
@@ -3522,6 +3537,17 @@ mod test {
         }
     }
 
+    #[test]
+    fn preferred_keyserver() {
+        let tpk = TPK::from_bytes(
+            ::tests::key("preferred-keyserver.pgp")).unwrap();
+        assert_eq!(tpk.preferred_keyserver(),
+                   Some(&b"hkps://keys.example.org"[..]));
+
+        let tpk = TPK::from_bytes(::tests::key("testy.pgp")).unwrap();
+        assert_eq!(tpk.preferred_keyserver(), None);
+    }
+
     #[test]
     fn direct_key_sig() {
         use constants::SignatureType;
@@ -0,0 +1,190 @@
+//! A portable archive format for stores.
+//!
+//! An archive starts with a magic string and a format version,
+//! followed by a sequence of records.  Every record starts with a
+//! tag byte.  A binding record (tag 1) contains the label, the
+//! fingerprint, and the serialized TPK, each prefixed by its length
+//! as big-endian `u32`, followed by the eight stats fields as
+//! big-endian `i64`.  An empty TPK means that only the fingerprint is
+//! known, unset timestamps are recorded as zero.  The archive ends
+//! with a record of tag 0.
+
+use std::io::{self, Read, Write};
+
+use time::Timespec;
+
+use super::{Error, Result, Stamps, Stats, from_unix};
+
+/// Identifies archives.
+const MAGIC: &'static [u8] = b"SEQUOIA-STORE-ARCHIVE\n";
+
+/// The current format version.
+const VERSION: u32 = 1;
+
+/// Marks the end of the archive.
+const TAG_END: u8 = 0;
+
+/// Marks a binding record.
+const TAG_BINDING: u8 = 1;
+
+/// Upper bound for length-prefixed fields.
+///
+/// This is a sanity check so that a corrupted length does not make
+/// us allocate huge amounts of memory.
+const MAX_FIELD_LENGTH: u32 = 64 * 1024 * 1024;
+
+/// A binding as recorded in an archive.
+pub(crate) struct Record {
+    pub label: Vec<u8>,
+    pub fingerprint: String,
+    pub tpk: Vec<u8>,
+    pub stats: Stats,
+}
+
+/// Writes the archive header.
+pub(crate) fn write_header(w: &mut Write) -> Result<()> {
+    w.write_all(MAGIC)?;
+    write_u32(w, VERSION)?;
+    Ok(())
+}
+
+/// Writes a binding record.
+pub(crate) fn write_record(w: &mut Write, record: &Record) -> Result<()> {
+    w.write_all(&[TAG_BINDING])?;
+    write_field(w, &record.label)?;
+    write_field(w, record.fingerprint.as_bytes())?;
+    write_field(w, &record.tpk)?;
+
+    let s = &record.stats;
+    for t in [s.created, s.updated].iter() {
+        write_i64(w, to_unix(*t))?;
+    }
+    for stamps in [&s.encryption, &s.verification].iter() {
+        write_i64(w, stamps.count as i64)?;
+        write_i64(w, to_unix(stamps.first))?;
+        write_i64(w, to_unix(stamps.last))?;
+    }
+    Ok(())
+}
+
+/// Writes the end-of-archive marker.
+pub(crate) fn write_trailer(w: &mut Write) -> Result<()> {
+    w.write_all(&[TAG_END])?;
+    Ok(())
+}
+
+/// Reads and checks the archive header.
+pub(crate) fn read_header(r: &mut Read) -> Result<()> {
+    let mut magic = vec![0; MAGIC.len()];
+    read_exact(r, &mut magic)?;
+    if &magic[..] != MAGIC || read_u32(r)? != VERSION {
+        return Err(Error::MalformedArchive.into());
+    }
+    Ok(())
+}
+
+/// Reads the next binding record.
+///
+/// Returns `None` at the end of the archive.
+pub(crate) fn read_record(r: &mut Read) -> Result<Option<Record>> {
+    let mut tag = [0; 1];
+    read_exact(r, &mut tag)?;
+    match tag[0] {
+        TAG_END => return Ok(None),
+        TAG_BINDING => (),
+        _ => return Err(Error::MalformedArchive.into()),
+    }
+
+    let label = read_field(r)?;
+    let fingerprint = String::from_utf8(read_field(r)?)
+        .map_err(|_| Error::MalformedArchive)?;
+    let tpk = read_field(r)?;
+
+    let created = from_unix(read_i64(r)?);
+    let updated = from_unix(read_i64(r)?);
+    let mut stamps = || -> Result<Stamps> {
+        Ok(Stamps::new(read_i64(r)?,
+                       from_unix(read_i64(r)?),
+                       from_unix(read_i64(r)?)))
+    };
+    let encryption = stamps()?;
+    let verification = stamps()?;
+
+    Ok(Some(Record {
+        label: label,
+        fingerprint: fingerprint,
+        tpk: tpk,
+        stats: Stats {
+            created: created,
+            updated: updated,
+            encryption: encryption,
+            verification: verification,
+            next_update: None,
+        },
+    }))
+}
+
+/// Converts an optional timestamp to unix time, mapping `None` to
+/// zero.
+pub(crate) fn to_unix(t: Option<Timespec>) -> i64 {
+    t.map(|t| t.sec).unwrap_or(0)
+}
+
+fn write_u32(w: &mut Write, v: u32) -> io::Result<()> {
+    w.write_all(&[(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8])
+}
+
+fn write_i64(w: &mut Write, v: i64) -> io::Result<()> {
+    let mut buf = [0; 8];
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = (v >> (56 - 8 * i)) as u8;
+    }
+    w.write_all(&buf)
+}
+
+/// Writes a length-prefixed field.
+///
+/// Fields longer than `MAX_FIELD_LENGTH` are rejected, because
+/// `read_field` would not accept them.
+fn write_field(w: &mut Write, data: &[u8]) -> Result<()> {
+    if data.len() > MAX_FIELD_LENGTH as usize {
+        return Err(Error::MalformedArchive.into());
+    }
+    write_u32(w, data.len() as u32)?;
+    w.write_all(data)?;
+    Ok(())
+}
+
+/// Like `Read::read_exact`, but a truncated archive is reported as
+/// `Error::MalformedArchive`.
+fn read_exact(r: &mut Read, buf: &mut [u8]) -> Result<()> {
+    r.read_exact(buf).map_err(|e| {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            Error::MalformedArchive.into()
+        } else {
+            e.into()
+        }
+    })
+}
+
+fn read_u32(r: &mut Read) -> Result<u32> {
+    let mut buf = [0; 4];
+    read_exact(r, &mut buf)?;
+    Ok(buf.iter().fold(0, |acc, &b| acc << 8 | b as u32))
+}
+
+fn read_i64(r: &mut Read) -> Result<i64> {
+    let mut buf = [0; 8];
+    read_exact(r, &mut buf)?;
+    Ok(buf.iter().fold(0, |acc, &b| acc << 8 | b as i64))
+}
+
+fn read_field(r: &mut Read) -> Result<Vec<u8>> {
+    let len = read_u32(r)?;
+    if len > MAX_FIELD_LENGTH {
+        return Err(Error::MalformedArchive.into());
+    }
+    let mut buf = vec![0; len as usize];
+    read_exact(r, &mut buf)?;
+    Ok(buf)
+}
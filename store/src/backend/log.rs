@@ -44,26 +44,43 @@ impl Refers {
 pub fn message(c: &Rc<Connection>, refers: Refers,
                slug: &str, message: &str)
                -> Result<ID> {
-    log(c, refers, slug, message, None)
+    log(c, refers, slug, message, None, None)
 }
 
 /// Writes an error message to the log.
 pub fn error(c: &Rc<Connection>, refers: Refers,
              slug: &str, message: &str, error: &str)
              -> Result<ID> {
-    log(c, refers, slug, message, Some(error))
+    log(c, refers, slug, message, Some(error), None)
+}
+
+/// Writes a log message about data obtained from `source` to the log.
+///
+/// `source` is e.g. the URL of a keyserver.
+pub fn message_from(c: &Rc<Connection>, refers: Refers,
+                    slug: &str, message: &str, source: &str)
+                    -> Result<ID> {
+    log(c, refers, slug, message, None, Some(source))
+}
+
+/// Writes an error message about `source` to the log.
+pub fn error_from(c: &Rc<Connection>, refers: Refers,
+                  slug: &str, message: &str, error: &str, source: &str)
+                  -> Result<ID> {
+    log(c, refers, slug, message, Some(error), Some(source))
 }
 
 /// Writes a log message to the log.
 fn log(c: &Rc<Connection>, refers: Refers,
-       slug: &str, message: &str, error: Option<&str>)
+       slug: &str, message: &str, error: Option<&str>, source: Option<&str>)
        -> Result<ID> {
     c.execute("INSERT INTO log
-                   (timestamp, level, store, binding, key, slug, message, error)
-                   VALUES (?1, 0, ?2, ?3, ?4, ?5, ?6, ?7)",
+                   (timestamp, level, store, binding, key, slug, message, error,
+                    source)
+                   VALUES (?1, 0, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
               &[&Timestamp::now(),
                 &refers.store, &refers.binding, &refers.key,
-                &slug, &message, &error])?;
+                &slug, &message, &error, &source])?;
     Ok(c.last_insert_rowid().into())
 }
 
@@ -75,16 +92,40 @@ pub enum Selector {
     Key(ID),
 }
 
+/// Restricts the log entries to iterate over.
+pub struct Filter {
+    /// Only entries logged at or after this unix time.
+    pub since: i64,
+    /// Only entries logged before this unix time.
+    pub until: i64,
+    /// Only entries recording an error.
+    pub failures_only: bool,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter{since: ::std::i64::MIN, until: ::std::i64::MAX,
+               failures_only: false}
+    }
+}
+
 /// Iterator for log entries.
 pub struct IterServer {
     c: Rc<Connection>,
     selector: Selector,
+    filter: Filter,
     n: ID,
 }
 
 impl IterServer {
     pub fn new(c: Rc<Connection>, selector: Selector) -> Self {
-        IterServer{c: c, selector: selector, n: ID::max()}
+        Self::filtered(c, selector, Default::default())
+    }
+
+    /// Like `new()`, but only returns entries matching `filter`.
+    pub fn filtered(c: Rc<Connection>, selector: Selector, filter: Filter)
+                    -> Self {
+        IterServer{c: c, selector: selector, filter: filter, n: ID::max()}
     }
 }
 
@@ -95,72 +136,83 @@ impl node::log_iter::Server for IterServer {
             -> Promise<(), capnp::Error> {
         bind_results!(results);
 
+        let since = self.filter.since;
+        let until = self.filter.until;
+        let failures_only = self.filter.failures_only;
         let (
             id, timestamp,
             store, binding, key,
-            slug, message, error
+            slug, message, error, source
         ): (
             ID, Timestamp,
             Option<ID>, Option<ID>, Option<ID>,
-            String, String, Option<String>
+            String, String, Option<String>, Option<String>
         ) = sry!(match self.selector {
             Selector::All =>
                 self.c.query_row(
                     "SELECT id, timestamp,
                             store, binding, key,
-                            slug, message, error
+                            slug, message, error, source
                          FROM log
                          WHERE id < ?1
+                           AND timestamp >= ?2 AND timestamp < ?3
+                           AND (?4 = 0 OR error IS NOT NULL)
                          ORDER BY id DESC LIMIT 1",
-                    &[&self.n],
+                    &[&self.n, &since, &until, &failures_only],
                     |row| (row.get(0), row.get(1),
                            row.get(2), row.get(3), row.get(4),
-                           row.get(5), row.get(6), row.get(7))),
+                           row.get(5), row.get(6), row.get(7), row.get(8))),
 
             Selector::Store(store) =>
                 self.c.query_row(
                     "SELECT id, timestamp,
                             store, binding, key,
-                            slug, message, error
+                            slug, message, error, source
                          FROM log
                          WHERE id < ?1
-                           AND (store = ?2
-                                OR binding IN (SELECT id FROM bindings WHERE store = ?2)
-                                OR key IN (SELECT key FROM bindings WHERE store = ?2))
+                           AND timestamp >= ?2 AND timestamp < ?3
+                           AND (?4 = 0 OR error IS NOT NULL)
+                           AND (store = ?5
+                                OR binding IN (SELECT id FROM bindings WHERE store = ?5)
+                                OR key IN (SELECT key FROM bindings WHERE store = ?5))
                          ORDER BY id DESC LIMIT 1",
-                    &[&self.n, &store],
+                    &[&self.n, &since, &until, &failures_only, &store],
                     |row| (row.get(0), row.get(1),
                            row.get(2), row.get(3), row.get(4),
-                           row.get(5), row.get(6), row.get(7))),
+                           row.get(5), row.get(6), row.get(7), row.get(8))),
 
             Selector::Binding(binding) =>
                 self.c.query_row(
                     "SELECT id, timestamp,
                             store, binding, key,
-                            slug, message, error
+                            slug, message, error, source
                          FROM log
                          WHERE id < ?1
-                           AND (binding = ?2
-                                OR key IN (SELECT key FROM bindings WHERE id = ?2))
+                           AND timestamp >= ?2 AND timestamp < ?3
+                           AND (?4 = 0 OR error IS NOT NULL)
+                           AND (binding = ?5
+                                OR key IN (SELECT key FROM bindings WHERE id = ?5))
                          ORDER BY id DESC LIMIT 1",
-                    &[&self.n, &binding],
+                    &[&self.n, &since, &until, &failures_only, &binding],
                     |row| (row.get(0), row.get(1),
                            row.get(2), row.get(3), row.get(4),
-                           row.get(5), row.get(6), row.get(7))),
+                           row.get(5), row.get(6), row.get(7), row.get(8))),
 
             Selector::Key(key) =>
                 self.c.query_row(
                     "SELECT id, timestamp,
                             store, binding, key,
-                            slug, message, error
+                            slug, message, error, source
                          FROM log
                          WHERE id < ?1
-                           AND key = ?2
+                           AND timestamp >= ?2 AND timestamp < ?3
+                           AND (?4 = 0 OR error IS NOT NULL)
+                           AND key = ?5
                          ORDER BY id DESC LIMIT 1",
-                    &[&self.n, &key],
+                    &[&self.n, &since, &until, &failures_only, &key],
                     |row| (row.get(0), row.get(1),
                            row.get(2), row.get(3), row.get(4),
-                           row.get(5), row.get(6), row.get(7))),
+                           row.get(5), row.get(6), row.get(7), row.get(8))),
         });
 
         let mut entry = pry!(results.get().get_result()).init_ok();
@@ -189,6 +241,9 @@ impl node::log_iter::Server for IterServer {
         if let Some(error) = error {
             entry.set_error(&error);
         }
+        if let Some(source) = source {
+            entry.set_source(&source);
+        }
 
         self.n = id;
         Promise::ok(())
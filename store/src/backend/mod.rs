@@ -2,8 +2,10 @@
 
 use failure;
 use std::cmp;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::io;
+use std::path::Path;
 use std::rc::Rc;
 use time::Duration;
 
@@ -22,19 +24,19 @@ use tokio_core;
 use tokio_io::io::ReadHalf;
 
 use openpgp::{self, TPK, KeyID, Fingerprint};
-use openpgp::parse::Parse;
-use openpgp::serialize::Serialize;
+use openpgp::parse::{Parse, PacketParserBuilder};
+use openpgp::serialize::{Serialize, SerializeInto};
 use sequoia_core as core;
 use sequoia_net as net;
 use sequoia_ipc as ipc;
 
 use store_protocol_capnp::node;
 
-use super::Result;
+use super::{ConflictInfo, Result};
 
 // Data types for working with `rusqlite`.
 pub mod support;
-use self::support::{ID, Timestamp};
+use self::support::{ID, Label, Timestamp};
 
 // Logging.
 mod log;
@@ -51,6 +53,50 @@ fn refresh_interval() -> Duration {
     Duration::weeks(1)
 }
 
+/// Longest interval a binding may request between updates.
+///
+/// Longer intervals are clamped, so that computing the time of the
+/// next update cannot overflow.
+fn max_update_interval() -> Duration {
+    Duration::days(365)
+}
+
+/// Controls how keys are refreshed in the background.
+#[derive(Clone, Copy, Debug)]
+struct UpdatePolicy {
+    /// Interval after which all keys should be refreshed once.
+    interval: Duration,
+
+    /// Time without a successful update after which a key is
+    /// considered overdue.
+    overdue_after: Duration,
+
+    /// How to retry failed requests to the keyserver.
+    retry: core::RetryPolicy,
+}
+
+impl Default for UpdatePolicy {
+    fn default() -> Self {
+        UpdatePolicy {
+            interval: refresh_interval(),
+            overdue_after: refresh_interval() * 2,
+            retry: core::RetryPolicy::exponential(),
+        }
+    }
+}
+
+impl UpdatePolicy {
+    /// Returns the policy configured in `ctx`.
+    fn from_context(ctx: &core::Context) -> Self {
+        let mut policy = Self::default();
+        if let Some(after) = ctx.update_overdue_after() {
+            policy.overdue_after = Duration::from_std(after)
+                .unwrap_or(policy.overdue_after);
+        }
+        policy
+    }
+}
+
 /// Returns a value from the uniform distribution over [0, 2*d).
 ///
 /// This function is used to randomize key refresh times.
@@ -60,6 +106,99 @@ fn random_duration(d: Duration) -> Duration {
     Duration::seconds(s)
 }
 
+/// Maximum number of packets in a TPK supplied by a client.
+///
+/// A TPK flooded with millions of tiny signature packets would make
+/// parsing and merging pathologically slow, wedging the service.
+const MAX_TPK_PACKETS: usize = 32 * 1024;
+
+/// Parses a TPK supplied by a client, enforcing `MAX_TPK_PACKETS`.
+fn parse_tpk(bytes: &[u8]) -> Result<TPK> {
+    TPK::from_packet_parser(
+        PacketParserBuilder::from_bytes(bytes)?
+            .max_packets(MAX_TPK_PACKETS)
+            .finalize()?)
+}
+
+/// Parses a fingerprint supplied by a client.
+///
+/// `Fingerprint::from_hex` only accepts version 4 fingerprints.
+fn parse_fingerprint(fp: &str) -> Result<Fingerprint> {
+    Ok(Fingerprint::from_hex(fp)
+       .map_err(|_| super::Error::MalformedFingerprint(fp.into()))?)
+}
+
+/* Transactions.  */
+
+/// How long to wait for locks held by other connections.
+///
+/// This is the default used by `rusqlite`.
+const BUSY_TIMEOUT: ::std::time::Duration =
+    ::std::time::Duration::from_millis(5000);
+
+/// Starts a transaction.
+///
+/// If a transaction is already in progress, a savepoint is created
+/// instead, so that only our changes are undone on rollback.
+/// Returns whether we are nested in another transaction.
+fn begin(c: &Connection) -> Result<bool> {
+    let nested = ! c.is_autocommit();
+    c.execute_batch(if nested {
+        "SAVEPOINT nested"
+    } else {
+        "BEGIN IMMEDIATE TRANSACTION"
+    })?;
+    Ok(nested)
+}
+
+/// Commits the transaction or savepoint started by `begin`.
+fn commit(c: &Connection, nested: bool) -> Result<()> {
+    c.execute_batch(if nested { "RELEASE nested" } else { "COMMIT" })?;
+    Ok(())
+}
+
+/// Rolls back the transaction or savepoint started by `begin`.
+fn rollback(c: &Connection, nested: bool) -> Result<()> {
+    c.execute_batch(if nested {
+        "ROLLBACK TO nested; RELEASE nested"
+    } else {
+        "ROLLBACK"
+    })?;
+    Ok(())
+}
+
+/// Runs `f` in a transaction, see `begin`.
+///
+/// The changes are committed if `f` returns `Ok(Ok(_))`, and rolled
+/// back otherwise.
+fn transaction_if<T, E, F>(c: &Connection, f: F)
+                           -> Result<::std::result::Result<T, E>>
+    where F: FnOnce() -> Result<::std::result::Result<T, E>>
+{
+    let nested = begin(c)?;
+    let r = f();
+    if let Ok(Ok(_)) = r {
+        commit(c, nested)?;
+    } else {
+        // Rolling back might fail if SQLite already rolled back the
+        // transaction.  Report the original error.
+        let _ = rollback(c, nested);
+    }
+    r
+}
+
+/// Runs `f` in a transaction, see `begin`.
+///
+/// The changes are committed if `f` returns successfully, and
+/// rolled back otherwise.
+fn transaction<T, F>(c: &Connection, f: F) -> Result<T>
+    where F: FnOnce() -> Result<T>
+{
+    transaction_if(c, || f().map(|v| -> ::std::result::Result<T, ()> {
+        Ok(v)
+    })).map(|r| r.unwrap_or_else(|()| unreachable!()))
+}
+
 /* Entry point.  */
 
 /// Makes backends.
@@ -92,9 +231,75 @@ impl ipc::Handler for Backend {
 
 /* Server implementation.  */
 
+/// Returns whether `tpk` carries a certification issued by `issuer`.
+fn certified_by(tpk: &TPK, issuer: &Fingerprint) -> bool {
+    let keyid = issuer.to_keyid();
+    tpk.userids().flat_map(|b| b.certifications().iter())
+        .chain(tpk.user_attributes().flat_map(|b| b.certifications().iter()))
+        .any(|sig| sig.issuer_fingerprint().map(|fp| &fp == issuer)
+             .or_else(|| sig.issuer().map(|id| id == keyid))
+             .unwrap_or(false))
+}
+
+/// Records the components of `new` that are not in `old`.
+fn key_diff(old: Option<&TPK>, new: &TPK, mut diff: node::key_diff::Builder)
+            -> Result<()> {
+    let userids: Vec<&[u8]> = new.userids()
+        .map(|b| b.userid().value())
+        .filter(|u| ! old.map(|o| o.userids()
+                              .any(|b| b.userid().value() == *u))
+                .unwrap_or(false))
+        .collect();
+    let subkeys: Vec<String> = new.subkeys()
+        .map(|b| b.subkey().fingerprint())
+        .filter(|fp| ! old.map(|o| o.subkeys()
+                               .any(|b| b.subkey().fingerprint() == *fp))
+                .unwrap_or(false))
+        .map(|fp| fp.to_hex())
+        .collect();
+
+    let signatures = |tpk: &TPK| -> Result<Vec<Vec<u8>>> {
+        let mut sigs = Vec::new();
+        for p in tpk.clone().into_packets() {
+            if let openpgp::Packet::Signature(sig) = p {
+                sigs.push(sig.to_vec()?);
+            }
+        }
+        Ok(sigs)
+    };
+    let known: HashSet<Vec<u8>> = match old {
+        Some(old) => signatures(old)?.into_iter().collect(),
+        None => HashSet::new(),
+    };
+    let added: Vec<Vec<u8>> = signatures(new)?.into_iter()
+        .filter(|s| ! known.contains(s))
+        .collect();
+
+    {
+        let mut l = diff.reborrow().init_userids(userids.len() as u32);
+        for (i, u) in userids.iter().enumerate() {
+            l.set(i as u32, u);
+        }
+    }
+    {
+        let mut l = diff.reborrow().init_subkeys(subkeys.len() as u32);
+        for (i, fp) in subkeys.iter().enumerate() {
+            l.set(i as u32, fp);
+        }
+    }
+    let mut l = diff.init_signatures(added.len() as u32);
+    for (i, s) in added.iter().enumerate() {
+        l.set(i as u32, s);
+    }
+    Ok(())
+}
+
 struct NodeServer {
     _descriptor: ipc::Descriptor,
+    handle: Handle,
     c: Rc<Connection>,
+    /// The version of the database if we cannot use it.
+    incompatible: Option<i64>,
 }
 
 impl NodeServer {
@@ -102,16 +307,31 @@ impl NodeServer {
         let mut db_path = descriptor.context().home().to_path_buf();
         db_path.push("public-key-store.sqlite");
 
-        let c = Connection::open(db_path)?;
-        c.execute_batch("PRAGMA secure_delete = true;")?;
-        c.execute_batch("PRAGMA foreign_keys = true;")?;
-        let server = NodeServer {
+        let c = open_database(&db_path)?;
+        let policy = UpdatePolicy::from_context(descriptor.context());
+        let logger = descriptor.context().logger().cloned();
+        let mut server = NodeServer {
             _descriptor: descriptor,
+            handle: handle.clone(),
             c: Rc::new(c),
+            incompatible: None,
         };
-        server.init()?;
 
-        KeyServer::start_housekeeping(server.c.clone(), handle)?;
+        // If we cannot use the database, keep running so that we can
+        // tell clients why.
+        if let Err(e) = server.init() {
+            match e.downcast::<super::Error>() {
+                Ok(super::Error::IncompatibleVersion { found, .. }) => {
+                    server.incompatible = Some(found);
+                    return Ok(server);
+                },
+                Ok(e) => return Err(e.into()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        KeyServer::start_housekeeping(server.c.clone(), handle, policy,
+                                      logger)?;
         Ok(server)
     }
 
@@ -122,16 +342,70 @@ impl NodeServer {
             &[], |row| row.get(0));
 
         if let Ok(v) = v {
-            match v {
-                1 => return Ok(()),
-                _ => unimplemented!(),
-            }
+            return self.migrate(v);
         }
 
         self.c.execute_batch(DB_SCHEMA_1)?;
         log::message(&self.c, log::Refers::to(), "server",
                      "Created database version 1")?;
-        Ok(())
+        self.migrate(1)
+    }
+
+    /// Migrates the database from schema version `from`.
+    ///
+    /// Databases created by newer versions are rejected with
+    /// `Error::IncompatibleVersion`.
+    fn migrate(&self, from: i64) -> Result<()> {
+        match from {
+            DB_SCHEMA_VERSION => Ok(()),
+            1 => {
+                transaction(&self.c, || {
+                    self.c.execute_batch(DB_SCHEMA_2)?;
+                    log::message(&self.c, log::Refers::to(), "server",
+                                 "Upgraded database to version 2")
+                })?;
+                self.migrate(2)
+            },
+            2 => {
+                transaction(&self.c, || {
+                    self.c.execute_batch(DB_SCHEMA_3)?;
+                    log::message(&self.c, log::Refers::to(), "server",
+                                 "Upgraded database to version 3")
+                })?;
+                self.migrate(3)
+            },
+            3 => {
+                transaction(&self.c, || {
+                    self.c.execute_batch(DB_SCHEMA_4)?;
+                    log::message(&self.c, log::Refers::to(), "server",
+                                 "Upgraded database to version 4")
+                })?;
+                self.migrate(4)
+            },
+            4 => {
+                transaction(&self.c, || {
+                    self.c.execute_batch(DB_SCHEMA_5)?;
+                    log::message(&self.c, log::Refers::to(), "server",
+                                 "Upgraded database to version 5")
+                })?;
+                self.migrate(5)
+            },
+            _ => Err(super::Error::IncompatibleVersion {
+                found: from,
+                supported: DB_SCHEMA_VERSION,
+            }.into()),
+        }
+    }
+
+    /// Checks that the database is usable.
+    fn check_version(&self) -> Result<()> {
+        match self.incompatible {
+            None => Ok(()),
+            Some(found) => Err(super::Error::IncompatibleVersion {
+                found: found,
+                supported: DB_SCHEMA_VERSION,
+            }.into()),
+        }
     }
 }
 
@@ -141,14 +415,22 @@ impl node::Server for NodeServer {
             mut results: node::OpenResults)
             -> Promise<(), capnp::Error> {
         bind_results!(results);
+        sry!(self.check_version());
         let params = pry!(params.get());
 
         // XXX maybe check ephemeral and use in-core sqlite db
 
+        let keyserver = pry!(params.get_keyserver());
         let store = sry!(StoreServer::open(self.c.clone(),
+                                           &self.handle,
                                            pry!(params.get_realm()),
                                            pry!(params.get_network_policy()).into(),
-                                           pry!(params.get_name())));
+                                           pry!(params.get_name()),
+                                           if keyserver.is_empty() {
+                                               None
+                                           } else {
+                                               Some(keyserver)
+                                           }));
         pry!(pry!(results.get().get_result()).set_ok(
             node::store::ToClient::new(store).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
@@ -159,6 +441,7 @@ impl node::Server for NodeServer {
             mut results: node::IterResults)
             -> Promise<(), capnp::Error> {
         bind_results!(results);
+        sry!(self.check_version());
         let prefix = pry!(pry!(params.get()).get_realm_prefix());
         let iter = StoreIterServer::new(self.c.clone(), prefix);
         pry!(pry!(results.get().get_result()).set_ok(
@@ -171,6 +454,7 @@ impl node::Server for NodeServer {
                  mut results: node::IterKeysResults)
                  -> Promise<(), capnp::Error> {
         bind_results!(results);
+        sry!(self.check_version());
         let iter = KeyIterServer::new(self.c.clone());
         pry!(pry!(results.get().get_result()).set_ok(
             node::key_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
@@ -178,11 +462,20 @@ impl node::Server for NodeServer {
     }
 
     fn log(&mut self,
-           _: node::LogParams,
+           params: node::LogParams,
            mut results: node::LogResults)
            -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let iter = log::IterServer::new(self.c.clone(), log::Selector::All);
+        sry!(self.check_version());
+        let params = pry!(params.get());
+        let mut filter = log::Filter::default();
+        filter.since = params.get_since();
+        if params.get_until() != 0 {
+            filter.until = params.get_until();
+        }
+        filter.failures_only = params.get_failures_only();
+        let iter = log::IterServer::filtered(self.c.clone(), log::Selector::All,
+                                             filter);
         pry!(pry!(results.get().get_result()).set_ok(
             node::log_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
@@ -193,7 +486,8 @@ impl node::Server for NodeServer {
               mut results: node::ImportResults)
               -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let new = sry!(TPK::from_bytes(&pry!(pry!(params.get()).get_key())));
+        sry!(self.check_version());
+        let new = sry!(parse_tpk(&pry!(pry!(params.get()).get_key())));
         let fp = new.fingerprint();
         let key_id = sry!(KeyServer::lookup_or_create(&self.c, &fp));
         let key = KeyServer::new(self.c.clone(), key_id);
@@ -209,6 +503,7 @@ impl node::Server for NodeServer {
                        mut results: node::LookupByKeyidResults)
                        -> Promise<(), capnp::Error> {
         bind_results!(results);
+        sry!(self.check_version());
         let keyid = pry!(params.get()).get_keyid();
         let keyid = KeyID::new(keyid);
         let key_id = sry!(KeyServer::lookup_by_id(&self.c, &keyid));
@@ -225,6 +520,7 @@ impl node::Server for NodeServer {
                              mut results: node::LookupByFingerprintResults)
                              -> Promise<(), capnp::Error> {
         bind_results!(results);
+        sry!(self.check_version());
         let fingerprint = pry!(pry!(params.get()).get_fingerprint());
         let fingerprint = sry!(Fingerprint::from_hex(fingerprint));
         let key_id = sry!(KeyServer::lookup(&self.c, &fingerprint));
@@ -241,6 +537,7 @@ impl node::Server for NodeServer {
                           mut results: node::LookupBySubkeyidResults)
                           -> Promise<(), capnp::Error> {
         bind_results!(results);
+        sry!(self.check_version());
         let keyid = pry!(params.get()).get_keyid();
 
         let key_id: ID = sry!(
@@ -255,6 +552,61 @@ impl node::Server for NodeServer {
                 .into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
+
+    fn compact_keys(&mut self,
+                    _: node::CompactKeysParams,
+                    mut results: node::CompactKeysResults)
+                    -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        sry!(self.check_version());
+        let before = sry!(database_size(&self.c));
+        let (merged, rewired) = sry!(KeyServer::compact(&self.c));
+        // Give the space back to the file system.  This cannot be
+        // done within a transaction.
+        if self.c.is_autocommit() {
+            sry!(self.c.execute_batch("VACUUM"));
+        }
+        let after = sry!(database_size(&self.c));
+
+        let mut report = pry!(results.get().get_result()).init_ok();
+        report.set_keys_merged(merged);
+        report.set_bindings_rewired(rewired);
+        report.set_bytes_reclaimed(before.saturating_sub(after));
+        Promise::ok(())
+    }
+}
+
+/// Checks that the keyserver at `uri` can be used to update stores
+/// with the given network policy.
+///
+/// Stores with the offline policy are never updated, hence for them
+/// only the URI is checked.
+fn check_keyserver(handle: &Handle, uri: &str, policy: core::NetworkPolicy)
+                   -> Result<()> {
+    let policy = match policy {
+        core::NetworkPolicy::Offline => core::NetworkPolicy::Insecure,
+        p => p,
+    };
+    let ctx = core::Context::configure()
+        .network_policy(policy)
+        .build()?;
+    net::async::KeyServer::new(&ctx, uri, handle)?;
+    Ok(())
+}
+
+/// Opens the database at `path`.
+fn open_database<P: AsRef<Path>>(path: P) -> Result<Connection> {
+    let c = Connection::open(path)?;
+    c.execute_batch("PRAGMA secure_delete = true;")?;
+    c.execute_batch("PRAGMA foreign_keys = true;")?;
+    Ok(c)
+}
+
+/// Returns the size of the database in bytes.
+fn database_size(c: &Connection) -> Result<u64> {
+    let pages: i64 = c.query_row("PRAGMA page_count", &[], |row| row.get(0))?;
+    let size: i64 = c.query_row("PRAGMA page_size", &[], |row| row.get(0))?;
+    Ok((pages * size) as u64)
 }
 
 struct StoreServer {
@@ -292,8 +644,13 @@ impl StoreServer {
         StoreServer{c: c, id: id}
     }
 
-    fn open(c: Rc<Connection>, realm: &str, policy: core::NetworkPolicy, name: &str)
+    fn open(c: Rc<Connection>, handle: &Handle, realm: &str,
+            policy: core::NetworkPolicy, name: &str, keyserver: Option<&str>)
            -> Result<Self> {
+        if let Some(keyserver) = keyserver {
+            check_keyserver(handle, keyserver, policy)?;
+        }
+
         // We cannot implement ToSql and friends for
         // core::NetworkPolicy, hence we need to do it by foot.
         let p: u8 = (&policy).into();
@@ -317,8 +674,81 @@ impl StoreServer {
                        .into());
         }
 
+        if let Some(keyserver) = keyserver {
+            c.execute("UPDATE stores SET keyserver = ?1 WHERE id = ?2",
+                      &[&keyserver, &id])?;
+        }
+
         Ok(Self::new(c, id))
     }
+
+    /// Binds `label` to `fp`, returning the binding's id.
+    fn add_label(&self, label: &Label, fp: &Fingerprint) -> Result<ID> {
+        let (binding_id, key_id, created) =
+            BindingServer::lookup_or_create(&self.c, self.id, label, fp)?;
+
+        if created {
+            log::message(
+                &self.c,
+                log::Refers::to().store(self.id).binding(binding_id).key(key_id),
+                &self.slug(),
+                &format!("New binding {} -> {}", label, fp.to_keyid()))?;
+        }
+
+        Ok(binding_id)
+    }
+
+    /// Returns the id of the binding with the given label.
+    fn lookup_label(&self, label: &Label) -> Result<ID> {
+        Ok(self.c.query_row(
+            "SELECT id FROM bindings WHERE store = ?1 AND label = ?2",
+            &[&self.id, label], |row| row.get(0))?)
+    }
+
+    /// Adds the given bindings.
+    ///
+    /// Either all bindings are added, or, if any label is bound to a
+    /// different key, none are.  In the latter case, the conflicts
+    /// are returned together with the index of the offending binding.
+    fn add_labels(&self, bindings: &[(Label, Fingerprint)])
+                  -> Result<::std::result::Result<Vec<ID>,
+                                                  Vec<(usize, ConflictInfo)>>> {
+        transaction_if(&self.c, || {
+            let mut ids = Vec::with_capacity(bindings.len());
+            let mut conflicts = Vec::new();
+            for (i, &(ref label, ref fp)) in bindings.iter().enumerate() {
+                match self.add_label(label, fp) {
+                    Ok(id) => ids.push(id),
+                    Err(e) => match e.conflict_info() {
+                        Some(info) => conflicts.push((i, info.clone())),
+                        None => return Err(e),
+                    },
+                }
+            }
+
+            if conflicts.is_empty() {
+                Ok(Ok(ids))
+            } else {
+                Ok(Err(conflicts))
+            }
+        })
+    }
+
+    /// Deletes the bindings with the given labels.
+    ///
+    /// Either all bindings are deleted, or none are.  For every
+    /// label, returns whether a binding was deleted.
+    fn delete_labels(&self, labels: &[Label]) -> Result<Vec<bool>> {
+        transaction(&self.c, || {
+            let mut stmt = self.c.prepare(
+                "DELETE FROM bindings WHERE store = ?1 AND label = ?2")?;
+            let mut deleted = Vec::with_capacity(labels.len());
+            for label in labels {
+                deleted.push(stmt.execute(&[&self.id, label])? > 0);
+            }
+            Ok(deleted)
+        })
+    }
 }
 
 impl node::store::Server for StoreServer {
@@ -329,21 +759,9 @@ impl node::store::Server for StoreServer {
         bind_results!(results);
         let params = pry!(params.get());
         let fp = pry!(params.get_fingerprint());
-        let fp = sry!(Fingerprint::from_hex(fp)
-                      .map_err(|_| node::Error::MalformedFingerprint));
-        let label = pry!(params.get_label());
-
-        let (binding_id, key_id, created) = sry!(
-            BindingServer::lookup_or_create(&self.c, self.id, label, &fp));
-
-        if created {
-            sry!(log::message(
-                &self.c,
-                log::Refers::to().store(self.id).binding(binding_id).key(key_id),
-                &self.slug(),
-                &format!("New binding {} -> {}", label, fp.to_keyid())));
-        }
-
+        let fp = sry!(parse_fingerprint(fp));
+        let label = Label::from(pry!(params.get_label()));
+        let binding_id = sry!(self.add_label(&label, &fp));
 
         pry!(pry!(results.get().get_result()).set_ok(
             node::binding::ToClient::new(
@@ -357,12 +775,93 @@ impl node::store::Server for StoreServer {
               mut results: node::store::LookupResults)
               -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let label = pry!(pry!(params.get()).get_label());
+        let label = Label::from(pry!(pry!(params.get()).get_label()));
+        let binding_id = sry!(self.lookup_label(&label));
 
-        let binding_id: ID = sry!(
-            self.c.query_row(
-                "SELECT id FROM bindings WHERE store = ?1 AND label = ?2",
-                &[&self.id, &label], |row| row.get(0)));
+        pry!(pry!(results.get().get_result()).set_ok(
+            node::binding::ToClient::new(
+                BindingServer::new(self.c.clone(), binding_id))
+                .into_client::<capnp_rpc::Server>()));
+        Promise::ok(())
+    }
+
+    fn lookup_by_fingerprint(&mut self,
+                             params: node::store::LookupByFingerprintParams,
+                             mut results: node::store::LookupByFingerprintResults)
+                             -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let fp = pry!(pry!(params.get()).get_fingerprint());
+        let fp = sry!(parse_fingerprint(fp)).to_hex();
+
+        // keys.fingerprint is not unique, so consider all keys with
+        // the given fingerprint.  If there is none, there is no row
+        // at all, otherwise there is a row without a binding for
+        // every key that is not bound in this store.
+        let (found, bindings) = {
+            let mut stmt = sry!(self.c.prepare(
+                "SELECT bindings.id, bindings.label FROM keys
+                     LEFT JOIN bindings
+                       ON bindings.key = keys.id AND bindings.store = ?1
+                     WHERE keys.fingerprint = ?2
+                     ORDER BY bindings.label"));
+            let rows = sry!(stmt.query_map(
+                &[&self.id, &fp],
+                |row| -> (Option<ID>, Option<Label>) {
+                    (row.get(0), row.get(1))
+                }));
+            let mut found = false;
+            let mut bindings = Vec::new();
+            for row in rows {
+                found = true;
+                if let (Some(id), Some(label)) = sry!(row) {
+                    bindings.push((id, label));
+                }
+            }
+            (found, bindings)
+        };
+        if ! found {
+            fail!(node::Error::NotFound);
+        }
+
+        let mut list = pry!(results.get().get_result())
+            .initn_ok(bindings.len() as u32);
+        for (i, (id, label)) in bindings.into_iter().enumerate() {
+            let mut entry = list.reborrow().get(i as u32);
+            entry.set_label(&label.to_string_lossy());
+            entry.set_label_bytes(label.as_bytes());
+            entry.set_fingerprint(&fp);
+            entry.set_binding(node::binding::ToClient::new(
+                BindingServer::new(self.c.clone(), id))
+                              .into_client::<capnp_rpc::Server>());
+        }
+        Promise::ok(())
+    }
+
+    fn add_bytes(&mut self,
+                 params: node::store::AddBytesParams,
+                 mut results: node::store::AddBytesResults)
+                 -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let params = pry!(params.get());
+        let fp = pry!(params.get_fingerprint());
+        let fp = sry!(parse_fingerprint(fp));
+        let label = Label::from(pry!(params.get_label()));
+        let binding_id = sry!(self.add_label(&label, &fp));
+
+        pry!(pry!(results.get().get_result()).set_ok(
+            node::binding::ToClient::new(
+                BindingServer::new(self.c.clone(), binding_id))
+                .into_client::<capnp_rpc::Server>()));
+        Promise::ok(())
+    }
+
+    fn lookup_bytes(&mut self,
+                    params: node::store::LookupBytesParams,
+                    mut results: node::store::LookupBytesResults)
+                    -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let label = Label::from(pry!(pry!(params.get()).get_label()));
+        let binding_id = sry!(self.lookup_label(&label));
 
         pry!(pry!(results.get().get_result()).set_ok(
             node::binding::ToClient::new(
@@ -402,6 +901,84 @@ impl node::store::Server for StoreServer {
         Promise::ok(())
     }
 
+    fn delete_bindings(&mut self,
+                       params: node::store::DeleteBindingsParams,
+                       mut results: node::store::DeleteBindingsResults)
+                       -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let mut labels = Vec::new();
+        for label in pry!(pry!(params.get()).get_labels()).iter() {
+            labels.push(Label::from(pry!(label)));
+        }
+        let deleted = sry!(self.delete_labels(&labels));
+
+        let mut list = pry!(results.get().get_result())
+            .initn_ok(deleted.len() as u32);
+        for (i, deleted) in deleted.into_iter().enumerate() {
+            list.set(i as u32, deleted);
+        }
+        Promise::ok(())
+    }
+
+    fn clear(&mut self,
+             _: node::store::ClearParams,
+             mut results: node::store::ClearResults)
+             -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        sry!(self.c.execute("DELETE FROM bindings WHERE store = ?1",
+                            &[&self.id]));
+        Promise::ok(())
+    }
+
+    fn export_all(&mut self,
+                  _: node::store::ExportAllParams,
+                  mut results: node::store::ExportAllResults)
+                  -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let iter = ExportIterServer::new(self.c.clone(), self.id);
+        pry!(pry!(results.get().get_result()).set_ok(
+            node::export_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
+        Promise::ok(())
+    }
+
+    fn add_many(&mut self,
+                params: node::store::AddManyParams,
+                mut results: node::store::AddManyResults)
+                -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let mut bindings = Vec::new();
+        for b in pry!(pry!(params.get()).get_bindings()).iter() {
+            let label = Label::from(pry!(b.get_label()));
+            let fp = sry!(parse_fingerprint(pry!(b.get_fingerprint())));
+            bindings.push((label, fp));
+        }
+
+        let mut r = pry!(results.get().get_result()).init_ok();
+        match sry!(self.add_labels(&bindings)) {
+            Ok(ids) => {
+                let mut list = r.init_bindings(ids.len() as u32);
+                for (i, id) in ids.into_iter().enumerate() {
+                    list.set(i as u32,
+                             node::binding::ToClient::new(
+                                 BindingServer::new(self.c.clone(), id))
+                             .into_client::<capnp_rpc::Server>().client.hook);
+                }
+            },
+            Err(conflicts) => {
+                let mut list = r.init_conflicts(conflicts.len() as u32);
+                for (i, (index, info)) in conflicts.into_iter().enumerate() {
+                    let mut c = list.reborrow().get(i as u32);
+                    c.set_index(index as u32);
+                    let mut c = c.init_info();
+                    c.set_existing(&info.existing.to_hex());
+                    c.set_incoming(&info.incoming.to_hex());
+                    c.set_certified(info.certified);
+                }
+            },
+        }
+        Promise::ok(())
+    }
+
     fn iter(&mut self,
             _: node::store::IterParams,
             mut results: node::store::IterResults)
@@ -413,6 +990,29 @@ impl node::store::Server for StoreServer {
         Promise::ok(())
     }
 
+    fn iter_prefix(&mut self,
+                   params: node::store::IterPrefixParams,
+                   mut results: node::store::IterPrefixResults)
+                   -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let prefix = pry!(pry!(params.get()).get_prefix());
+        let iter = BindingIterServer::prefix(self.c.clone(), self.id, prefix);
+        pry!(pry!(results.get().get_result()).set_ok(
+            node::binding_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
+        Promise::ok(())
+    }
+
+    fn iter_revoked(&mut self,
+                    _: node::store::IterRevokedParams,
+                    mut results: node::store::IterRevokedResults)
+                    -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let iter = BindingIterServer::revoked(self.c.clone(), self.id);
+        pry!(pry!(results.get().get_result()).set_ok(
+            node::binding_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
+        Promise::ok(())
+    }
+
     fn log(&mut self,
            _: node::store::LogParams,
            mut results: node::store::LogResults)
@@ -423,6 +1023,146 @@ impl node::store::Server for StoreServer {
             node::log_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
+
+    fn location(&mut self,
+                _: node::store::LocationParams,
+                mut results: node::store::LocationResults)
+                -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        // The first row describes the main database.
+        let path = sry!(self.c.query_row(
+            "PRAGMA database_list", &[], |row| -> String {
+                row.get(2)
+            }));
+
+        pry!(pry!(results.get().get_result()).set_ok(path.as_str()));
+        Promise::ok(())
+    }
+
+    fn begin(&mut self,
+             _: node::store::BeginParams,
+             mut results: node::store::BeginResults)
+             -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let tx = sry!(TransactionServer::begin(self.c.clone(), self.id));
+        pry!(pry!(results.get().get_result()).set_ok(
+            node::transaction::ToClient::new(tx)
+                .into_client::<capnp_rpc::Server>()));
+        Promise::ok(())
+    }
+
+    fn keyserver(&mut self,
+                 _: node::store::KeyserverParams,
+                 mut results: node::store::KeyserverResults)
+                 -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let keyserver: Option<String> = sry!(self.c.query_row(
+            "SELECT keyserver FROM stores WHERE id = ?1",
+            &[&self.id], |row| row.get(0)));
+        pry!(pry!(results.get().get_result()).set_ok(
+            keyserver.as_ref().map(|s| s.as_str()).unwrap_or("")));
+        Promise::ok(())
+    }
+}
+
+/// A transaction spanning multiple requests.
+///
+/// The transaction uses its own database connection, so that changes
+/// done by other clients do not become part of it.  Changes must be
+/// done using the store returned by the `store` request.  There can
+/// only be one transaction at any time.  While it is open, changes
+/// done by others fail with `TransactionInProgress`.
+struct TransactionServer {
+    /// The connection shared by all other clients.
+    shared: Rc<Connection>,
+    c: Rc<Connection>,
+    store: ID,
+    done: bool,
+}
+
+impl TransactionServer {
+    fn begin(shared: Rc<Connection>, store: ID) -> Result<Self> {
+        // The first row describes the main database.
+        let path: String = shared.query_row(
+            "PRAGMA database_list", &[], |row| row.get(2))?;
+        let c = open_database(&path)?;
+
+        // Fail right away if there is another transaction.
+        c.busy_timeout(::std::time::Duration::from_secs(0))?;
+        begin(&c)?;
+
+        // Likewise, fail right away when others try to change the
+        // database.  Waiting for the lock would block the event
+        // loop, preventing us from finishing the transaction.
+        shared.busy_timeout(::std::time::Duration::from_secs(0))?;
+
+        Ok(TransactionServer {
+            shared: shared,
+            c: Rc::new(c),
+            store: store,
+            done: false,
+        })
+    }
+
+    /// Ends the transaction, committing it if `commit` is true.
+    fn end(&mut self, commit: bool) -> Result<()> {
+        if self.done {
+            return Err(super::Error::NotFound.into());
+        }
+        self.done = true;
+        let r = if commit {
+            self::commit(&self.c, false)
+        } else {
+            rollback(&self.c, false)
+        };
+        self.shared.busy_timeout(BUSY_TIMEOUT)?;
+        r
+    }
+}
+
+impl Drop for TransactionServer {
+    fn drop(&mut self) {
+        if ! self.done {
+            // The client went away without committing.
+            let _ = rollback(&self.c, false);
+            let _ = self.shared.busy_timeout(BUSY_TIMEOUT);
+        }
+    }
+}
+
+impl node::transaction::Server for TransactionServer {
+    fn commit(&mut self,
+              _: node::transaction::CommitParams,
+              mut results: node::transaction::CommitResults)
+              -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        sry!(self.end(true));
+        Promise::ok(())
+    }
+
+    fn rollback(&mut self,
+                _: node::transaction::RollbackParams,
+                mut results: node::transaction::RollbackResults)
+                -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        sry!(self.end(false));
+        Promise::ok(())
+    }
+
+    fn store(&mut self,
+             _: node::transaction::StoreParams,
+             mut results: node::transaction::StoreResults)
+             -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        if self.done {
+            fail!(node::Error::NotFound);
+        }
+        pry!(pry!(results.get().get_result()).set_ok(
+            node::store::ToClient::new(
+                StoreServer::new(self.c.clone(), self.store))
+                .into_client::<capnp_rpc::Server>()));
+        Promise::ok(())
+    }
 }
 
 struct BindingServer {
@@ -438,8 +1178,64 @@ impl BindingServer {
         }
     }
 
-    fn key_id(&mut self) -> Result<ID> {
-        self.query("key").map(|id| id.into())
+    fn key_id(&mut self) -> Result<ID> {
+        self.query("key").map(|id| id.into())
+    }
+
+    /// Merges `new` into the bound key, and stores the result.
+    ///
+    /// Returns the previously stored key, if any, the merged key, and
+    /// its serialized form.  If `new` does not match the bound key,
+    /// a conflict is returned.
+    fn merge(&mut self, new: TPK) -> Result<(Option<TPK>, TPK, Vec<u8>)> {
+        // Check in the database for the current key.
+        let key_id = self.key_id()?;
+        let (fingerprint, key): (String, Option<Vec<u8>>)
+            = self.c.query_row(
+                "SELECT fingerprint, key FROM keys WHERE id = ?1",
+                &[&key_id],
+                |row| (row.get(0), row.get_checked(1).ok()))?;
+
+        // If we found one, convert it to TPK.
+        let current = if let Some(current) = key {
+            let current = TPK::from_bytes(&current)?;
+            if current.fingerprint().to_hex() != fingerprint {
+                // Inconsistent database.
+                return Err(node::Error::SystemError.into());
+            }
+            Some(current)
+        } else {
+            None
+        };
+
+        // Check for conflicts.
+        if new.fingerprint().to_hex() != fingerprint {
+            let existing = Fingerprint::from_hex(&fingerprint)?;
+            let certified = certified_by(&new, &existing);
+            return Err(super::Error::Conflict(ConflictInfo {
+                existing: existing,
+                incoming: new.fingerprint(),
+                certified: certified,
+            }).into());
+        }
+
+        let merged = match current {
+            Some(ref current) => current.clone().merge(new)?,
+            None => new,
+        };
+
+        let mut blob = vec![];
+        merged.serialize(&mut blob)?;
+
+        // Write key back to the database, unless the new key is a
+        // subset of the current one.
+        if current.as_ref() != Some(&merged) {
+            self.c.execute("UPDATE keys SET key = ?1 WHERE id = ?2",
+                           &[&blob, &key_id])?;
+            KeyServer::reindex_subkeys(&self.c, key_id, &merged)?;
+        }
+
+        Ok((current, merged, blob))
     }
 
 
@@ -447,22 +1243,24 @@ impl BindingServer {
     ///
     /// On success, the id of the binding and the key is returned, and
     /// whether or not the entry was just created.
-    fn lookup_or_create(c: &Connection, store: ID, label: &str, fp: &Fingerprint)
+    fn lookup_or_create(c: &Connection, store: ID, label: &Label,
+                        fp: &Fingerprint)
                         -> Result<(ID, ID, bool)> {
         let key_id = KeyServer::lookup_or_create(c, fp)?;
         if let Ok((binding, key)) = c.query_row(
             "SELECT id, key FROM bindings WHERE store = ?1 AND label = ?2",
-            &[&store, &label], |row| -> (ID, ID) {(row.get(0), row.get(1))}) {
+            &[&store, label], |row| -> (ID, ID) {(row.get(0), row.get(1))}) {
             if key == key_id {
                 Ok((binding, key_id, false))
             } else {
-                Err(super::Error::Conflict.into())
+                Err(super::Error::Conflict(
+                    KeyServer::conflict_info(c, key, key_id)?).into())
             }
         } else {
             let r = c.execute(
                 "INSERT INTO bindings (store, label, key, created)
                  VALUES (?, ?, ?, ?)",
-                &[&store, &label, &key_id, &Timestamp::now()]);
+                &[&store, label, &key_id, &Timestamp::now()]);
 
             // Some other mutator might race us to the insertion.
             match r {
@@ -471,11 +1269,13 @@ impl BindingServer {
                     rusqlite::ErrorCode::ConstraintViolation => {
                         let (binding, key): (ID, ID) = c.query_row(
                             "SELECT id, key FROM bindings WHERE store = ?1 AND label = ?2",
-                            &[&store, &label], |row| (row.get(0), row.get(1)))?;
+                            &[&store, label], |row| (row.get(0), row.get(1)))?;
                         if key == key_id {
                             Ok((binding, key_id, false))
                         } else {
-                            Err(node::Error::Conflict)
+                            return Err(super::Error::Conflict(
+                                KeyServer::conflict_info(c, key, key_id)?)
+                                       .into());
                         }
                     },
                     // Raise otherwise.
@@ -493,6 +1293,10 @@ impl Query for BindingServer {
         "bindings"
     }
 
+    fn key_column() -> Option<&'static str> {
+        Some("key")
+    }
+
     fn id(&self) -> ID {
         self.id
     }
@@ -504,9 +1308,10 @@ impl Query for BindingServer {
     fn slug(&self) -> String {
         self.c.query_row(
             "SELECT label FROM bindings WHERE id = ?1",
-            &[&self.id], |row| -> String {
+            &[&self.id], |row| -> Label {
                 row.get(0)
             })
+            .map(|label| label.to_string())
             .unwrap_or(
                 format!("{}::{}", Self::table_name(), self.id())
             )
@@ -542,58 +1347,76 @@ impl node::binding::Server for BindingServer {
               -> Promise<(), capnp::Error> {
         bind_results!(results);
         let force = pry!(params.get()).get_force();
+        let strict = pry!(params.get()).get_strict();
 
         // This is the key to import.
-        let mut new = sry!(TPK::from_bytes(&pry!(pry!(params.get()).get_key())));
-
-        // Check in the database for the current key.
-        let key_id = sry!(self.key_id());
-        let (fingerprint, key): (String, Option<Vec<u8>>)
-            = sry!(self.c.query_row(
-                "SELECT fingerprint, key FROM keys WHERE id = ?1",
-                &[&key_id],
-                |row| (row.get(0), row.get_checked(1).ok())));
-
-        // If we found one, convert it to TPK.
-        let current = if let Some(current) = key {
-            let current = sry!(TPK::from_bytes(&current));
-            if current.fingerprint().to_hex() != fingerprint {
-                // Inconsistent database.
-                fail!(node::Error::SystemError);
+        let new = sry!(parse_tpk(&pry!(pry!(params.get()).get_key())));
+
+        if strict {
+            // Refuse to touch the store if the new key is not the
+            // bound key.
+            let key_id = sry!(self.key_id());
+            let fingerprint: String = sry!(self.c.query_row(
+                "SELECT fingerprint FROM keys WHERE id = ?1",
+                &[&key_id], |row| row.get(0)));
+            if new.fingerprint().to_hex() != fingerprint {
+                let existing = sry!(Fingerprint::from_hex(&fingerprint));
+                conflict!(&ConflictInfo {
+                    existing: existing,
+                    incoming: new.fingerprint(),
+                    certified: false,
+                });
             }
-            Some(current)
-        } else {
-            None
-        };
-
-        // Check for conflicts.
-        if new.fingerprint().to_hex() != fingerprint {
-            if force {
-                // Update binding, and retry.
+        } else if force {
+            // Rebind to the new key if it does not match the current
+            // one.
+            let key_id = sry!(self.key_id());
+            let fingerprint: String = sry!(self.c.query_row(
+                "SELECT fingerprint FROM keys WHERE id = ?1",
+                &[&key_id], |row| row.get(0)));
+            if new.fingerprint().to_hex() != fingerprint {
                 let key_id =
                     sry!(KeyServer::lookup_or_create(
                         &self.c, &new.fingerprint()));
                 sry!(self.c.execute("UPDATE bindings SET key = ?1 WHERE id = ?2",
                                     &[&key_id, &self.id]));
-                return self.import(params, results);
-            } else {
-                fail!(node::Error::Conflict);
             }
         }
 
-        if current.is_some() {
-            new = sry!(current.unwrap().merge(new));
-        }
+        let (_, _, blob) = sry!(self.merge(new));
+        pry!(pry!(results.get().get_result()).set_ok(&blob[..]));
+        Promise::ok(())
+    }
 
-        // Write key back to the database.
-        let mut blob = vec![];
-        sry!(new.serialize(&mut blob));
+    fn import_if_newer(&mut self,
+                       params: node::binding::ImportIfNewerParams,
+                       mut results: node::binding::ImportIfNewerResults)
+                       -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let new = sry!(parse_tpk(&pry!(pry!(params.get()).get_key())));
+        let (current, merged, blob) = sry!(self.merge(new));
+
+        if current.as_ref() == Some(&merged) {
+            // Nothing new, possibly a rollback.  `merge` did not
+            // touch the database.
+            pry!(pry!(results.get().get_result()).set_ok(&[][..]));
+        } else {
+            pry!(pry!(results.get().get_result()).set_ok(&blob[..]));
+        }
+        Promise::ok(())
+    }
 
-        sry!(self.c.execute("UPDATE keys SET key = ?1 WHERE id = ?2",
-                            &[&blob, &key_id]));
-        sry!(KeyServer::reindex_subkeys(&self.c, key_id, &new));
+    fn import_diff(&mut self,
+                   params: node::binding::ImportDiffParams,
+                   mut results: node::binding::ImportDiffResults)
+                   -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let new = sry!(parse_tpk(&pry!(pry!(params.get()).get_key())));
+        let (current, merged, blob) = sry!(self.merge(new));
 
-        pry!(pry!(results.get().get_result()).set_ok(&blob[..]));
+        let mut diff = pry!(results.get().get_result()).init_ok();
+        diff.set_key(&blob[..]);
+        sry!(key_diff(current.as_ref(), &merged, diff));
         Promise::ok(())
     }
 
@@ -679,11 +1502,117 @@ impl node::binding::Server for BindingServer {
         bind_results!(results);
         let label = sry!(self.c.query_row(
             "SELECT label FROM bindings WHERE id = ?1",
-            &[&self.id], |row| -> String {
+            &[&self.id], |row| -> Label {
+                row.get(0)
+            }));
+
+        pry!(pry!(results.get().get_result()).set_ok(&*label.to_string_lossy()));
+        Promise::ok(())
+    }
+
+    fn label_bytes(&mut self,
+                   _: node::binding::LabelBytesParams,
+                   mut results: node::binding::LabelBytesResults)
+                   -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let label = sry!(self.c.query_row(
+            "SELECT label FROM bindings WHERE id = ?1",
+            &[&self.id], |row| -> Label {
                 row.get(0)
             }));
 
-        pry!(pry!(results.get().get_result()).set_ok(label.as_str()));
+        pry!(pry!(results.get().get_result()).set_ok(label.as_bytes()));
+        Promise::ok(())
+    }
+
+    fn fingerprint(&mut self,
+                   _: node::binding::FingerprintParams,
+                   mut results: node::binding::FingerprintResults)
+                   -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let fingerprint: String = sry!(self.c.query_row(
+            "SELECT keys.fingerprint FROM bindings
+                 JOIN keys ON bindings.key = keys.id
+                 WHERE bindings.id = ?1",
+            &[&self.id], |row| row.get(0)));
+
+        pry!(pry!(results.get().get_result()).set_ok(fingerprint.as_str()));
+        Promise::ok(())
+    }
+
+    fn restore_stats(&mut self,
+                     params: node::binding::RestoreStatsParams,
+                     mut results: node::binding::RestoreStatsResults)
+                     -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let stats = pry!(pry!(params.get()).get_stats());
+        let some = |t: i64| if t == 0 { None } else { Some(t) };
+
+        sry!(self.c
+             .execute("UPDATE bindings
+                       SET created = coalesce(?2, created),
+                           updated = ?3,
+                           encryption_count = ?4,
+                           encryption_first = ?5,
+                           encryption_last = ?6,
+                           verification_count = ?7,
+                           verification_first = ?8,
+                           verification_last = ?9
+                       WHERE id = ?1",
+                      &[&self.id,
+                        &some(stats.get_created()),
+                        &some(stats.get_updated()),
+                        &stats.get_encryption_count(),
+                        &some(stats.get_encryption_first()),
+                        &some(stats.get_encryption_last()),
+                        &stats.get_verification_count(),
+                        &some(stats.get_verification_first()),
+                        &some(stats.get_verification_last())]));
+
+        sry!(self.query_stats( pry!(results.get().get_result()).init_ok()));
+        Promise::ok(())
+    }
+
+    fn set_update_interval(&mut self,
+                           params: node::binding::SetUpdateIntervalParams,
+                           mut results: node::binding::SetUpdateIntervalResults)
+                           -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let seconds = pry!(params.get()).get_seconds();
+        let interval = if seconds > 0 {
+            Some(cmp::min(seconds, max_update_interval().num_seconds()))
+        } else {
+            None
+        };
+        sry!(self.c.execute("UPDATE bindings SET update_interval = ?2
+                             WHERE id = ?1",
+                            &[&self.id, &interval]));
+
+        // If the new interval is shorter, don't wait for the update
+        // scheduled using the old one.
+        if let Some(seconds) = interval {
+            let key = sry!(self.key_id());
+            sry!(self.c.execute("UPDATE keys
+                                 SET update_at = MIN(update_at, ?2)
+                                 WHERE id = ?1",
+                                &[&key,
+                                  &(Timestamp::now()
+                                    + Duration::seconds(seconds))]));
+        }
+        Promise::ok(())
+    }
+
+    fn update_interval(&mut self,
+                       _: node::binding::UpdateIntervalParams,
+                       mut results: node::binding::UpdateIntervalResults)
+                       -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let seconds: Option<i64> = sry!(self.c.query_row(
+            "SELECT update_interval FROM bindings WHERE id = ?1",
+            &[&self.id], |row| row.get(0)));
+
+        pry!(results.get().get_result()).init_ok()
+            .set_seconds(seconds.unwrap_or(0));
         Promise::ok(())
     }
 }
@@ -752,6 +1681,36 @@ impl KeyServer {
         }
     }
 
+    /// Describes the conflict between the keys `existing` and
+    /// `incoming`.
+    fn conflict_info(c: &Connection, existing: ID, incoming: ID)
+                     -> Result<ConflictInfo> {
+        let fingerprint = |id: &ID| -> Result<Fingerprint> {
+            let fp: String = c.query_row(
+                "SELECT fingerprint FROM keys WHERE id = ?1",
+                &[id], |row| row.get(0))?;
+            Fingerprint::from_hex(&fp)
+        };
+        let existing = fingerprint(&existing)?;
+
+        // If we have the incoming key, see if the existing key
+        // vouches for it.
+        let key: Option<Vec<u8>> = c.query_row(
+            "SELECT key FROM keys WHERE id = ?1",
+            &[&incoming], |row| row.get_checked(0).ok())?;
+        let certified = if let Some(key) = key {
+            certified_by(&TPK::from_bytes(&key)?, &existing)
+        } else {
+            false
+        };
+
+        Ok(ConflictInfo {
+            existing: existing,
+            incoming: fingerprint(&incoming)?,
+            certified: certified,
+        })
+    }
+
     /// Merges other into this key updating the database.
     ///
     /// Returnes the merged key as blob.
@@ -775,10 +1734,22 @@ impl KeyServer {
             }
 
             if current.fingerprint() != new.fingerprint() {
-                return Err(node::Error::Conflict.into());
+                return Err(super::Error::Conflict(ConflictInfo {
+                    existing: current.fingerprint(),
+                    incoming: new.fingerprint(),
+                    certified: certified_by(&new, &current.fingerprint()),
+                }).into());
             }
 
-            new = current.merge(new)?;
+            let merged = current.clone().merge(new)?;
+            if merged == current {
+                // The new key is a subset of the current one, e.g.,
+                // a keyserver served an old copy.  Avoid the write.
+                let mut blob = vec![];
+                merged.serialize(&mut blob)?;
+                return Ok(blob);
+            }
+            new = merged;
         }
 
         // Write key back to the database.
@@ -818,10 +1789,109 @@ impl KeyServer {
         Ok(())
     }
 
-    /// Records a successful key update.
-    fn success(&self, message: &str, next: Duration) -> Result<()> {
-        log::message(&self.c, log::Refers::to().key(self.id),
-                     &self.slug(), message)?;
+    /// Coalesces keys in the common key pool that have the same
+    /// fingerprint.
+    ///
+    /// The work is done in a single transaction, so that an
+    /// interruption cannot leave bindings referring to deleted keys.
+    ///
+    /// On success, the number of keys merged and bindings rewired is
+    /// returned.
+    fn compact(c: &Connection) -> Result<(u64, u64)> {
+        transaction(c, || KeyServer::compact_helper(c))
+    }
+
+    /// Helper for `compact`.
+    fn compact_helper(c: &Connection) -> Result<(u64, u64)> {
+        // Group the keys by the canonical form of their fingerprint.
+        let mut groups: BTreeMap<String, Vec<(ID, String)>> = BTreeMap::new();
+        {
+            let mut stmt = c.prepare(
+                "SELECT id, fingerprint FROM keys ORDER BY id")?;
+            let rows = stmt.query_map(&[], |row| -> (ID, String) {
+                (row.get(0), row.get(1))
+            })?;
+            for row in rows {
+                let (id, fingerprint) = row?;
+                // Leave keys we cannot make sense of alone.
+                if let Ok(fp) = Fingerprint::from_hex(&fingerprint) {
+                    groups.entry(fp.to_hex()).or_insert_with(Vec::new)
+                        .push((id, fingerprint));
+                }
+            }
+        }
+
+        let mut merged = 0;
+        let mut rewired = 0;
+        for (canonical, mut keys) in groups {
+            // Prefer the key that is already in canonical form.
+            let target = keys.iter().position(|k| k.1 == canonical)
+                .unwrap_or(0);
+            let (target, fingerprint) = keys.remove(target);
+
+            let get_key = |id: &ID| -> Result<Option<TPK>> {
+                let key: Option<Vec<u8>> = c.query_row(
+                    "SELECT key FROM keys WHERE id = ?1",
+                    &[id], |row| row.get_checked(0).ok())?;
+                match key {
+                    Some(k) => Ok(Some(TPK::from_bytes(&k)?)),
+                    None => Ok(None),
+                }
+            };
+
+            let mut tpk = get_key(&target)?;
+            for (id, _) in keys.iter() {
+                tpk = match (tpk, get_key(id)?) {
+                    (Some(a), Some(b)) => Some(a.merge(b)?),
+                    (a, b) => a.or(b),
+                };
+
+                rewired += c.execute("UPDATE bindings SET key = ?1 WHERE key = ?2",
+                                     &[&target, id])? as u64;
+                c.execute("UPDATE log SET key = ?1 WHERE key = ?2",
+                          &[&target, id])?;
+                // Deleting the key drops its (sub)KeyID mappings.
+                c.execute("DELETE FROM keys WHERE id = ?1", &[id])?;
+                merged += 1;
+            }
+
+            if fingerprint != canonical {
+                c.execute("UPDATE keys SET fingerprint = ?1 WHERE id = ?2",
+                          &[&canonical, &target])?;
+            }
+
+            if ! keys.is_empty() {
+                if let Some(tpk) = tpk {
+                    let mut blob = vec![];
+                    tpk.serialize(&mut blob)?;
+                    c.execute("UPDATE keys SET key = ?1 WHERE id = ?2",
+                              &[&blob, &target])?;
+                    KeyServer::reindex_subkeys(c, target, &tpk)?;
+                }
+            }
+        }
+
+        Ok((merged, rewired))
+    }
+
+    /// Returns the update interval of this key, if any binding
+    /// overrides the default.
+    ///
+    /// If several bindings refer to this key, the shortest interval
+    /// wins.
+    fn update_interval(&self) -> Result<Option<Duration>> {
+        let seconds: Option<i64> = self.c.query_row(
+            "SELECT MIN(update_interval) FROM bindings WHERE key = ?1",
+            &[&self.id], |row| row.get(0))?;
+        Ok(seconds.map(|s| Duration::seconds(
+            cmp::min(s, max_update_interval().num_seconds()))))
+    }
+
+    /// Records a successful key update from `source`.
+    fn success(&self, message: &str, source: &str, next: Duration)
+               -> Result<()> {
+        log::message_from(&self.c, log::Refers::to().key(self.id),
+                          &self.slug(), message, source)?;
         self.c.execute("UPDATE keys
                         SET updated = ?2, update_at = ?3
                         WHERE id = ?1",
@@ -830,10 +1900,11 @@ impl KeyServer {
         Ok(())
     }
 
-    /// Records an unsuccessful key update.
-    fn error(&self, message: &str, error: &str, next: Duration) -> Result<()> {
-        log::error(&self.c, log::Refers::to().key(self.id),
-                   &self.slug(), message, error)?;
+    /// Records an unsuccessful key update from `source`.
+    fn error(&self, message: &str, error: &str, source: &str,
+             next: Duration) -> Result<()> {
+        log::error_from(&self.c, log::Refers::to().key(self.id),
+                        &self.slug(), message, error, source)?;
         self.c.execute("UPDATE keys
                         SET update_at = ?2
                         WHERE id = ?1",
@@ -874,67 +1945,186 @@ impl KeyServer {
 
     /// Helper for `update`.
     fn update_helper(c: &Rc<Connection>, handle: &Handle,
-                     network_policy: core::NetworkPolicy)
+                     network_policy: core::NetworkPolicy,
+                     policy: &UpdatePolicy)
                      -> Result<(KeyServer,
-                                openpgp::KeyID,
+                                Fingerprint,
                                 net::async::KeyServer)> {
         assert!(network_policy != core::NetworkPolicy::Offline);
         let network_policy_u8 = u8::from(&network_policy);
 
-        // Select the key that was updated least recently.
-        let (id, fingerprint): (ID, String) = c.query_row(
-            "SELECT keys.id, keys.fingerprint FROM keys
+        // Select the key that was updated least recently, the
+        // keyserver of a store it is bound in, and the key itself.
+        let (id, fingerprint, keyserver, key_blob)
+            : (ID, String, Option<String>, Option<Vec<u8>>)
+            = c.query_row(
+            "SELECT keys.id, keys.fingerprint, stores.keyserver, keys.key
+                 FROM keys
                  JOIN bindings on keys.id = bindings.key
                  JOIN stores on stores.id = bindings.store
                  WHERE stores.network_policy >= ?1
                    AND keys.update_at < ?2
                  ORDER BY keys.update_at LIMIT 1",
             &[&network_policy_u8, &Timestamp::now()], |row| (row.get(0),
-                                                             row.get(1)))?;
+                                                             row.get(1),
+                                                             row.get(2),
+                                                             row.get(3)))?;
         let fingerprint = openpgp::Fingerprint::from_hex(&fingerprint)
             .map_err(|_| node::Error::SystemError)?;
 
-        let ctx = core::Context::configure()
-            .network_policy(network_policy).build()?;
-        let keyserver = net::async::KeyServer::sks_pool(&ctx, handle)?;
+        let keyserver_from = |keyserver: Option<String>| {
+            let mut ctx = core::Context::configure()
+                .network_policy(network_policy);
+            if let Some(keyserver) = keyserver {
+                ctx.set_keyserver(keyserver);
+            }
+            let ctx = ctx.build()?;
+            net::async::KeyServer::from_context(&ctx, handle)
+        };
+
+        let key = KeyServer::new(c.clone(), id);
+
+        // A keyserver configured for the store takes precedence.
+        // Otherwise, try the key's preferred keyserver.  In either
+        // case, fall back to the default one if it is not usable.
+        let keyserver = match keyserver {
+            Some(uri) => keyserver_from(Some(uri.clone())).or_else(|e| {
+                // Databases written by older versions may contain
+                // keyservers we cannot use.  This must not stop the
+                // updates.
+                log::error_from(c, log::Refers::to().key(id), &key.slug(),
+                                "Keyserver not usable, using the default",
+                                &format!("{}", e), &uri)?;
+                keyserver_from(None)
+            }),
+            None => match preferred_keyserver(key_blob)
+                .and_then(|uri| keyserver_from(Some(uri)).ok())
+            {
+                Some(keyserver) => Ok(keyserver),
+                None => keyserver_from(None),
+            },
+        };
+
+        match keyserver {
+            Ok(keyserver) => Ok((key, fingerprint, keyserver)),
+            Err(e) => {
+                // Try the other keys first.
+                key.error("Update unsuccessful", &format!("{}", e), "",
+                          policy.interval / 2)?;
+                Err(e)
+            },
+        }
+    }
+
+    /// Logs a warning for every binding of a key that has not been
+    /// successfully updated within the overdue threshold of `policy`.
+    ///
+    /// A warning is only emitted once per key until it is updated
+    /// successfully again.  Returns the number of entries written.
+    fn log_overdue(c: &Rc<Connection>, network_policy: core::NetworkPolicy,
+                   policy: &UpdatePolicy, now: Timestamp)
+                   -> Result<usize> {
+        let network_policy_u8 = u8::from(&network_policy);
+        let deadline = now - policy.overdue_after;
+
+        let overdue: Vec<(ID, ID, ID, String)> = {
+            let mut stmt = c.prepare(
+                "SELECT bindings.store, bindings.id, keys.id, bindings.label
+                     FROM keys
+                     JOIN bindings on keys.id = bindings.key
+                     JOIN stores on stores.id = bindings.store
+                     WHERE stores.network_policy >= ?1
+                       AND COALESCE(keys.updated, keys.created) < ?2
+                       AND NOT EXISTS (
+                           SELECT 1 FROM log
+                               WHERE log.key = keys.id
+                                 AND log.message = 'update overdue'
+                                 AND log.timestamp
+                                     >= COALESCE(keys.updated, keys.created))
+                     ORDER BY keys.id, bindings.id")?;
+            let rows = stmt.query_map(
+                &[&network_policy_u8, &deadline],
+                |row| (row.get(0), row.get(1), row.get(2), row.get(3)))?;
+            let mut overdue = Vec::new();
+            for row in rows {
+                overdue.push(row?);
+            }
+            overdue
+        };
 
-        Ok((KeyServer::new(c.clone(), id),
-            fingerprint.to_keyid(),
-            keyserver))
+        for &(store, binding, key, ref label) in overdue.iter() {
+            log::message(c, log::Refers::to()
+                         .store(store).binding(binding).key(key),
+                         label, "update overdue")?;
+        }
+        Ok(overdue.len())
     }
 
     /// Updates the key that was least recently updated.
+    ///
+    /// The outcome is reported to `logger`, if any.
     fn update(c: &Rc<Connection>, handle: &Handle,
-              network_policy: core::NetworkPolicy)
+              network_policy: core::NetworkPolicy,
+              policy: UpdatePolicy, logger: Option<core::Logger>)
               -> Box<Future<Item=Duration, Error=failure::Error> + 'static> {
-        let (key, id, mut keyserver)
-            = match Self::update_helper(c, handle, network_policy) {
-            Ok((key, id, keyserver)) => (key, id, keyserver),
+        let (key, fingerprint, mut keyserver)
+            = match Self::update_helper(c, handle, network_policy, &policy) {
+            Ok((key, fingerprint, keyserver)) => (key, fingerprint, keyserver),
             Err(e) => return Box::new(future::err(e.into())),
         };
+        keyserver.set_retry_policy(policy.retry);
 
         let c = c.clone();
+        let source = keyserver.uri().to_string();
         let now = Timestamp::now();
         let at = Self::next_update_at(&c, network_policy)
             .unwrap_or(now + min_sleep_time());
 
         if at <= now {
             Box::new(
-                keyserver.get(&id)
+                keyserver.get(&fingerprint.to_keyid())
                     .then(move |tpk| {
                         let next = Self::need_update(&c, network_policy)
-                            .map(|c| refresh_interval() / c)
+                            .map(|c| policy.interval / c)
                             .unwrap_or(min_sleep_time());
 
+                        // Bindings may ask for this key to be updated
+                        // more or less frequently.
+                        let interval = key.update_interval()
+                            .unwrap_or(None).unwrap_or(next);
+
                         if let Err(e) = tpk.map(|t| key.merge(t)) {
                             key.error("Update unsuccessful",
-                                      &format!("{:?}", e), next / 2)
+                                      &format!("{:?}", e), &source,
+                                      interval / 2)
                                 .unwrap_or(());
+                            if let Some(logger) = logger {
+                                logger(core::LogEvent::UpdateFailed {
+                                    fingerprint: fingerprint.to_hex(),
+                                    uri: source,
+                                    error: e.to_string(),
+                                });
+                            }
                         } else {
-                            key.success("Update successful", next)
+                            key.success("Update successful", &source,
+                                        interval)
                                 .unwrap_or(());
+                            if let Some(logger) = logger {
+                                logger(core::LogEvent::UpdateSucceeded {
+                                    fingerprint: fingerprint.to_hex(),
+                                    uri: source,
+                                });
+                            }
                         }
 
+                        // Wake up in time for the next key that is due.
+                        let now = Timestamp::now();
+                        let next = match Self::next_update_at(&c,
+                                                              network_policy) {
+                            Some(at) if at > now => cmp::min(next, at - now),
+                            _ => next,
+                        };
+
                         future::ok(next)
                     }))
         } else {
@@ -944,7 +2134,11 @@ impl KeyServer {
     }
 
     /// Starts the periodic housekeeping.
-    fn start_housekeeping(c: Rc<Connection>, handle: Handle) -> Result<()> {
+    ///
+    /// Updates are reported to `logger`, if any.
+    fn start_housekeeping(c: Rc<Connection>, handle: Handle,
+                          policy: UpdatePolicy,
+                          logger: Option<core::Logger>) -> Result<()> {
         let h0 = handle.clone();
 
         let forever = loop_fn(0, move |_| {
@@ -953,7 +2147,10 @@ impl KeyServer {
 
             let h1 = h0.clone();
 
-            Self::update(&c, &h0, network_policy)
+            Self::log_overdue(&c, network_policy, &policy, Timestamp::now())
+                .unwrap_or(0);
+
+            Self::update(&c, &h0, network_policy, policy, logger.clone())
                 .then(move |d| {
                     let d = d.unwrap_or(min_sleep_time());
                      Timeout::new(
@@ -980,6 +2177,10 @@ impl Query for KeyServer {
         "keys"
     }
 
+    fn key_column() -> Option<&'static str> {
+        Some("id")
+    }
+
     fn id(&self) -> ID {
         self.id
     }
@@ -1030,7 +2231,7 @@ impl node::key::Server for KeyServer {
               mut results: node::key::ImportResults)
               -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let new = sry!(TPK::from_bytes(&pry!(pry!(params.get()).get_key())));
+        let new = sry!(parse_tpk(&pry!(pry!(params.get()).get_key())));
         let blob = sry!(self.merge(new));
         pry!(pry!(results.get().get_result()).set_ok(&blob[..]));
         Promise::ok(())
@@ -1046,11 +2247,113 @@ impl node::key::Server for KeyServer {
             node::log_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
+
+    fn fingerprint(&mut self,
+                   _: node::key::FingerprintParams,
+                   mut results: node::key::FingerprintResults)
+                   -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let fingerprint: String = sry!(self.c.query_row(
+            "SELECT fingerprint FROM keys WHERE id = ?1",
+            &[&self.id], |row| row.get(0)));
+
+        pry!(pry!(results.get().get_result()).set_ok(fingerprint.as_str()));
+        Promise::ok(())
+    }
+
+    fn summary(&mut self,
+               _: node::key::SummaryParams,
+               mut results: node::key::SummaryResults)
+               -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let key: Option<Vec<u8>> = sry!(
+            self.c.query_row(
+                "SELECT key FROM keys WHERE id = ?1",
+                &[&self.id],
+                |row| row.get_checked(0).ok()));
+        let tpk = match key {
+            Some(key) => sry!(TPK::from_bytes(&key)),
+            // We only know the fingerprint.
+            None => fail!(node::Error::NotFound),
+        };
+
+        let primary = tpk.primary();
+        let mut summary = pry!(results.get().get_result()).init_ok();
+        summary.set_fingerprint(&tpk.fingerprint().to_hex());
+        summary.set_algo(primary.pk_algo().into());
+        summary.set_bits(primary.mpis().bits().unwrap_or(0) as u32);
+        let created = primary.creation_time().to_timespec();
+        summary.set_created(created.sec);
+        if let Some(expiry) = tpk.primary_key_signature()
+            .and_then(|sig| sig.key_expiration_time())
+        {
+            summary.set_expires((created + expiry).sec);
+        }
+        summary.set_revoked(match tpk.revocation_status() {
+            openpgp::RevocationStatus::Revoked(_) => true,
+            _ => false,
+        });
+        Promise::ok(())
+    }
+
+    fn capabilities(&mut self,
+                    _: node::key::CapabilitiesParams,
+                    mut results: node::key::CapabilitiesResults)
+                    -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let key: Option<Vec<u8>> = sry!(
+            self.c.query_row(
+                "SELECT key FROM keys WHERE id = ?1",
+                &[&self.id],
+                |row| row.get_checked(0).ok()));
+        let tpk = match key {
+            Some(key) => sry!(TPK::from_bytes(&key)),
+            // We only know the fingerprint.
+            None => fail!(node::Error::NotFound),
+        };
+
+        fn revoked(status: openpgp::RevocationStatus) -> bool {
+            match status {
+                openpgp::RevocationStatus::Revoked(_) => true,
+                _ => false,
+            }
+        }
+
+        let mut keys = vec![
+            (tpk.fingerprint(),
+             tpk.primary_key_signature().map(|sig| sig.key_flags()),
+             revoked(tpk.revocation_status())),
+        ];
+        for skb in tpk.subkeys() {
+            keys.push((skb.subkey().fingerprint(),
+                       skb.binding_signature().map(|sig| sig.key_flags()),
+                       revoked(skb.revoked(None))));
+        }
+
+        let mut list = pry!(results.get().get_result())
+            .initn_ok(keys.len() as u32);
+        for (i, (fingerprint, flags, revoked)) in keys.into_iter().enumerate() {
+            let flags = flags.unwrap_or_default();
+            let mut c = list.reborrow().get(i as u32);
+            c.set_fingerprint(&fingerprint.to_hex());
+            c.set_certify(flags.can_certify());
+            c.set_sign(flags.can_sign());
+            c.set_encrypt_for_transport(flags.can_encrypt_for_transport());
+            c.set_encrypt_at_rest(flags.can_encrypt_at_rest());
+            c.set_authenticate(flags.can_authenticate());
+            c.set_revoked(revoked);
+        }
+        Promise::ok(())
+    }
 }
 
 /// Common code for BindingServer and KeyServer.
 trait Query {
     fn table_name() -> &'static str;
+    /// Names the column referring to the key, if any.
+    fn key_column() -> Option<&'static str> {
+        None
+    }
     fn id(&self) -> ID;
     fn connection(&self) -> Rc<Connection>;
     fn slug(&self) -> String;
@@ -1105,6 +2408,15 @@ trait Query {
         stats.set_verification_count(verification_count);
         set!(set_verification_first, verification_first);
         set!(set_verification_last, verification_last);
+
+        if let Some(column) = Self::key_column() {
+            let update_at: i64 = self.connection().query_row(
+                &format!("SELECT update_at FROM keys
+                          WHERE id = (SELECT {} FROM {} WHERE id = ?1)",
+                         column, Self::table_name()),
+                &[&self.id()], |row| row.get(0))?;
+            stats.set_next_update(update_at);
+        }
         Ok(())
     }
 }
@@ -1155,16 +2467,73 @@ impl node::store_iter::Server for StoreIterServer {
     }
 }
 
+/// Iterates over bindings ordered by label.
+///
+/// Labels are unique within a store, so the last label returned is
+/// all we need to remember.  Labels are compared byte-wise, and
+/// labels that are not valid UTF-8 sort last.
 struct BindingIterServer {
     c: Rc<Connection>,
     store_id: ID,
-    n: ID,
+    last: Option<Label>,
+    revoked_only: bool,
+    prefix: String,
 }
 
 impl BindingIterServer {
     fn new(c: Rc<Connection>, store_id: ID) -> Self {
-        BindingIterServer{c: c, store_id: store_id, n: ID::null()}
+        BindingIterServer{c: c, store_id: store_id, last: None,
+                          revoked_only: false, prefix: String::new()}
+    }
+
+    /// Only returns bindings whose label starts with `prefix`.
+    fn prefix(c: Rc<Connection>, store_id: ID, prefix: &str) -> Self {
+        BindingIterServer{prefix: prefix.into(), .. Self::new(c, store_id)}
+    }
+
+    /// Only returns bindings whose key is revoked.
+    fn revoked(c: Rc<Connection>, store_id: ID) -> Self {
+        BindingIterServer{revoked_only: true, .. Self::new(c, store_id)}
+    }
+}
+
+/// Returns a pattern for SQL's LIKE operator matching strings that
+/// start with `prefix`.
+///
+/// The wildcards `%` and `_` in the prefix are escaped using `\`.
+fn like_prefix_pattern(prefix: &str) -> String {
+    let mut pattern = String::with_capacity(prefix.len() + 1);
+    for c in prefix.chars() {
+        if c == '%' || c == '_' || c == '\\' {
+            pattern.push('\\');
+        }
+        pattern.push(c);
     }
+    pattern.push('%');
+    pattern
+}
+
+/// Returns whether the primary key of the given key blob is revoked.
+///
+/// Keys we only know the fingerprint of, and keys that fail to parse,
+/// are not considered revoked.
+fn primary_revoked(key: Option<Vec<u8>>) -> bool {
+    key.and_then(|key| TPK::from_bytes(&key).ok())
+        .map(|tpk| match tpk.revocation_status() {
+            openpgp::RevocationStatus::Revoked(_) => true,
+            _ => false,
+        })
+        .unwrap_or(false)
+}
+
+/// Returns the preferred keyserver of the given key blob, if any.
+///
+/// Keys we only know the fingerprint of, keys that fail to parse,
+/// and preferred keyservers that are not valid UTF-8 yield `None`.
+fn preferred_keyserver(key: Option<Vec<u8>>) -> Option<String> {
+    key.and_then(|key| TPK::from_bytes(&key).ok())
+        .and_then(|tpk| tpk.preferred_keyserver()
+                  .and_then(|uri| String::from_utf8(uri.to_vec()).ok()))
 }
 
 impl node::binding_iter::Server for BindingIterServer {
@@ -1173,33 +2542,61 @@ impl node::binding_iter::Server for BindingIterServer {
             mut results: node::binding_iter::NextResults)
             -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let (id, label, fingerprint): (ID, String, String) =
-            sry!(self.c.query_row(
-                 "SELECT bindings.id, bindings.label, keys.fingerprint FROM bindings
-                      JOIN keys ON bindings.key = keys.id
-                      WHERE bindings.id > ?1 AND bindings.store = ?2
-                      ORDER BY bindings.id LIMIT 1",
-                &[&self.n, &self.store_id],
-                |row| (row.get(0), row.get(1), row.get(2))));
+        let pattern = like_prefix_pattern(&self.prefix);
+        let (id, label, fingerprint) = loop {
+            let (id, label, fingerprint, key)
+                : (ID, Label, String, Option<Vec<u8>>) =
+                sry!(self.c.query_row(
+                    "SELECT bindings.id, bindings.label, keys.fingerprint,
+                            keys.key FROM bindings
+                         JOIN keys ON bindings.key = keys.id
+                         WHERE (?1 IS NULL OR bindings.label > ?1)
+                           AND bindings.store = ?2
+                           AND (bindings.label LIKE ?3 ESCAPE '\\'
+                                OR typeof(bindings.label) = 'blob')
+                         ORDER BY bindings.label LIMIT 1",
+                    &[&self.last, &self.store_id, &pattern],
+                    |row| (row.get(0), row.get(1), row.get(2),
+                           row.get_checked(3).ok())));
+
+            // LIKE is case-insensitive for ASCII characters, and
+            // does not work on labels that are not valid UTF-8, so
+            // we need to check the prefix ourselves.
+            if ! label.as_bytes().starts_with(self.prefix.as_bytes()) {
+                self.last = Some(label);
+                continue;
+            }
+
+            if self.revoked_only && ! primary_revoked(key) {
+                self.last = Some(label);
+                continue;
+            }
+            break (id, label, fingerprint);
+        };
 
         let mut entry = pry!(results.get().get_result()).init_ok();
-        entry.set_label(&label);
+        entry.set_label(&label.to_string_lossy());
+        entry.set_label_bytes(label.as_bytes());
         entry.set_fingerprint(&fingerprint);
         entry.set_binding(node::binding::ToClient::new(
             BindingServer::new(self.c.clone(), id)).into_client::<capnp_rpc::Server>());
-        self.n = id;
+        self.last = Some(label);
         Promise::ok(())
     }
 }
 
+/// Iterates over keys ordered by fingerprint.
+///
+/// Fingerprints are not unique, see `compact_keys`, so ties are
+/// broken using the id.
 struct KeyIterServer {
     c: Rc<Connection>,
-    n: ID,
+    last: Option<(String, ID)>,
 }
 
 impl KeyIterServer {
     fn new(c: Rc<Connection>) -> Self {
-        KeyIterServer{c: c, n: ID::null()}
+        KeyIterServer{c: c, last: None}
     }
 }
 
@@ -1209,25 +2606,118 @@ impl node::key_iter::Server for KeyIterServer {
             mut results: node::key_iter::NextResults)
             -> Promise<(), capnp::Error> {
         bind_results!(results);
+        let (last_fingerprint, last_id) = match self.last {
+            Some((ref fp, id)) => (Some(fp.clone()), id),
+            None => (None, ID::null()),
+        };
         let (id, fingerprint): (ID, String) =
             sry!(self.c.query_row(
                  "SELECT id, fingerprint FROM keys
-                      WHERE keys.id > ?1
-                      ORDER BY id LIMIT 1",
-                &[&self.n],
+                      WHERE ?1 IS NULL
+                         OR fingerprint > ?1
+                         OR (fingerprint = ?1 AND id > ?2)
+                      ORDER BY fingerprint, id LIMIT 1",
+                &[&last_fingerprint, &last_id],
                 |row| (row.get(0), row.get(1))));
 
         let mut entry = pry!(results.get().get_result()).init_ok();
         entry.set_fingerprint(&fingerprint);
         entry.set_key(node::key::ToClient::new(
             KeyServer::new(self.c.clone(), id)).into_client::<capnp_rpc::Server>());
-        self.n = id;
+        self.last = Some((fingerprint, id));
+        Promise::ok(())
+    }
+}
+
+/// Iterates over the keys bound in a store ordered by fingerprint.
+///
+/// Like `KeyIterServer`, ties are broken using the id.
+struct ExportIterServer {
+    c: Rc<Connection>,
+    store_id: ID,
+    last: Option<(String, ID)>,
+}
+
+impl ExportIterServer {
+    fn new(c: Rc<Connection>, store_id: ID) -> Self {
+        ExportIterServer{c: c, store_id: store_id, last: None}
+    }
+}
+
+impl node::export_iter::Server for ExportIterServer {
+    fn next(&mut self,
+            _: node::export_iter::NextParams,
+            mut results: node::export_iter::NextResults)
+            -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let (last_fingerprint, last_id) = match self.last {
+            Some((ref fp, id)) => (Some(fp.clone()), id),
+            None => (None, ID::null()),
+        };
+        let (id, fingerprint, key): (ID, String, Vec<u8>) =
+            sry!(self.c.query_row(
+                 "SELECT id, fingerprint, key FROM keys
+                      WHERE key IS NOT NULL
+                        AND EXISTS (SELECT 1 FROM bindings
+                                        WHERE bindings.key = keys.id
+                                          AND bindings.store = ?3)
+                        AND (?1 IS NULL
+                             OR fingerprint > ?1
+                             OR (fingerprint = ?1 AND id > ?2))
+                      ORDER BY fingerprint, id LIMIT 1",
+                &[&last_fingerprint, &last_id, &self.store_id],
+                |row| (row.get(0), row.get(1), row.get(2))));
+
+        pry!(pry!(results.get().get_result()).set_ok(&key[..]));
+        self.last = Some((fingerprint, id));
         Promise::ok(())
     }
 }
 
 /* Error handling.  */
 
+/// Extracts conflict details from errors.
+///
+/// Conflicts and incompatible database versions are reported to the
+/// client using the `conflict` and `incompatibleVersion` variants of
+/// `Result`, all other errors using the `err` variant.
+pub trait ConflictDetails {
+    /// Returns the details if this error is a conflict.
+    fn conflict_info(&self) -> Option<&ConflictInfo> {
+        None
+    }
+
+    /// Returns the found and supported database versions if this
+    /// error is an incompatible version.
+    fn incompatible_version(&self) -> Option<(i64, i64)> {
+        None
+    }
+}
+
+impl ConflictDetails for failure::Error {
+    fn conflict_info(&self) -> Option<&ConflictInfo> {
+        match self.downcast_ref::<super::Error>() {
+            Some(&super::Error::Conflict(ref info)) => Some(info),
+            _ => None,
+        }
+    }
+
+    fn incompatible_version(&self) -> Option<(i64, i64)> {
+        match self.downcast_ref::<super::Error>() {
+            Some(&super::Error::IncompatibleVersion { found, supported }) =>
+                Some((found, supported)),
+            _ => None,
+        }
+    }
+}
+
+impl ConflictDetails for node::Error {}
+impl ConflictDetails for rusqlite::Error {}
+impl ConflictDetails for openpgp::Error {}
+impl ConflictDetails for core::Error {}
+impl ConflictDetails for net::Error {}
+impl ConflictDetails for io::Error {}
+
 impl fmt::Debug for node::Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "node::Error::{}",
@@ -1238,6 +2728,8 @@ impl fmt::Debug for node::Error {
                    &node::Error::SystemError => "SystemError",
                    &node::Error::MalformedTPK => "MalformedTPK",
                    &node::Error::MalformedFingerprint => "MalformedFingerprint",
+                   &node::Error::TransactionInProgress =>
+                       "TransactionInProgress",
                    &node::Error::NetworkPolicyViolationOffline =>
                        "NetworkPolicyViolation(Offline)",
                    &node::Error::NetworkPolicyViolationAnonymized =>
@@ -1256,6 +2748,8 @@ impl From<rusqlite::Error> for node::Error {
             rusqlite::Error::SqliteFailure(f, _) => match f.code {
                 rusqlite::ErrorCode::ConstraintViolation =>
                     node::Error::NotFound,
+                rusqlite::ErrorCode::DatabaseBusy =>
+                    node::Error::TransactionInProgress,
                 _ => node::Error::SystemError,
             },
             rusqlite::Error::QueryReturnedNoRows =>
@@ -1271,6 +2765,8 @@ impl From<failure::Error> for node::Error {
             return match e {
                 &openpgp::Error::MalformedTPK(_) =>
                     node::Error::MalformedTPK,
+                &openpgp::Error::MalformedPacket(_) =>
+                    node::Error::MalformedTPK,
                 _ => node::Error::SystemError,
             }
         }
@@ -1278,7 +2774,13 @@ impl From<failure::Error> for node::Error {
         if let Some(e) = e.downcast_ref::<super::Error>() {
             return match e {
                 &super::Error::NotFound => node::Error::NotFound,
-                &super::Error::Conflict => node::Error::Conflict,
+                &super::Error::Conflict(_) => node::Error::Conflict,
+                &super::Error::TransactionInProgress =>
+                    node::Error::TransactionInProgress,
+                &super::Error::MalformedFingerprint(_) =>
+                    node::Error::MalformedFingerprint,
+                &super::Error::IncompatibleVersion { .. } =>
+                    node::Error::SystemError,
                 _ => unreachable!(),
             }
         }
@@ -1305,6 +2807,8 @@ impl From<failure::Error> for node::Error {
                 &rusqlite::Error::SqliteFailure(f, _) => match f.code {
                     rusqlite::ErrorCode::ConstraintViolation =>
                         node::Error::NotFound,
+                    rusqlite::ErrorCode::DatabaseBusy =>
+                        node::Error::TransactionInProgress,
                     _ => node::Error::SystemError,
                 },
                 &rusqlite::Error::QueryReturnedNoRows =>
@@ -1323,6 +2827,8 @@ impl From<openpgp::Error> for node::Error {
         match e {
             openpgp::Error::MalformedTPK(_) =>
                 node::Error::MalformedTPK,
+            openpgp::Error::MalformedPacket(_) =>
+                node::Error::MalformedTPK,
             _ => node::Error::SystemError,
         }
     }
@@ -1349,8 +2855,39 @@ impl From<io::Error> for node::Error {
 
 /* Database schemata and migrations.  */
 
+/// The database schema version this backend uses.
+const DB_SCHEMA_VERSION: i64 = 5;
+
+/* Version 5.  */
+const DB_SCHEMA_5: &'static str = "
+CREATE INDEX log_overdue ON log (key, message, timestamp);
+
+UPDATE version SET version = 5 WHERE id = 1;
+";
+
+/* Version 4.  */
+const DB_SCHEMA_4: &'static str = "
+ALTER TABLE log ADD COLUMN source TEXT NULL;
+
+UPDATE version SET version = 4 WHERE id = 1;
+";
+
+/* Version 3.  */
+const DB_SCHEMA_3: &'static str = "
+ALTER TABLE bindings ADD COLUMN update_interval INTEGER NULL;
+
+UPDATE version SET version = 3 WHERE id = 1;
+";
+
+/* Version 2.  */
+const DB_SCHEMA_2: &'static str = "
+ALTER TABLE stores ADD COLUMN keyserver TEXT NULL;
+
+UPDATE version SET version = 2 WHERE id = 1;
+";
+
 /* Version 1.  */
-const DB_SCHEMA_1: &'static str = "
+pub(crate) const DB_SCHEMA_1: &'static str = "
 CREATE TABLE version (
     id INTEGER PRIMARY KEY,
     version INTEGER);
@@ -1454,3 +2991,163 @@ impl From<node::NetworkPolicy> for core::NetworkPolicy {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn like_prefix_pattern() {
+        assert_eq!(super::like_prefix_pattern(""), "%");
+        assert_eq!(super::like_prefix_pattern("work/"), "work/%");
+        assert_eq!(super::like_prefix_pattern("50%_\\"), "50\\%\\_\\\\%");
+    }
+
+    #[test]
+    fn preferred_keyserver() {
+        let key = include_bytes!(
+            "../../../openpgp/tests/data/keys/preferred-keyserver.pgp");
+        assert_eq!(super::preferred_keyserver(Some(key.to_vec())),
+                   Some("hkps://keys.example.org".into()));
+
+        let key = include_bytes!(
+            "../../../openpgp/tests/data/keys/testy.pgp");
+        assert_eq!(super::preferred_keyserver(Some(key.to_vec())), None);
+        assert_eq!(super::preferred_keyserver(None), None);
+    }
+
+    /// Returns an in-memory database using the current schema.
+    fn database() -> Rc<Connection> {
+        let c = Rc::new(Connection::open_in_memory().unwrap());
+        for schema in [DB_SCHEMA_1, DB_SCHEMA_2, DB_SCHEMA_3, DB_SCHEMA_4,
+                       DB_SCHEMA_5].iter()
+        {
+            c.execute_batch(schema).unwrap();
+        }
+        c
+    }
+
+    #[test]
+    fn nested_transactions() {
+        let c = database();
+        let count = || -> i64 {
+            c.query_row("SELECT COUNT(*) FROM stores", &[], |row| row.get(0))
+                .unwrap()
+        };
+        let insert = |name: &str| {
+            c.execute("INSERT INTO stores (realm, network_policy, name)
+                           VALUES ('realm', 0, ?1)", &[&name]).unwrap();
+        };
+
+        transaction(&c, || {
+            insert("outer");
+            // Failing inner transactions only undo their own changes.
+            let r: Result<()> = transaction(&c, || {
+                insert("inner");
+                Err(super::super::Error::NotFound.into())
+            });
+            assert!(r.is_err());
+            assert_eq!(count(), 1);
+            transaction(&c, || Ok(insert("kept")))
+        }).unwrap();
+        assert!(c.is_autocommit());
+        assert_eq!(count(), 2);
+
+        // Even if they are the outermost transaction.
+        let r: Result<()> = transaction(&c, || {
+            insert("rolled back");
+            Err(super::super::Error::NotFound.into())
+        });
+        assert!(r.is_err());
+        assert!(c.is_autocommit());
+        assert_eq!(count(), 2);
+
+        // Compacting works within a transaction.
+        transaction(&c, || KeyServer::compact(&c)).unwrap();
+    }
+
+    #[test]
+    fn log_overdue() {
+        let c = database();
+
+        let policy = UpdatePolicy::default();
+        let network_policy = core::NetworkPolicy::Encrypted;
+        let t0 = Timestamp::now();
+        c.execute("INSERT INTO stores (realm, network_policy, name)
+                       VALUES ('realm', ?1, 'default')",
+                  &[&u8::from(&network_policy)]).unwrap();
+        c.execute("INSERT INTO keys (fingerprint, created, update_at)
+                       VALUES ('0123456789ABCDEF0123456789ABCDEF01234567',
+                               ?1, ?1)",
+                  &[&t0]).unwrap();
+        c.execute("INSERT INTO bindings (store, label, key, created)
+                       VALUES (1, 'Ἀριστοτέλης', 1, ?1)",
+                  &[&t0]).unwrap();
+
+        let overdue = |now| {
+            KeyServer::log_overdue(&c, network_policy, &policy, now).unwrap()
+        };
+        let entries = || -> i64 {
+            c.query_row("SELECT COUNT(*) FROM log
+                             WHERE slug = 'Ἀριστοτέλης'
+                               AND message = 'update overdue'",
+                        &[], |row| row.get(0)).unwrap()
+        };
+
+        // Within the threshold, nothing is logged.
+        assert_eq!(overdue(t0 + policy.interval), 0);
+        assert_eq!(entries(), 0);
+
+        // Past the threshold, a warning is logged exactly once.
+        let late = t0 + policy.overdue_after + Duration::days(1);
+        assert_eq!(overdue(late), 1);
+        assert_eq!(overdue(late + Duration::days(1)), 0);
+        assert_eq!(entries(), 1);
+
+        // A successful update resets the clock.
+        c.execute("UPDATE keys SET updated = ?1", &[&late]).unwrap();
+        assert_eq!(overdue(late + policy.interval), 0);
+        assert_eq!(overdue(late + policy.overdue_after
+                           + Duration::days(1)), 1);
+        assert_eq!(entries(), 2);
+    }
+
+    #[test]
+    fn update_policy_from_context() {
+        let ctx = core::Context::configure().ephemeral().build().unwrap();
+        assert_eq!(UpdatePolicy::from_context(&ctx).overdue_after,
+                   UpdatePolicy::default().overdue_after);
+
+        let ctx = core::Context::configure().ephemeral()
+            .update_overdue_after(::std::time::Duration::from_secs(3600))
+            .build().unwrap();
+        assert_eq!(UpdatePolicy::from_context(&ctx).overdue_after,
+                   Duration::hours(1));
+    }
+
+    #[test]
+    fn log_source() {
+        let c = database();
+        c.execute("INSERT INTO keys (fingerprint, created, update_at)
+                       VALUES ('0123456789ABCDEF0123456789ABCDEF01234567',
+                               ?1, ?1)",
+                  &[&Timestamp::now()]).unwrap();
+        let key = KeyServer::new(c.clone(), ID::from(1));
+        let source = "hkps://keys.example.org/";
+        key.success("Update successful", source, Duration::days(1)).unwrap();
+        key.error("Update unsuccessful", "timeout", source, Duration::days(1))
+            .unwrap();
+        log::message(&c, log::Refers::to().key(ID::from(1)), "key", "Hello")
+            .unwrap();
+
+        let sources: Vec<Option<String>> = {
+            let mut stmt = c.prepare("SELECT source FROM log ORDER BY id")
+                .unwrap();
+            let rows = stmt.query_map(&[], |row| row.get(0)).unwrap();
+            rows.map(|r| r.unwrap()).collect()
+        };
+        assert_eq!(sources, vec![Some(source.to_string()),
+                                 Some(source.to_string()),
+                                 None]);
+    }
+}
@@ -2,8 +2,10 @@
 
 use rusqlite;
 use rusqlite::types::{ToSql, ToSqlOutput, FromSql, FromSqlResult, ValueRef};
+use std::borrow::Cow;
 use std::fmt;
 use std::ops::{Add, Sub};
+use std::str;
 use time::{Timespec, Duration, now_utc};
 
 /// Represents a row id.
@@ -51,6 +53,65 @@ impl FromSql for ID {
 }
 
 
+/// A binding's label.
+///
+/// Labels are arbitrary byte strings.  Labels that are valid UTF-8
+/// are stored as text, all others as blobs.  This way, labels added
+/// using the string interface remain interchangeable with labels
+/// added using the byte interface.
+#[derive(Clone, PartialEq)]
+pub struct Label(Vec<u8>);
+
+impl Label {
+    /// Returns the label's bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the label as string, replacing invalid UTF-8
+    /// sequences.
+    pub fn to_string_lossy(&self) -> Cow<str> {
+        String::from_utf8_lossy(&self.0)
+    }
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string_lossy())
+    }
+}
+
+impl<'a> From<&'a [u8]> for Label {
+    fn from(label: &'a [u8]) -> Self {
+        Label(label.to_vec())
+    }
+}
+
+impl<'a> From<&'a str> for Label {
+    fn from(label: &'a str) -> Self {
+        Label(label.as_bytes().to_vec())
+    }
+}
+
+impl ToSql for Label {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput> {
+        Ok(match str::from_utf8(&self.0) {
+            Ok(s) => ToSqlOutput::from(s),
+            Err(_) => ToSqlOutput::from(&self.0[..]),
+        })
+    }
+}
+
+impl FromSql for Label {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Text(s) => Ok(Label::from(s)),
+            _ => value.as_blob().map(|b| b.into()),
+        }
+    }
+}
+
+
 /// A serializable system time.
 #[derive(Clone, Copy, PartialEq, PartialOrd)]
 pub struct Timestamp(Timespec);
@@ -86,6 +147,14 @@ impl Add<Duration> for Timestamp {
     }
 }
 
+impl Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, other: Duration) -> Timestamp {
+        Timestamp(self.0 - other)
+    }
+}
+
 impl Sub<Timestamp> for Timestamp {
     type Output = Duration;
 
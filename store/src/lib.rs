@@ -42,7 +42,8 @@
 //! //     created: Some(Timespec { tv_sec: 1513704042, tv_nsec: 0 }),
 //! //     updated: None,
 //! //     encryption: Stamps { count: 0, first: None, last: None },
-//! //     verification: Stamps { count: 0, first: None, last: None }
+//! //     verification: Stamps { count: 0, first: None, last: None },
+//! //     next_update: Some(SystemTime { tv_sec: 1513704042, tv_nsec: 0 })
 //! // }
 //! # Ok(())
 //! # }
@@ -64,13 +65,21 @@ extern crate tokio_io;
 
 use std::cell::RefCell;
 use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::panic;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::{Mutex, mpsc};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use capnp::capability::Promise;
 use capnp_rpc::rpc_twoparty_capnp::Side;
 use futures::{Future};
-use time::Timespec;
-use tokio_core::reactor::Core;
+use time::{Duration, Timespec};
+use tokio_core::reactor;
 
 extern crate sequoia_openpgp as openpgp;
 #[allow(unused_imports)]
@@ -82,6 +91,9 @@ extern crate sequoia_net;
 use openpgp::Fingerprint;
 use openpgp::KeyID;
 use openpgp::TPK;
+use openpgp::armor;
+use openpgp::constants::{PublicKeyAlgorithm, SignatureType};
+use openpgp::packet::{KeyFlags, Signature, UserID};
 use openpgp::parse::Parse;
 use openpgp::serialize::Serialize;
 use sequoia_core as core;
@@ -96,17 +108,87 @@ use store_protocol_capnp::node;
 
 pub(crate) mod backend;
 
+mod archive;
+
 /// Returns the service descriptor.
 #[doc(hidden)]
 pub fn descriptor(c: &Context) -> ipc::Descriptor {
+    descriptor_at(c, c.home().join("public-key-store.cookie"))
+}
+
+/// Returns the descriptor for the service with the given rendezvous
+/// point.
+fn descriptor_at(c: &Context, rendezvous: PathBuf) -> ipc::Descriptor {
     ipc::Descriptor::new(
         c,
-        c.home().join("public-key-store.cookie"),
+        rendezvous,
         c.lib().join("sequoia-public-key-store"),
         backend::factory,
     )
 }
 
+/// Drives the RPC system, reporting requests to the logger.
+///
+/// All requests are made using `make_request!` and friends, which
+/// use `Core::run`.
+struct Core {
+    reactor: reactor::Core,
+    logger: Option<core::Logger>,
+}
+
+impl Core {
+    fn new(c: &Context) -> io::Result<Self> {
+        Ok(Core {
+            reactor: reactor::Core::new()?,
+            logger: c.logger().cloned(),
+        })
+    }
+
+    fn handle(&self) -> reactor::Handle {
+        self.reactor.handle()
+    }
+
+    /// Runs a request to completion, and logs the outcome.
+    fn run<F, T>(&mut self, operation: String, f: F)
+                 -> std::result::Result<Result<T>, capnp::Error>
+        where F: Future<Item=Result<T>, Error=capnp::Error>
+    {
+        let start = ::std::time::Instant::now();
+        let r = self.reactor.run(f);
+
+        if let Some(ref logger) = self.logger {
+            logger(core::LogEvent::StoreRequest {
+                operation: operation,
+                duration: start.elapsed(),
+                error: match r {
+                    Ok(Ok(_)) => None,
+                    Ok(Err(ref e)) => Some(e.to_string()),
+                    Err(ref e) => Some(e.to_string()),
+                },
+            });
+        }
+        r
+    }
+}
+
+/// Returns the name of the operation invoked by `request`.
+///
+/// The name is derived from the type of the request's parameters,
+/// e.g. `store.lookup` for `node::store::lookup_params`.
+fn operation<P, R>(_: &capnp::capability::Request<P, R>) -> String {
+    let name = ::std::any::type_name::<P>();
+    let path = name.split("::")
+        .skip_while(|s| *s != "node").skip(1)
+        .filter(|s| *s != "Owned")
+        .map(|s| s.trim_end_matches("_params"))
+        .collect::<Vec<_>>();
+    if path.is_empty() {
+        name.into()
+    } else {
+        path.join(".")
+    }
+}
+
 /// Keys used for communications.
 pub const REALM_CONTACTS: &'static str =
     "org.sequoia-pgp.contacts";
@@ -153,7 +235,8 @@ impl Pool {
         let mut request = client.import_request();
         request.get().set_key(&blob);
         let key = make_request!(&mut core, request)?;
-        Ok(Key::new(Rc::new(RefCell::new(core)), key))
+        Ok(Key::new(Rc::new(RefCell::new(core)), Some(tpk.fingerprint()),
+                    key, true))
     }
 
     /// Looks up a key in the common key pool.
@@ -183,12 +266,12 @@ impl Pool {
     /// # }
     /// ```
     pub fn lookup(c: &Context, fp: &Fingerprint) -> Result<Key> {
+        check_fingerprint(fp)?;
         let (mut core, client) = Store::connect(c)?;
         let mut request = client.lookup_by_fingerprint_request();
-        let fp = fp.to_hex();
-        request.get().set_fingerprint(&fp);
+        request.get().set_fingerprint(&fp.to_hex());
         let key = make_request!(&mut core, request)?;
-        Ok(Key::new(Rc::new(RefCell::new(core)), key))
+        Ok(Key::new(Rc::new(RefCell::new(core)), Some(fp.clone()), key, true))
     }
 
     /// Looks up a key in the common key pool by KeyID.
@@ -222,7 +305,7 @@ impl Pool {
         let mut request = client.lookup_by_keyid_request();
         request.get().set_keyid(keyid.as_u64()?);
         let key = make_request!(&mut core, request)?;
-        Ok(Key::new(Rc::new(RefCell::new(core)), key))
+        Ok(Key::new(Rc::new(RefCell::new(core)), None, key, true))
     }
 
     /// Looks up a key in the common key pool by (Sub)KeyID.
@@ -273,9 +356,46 @@ impl Pool {
         let mut request = client.lookup_by_subkeyid_request();
         request.get().set_keyid(keyid.as_u64()?);
         let key = make_request!(&mut core, request)?;
-        Ok(Key::new(Rc::new(RefCell::new(core)), key))
+        Ok(Key::new(Rc::new(RefCell::new(core)), None, key, true))
+    }
+
+}
+
+/// Options for opening a store.
+///
+/// See [`Store::open_with_options`].
+///
+///   [`Store::open_with_options`]: struct.Store.html#method.open_with_options
+#[derive(Clone, Debug)]
+pub struct OpenOptions {
+    integrity_checks: bool,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        OpenOptions {
+            integrity_checks: true,
+        }
+    }
+}
+
+impl OpenOptions {
+    /// Returns the default options.
+    pub fn new() -> Self {
+        Default::default()
     }
 
+    /// Controls whether keys are checked for integrity.
+    ///
+    /// If enabled, `Key::tpk` and `Binding::tpk` check that the
+    /// retrieved TPK matches the fingerprint recorded by the
+    /// backend, and return `Error::IntegrityFailure` otherwise.
+    /// This costs an additional round trip per key.  The default is
+    /// to check keys.
+    pub fn integrity_checks(mut self, enabled: bool) -> Self {
+        self.integrity_checks = enabled;
+        self
+    }
 }
 
 /// A public key store.
@@ -283,6 +403,8 @@ pub struct Store {
     name: String,
     core: Rc<RefCell<Core>>,
     store: node::store::Client,
+    network_policy: core::NetworkPolicy,
+    integrity_checks: bool,
 }
 
 impl fmt::Debug for Store {
@@ -295,11 +417,32 @@ impl Store {
     /// Establishes a connection to the backend.
     fn connect(c: &Context) -> Result<(Core, node::Client)> {
         let descriptor = descriptor(c);
-        let core = Core::new()?;
+        Self::bootstrap(c, |handle| descriptor.connect(handle))
+    }
+
+    /// Establishes a connection to a running backend.
+    ///
+    /// See `Store::open_at`.
+    fn connect_at(c: &Context, rendezvous: &Path)
+                  -> Result<(Core, node::Client)> {
+        if ! rendezvous.exists() {
+            return Err(Error::ServiceNotRunning(rendezvous.into()).into());
+        }
+
+        let descriptor = descriptor_at(c, rendezvous.into());
+        Self::bootstrap(c, |handle| descriptor.connect_existing(handle))
+    }
+
+    /// Sets up the RPC system using `connect`.
+    fn bootstrap<F>(c: &Context, connect: F) -> Result<(Core, node::Client)>
+        where F: FnOnce(&reactor::Handle)
+                        -> Result<capnp_rpc::RpcSystem<Side>>
+    {
+        let core = Core::new(c)?;
         let handle = core.handle();
 
         let mut rpc_system
-            = match descriptor.connect(&handle) {
+            = match connect(&handle) {
                 Ok(r) => r,
                 Err(e) => return Err(e.into()),
             };
@@ -321,21 +464,158 @@ impl Store {
     /// of the context that created the store in the first place.
     /// Opening the store with a different network policy is
     /// forbidden.
+    ///
+    /// If the context configures a default keyserver, it is recorded
+    /// with the store and used to update its TPKs.  See
+    /// `Store::keyserver_uri`.
     pub fn open(c: &Context, realm: &str, name: &str) -> Result<Self> {
-        let (mut core, client) = Self::connect(c)?;
+        Self::open_with_options(c, realm, name, &OpenOptions::new())
+    }
+
+    /// Opens a store using the given options.
+    ///
+    /// Like `open`, but allows to configure the returned handle.
+    /// See [`OpenOptions`] for details.
+    ///
+    ///   [`OpenOptions`]: struct.OpenOptions.html
+    pub fn open_with_options(c: &Context, realm: &str, name: &str,
+                             options: &OpenOptions) -> Result<Self> {
+        let (core, client) = Self::connect(c)?;
+        Self::open_on(c, core, client, realm, name, options)
+    }
 
+    /// Opens a store maintained by a running background service.
+    ///
+    /// Like `open`, but instead of using, and if necessary starting,
+    /// the service associated with the context's home directory,
+    /// this connects to the service with the given rendezvous point.
+    /// The rendezvous point is the file the service advertises its
+    /// address in, e.g. `public-key-store.cookie` in the home
+    /// directory of the context that started it.  This allows
+    /// processes with different home directories to share a
+    /// service, for example a system-wide one.
+    ///
+    /// No service is started.  If `rendezvous` does not exist,
+    /// `Error::ServiceNotRunning` is returned.
+    pub fn open_at<P>(c: &Context, rendezvous: P, realm: &str, name: &str)
+                      -> Result<Self>
+        where P: AsRef<Path>
+    {
+        let (core, client) = Self::connect_at(c, rendezvous.as_ref())?;
+        Self::open_on(c, core, client, realm, name, &OpenOptions::new())
+    }
+
+    /// Opens a store using the given connection.
+    fn open_on(c: &Context, mut core: Core, client: node::Client,
+               realm: &str, name: &str, options: &OpenOptions)
+               -> Result<Self> {
         let mut request = client.open_request();
         request.get().set_realm(realm);
         request.get().set_network_policy(c.network_policy().into());
         request.get().set_ephemeral(c.ephemeral());
         request.get().set_name(name);
+        request.get().set_keyserver(c.keyserver().unwrap_or(""));
 
         let store = make_request!(&mut core, request)?;
-        Ok(Self::new(Rc::new(RefCell::new(core)), name, store))
+        let mut store = Self::new(Rc::new(RefCell::new(core)), name,
+                                  *c.network_policy(), store);
+        store.integrity_checks = options.integrity_checks;
+        Ok(store)
+    }
+
+    /// Opens a store that transparently reconnects.
+    ///
+    /// Like `open`, but the returned handle survives restarts of
+    /// the background service: if a call fails with
+    /// `Error::Disconnected`, the store is opened again, and the call
+    /// is retried.  At most one retry per call is attempted, so that
+    /// persistent failures are not masked.
+    ///
+    /// Objects handed out by the store before it reconnected, like
+    /// `Binding`s, refer to the old connection and are not
+    /// reconnected.
+    pub fn open_resilient(c: &Context, realm: &str, name: &str)
+                          -> Result<ResilientStore> {
+        let store = retry_disconnected(
+            || Self::open(c, realm, name), || Ok(()))?;
+        Ok(ResilientStore {
+            ctx: c.clone(),
+            realm: realm.into(),
+            name: name.into(),
+            store: RefCell::new(store),
+        })
+    }
+
+    /// Opens a store that can be shared across threads.
+    ///
+    /// The store is opened on a dedicated thread, which owns it.
+    /// See [`SharedStore`] for details.
+    ///
+    ///   [`SharedStore`]: struct.SharedStore.html
+    pub fn open_shared(c: &Context, realm: &str, name: &str)
+                       -> Result<SharedStore> {
+        let (sender, receiver) = mpsc::channel::<SharedStoreRequest>();
+        let (opened_sender, opened_receiver) = mpsc::channel();
+
+        let ctx = c.clone();
+        let realm = realm.to_string();
+        let name_ = name.to_string();
+        let thread = thread::spawn(move || {
+            let store = match Self::open(&ctx, &realm, &name_) {
+                Ok(store) => {
+                    let _ = opened_sender.send(Ok(()));
+                    store
+                },
+                Err(e) => {
+                    let _ = opened_sender.send(Err(e));
+                    return;
+                },
+            };
+
+            // Serve requests until the SharedStore is dropped.
+            for request in receiver {
+                request(&store);
+            }
+        });
+
+        match opened_receiver.recv() {
+            Ok(Ok(())) => Ok(SharedStore {
+                name: name.into(),
+                sender: Mutex::new(Some(sender)),
+                thread: Some(thread),
+            }),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e)
+            },
+            Err(_) => {
+                let _ = thread.join();
+                Err(Error::StoreError.into())
+            },
+        }
+    }
+
+    fn new(core: Rc<RefCell<Core>>, name: &str,
+           network_policy: core::NetworkPolicy, store: node::store::Client)
+           -> Self {
+        Store{core: core, name: name.into(), store: store,
+              network_policy: network_policy, integrity_checks: true}
     }
 
-    fn new(core: Rc<RefCell<Core>>, name: &str, store: node::store::Client) -> Self {
-        Store{core: core, name: name.into(), store: store}
+    /// Returns the network policy the given store was created with.
+    ///
+    /// As opening a store with a different network policy is
+    /// forbidden, this can be used to reconcile the policies before
+    /// opening the store.  Returns `Error::NotFound` if there is no
+    /// such store.
+    pub fn policy_of(c: &Context, realm: &str, name: &str)
+                     -> Result<core::NetworkPolicy> {
+        let mut iter = Self::list(c, realm)?;
+        let policy = iter
+            .find(|&(ref r, ref n, _, _)| r == realm && n == name)
+            .map(|(_, _, policy, _)| policy);
+        iter.finish()?;
+        policy.ok_or(Error::NotFound.into())
     }
 
     /// Lists all stores with the given prefix.
@@ -344,15 +624,33 @@ impl Store {
         let mut request = client.iter_request();
         request.get().set_realm_prefix(realm_prefix);
         let iter = make_request!(&mut core, request)?;
-        Ok(StoreIter{core: Rc::new(RefCell::new(core)), iter: iter})
+        Ok(StoreIter{core: Rc::new(RefCell::new(core)), iter: iter, error: None})
     }
 
     /// Lists all keys in the common key pool.
+    ///
+    /// The keys are ordered by fingerprint.
     pub fn list_keys(c: &Context) -> Result<KeyIter> {
         let (mut core, client) = Self::connect(c)?;
         let request = client.iter_keys_request();
         let iter = make_request!(&mut core, request)?;
-        Ok(KeyIter{core: Rc::new(RefCell::new(core)), iter: iter})
+        Ok(KeyIter{core: Rc::new(RefCell::new(core)), iter: iter, error: None})
+    }
+
+    /// Returns the fingerprints of all keys in the common key pool
+    /// matching the given KeyID.
+    ///
+    /// The KeyID may be a short key ID.  As short key IDs are easy
+    /// to forge, callers should reject them if they match more than
+    /// one key.  Only primary keys are considered.
+    pub fn resolve_keyid(c: &Context, keyid: &KeyID) -> Result<Vec<Fingerprint>> {
+        let mut iter = Self::list_keys(c)?;
+        let fps = iter.by_ref()
+            .map(|(fp, _)| fp)
+            .filter(|fp| keyid.is_suffix_of(fp))
+            .collect();
+        iter.finish()?;
+        Ok(fps)
     }
 
     /// Lists all log entries.
@@ -360,11 +658,58 @@ impl Store {
         let (mut core, client) = Self::connect(c)?;
         let request = client.log_request();
         let iter = make_request!(&mut core, request)?;
-        Ok(LogIter{core: Rc::new(RefCell::new(core)), iter: iter})
+        Ok(LogIter{core: Rc::new(RefCell::new(core)), iter: iter, error: None})
+    }
+
+    /// Lists log entries matching the given criteria.
+    ///
+    /// Only entries logged at or after `since`, and before `until`
+    /// are returned.  If `failures_only` is set, only entries
+    /// recording an error are returned.  The entries are filtered by
+    /// the backend, and returned in the same order as `server_log`.
+    pub fn server_log_filtered(c: &Context, since: Option<Timespec>,
+                               until: Option<Timespec>, failures_only: bool)
+                               -> Result<LogIter> {
+        let (mut core, client) = Self::connect(c)?;
+        let mut request = client.log_request();
+        if let Some(since) = since {
+            request.get().set_since(since.sec);
+        }
+        if let Some(until) = until {
+            // Zero means unbounded, so don't let the bound vanish.
+            request.get().set_until(if until.sec == 0 { -1 } else { until.sec });
+        }
+        request.get().set_failures_only(failures_only);
+        let iter = make_request!(&mut core, request)?;
+        Ok(LogIter{core: Rc::new(RefCell::new(core)), iter: iter, error: None})
+    }
+
+    /// Compacts the common key pool.
+    ///
+    /// Coalesces keys with the same fingerprint, rewiring all
+    /// bindings to the surviving key, and returns the space to the
+    /// file system.  This is a maintenance operation, and it is done
+    /// in a single transaction.
+    pub fn compact_keys(c: &Context) -> Result<CompactReport> {
+        let (mut core, client) = Self::connect(c)?;
+        let request = client.compact_keys_request();
+        make_request_map!(&mut core, request,
+                          |r: node::compact_report::Reader|
+                          Ok(CompactReport {
+                              keys_merged: r.get_keys_merged(),
+                              bindings_rewired: r.get_bindings_rewired(),
+                              bytes_reclaimed: r.get_bytes_reclaimed(),
+                          }))
     }
 
     /// Adds a key identified by fingerprint to the store.
     ///
+    /// If `label` is already bound to `fingerprint`, the existing
+    /// binding is returned.  If it is bound to a different key,
+    /// `Error::Conflict` is returned.  The lookup and the insertion
+    /// happen atomically in the backend, so concurrent callers adding
+    /// the same binding do not race each other.
+    ///
     /// # Example
     ///
     /// ```
@@ -381,17 +726,139 @@ impl Store {
     /// #     .ipc_policy(IPCPolicy::Internal)
     /// #     .ephemeral().build()?;
     /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
-    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-    /// store.add("Mister B.", &fp)?;
+    /// let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// store.add("Mister B.", &b)?;
+    /// store.add("Mister B.", &b)?; // Idempotent.
+    ///
+    /// let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+    /// let r = store.add("Mister B.", &c);
+    /// assert!(r.is_err()); // Conflict!
     /// # Ok(())
     /// # }
     /// ```
     pub fn add(&self, label: &str, fingerprint: &Fingerprint) -> Result<Binding> {
-        let mut request = self.store.add_request();
+        self.add_bytes(label.as_bytes(), fingerprint)
+    }
+
+    /// Adds a key identified by fingerprint to the store.
+    ///
+    /// Like `Store::add`, but the label may be an arbitrary byte
+    /// string, e.g. a binary identifier.  Labels that are valid UTF-8
+    /// are interchangeable with those used with `Store::add`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// store.add_bytes(b"\xffB", &fp)?;
+    /// let binding = store.lookup_bytes(b"\xffB")?;
+    /// assert_eq!(binding.label_bytes()?, b"\xffB");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_bytes(&self, label: &[u8], fingerprint: &Fingerprint)
+                     -> Result<Binding> {
+        check_fingerprint(fingerprint)?;
+        let mut request = self.store.add_bytes_request();
         request.get().set_label(label);
         request.get().set_fingerprint(fingerprint.to_hex().as_ref());
         let binding = make_request!(self.core.borrow_mut(), request)?;
-        Ok(Binding::new(self.core.clone(), Some(label), binding))
+        Ok(Binding::new(self.core.clone(), Some(label),
+                        Some(fingerprint.clone()), binding,
+                        self.integrity_checks))
+    }
+
+    /// Adds many bindings at once.
+    ///
+    /// The bindings are added in a single transaction: either all of
+    /// them are added, or none are.  Labels that are already bound to
+    /// the given fingerprint are kept.  If any label is bound to a
+    /// different key, `Error::Conflicts` is returned, listing every
+    /// conflicting label.  On success, the bindings are returned in
+    /// the same order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+    /// let bindings = store.add_many(&[("Mister B.", &b), ("Mister C.", &c)])?;
+    /// assert_eq!(bindings.len(), 2);
+    ///
+    /// // Conflicts with "Mister B.", so nothing is added.
+    /// assert!(store.add_many(&[("Mister D.", &b), ("Mister B.", &c)]).is_err());
+    /// assert!(store.lookup("Mister D.").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_many(&self, bindings: &[(&str, &Fingerprint)])
+                    -> Result<Vec<Binding>> {
+        for &(_, fp) in bindings {
+            check_fingerprint(fp)?;
+        }
+        let mut request = self.store.add_many_request();
+        {
+            let mut list = request.get().init_bindings(bindings.len() as u32);
+            for (i, &(label, fp)) in bindings.iter().enumerate() {
+                let mut b = list.reborrow().get(i as u32);
+                b.set_label(label);
+                b.set_fingerprint(fp.to_hex().as_ref());
+            }
+        }
+        make_request_map!(self.core.borrow_mut(), request,
+                          |r: node::add_many_result::Reader| {
+            let conflicts = r.get_conflicts()?;
+            if conflicts.len() > 0 {
+                let mut c = Vec::with_capacity(conflicts.len() as usize);
+                for conflict in conflicts.iter() {
+                    let label = bindings.get(conflict.get_index() as usize)
+                        .ok_or(Error::ProtocolError)?.0;
+                    c.push((label.to_string(),
+                            conflict_info(conflict.get_info()?)?));
+                }
+                return Err(Error::Conflicts(c).into());
+            }
+
+            let clients = r.get_bindings()?;
+            if clients.len() as usize != bindings.len() {
+                return Err(Error::ProtocolError.into());
+            }
+            let mut result = Vec::with_capacity(bindings.len());
+            for (i, &(label, fp)) in bindings.iter().enumerate() {
+                result.push(Binding::new(self.core.clone(),
+                                         Some(label.as_bytes()),
+                                         Some(fp.clone()),
+                                         clients.get(i as u32)?,
+                                         self.integrity_checks));
+            }
+            Ok(result)
+        })
     }
 
     /// Imports a key into the store.
@@ -420,13 +887,53 @@ impl Store {
     /// # }
     /// ```
     pub fn import(&self, label: &str, tpk: &TPK) -> Result<TPK> {
-        let fingerprint = tpk.fingerprint();
-        let mut request = self.store.add_request();
-        request.get().set_label(label);
-        request.get().set_fingerprint(fingerprint.to_hex().as_ref());
-        let binding = make_request!(self.core.borrow_mut(), request)?;
-        let binding = Binding::new(self.core.clone(), Some(label), binding);
-        binding.import(tpk)
+        self.add(label, &tpk.fingerprint())?.import(tpk)
+    }
+
+    /// Retrieves a key from a keyserver and binds it to `label`.
+    ///
+    /// Fetches the key with the given `keyid` from `ks`, checks that
+    /// the primary key or one of the subkeys has the requested KeyID,
+    /// and imports it like `Store::import`.  If the server returns a
+    /// different key, `Error::UnexpectedKey` is returned.
+    ///
+    /// The store's network policy must permit talking to the
+    /// keyserver, i.e. an `Encrypted` store refuses to use an
+    /// `hkp://` keyserver, even if the keyserver handle was created
+    /// with a less strict context.
+    pub fn import_from_keyserver(&self, label: &str,
+                                 ks: &mut sequoia_net::KeyServer,
+                                 keyid: &KeyID)
+                                 -> Result<TPK> {
+        self.network_policy.assert(
+            if ks.uri().starts_with("https:") {
+                core::NetworkPolicy::Encrypted
+            } else {
+                core::NetworkPolicy::Insecure
+            })?;
+
+        let tpk = ks.get(keyid)?;
+        if tpk.keyid() != *keyid
+            && ! tpk.subkeys().any(|skb| skb.subkey().keyid() == *keyid)
+        {
+            return Err(Error::UnexpectedKey {
+                expected: keyid.clone(),
+                found: tpk.fingerprint(),
+            }.into());
+        }
+
+        self.import(label, &tpk)
+    }
+
+    /// Parses the given key and binds it to `label`.
+    ///
+    /// `data` may either be an armored or a binary TPK.  This is a
+    /// convenience function combining `TPK::from_bytes` and `import`.
+    /// If `data` is not a well-formed TPK, `Error::MalformedTPK` is
+    /// returned.
+    pub fn import_bytes(&self, label: &str, data: &[u8]) -> Result<TPK> {
+        let tpk = TPK::from_bytes(data).map_err(|_| Error::MalformedTPK)?;
+        self.import(label, &tpk)
     }
 
     /// Returns the binding for the given label.
@@ -457,10 +964,73 @@ impl Store {
     /// # }
     /// ```
     pub fn lookup(&self, label: &str) -> Result<Binding> {
-        let mut request = self.store.lookup_request();
+        self.lookup_bytes(label.as_bytes())
+    }
+
+    /// Returns all bindings referring to the given key.
+    ///
+    /// A key may be filed under several labels, hence all matching
+    /// bindings are returned, ordered by label.  If the key is known,
+    /// but not bound in this store, the result is empty.  If the key
+    /// is not in the common key pool at all, `Error::NotFound` is
+    /// returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// store.add("Mister B.", &fp)?;
+    /// store.add("Bee", &fp)?;
+    ///
+    /// let bindings = store.lookup_by_fingerprint(&fp)?;
+    /// assert_eq!(bindings.len(), 2);
+    /// assert_eq!(bindings[0].label()?, "Bee");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn lookup_by_fingerprint(&self, fp: &Fingerprint)
+                                 -> Result<Vec<Binding>> {
+        check_fingerprint(fp)?;
+        let mut request = self.store.lookup_by_fingerprint_request();
+        request.get().set_fingerprint(fp.to_hex().as_ref());
+        make_request_map!(
+            self.core.borrow_mut(), request,
+            |items: capnp::struct_list::Reader<node::binding_iter::item::Owned>| {
+                let mut bindings = Vec::with_capacity(items.len() as usize);
+                for item in items.iter() {
+                    bindings.push(Binding::new(self.core.clone(),
+                                               Some(item.get_label_bytes()?),
+                                               Some(fp.clone()),
+                                               item.get_binding()?,
+                                               self.integrity_checks));
+                }
+                Ok(bindings)
+            })
+    }
+
+    /// Looks up a key by label.
+    ///
+    /// Like `Store::lookup`, but the label may be an arbitrary byte
+    /// string.  See `Store::add_bytes`.
+    pub fn lookup_bytes(&self, label: &[u8]) -> Result<Binding> {
+        let mut request = self.store.lookup_bytes_request();
         request.get().set_label(label);
         let binding = make_request!(self.core.borrow_mut(), request)?;
-        Ok(Binding::new(self.core.clone(), Some(label), binding))
+        Ok(Binding::new(self.core.clone(), Some(label), None, binding,
+                        self.integrity_checks))
     }
 
     /// Looks up a key by (Sub)KeyID.
@@ -505,8 +1075,9 @@ impl Store {
         let mut request = self.store.lookup_by_subkeyid_request();
         request.get().set_keyid(keyid.as_u64()?);
         let binding = make_request!(self.core.borrow_mut(), request)?;
-        let mut binding = Binding::new(self.core.clone(), None, binding);
-        binding.label = binding.label().ok();
+        let mut binding = Binding::new(self.core.clone(), None, None, binding,
+                                       self.integrity_checks);
+        binding.label = binding.label_bytes().ok();
         Ok(binding)
     }
 
@@ -543,66 +1114,13 @@ impl Store {
         make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
     }
 
-    /// Lists all bindings.
-    pub fn iter(&self) -> Result<BindingIter> {
-        let request = self.store.iter_request();
-        let iter = make_request!(self.core.borrow_mut(), request)?;
-        Ok(BindingIter{core: self.core.clone(), iter: iter})
-    }
-
-    /// Lists all log entries related to this store.
-    pub fn log(&self) -> Result<LogIter> {
-        let request = self.store.log_request();
-        let iter = make_request!(self.core.borrow_mut(), request)?;
-        Ok(LogIter{core: self.core.clone(), iter: iter})
-    }
-}
-
-/// Makes a stats request and parses the result.
-macro_rules! make_stats_request {
-    ( $core: expr, $request: expr ) => {{
-        make_request_map!(
-            $core, $request,
-            |s: node::stats::Reader| Ok(Stats{
-                created: from_unix(s.get_created()),
-                updated: from_unix(s.get_updated()),
-                encryption: Stamps::new(
-                    s.get_encryption_count(),
-                    from_unix(s.get_encryption_first()),
-                    from_unix(s.get_encryption_last())),
-                verification: Stamps::new(
-                    s.get_verification_count(),
-                    from_unix(s.get_verification_first()),
-                    from_unix(s.get_verification_last())),
-            }))
-    }}
-}
-
-/// Represents an entry in a Store.
-///
-/// Stores map labels to TPKs.  A `Binding` represents a pair in this
-/// relation.  We make this explicit because we associate metadata
-/// with these pairs.
-pub struct Binding {
-    label: Option<String>,
-    core: Rc<RefCell<Core>>,
-    binding: node::binding::Client,
-}
-
-impl fmt::Debug for Binding {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Binding {{ label: {:?} }}", self.label)
-    }
-}
-
-impl Binding {
-    fn new(core: Rc<RefCell<Core>>,
-           label: Option<&str>,
-           binding: node::binding::Client) -> Self {
-        Binding{label: label.map(|l| l.into()), core: core, binding: binding}
-    }
-
-    /// Returns stats for this binding.
+    /// Deletes the bindings with the given labels.
+    ///
+    /// All bindings are deleted in a single transaction, which is
+    /// considerably faster than deleting them one by one.  The
+    /// returned vector contains a result for every label, in the same
+    /// order.  If there is no binding with the given label,
+    /// `Error::NotFound` is reported for that label.
     ///
     /// # Example
     ///
@@ -620,116 +1138,143 @@ impl Binding {
     /// #     .ipc_policy(IPCPolicy::Internal)
     /// #     .ephemeral().build()?;
     /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
-    ///
-    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-    /// let binding = store.add("Mister B.", &fp)?;
-    ///
-    /// println!("Binding {:?}", binding.stats()?);
-    /// // prints:
-    /// // Binding Stats {
-    /// //     created: Some(Timespec { tv_sec: 1513704042, tv_nsec: 0 }),
-    /// //     updated: None,
-    /// //     encryption: Stamps { count: 0, first: None, last: None },
-    /// //     verification: Stamps { count: 0, first: None, last: None }
-    /// // }
+    /// store.add("Mister B.", &Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb"))?;
+    /// store.add("Mister C.", &Fingerprint::from_bytes(b"cccccccccccccccccccc"))?;
+    /// let r = store.delete_bindings(&["Mister B.", "Mister X.", "Mister C."])?;
+    /// assert!(r[0].is_ok());
+    /// assert!(r[1].is_err()); // not found
+    /// assert!(r[2].is_ok());
     /// # Ok(())
     /// # }
     /// ```
-    pub fn stats(&self) -> Result<Stats> {
-        make_stats_request!(self.core.borrow_mut(),
-                            self.binding.stats_request())
+    pub fn delete_bindings(&self, labels: &[&str]) -> Result<Vec<Result<()>>> {
+        let mut request = self.store.delete_bindings_request();
+        {
+            let mut list = request.get().init_labels(labels.len() as u32);
+            for (i, label) in labels.iter().enumerate() {
+                list.set(i as u32, label);
+            }
+        }
+        make_request_map!(self.core.borrow_mut(), request,
+                          |deleted: capnp::primitive_list::Reader<bool>| {
+                              Ok(deleted.iter().map(|deleted| if deleted {
+                                  Ok(())
+                              } else {
+                                  Err(Error::NotFound.into())
+                              }).collect())
+                          })
     }
 
-    /// Returns the `Key` of this binding.
-    pub fn key(&self) -> Result<Key> {
-        make_request_map!(self.core.borrow_mut(),
-                          self.binding.key_request(),
-                          |tpk| Ok(Key::new(self.core.clone(), tpk)))
-    }
-
-    /// Returns the `Tpk` of this binding.
+    /// Deletes all bindings in this store.
     ///
-    /// A shortcut for `self.key()?.tpk()`.
-    pub fn tpk(&self) -> Result<TPK> {
-        self.key()?.tpk()
+    /// Unlike `Store::delete`, the store itself is kept, including
+    /// its configuration and log.
+    pub fn clear(&self) -> Result<()> {
+        let request = self.store.clear_request();
+        make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
     }
 
-    /// Updates this binding with the given TPK.
+    /// Runs `f` in a transaction.
     ///
-    /// If the new key `tpk` matches the current key, i.e. they have
-    /// the same fingerprint, both keys are merged and normalized.
-    /// The returned key contains all packets known to Sequoia, and
-    /// should be used instead of `tpk`.
+    /// The changes made using the given `Transaction` are committed
+    /// atomically if `f` returns successfully.  If `f` returns an
+    /// error or panics, the changes are rolled back.
     ///
-    /// If the new key does not match the current key, and it does not
-    /// carry a valid signature from the current key, an
-    /// `Error::Conflict` is returned, and you have to resolve the
-    /// conflict, either by ignoring the new key, or by using
-    /// `Binding::rotate` to force a rotation.
+    /// There can only be one transaction at any time.  If another
+    /// transaction is in progress, `Error::TransactionInProgress` is
+    /// returned.  Likewise, changes not made using the given
+    /// `Transaction` fail with `Error::TransactionInProgress` while
+    /// the transaction is in progress.
     ///
     /// # Example
     ///
     /// ```
     /// # extern crate sequoia_openpgp as openpgp;
-    /// # #[macro_use] extern crate sequoia_core;
+    /// # extern crate sequoia_core;
     /// # extern crate sequoia_store;
     /// # use openpgp::TPK;
     /// # use openpgp::parse::Parse;
     /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
-    /// # use sequoia_store::*;
+    /// # use sequoia_store::{Store, REALM_CONTACTS, Result};
     /// # fn main() { f().unwrap(); }
     /// # fn f() -> Result<()> {
     /// # let ctx = Context::configure()
     /// #     .network_policy(NetworkPolicy::Offline)
     /// #     .ipc_policy(IPCPolicy::Internal)
     /// #     .ephemeral().build()?;
-    /// # let old = TPK::from_bytes(
+    /// # let tpk = TPK::from_bytes(
     /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
-    /// # let new = TPK::from_bytes(
-    /// #     include_bytes!("../../openpgp/tests/data/keys/testy-new.pgp")).unwrap();
     /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
-    /// store.import("Testy McTestface", &old)?;
-    /// // later...
-    /// let binding = store.lookup("Testy McTestface")?;
-    /// let r = binding.import(&new);
-    /// assert!(r.is_err()); // Conflict!
+    /// store.transaction(|tx| {
+    ///     tx.add("Testy McTestface", &tpk.fingerprint())?;
+    ///     tx.import("Testy McTestface", &tpk)?;
+    ///     Ok(())
+    /// })?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn import(&self, tpk: &TPK) -> Result<TPK> {
-        let mut blob = vec![];
-        tpk.serialize(&mut blob)?;
-        let mut request = self.binding.import_request();
-        request.get().set_force(false);
-        request.get().set_key(&blob);
-        make_request_map!(
-            self.core.borrow_mut(),
-            request,
-            |data| TPK::from_bytes(data).map_err(|e| e.into()))
+    pub fn transaction<F, T>(&self, f: F) -> Result<T>
+        where F: FnOnce(&Transaction) -> Result<T>
+    {
+        let request = self.store.begin_request();
+        let tx = make_request!(self.core.borrow_mut(), request)?;
+        let request = tx.store_request();
+        let store = make_request!(self.core.borrow_mut(), request)?;
+        let transaction = Transaction {
+            store: Store::new(self.core.clone(), &self.name,
+                              self.network_policy, store),
+            _store: PhantomData,
+        };
+
+        let result = panic::catch_unwind(
+            panic::AssertUnwindSafe(|| f(&transaction)));
+        match result {
+            Ok(Ok(value)) => {
+                let request = tx.commit_request();
+                make_request_map!(self.core.borrow_mut(), request,
+                                  |_| Ok(()))?;
+                Ok(value)
+            },
+            Ok(Err(e)) => {
+                // Report the original error.
+                let request = tx.rollback_request();
+                let _ = make_request_map!(self.core.borrow_mut(), request,
+                                          |_| Ok(()));
+                Err(e)
+            },
+            Err(payload) => {
+                let request = tx.rollback_request();
+                let _ = make_request_map!(self.core.borrow_mut(), request,
+                                          |_| Ok(()));
+                panic::resume_unwind(payload)
+            },
+        }
     }
 
-    /// Forces a keyrotation to the given TPK.
+    /// Lists all bindings.
     ///
-    /// The current key is replaced with the new key `tpk`, even if
-    /// they do not have the same fingerprint.  If a key with the same
-    /// fingerprint as `tpk` is already in the store, is merged with
-    /// `tpk` and normalized.  The returned key contains all packets
-    /// known to Sequoia, and should be used instead of `tpk`.
+    /// The bindings are ordered by label.  Labels are compared
+    /// byte-wise, and labels that are not valid UTF-8 come last.
+    pub fn iter(&self) -> Result<BindingIter> {
+        let request = self.store.iter_request();
+        let iter = make_request!(self.core.borrow_mut(), request)?;
+        Ok(BindingIter{core: self.core.clone(), iter: iter, error: None,
+                       integrity_checks: self.integrity_checks})
+    }
+
+    /// Lists all bindings whose label starts with `prefix`.
     ///
-    /// Use this function to resolve conflicts returned from
-    /// `Binding::import`.  Make sure that you have authenticated
-    /// `tpk` properly.  How to do that depends on your thread model.
-    /// You could simply ask Alice to call her communication partner
-    /// Bob and confirm that he rotated his keys.
+    /// This supports organizing labels hierarchically, e.g. using
+    /// labels like `work/alice` and `personal/bob`.  The match is
+    /// case-sensitive, and `%` and `_` have no special meaning.
     ///
     /// # Example
     ///
     /// ```
     /// # extern crate sequoia_openpgp as openpgp;
-    /// # #[macro_use] extern crate sequoia_core;
+    /// # extern crate sequoia_core;
     /// # extern crate sequoia_store;
-    /// # use openpgp::TPK;
-    /// # use openpgp::parse::Parse;
+    /// # use openpgp::Fingerprint;
     /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
     /// # use sequoia_store::*;
     /// # fn main() { f().unwrap(); }
@@ -738,40 +1283,80 @@ impl Binding {
     /// #     .network_policy(NetworkPolicy::Offline)
     /// #     .ipc_policy(IPCPolicy::Internal)
     /// #     .ephemeral().build()?;
-    /// # let old = TPK::from_bytes(
-    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
-    /// # let new = TPK::from_bytes(
-    /// #     include_bytes!("../../openpgp/tests/data/keys/testy-new.pgp")).unwrap();
     /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
-    /// store.import("Testy McTestface", &old)?;
-    /// // later...
-    /// let binding = store.lookup("Testy McTestface")?;
-    /// let r = binding.import(&new);
-    /// assert!(r.is_err()); // Conflict!
-    /// let r = binding.rotate(&new)?;
-    /// assert_eq!(new.fingerprint(), r.fingerprint());
+    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// store.add("work/bob", &fp)?;
+    /// store.add("personal/bob", &fp)?;
+    ///
+    /// let labels = store.iter_prefix("work/")?
+    ///     .map(|(label, _, _)| label).collect::<Vec<_>>();
+    /// assert_eq!(labels, vec!["work/bob"]);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn rotate(&self, tpk: &TPK) -> Result<TPK> {
-        let mut blob = vec![];
-        tpk.serialize(&mut blob)?;
-        let mut request = self.binding.import_request();
-        request.get().set_force(true);
-        request.get().set_key(&blob);
-        make_request_map!(
-            self.core.borrow_mut(),
-            request,
-            |data| TPK::from_bytes(data).map_err(|e| e.into()))
+    pub fn iter_prefix(&self, prefix: &str) -> Result<BindingIter> {
+        let mut request = self.store.iter_prefix_request();
+        request.get().set_prefix(prefix);
+        let iter = make_request!(self.core.borrow_mut(), request)?;
+        Ok(BindingIter{core: self.core.clone(), iter: iter, error: None,
+                       integrity_checks: self.integrity_checks})
     }
 
-    /// Deletes this binding.
+    /// Lists all bindings whose key is revoked.
+    ///
+    /// Returns the label and fingerprint of every binding whose
+    /// primary key has been revoked.  The keys are checked by the
+    /// backend.  Currently, only self-revocations of the primary key
+    /// are considered, whatever the reason for revocation is.
+    /// Revocations of subkeys and user ids, and revocations issued
+    /// by designated revokers are not taken into account.
+    pub fn revoked(&self) -> Result<Vec<(String, Fingerprint)>> {
+        let request = self.store.iter_revoked_request();
+        let iter = make_request!(self.core.borrow_mut(), request)?;
+        let mut iter =
+            BindingIter{core: self.core.clone(), iter: iter, error: None,
+                       integrity_checks: self.integrity_checks};
+        let revoked = iter.by_ref()
+            .map(|(label, fingerprint, _)| (label, fingerprint))
+            .collect();
+        iter.finish()?;
+        Ok(revoked)
+    }
+
+    /// Lists all log entries related to this store.
+    pub fn log(&self) -> Result<LogIter> {
+        let request = self.store.log_request();
+        let iter = make_request!(self.core.borrow_mut(), request)?;
+        Ok(LogIter{core: self.core.clone(), iter: iter, error: None})
+    }
+
+    /// Returns the URI of the keyserver used to update this store.
+    ///
+    /// This is the default keyserver of the context that most
+    /// recently opened the store configuring one, see
+    /// `Context::keyserver`.  Returns `None` if no keyserver has been
+    /// recorded, in which case the SKS keyserver pool is used.
+    pub fn keyserver_uri(&self) -> Result<Option<String>> {
+        let request = self.store.keyserver_request();
+        make_request_map!(self.core.borrow_mut(), request,
+                          |uri: &str| Ok(if uri.is_empty() {
+                              None
+                          } else {
+                              Some(uri.to_string())
+                          }))
+    }
+
+    /// Returns the location of the database backing this store.
+    ///
+    /// All stores are kept in a single database maintained by the
+    /// background service.  For ephemeral contexts, this is a
+    /// location in a temporary directory.
     ///
     /// # Example
     ///
     /// ```
     /// # extern crate sequoia_openpgp as openpgp;
-    /// # #[macro_use] extern crate sequoia_core;
+    /// # extern crate sequoia_core;
     /// # extern crate sequoia_store;
     /// # use openpgp::Fingerprint;
     /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
@@ -783,103 +1368,47 @@ impl Binding {
     /// #     .ipc_policy(IPCPolicy::Internal)
     /// #     .ephemeral().build()?;
     /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
-    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-    /// let binding = store.add("Mister B.", &fp)?;
-    /// binding.delete()?;
-    /// let binding = store.lookup("Mister B.");
-    /// assert!(binding.is_err()); // not found
+    /// assert!(store.location()?.starts_with(ctx.home()));
     /// # Ok(())
     /// # }
     /// ```
-    pub fn delete(self) -> Result<()> {
-        let request = self.binding.delete_request();
-        make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
-    }
-
-    fn register_encryption(&self) -> Result<Stats> {
-        #![allow(dead_code)]     // XXX use
-        make_stats_request!(
-            self.core.borrow_mut(),
-            self.binding.register_encryption_request())
-    }
-
-    fn register_verification(&self) -> Result<Stats> {
-        #![allow(dead_code)]     // XXX use
-        make_stats_request!(
-            self.core.borrow_mut(),
-            self.binding.register_verification_request())
-    }
-
-    /// Lists all log entries related to this binding.
-    pub fn log(&self) -> Result<LogIter> {
-        let request = self.binding.log_request();
-        let iter = make_request!(self.core.borrow_mut(), request)?;
-        Ok(LogIter{core: self.core.clone(), iter: iter})
-    }
-
-    /// Gets this binding's label.
-    pub fn label(&self) -> Result<String> {
-        if let Some(ref label) = self.label {
-            return Ok(label.clone());
-        }
-
-        let request = self.binding.label_request();
+    pub fn location(&self) -> Result<PathBuf> {
+        let request = self.store.location_request();
         make_request_map!(self.core.borrow_mut(),
                           request,
-                          |l: &str| Ok(l.into()))
-    }
-}
-
-/// Represents a key in a store.
-///
-/// A `Key` is a handle to a stored TPK.  We make this explicit
-/// because we associate metadata with TPKs.
-pub struct Key {
-    core: Rc<RefCell<Core>>,
-    key: node::key::Client,
-}
-
-impl fmt::Debug for Key {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Key {{ }}")
-    }
-}
-
-impl Key {
-    fn new(core: Rc<RefCell<Core>>, key: node::key::Client) -> Self {
-        Key{core: core, key: key}
-    }
-
-    /// Returns the TPK.
-    pub fn tpk(&self) -> Result<TPK> {
-        make_request_map!(self.core.borrow_mut(),
-                          self.key.tpk_request(),
-                          |tpk| TPK::from_bytes(tpk).map_err(|e| e.into()))
+                          |p: &str| Ok(p.into()))
     }
 
-    /// Returns stats for this key.
-    pub fn stats(&self) -> Result<Stats> {
-        make_stats_request!(self.core.borrow_mut(),
-                            self.key.stats_request())
+    /// Returns the size of the database backing this store in bytes.
+    ///
+    /// As all stores share a database, this is the disk usage of all
+    /// stores, not just this one.
+    pub fn disk_usage(&self) -> Result<u64> {
+        Ok(fs::metadata(self.location()?)?.len())
     }
 
-    /// Updates this stored key with the given TPK.
+    /// Writes every key in this store to `w` as a keyring.
     ///
-    /// If the new key `tpk` matches the current key, i.e. they have
-    /// the same fingerprint, both keys are merged and normalized.
-    /// The returned key contains all packets known to Sequoia, and
-    /// should be used instead of `tpk`.
+    /// The keyring is a concatenation of TPKs, which can be imported
+    /// into GnuPG.  Keys bound to more than one label are written
+    /// once, ordered by fingerprint.  Keys of which only the
+    /// fingerprint is known are skipped.  If `armored` is true, the
+    /// keyring is wrapped in a single ASCII-armor block.
     ///
-    /// If the new key does not match the current key,
-    /// `Error::Conflict` is returned.
+    /// The keys are streamed from the backend one at a time.  If an
+    /// error occurs, the keys written so far are left in `w`.
+    ///
+    /// Note that labels are not preserved in the export.  Use
+    /// `Store::dump` to back up the bindings themselves.
+    ///
+    /// Returns the number of keys written.
     ///
     /// # Example
     ///
     /// ```
     /// # extern crate sequoia_openpgp as openpgp;
-    /// # #[macro_use] extern crate sequoia_core;
+    /// # extern crate sequoia_core;
     /// # extern crate sequoia_store;
-    /// # use openpgp::Fingerprint;
     /// # use openpgp::TPK;
     /// # use openpgp::parse::Parse;
     /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
@@ -890,609 +1419,3985 @@ impl Key {
     /// #     .network_policy(NetworkPolicy::Offline)
     /// #     .ipc_policy(IPCPolicy::Internal)
     /// #     .ephemeral().build()?;
-    /// # let old = TPK::from_bytes(
-    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
-    /// # let new = TPK::from_bytes(
-    /// #     include_bytes!("../../openpgp/tests/data/keys/testy-new.pgp")).unwrap();
+    /// # let tpk = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp"))
+    /// #     .unwrap();
     /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
-    /// let fp = Fingerprint::from_hex("3E8877C877274692975189F5D03F6F865226FE8B").unwrap();
-    /// let binding = store.add("Testy McTestface", &fp)?;
-    /// let key = binding.key()?;
-    /// let r = key.import(&old)?;
-    /// assert_eq!(r.fingerprint(), old.fingerprint());
-    /// let r = key.import(&new);
-    /// assert!(r.is_err()); // conflict
+    /// store.import("Testy McTestface", &tpk)?;
+    /// store.import("Testy", &tpk)?;
+    ///
+    /// let mut keyring = Vec::new();
+    /// assert_eq!(store.export_all(&mut keyring, true)?, 1);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn import(&self, tpk: &TPK) -> Result<TPK> {
-        let mut blob = vec![];
-        tpk.serialize(&mut blob)?;
-        let mut request = self.key.import_request();
-        request.get().set_key(&blob);
-        make_request_map!(
-            self.core.borrow_mut(),
-            request,
-            |data| TPK::from_bytes(data).map_err(|e| e.into()))
-    }
-
-    /// Lists all log entries related to this key.
-    pub fn log(&self) -> Result<LogIter> {
-        let request = self.key.log_request();
+    pub fn export_all(&self, w: &mut Write, armored: bool) -> Result<usize> {
+        let request = self.store.export_all_request();
         let iter = make_request!(self.core.borrow_mut(), request)?;
-        Ok(LogIter{core: self.core.clone(), iter: iter})
-    }
-}
 
-
-/// Returns `t` as Timespec.
-fn from_unix(t: i64) -> Option<Timespec> {
-    if t == 0 {
-        None
-    } else {
-        Some(Timespec::new(t, 0))
+        if armored {
+            let mut w = armor::Writer::new(w, armor::Kind::PublicKey, &[])?;
+            let n = self.write_keys(&iter, &mut w)?;
+            w.finalize()?;
+            Ok(n)
+        } else {
+            self.write_keys(&iter, w)
+        }
     }
-}
 
-/// Statistics about bindings and stored keys.
-///
-/// We collect some data about binginds and stored keys.  This
-/// information can be used to make informed decisions about key
-/// transitions.
-#[derive(Debug)]
-pub struct Stats {
-    /// Records the time this item was created.
-    pub created: Option<Timespec>,
+    /// Writes the keys returned by `iter` to `w`.
+    ///
+    /// Returns the number of keys written.
+    fn write_keys(&self, iter: &node::export_iter::Client, w: &mut Write)
+                  -> Result<usize> {
+        let mut n = 0;
+        let mut error = None;
+        loop {
+            let request = iter.next_request();
+            let key = make_request_map!(self.core.borrow_mut(), request,
+                                        |key: &[u8]| Ok(key.to_vec()));
+            match end_iteration(key, &mut error) {
+                Some(key) => {
+                    w.write_all(&key)?;
+                    n += 1;
+                },
+                None => break,
+            }
+        }
+        error.map(Err).unwrap_or(Ok(n))
+    }
 
-    /// Records the time this item was last updated.
-    pub updated: Option<Timespec>,
+    /// Writes all bindings of this store to a portable archive.
+    ///
+    /// Unlike `export_all`, the archive preserves the labels and
+    /// stats of the bindings, as well as bindings for which only the
+    /// fingerprint is known.  Use `Store::restore` to recreate the
+    /// store from the archive, e.g. on a different machine.
+    ///
+    /// Returns the number of bindings written.  Fails with
+    /// `Error::MalformedArchive` if a key is too large to be
+    /// archived, as `Store::restore` would reject it.
+    pub fn dump(&self, w: &mut Write) -> Result<usize> {
+        archive::write_header(w)?;
+        let mut n = 0;
+        let mut iter = self.iter()?;
+        for (_, fingerprint, binding) in &mut iter {
+            archive::write_record(w, &archive::Record {
+                label: binding.label_bytes()?,
+                fingerprint: fingerprint.to_hex(),
+                tpk: binding.key()?.tpk_bytes()?,
+                stats: binding.stats()?,
+            })?;
+            n += 1;
+        }
+        iter.finish()?;
+        archive::write_trailer(w)?;
+        Ok(n)
+    }
 
-    /// Records counters and timestamps of encryptions.
-    pub encryption: Stamps,
+    /// Recreates a store from an archive written by `Store::dump`.
+    ///
+    /// The bindings are added to the store with the given name,
+    /// which is opened (or created) using the network policy of `c`,
+    /// like `Store::open` does.  The network policy the archived
+    /// store was using is not taken into account.
+    ///
+    /// The whole archive is read and checked before the store is
+    /// opened, so a malformed archive does not create a store.  The
+    /// bindings are restored in a single transaction: if a binding
+    /// conflicts with an existing one, the store is left unchanged.
+    pub fn restore(c: &Context, realm: &str, name: &str, r: &mut Read)
+                   -> Result<Self> {
+        archive::read_header(r)?;
+
+        let mut records = Vec::new();
+        while let Some(record) = archive::read_record(r)? {
+            let fingerprint = Fingerprint::from_hex(&record.fingerprint)
+                .map_err(|_| Error::MalformedArchive)?;
+            let tpk = if record.tpk.is_empty() {
+                None
+            } else {
+                Some(TPK::from_bytes(&record.tpk)?)
+            };
+            records.push((record, fingerprint, tpk));
+        }
 
-    /// Records counters and timestamps of verifications.
-    pub verification: Stamps,
+        let store = Self::open(c, realm, name)?;
+        store.transaction(|tx| {
+            // The binding is bound to the transaction, so changing it
+            // is part of the transaction as well.
+            for (record, fingerprint, tpk) in records {
+                let binding = tx.add_bytes(&record.label, &fingerprint)?;
+                if let Some(tpk) = tpk {
+                    binding.import(&tpk)?;
+                }
+                binding.restore_stats(&record.stats)?;
+            }
+            Ok(())
+        })?;
+        Ok(store)
+    }
 }
 
-/// Represents a log entry.
-#[derive(Debug)]
-pub struct Log {
-    /// Records the time of the entry.
-    pub timestamp: Timespec,
+/// Makes a stats request and parses the result.
+macro_rules! make_stats_request {
+    ( $core: expr, $request: expr ) => {{
+        make_request_map!(
+            $core, $request,
+            |s: node::stats::Reader| Ok(Stats{
+                created: from_unix(s.get_created()),
+                updated: from_unix(s.get_updated()),
+                encryption: Stamps::new(
+                    s.get_encryption_count(),
+                    from_unix(s.get_encryption_first()),
+                    from_unix(s.get_encryption_last())),
+                verification: Stamps::new(
+                    s.get_verification_count(),
+                    from_unix(s.get_verification_first()),
+                    from_unix(s.get_verification_last())),
+                next_update: from_unix(s.get_next_update())
+                    .map(|t| UNIX_EPOCH
+                         + ::std::time::Duration::from_secs(t.sec as u64)),
+            }))
+    }}
+}
 
-    /// Relates the entry to a store.
-    pub store: Option<Store>,
+/// A store that reconnects to the background service.
+///
+/// See [`Store::open_resilient`].
+///
+///   [`Store::open_resilient`]: struct.Store.html#method.open_resilient
+pub struct ResilientStore {
+    ctx: Context,
+    realm: String,
+    name: String,
+    store: RefCell<Store>,
+}
 
-    /// Relates the entry to a binding.
-    pub binding: Option<Binding>,
+impl fmt::Debug for ResilientStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ResilientStore {{ name: {} }}", self.name)
+    }
+}
 
-    /// Relates the entry to a key.
-    pub key: Option<Key>,
+/// Calls `f`, and calls it again if it fails with
+/// `Error::Disconnected`.
+///
+/// Before retrying, `reconnect` is invoked.
+fn retry_disconnected<T, F, R>(f: F, reconnect: R) -> Result<T>
+    where F: Fn() -> Result<T>, R: FnOnce() -> Result<()>
+{
+    match f() {
+        Err(e) => match e.downcast::<Error>() {
+            Ok(Error::Disconnected) => {
+                reconnect()?;
+                f()
+            },
+            Ok(e) => Err(e.into()),
+            Err(e) => Err(e),
+        },
+        r => r,
+    }
+}
 
-    /// Relates the entry to some object.
+impl ResilientStore {
+    /// Calls `f` with the store, reconnecting once if necessary.
     ///
-    /// This is a human-readable description of what this log entry is
-    /// mainly concerned with.
-    pub slug: String,
+    /// This can be used to invoke operations of `Store` not directly
+    /// exposed by `ResilientStore`.
+    pub fn with<T, F>(&self, f: F) -> Result<T>
+        where F: Fn(&Store) -> Result<T>
+    {
+        retry_disconnected(|| f(&self.store.borrow()), || {
+            let store = Store::open(&self.ctx, &self.realm, &self.name)?;
+            *self.store.borrow_mut() = store;
+            Ok(())
+        })
+    }
 
-    /// Holds the result of the operation.
+    /// Adds a key identified by fingerprint to the store.
     ///
-    /// This is either `Ok(Message)`, or `Err((Message, Error))`.
-    pub status: ::std::result::Result<String, (String, String)>,
-}
-
-impl Log {
-    fn new(timestamp: i64,
-           store: Option<Store>, binding: Option<Binding>, key: Option<Key>,
-           slug: &str, message: &str, error: Option<&str>)
-           -> Option<Self> {
-        let timestamp = from_unix(timestamp)?;
-
-        Some(Log{
-            timestamp: timestamp,
-            store: store,
-            binding: binding,
-            key: key,
-            slug: slug.into(),
-            status: if let Some(error) = error {
-                Err((message.into(), error.into()))
-            } else {
-                Ok(message.into())
-            },
-        })
+    /// See [`Store::add`](struct.Store.html#method.add).
+    pub fn add(&self, label: &str, fingerprint: &Fingerprint)
+               -> Result<Binding> {
+        self.with(|s| s.add(label, fingerprint))
     }
 
-    /// Returns the message without context.
-    pub fn short(&self) -> String {
-        match self.status {
-            Ok(ref m) => m.clone(),
-            Err((ref m, ref e)) => format!("{}: {}", m, e),
-        }
+    /// Imports a key into the store.
+    ///
+    /// See [`Store::import`](struct.Store.html#method.import).
+    pub fn import(&self, label: &str, tpk: &TPK) -> Result<TPK> {
+        self.with(|s| s.import(label, tpk))
     }
 
-    /// Returns the message with some context.
-    pub fn string(&self) -> String {
-        match self.status {
-            Ok(ref m) => format!("{}: {}", self.slug, m),
-            Err((ref m, ref e)) => format!("{}: {}: {}", self.slug, m, e),
-        }
+    /// Returns the binding for the given label.
+    ///
+    /// See [`Store::lookup`](struct.Store.html#method.lookup).
+    pub fn lookup(&self, label: &str) -> Result<Binding> {
+        self.with(|s| s.lookup(label))
     }
 
-    /// Returns the message with timestamp and context.
-    pub fn full(&self) -> String {
-        let timestamp =
-            time::strftime("%F %H:%M", &time::at(self.timestamp))
-            .unwrap(); // Only parse errors can happen.
+    /// Lists all bindings.
+    ///
+    /// See [`Store::iter`](struct.Store.html#method.iter).
+    pub fn iter(&self) -> Result<BindingIter> {
+        self.with(|s| s.iter())
+    }
 
-        match self.status {
-            Ok(ref m) => format!(
-                "{}: {}: {}", timestamp, self.slug, m),
-            Err((ref m, ref e)) => format!(
-                "{}: {}: {}: {}", timestamp, self.slug, m, e),
-        }
+    /// Lists all log entries related to this store.
+    ///
+    /// See [`Store::log`](struct.Store.html#method.log).
+    pub fn log(&self) -> Result<LogIter> {
+        self.with(|s| s.log())
     }
 }
 
-/// Counter and timestamps.
-#[derive(Debug)]
-pub struct Stamps {
-    /// Counts how many times this has been used.
-    pub count: usize,
+/// A request executed by the thread owning a shared store.
+type SharedStoreRequest = Box<FnOnce(&Store) + Send>;
 
-    /// Records the time when this has been used first.
-    pub first:  Option<Timespec>,
+/// A store that can be shared across threads.
+///
+/// `Store` is neither `Send` nor `Sync`, because the RPC machinery
+/// is tied to the thread that created it.  A `SharedStore` opens the
+/// store on a dedicated thread, which owns the store and drives the
+/// reactor.  Calls are forwarded to this thread over a channel and
+/// executed one at a time, in the order they arrive.  From the
+/// caller's perspective, calls block until they are done.
+///
+/// As `Binding`s and `Key`s are tied to the thread owning the store,
+/// `SharedStore` returns plain data like TPKs instead.  Use
+/// `SharedStore::with` to run arbitrary code on the store's thread.
+///
+/// Dropping a `SharedStore` stops the thread after all pending calls
+/// have been processed.
+///
+/// See [`Store::open_shared`].
+///
+///   [`Store::open_shared`]: struct.Store.html#method.open_shared
+///
+/// # Example
+///
+/// ```
+/// # extern crate sequoia_openpgp as openpgp;
+/// # extern crate sequoia_core;
+/// # extern crate sequoia_store;
+/// # use std::sync::Arc;
+/// # use std::thread;
+/// # use openpgp::Fingerprint;
+/// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+/// # use sequoia_store::*;
+/// # fn main() { f().unwrap(); }
+/// # fn f() -> Result<()> {
+/// # let ctx = Context::configure()
+/// #     .network_policy(NetworkPolicy::Offline)
+/// #     .ipc_policy(IPCPolicy::Internal)
+/// #     .ephemeral().build()?;
+/// let store = Arc::new(Store::open_shared(&ctx, REALM_CONTACTS, "default")?);
+///
+/// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+/// let s = store.clone();
+/// thread::spawn(move || s.add("Mister B.", &fp)).join().unwrap()?;
+/// assert_eq!(store.list()?.len(), 1);
+/// # Ok(())
+/// # }
+/// ```
+pub struct SharedStore {
+    name: String,
+    sender: Mutex<Option<mpsc::Sender<SharedStoreRequest>>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
 
-    /// Records the time when this has been used last.
-    pub last: Option<Timespec>,
+impl fmt::Debug for SharedStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SharedStore {{ name: {} }}", self.name)
+    }
 }
 
-impl Stamps {
-    fn new(count: i64, first: Option<Timespec>, last: Option<Timespec>) -> Self {
-        Stamps {
-            count: count as usize,
-            first: first,
-            last: last,
+impl Drop for SharedStore {
+    fn drop(&mut self) {
+        // Closing the channel stops the thread.
+        self.sender.lock().map(|mut s| s.take()).ok();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
         }
     }
 }
 
-/* Iterators.  */
-
-/// Iterates over stores.
-pub struct StoreIter {
-    core: Rc<RefCell<Core>>,
-    iter: node::store_iter::Client,
-}
+impl SharedStore {
+    /// Calls `f` with the store on the store's thread.
+    ///
+    /// This can be used to invoke operations of `Store` not directly
+    /// exposed by `SharedStore`.  If the store's thread is gone,
+    /// e.g. because a previous call panicked, `Error::StoreError` is
+    /// returned.
+    pub fn with<T, F>(&self, f: F) -> Result<T>
+        where F: FnOnce(&Store) -> Result<T> + Send + 'static,
+              T: Send + 'static
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.sender.lock().map_err(|_| Error::StoreError)?
+            .as_ref().ok_or(Error::StoreError)?
+            .send(Box::new(move |store: &Store| {
+                let _ = sender.send(f(store));
+            }))
+            .map_err(|_| Error::StoreError)?;
+        receiver.recv().map_err(|_| Error::StoreError)?
+    }
 
-impl Iterator for StoreIter {
-    type Item = (String, String, core::NetworkPolicy, Store);
+    /// Adds a key identified by fingerprint to the store.
+    ///
+    /// See [`Store::add`](struct.Store.html#method.add).
+    pub fn add(&self, label: &str, fingerprint: &Fingerprint) -> Result<()> {
+        let label = label.to_string();
+        let fingerprint = fingerprint.clone();
+        self.with(move |s| s.add(&label, &fingerprint).map(|_| ()))
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let request = self.iter.next_request();
-        let doit = || {
-            make_request_map!(
-                self.core.borrow_mut(), request,
-                |r: node::store_iter::item::Reader|
-                Ok((
-                    r.get_realm()?.into(),
-                    r.get_name()?.into(),
-                    r.get_network_policy()?.into(),
-                    Store::new(self.core.clone(), r.get_name()?, r.get_store()?))))
-        };
-        doit().ok()
+    /// Imports a key into the store.
+    ///
+    /// See [`Store::import`](struct.Store.html#method.import).
+    pub fn import(&self, label: &str, tpk: &TPK) -> Result<TPK> {
+        let label = label.to_string();
+        let tpk = tpk.clone();
+        self.with(move |s| s.import(&label, &tpk))
     }
-}
 
-/// Iterates over bindings in a store.
-pub struct BindingIter {
-    core: Rc<RefCell<Core>>,
-    iter: node::binding_iter::Client,
-}
+    /// Returns the TPK bound to the given label.
+    ///
+    /// See [`Store::lookup`](struct.Store.html#method.lookup).
+    pub fn lookup(&self, label: &str) -> Result<TPK> {
+        let label = label.to_string();
+        self.with(move |s| s.lookup(&label)?.tpk())
+    }
 
-impl Iterator for BindingIter {
-    type Item = (String, openpgp::Fingerprint, Binding);
+    /// Deletes the binding with the given label.
+    pub fn delete(&self, label: &str) -> Result<()> {
+        let label = label.to_string();
+        self.with(move |s| s.lookup(&label)?.delete())
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let request = self.iter.next_request();
-        let doit = || {
-            make_request_map!(
-                self.core.borrow_mut(), request,
-                |r: node::binding_iter::item::Reader|
-                Ok((String::from(r.get_label()?),
-                    openpgp::Fingerprint::from_hex(r.get_fingerprint()?).unwrap(),
-                    Binding::new(self.core.clone(), Some(r.get_label()?),
-                                 r.get_binding()?))))
-        };
-        doit().ok()
+    /// Lists the labels and fingerprints of all bindings.
+    ///
+    /// See [`Store::iter`](struct.Store.html#method.iter).
+    pub fn list(&self) -> Result<Vec<(String, Fingerprint)>> {
+        self.with(|s| {
+            let mut iter = s.iter()?;
+            let list = iter.by_ref().map(|(label, fp, _)| (label, fp)).collect();
+            iter.finish()?;
+            Ok(list)
+        })
     }
 }
 
-/// Iterates over keys in the common key pool.
-pub struct KeyIter {
-    core: Rc<RefCell<Core>>,
-    iter: node::key_iter::Client,
+/// Changes a store within a transaction.
+///
+/// See [`Store::transaction`].
+///
+///   [`Store::transaction`]: struct.Store.html#method.transaction
+pub struct Transaction<'a> {
+    /// The store as seen from within the transaction.
+    store: Store,
+    _store: PhantomData<&'a Store>,
 }
 
-impl Iterator for KeyIter {
-    type Item = (openpgp::Fingerprint, Key);
+impl<'a> Transaction<'a> {
+    /// Adds a key identified by fingerprint to the store.
+    ///
+    /// See [`Store::add`](struct.Store.html#method.add).
+    pub fn add(&self, label: &str, fingerprint: &Fingerprint)
+               -> Result<Binding> {
+        self.store.add(label, fingerprint)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let request = self.iter.next_request();
-        let doit = || {
-            make_request_map!(
-                self.core.borrow_mut(), request,
-                |r: node::key_iter::item::Reader|
-                Ok((openpgp::Fingerprint::from_hex(r.get_fingerprint()?).unwrap(),
-                    Key::new(self.core.clone(), r.get_key()?))))
-        };
-        doit().ok()
+    /// Adds a key identified by fingerprint to the store, using a
+    /// label that need not be valid UTF-8.
+    ///
+    /// See [`Store::add_bytes`](struct.Store.html#method.add_bytes).
+    pub fn add_bytes(&self, label: &[u8], fingerprint: &Fingerprint)
+                     -> Result<Binding> {
+        self.store.add_bytes(label, fingerprint)
+    }
+
+    /// Imports a key into the store.
+    ///
+    /// See [`Store::import`](struct.Store.html#method.import).
+    pub fn import(&self, label: &str, tpk: &TPK) -> Result<TPK> {
+        self.store.import(label, tpk)
+    }
+
+    /// Deletes the binding with the given label.
+    pub fn delete(&self, label: &str) -> Result<()> {
+        self.store.lookup(label)?.delete()
     }
 }
 
-/// Iterates over logs.
-pub struct LogIter {
+/// Represents an entry in a Store.
+///
+/// Stores map labels to TPKs.  A `Binding` represents a pair in this
+/// relation.  We make this explicit because we associate metadata
+/// with these pairs.
+pub struct Binding {
+    label: Option<Vec<u8>>,
     core: Rc<RefCell<Core>>,
-    iter: node::log_iter::Client,
+    binding: node::binding::Client,
+    key: RefCell<Option<node::key::Client>>,
+    fingerprint: RefCell<Option<Fingerprint>>,
+    integrity_checks: bool,
 }
 
-impl Iterator for LogIter {
-    type Item = Log;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let request = self.iter.next_request();
-        let doit = || {
-            make_request_map!(
-                self.core.borrow_mut(), request,
-                |r: node::log_iter::entry::Reader|
-                Log::new(r.get_timestamp(),
-                         r.get_store().ok().map(
-                             |cap| Store::new(self.core.clone(), &"", cap)),
-                         r.get_binding().ok().map(
-                             |cap| Binding::new(self.core.clone(), None, cap)),
-                         r.get_key().ok().map(
-                             |cap| Key::new(self.core.clone(), cap)),
-                         r.get_slug()?,
-                         r.get_message()?,
-                         if r.has_error() {
-                             r.get_error().ok()
-                         } else {
-                             None
-                         }).ok_or(Error::StoreError.into()))
-        };
-        doit().ok()
+impl fmt::Debug for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // If we do not know the fingerprint yet, ask the backend.
+        // This is best-effort, Debug must not fail.
+        let fingerprint = self.fingerprint.borrow().clone()
+            .map(Ok)
+            .unwrap_or_else(|| self.fingerprint())
+            .map(|fp| fp.to_hex_abbreviated())
+            .unwrap_or_else(|_| "<unavailable>".into());
+        write!(f, "Binding {{ label: {:?}, fingerprint: {} }}",
+               self.label.as_ref().map(|l| String::from_utf8_lossy(l)),
+               fingerprint)
     }
 }
 
-/* Error handling.  */
-
-/// Results for sequoia-store.
-pub type Result<T> = ::std::result::Result<T, failure::Error>;
-
+impl Binding {
+    fn new(core: Rc<RefCell<Core>>,
+           label: Option<&[u8]>,
+           fingerprint: Option<Fingerprint>,
+           binding: node::binding::Client,
+           integrity_checks: bool) -> Self {
+        Binding{label: label.map(|l| l.to_vec()), core: core, binding: binding,
+                key: RefCell::new(None),
+                fingerprint: RefCell::new(fingerprint),
+                integrity_checks: integrity_checks}
+    }
 
-// Converts from backend errors.
-impl From<node::Error> for failure::Error {
-    fn from(error: node::Error) -> Self {
-        match error {
-            node::Error::Unspecified => Error::StoreError.into(),
-            node::Error::NotFound => Error::NotFound.into(),
-            node::Error::Conflict => Error::Conflict.into(),
-            node::Error::SystemError => Error::StoreError.into(),
-            node::Error::MalformedTPK => Error::MalformedTPK.into(),
-            node::Error::MalformedFingerprint =>
-                Error::MalformedFingerprint.into(),
-            node::Error::NetworkPolicyViolationOffline =>
-                core::Error::NetworkPolicyViolation(core::NetworkPolicy::Offline).into(),
-            node::Error::NetworkPolicyViolationAnonymized =>
-                core::Error::NetworkPolicyViolation(core::NetworkPolicy::Anonymized).into(),
-            node::Error::NetworkPolicyViolationEncrypted =>
-                core::Error::NetworkPolicyViolation(core::NetworkPolicy::Encrypted).into(),
+    /// Returns stats for this binding.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    ///
+    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// let binding = store.add("Mister B.", &fp)?;
+    ///
+    /// println!("Binding {:?}", binding.stats()?);
+    /// // prints:
+    /// // Binding Stats {
+    /// //     created: Some(Timespec { tv_sec: 1513704042, tv_nsec: 0 }),
+    /// //     updated: None,
+    /// //     encryption: Stamps { count: 0, first: None, last: None },
+    /// //     verification: Stamps { count: 0, first: None, last: None },
+    /// //     next_update: Some(SystemTime { tv_sec: 1513704042, tv_nsec: 0 })
+    /// // }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stats(&self) -> Result<Stats> {
+        make_stats_request!(self.core.borrow_mut(),
+                            self.binding.stats_request())
+    }
+
+    /// Returns how long ago this binding was created.
+    ///
+    /// The age is computed relative to `now`.  Returns `None` if the
+    /// creation time is unknown.
+    pub fn age(&self, now: Timespec) -> Result<Option<Duration>> {
+        Ok(self.stats()?.created.map(|t| now - t))
+    }
+
+    /// Returns how long ago this binding was last updated.
+    ///
+    /// The age is computed relative to `now`.  Returns `None` if the
+    /// binding has never been updated.
+    pub fn last_update_age(&self, now: Timespec) -> Result<Option<Duration>> {
+        Ok(self.stats()?.updated.map(|t| now - t))
+    }
+
+    /// Sets how often the key of this binding is updated.
+    ///
+    /// By default, the background service refreshes all keys once
+    /// within a store-wide interval.  This overrides the interval for
+    /// this binding, e.g. to poll high-value keys more frequently.
+    /// If several bindings refer to the same key, the shortest
+    /// interval wins.  A zero or negative interval restores the
+    /// default.  Intervals longer than a year are clamped to a year.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # extern crate time;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// let binding = store.add("Mister B.", &fp)?;
+    /// assert_eq!(binding.update_interval()?, None);
+    ///
+    /// binding.set_update_interval(time::Duration::hours(1))?;
+    /// assert_eq!(binding.update_interval()?, Some(time::Duration::hours(1)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_update_interval(&self, d: Duration) -> Result<()> {
+        let mut request = self.binding.set_update_interval_request();
+        request.get().set_seconds(d.num_seconds());
+        make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
+    }
+
+    /// Returns how often the key of this binding is updated.
+    ///
+    /// Returns `None` if the default interval is used.  See
+    /// [`Binding::set_update_interval`].
+    ///
+    ///   [`Binding::set_update_interval`]: #method.set_update_interval
+    pub fn update_interval(&self) -> Result<Option<Duration>> {
+        let request = self.binding.update_interval_request();
+        make_request_map!(self.core.borrow_mut(), request,
+                          |r: node::update_interval::Reader| {
+                              let seconds = r.get_seconds();
+                              Ok(if seconds > 0 {
+                                  Some(Duration::seconds(seconds))
+                              } else {
+                                  None
+                              })
+                          })
+    }
+
+    /// Returns the `Key` of this binding.
+    ///
+    /// The `Key` handle is cached for the lifetime of this `Binding`,
+    /// so that repeated calls (e.g. via `Binding::tpk`) do not incur
+    /// additional round trips.  Note that the cached handle refers to
+    /// the key the binding pointed to when it was first retrieved.
+    /// If the binding is rotated to a different key using another
+    /// `Binding` object or by a different process, the cache becomes
+    /// stale.  Use `Binding::refresh` to clear it.
+    pub fn key(&self) -> Result<Key> {
+        if let Some(ref key) = *self.key.borrow() {
+            return Ok(Key::new(self.core.clone(),
+                               self.fingerprint.borrow().clone(),
+                               key.clone(), self.integrity_checks));
+        }
+
+        let key = make_request!(self.core.borrow_mut(),
+                                self.binding.key_request())?;
+        *self.key.borrow_mut() = Some(key.clone());
+        Ok(Key::new(self.core.clone(), self.fingerprint.borrow().clone(),
+                    key, self.integrity_checks))
+    }
+
+    /// Clears the cached `Key` handle.
+    ///
+    /// The next call to `Binding::key` fetches the key the binding
+    /// currently points to from the store.  See `Binding::key` for
+    /// details.
+    pub fn refresh(&self) {
+        *self.key.borrow_mut() = None;
+        *self.fingerprint.borrow_mut() = None;
+    }
+
+    /// Returns the `Tpk` of this binding.
+    ///
+    /// A shortcut for `self.key()?.tpk()`.
+    pub fn tpk(&self) -> Result<TPK> {
+        self.key()?.tpk()
+    }
+
+    /// Updates this binding with the given TPK.
+    ///
+    /// If the new key `tpk` matches the current key, i.e. they have
+    /// the same fingerprint, both keys are merged and normalized.
+    /// The returned key contains all packets known to Sequoia, and
+    /// should be used instead of `tpk`.
+    ///
+    /// If the new key does not match the current key, and it does not
+    /// carry a valid signature from the current key, an
+    /// `Error::Conflict` is returned, and you have to resolve the
+    /// conflict, either by ignoring the new key, or by using
+    /// `Binding::rotate` to force a rotation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # #[macro_use] extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::TPK;
+    /// # use openpgp::parse::Parse;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// # let old = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
+    /// # let new = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy-new.pgp")).unwrap();
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// store.import("Testy McTestface", &old)?;
+    /// // later...
+    /// let binding = store.lookup("Testy McTestface")?;
+    /// let r = binding.import(&new);
+    /// assert!(r.is_err()); // Conflict!
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import(&self, tpk: &TPK) -> Result<TPK> {
+        let mut blob = vec![];
+        tpk.serialize(&mut blob)?;
+        let mut request = self.binding.import_request();
+        request.get().set_force(false);
+        request.get().set_key(&blob);
+        make_request_map!(
+            self.core.borrow_mut(),
+            request,
+            |data| TPK::from_bytes(data).map_err(|e| e.into()))
+    }
+
+    /// Updates this binding with the given TPK if it is newer.
+    ///
+    /// Like `Binding::import`, but the key is only merged if `tpk`
+    /// contains packets that the stored key lacks.  If `tpk` is a
+    /// subset of the stored key, e.g., because a keyserver served an
+    /// old copy of the key, the store is not modified, and `None` is
+    /// returned.  Otherwise, the merged key is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::TPK;
+    /// # use openpgp::parse::Parse;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// # let tpk = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// store.import("Testy McTestface", &tpk)?;
+    /// // later...
+    /// let binding = store.lookup("Testy McTestface")?;
+    /// assert!(binding.import_if_newer(&tpk)?.is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import_if_newer(&self, tpk: &TPK) -> Result<Option<TPK>> {
+        let mut blob = vec![];
+        tpk.serialize(&mut blob)?;
+        let mut request = self.binding.import_if_newer_request();
+        request.get().set_key(&blob);
+        make_request_map!(
+            self.core.borrow_mut(),
+            request,
+            |data: &[u8]| if data.is_empty() {
+                Ok(None)
+            } else {
+                TPK::from_bytes(data).map(Some).map_err(|e| e.into())
+            })
+    }
+
+    /// Updates this binding with the given TPK, checking that it is
+    /// the bound key.
+    ///
+    /// Like `Binding::import`, but before merging, the fingerprint of
+    /// `tpk` is compared to the fingerprint of the key the binding
+    /// currently refers to.  If they differ,
+    /// `Error::FingerprintMismatch` is returned and the store is not
+    /// modified.  The backend does the comparison as part of the
+    /// import, so a concurrent rebinding cannot slip in between.
+    ///
+    /// Use this function if `tpk` is supposed to be an update of the
+    /// bound key, and a different key indicates a mistake on the
+    /// caller's side rather than a key rotation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # #[macro_use] extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::TPK;
+    /// # use openpgp::parse::Parse;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// # let old = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
+    /// # let new = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy-new.pgp")).unwrap();
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// store.import("Testy McTestface", &old)?;
+    /// // later...
+    /// let binding = store.lookup("Testy McTestface")?;
+    /// binding.import_strict(&old)?;
+    /// match binding.import_strict(&new).unwrap_err().downcast::<Error>() {
+    ///     Ok(Error::FingerprintMismatch { .. }) => (),
+    ///     e => panic!("Expected Error::FingerprintMismatch, got {:?}.", e),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import_strict(&self, tpk: &TPK) -> Result<TPK> {
+        let mut blob = vec![];
+        tpk.serialize(&mut blob)?;
+        let mut request = self.binding.import_request();
+        request.get().set_force(false);
+        request.get().set_strict(true);
+        request.get().set_key(&blob);
+        make_request_map!(
+            self.core.borrow_mut(),
+            request,
+            |data| TPK::from_bytes(data).map_err(|e| e.into()))
+            .map_err(|e| match e.downcast::<Error>() {
+                Ok(Error::Conflict(info)) => Error::FingerprintMismatch {
+                    expected: info.existing,
+                    found: info.incoming,
+                }.into(),
+                Ok(e) => e.into(),
+                Err(e) => e,
+            })
+    }
+
+    /// Updates this binding with the given TPK, reporting changes.
+    ///
+    /// Like `Binding::import`, but in addition to the merged key,
+    /// the returned `KeyDiff` lists the user IDs, subkeys, and
+    /// signatures that were not part of the previously stored key.
+    /// If the binding only knew the fingerprint of the key, every
+    /// component of the merged key is reported as new.
+    pub fn import_diff(&self, tpk: &TPK) -> Result<KeyDiff> {
+        let mut blob = vec![];
+        tpk.serialize(&mut blob)?;
+        let mut request = self.binding.import_diff_request();
+        request.get().set_key(&blob);
+        make_request_map!(
+            self.core.borrow_mut(),
+            request,
+            |r: node::key_diff::Reader| {
+                let mut userids = Vec::new();
+                for u in r.get_userids()?.iter() {
+                    userids.push(UserID::from(u?));
+                }
+                let mut subkeys = Vec::new();
+                for fp in r.get_subkeys()?.iter() {
+                    subkeys.push(parse_fingerprint(fp?)?);
+                }
+                let mut signatures = Vec::new();
+                for sig in r.get_signatures()?.iter() {
+                    signatures.push(Signature::from_bytes(sig?)?);
+                }
+                Ok(KeyDiff {
+                    tpk: TPK::from_bytes(r.get_key()?)?,
+                    userids: userids,
+                    subkeys: subkeys,
+                    signatures: signatures,
+                })
+            })
+    }
+
+    /// Updates this binding with the given TPK, summarizing changes.
+    ///
+    /// Like `Binding::import`, but in addition to the merged key,
+    /// returns a `MergeReport` describing what the merge absorbed,
+    /// e.g. to tell the user that a key gained a new encryption
+    /// subkey.  The report is derived from the `KeyDiff` returned by
+    /// `Binding::import_diff`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::TPK;
+    /// # use openpgp::parse::Parse;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// # let old = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/bannon-base.gpg"))
+    /// #     .unwrap();
+    /// # let new = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/bannon-add-subkey-1.gpg"))
+    /// #     .unwrap();
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let binding = store.add("Steve", &old.fingerprint())?;
+    /// binding.import(&old)?;
+    ///
+    /// let (_, report) = binding.import_detailed(&new)?;
+    /// assert_eq!(report.subkeys.len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import_detailed(&self, tpk: &TPK) -> Result<(TPK, MergeReport)> {
+        let diff = self.import_diff(tpk)?;
+        let report = MergeReport::from(&diff);
+        Ok((diff.tpk, report))
+    }
+
+    /// Forces a keyrotation to the given TPK.
+    ///
+    /// The current key is replaced with the new key `tpk`, even if
+    /// they do not have the same fingerprint.  If a key with the same
+    /// fingerprint as `tpk` is already in the store, is merged with
+    /// `tpk` and normalized.  The returned key contains all packets
+    /// known to Sequoia, and should be used instead of `tpk`.
+    ///
+    /// Use this function to resolve conflicts returned from
+    /// `Binding::import`.  Make sure that you have authenticated
+    /// `tpk` properly.  How to do that depends on your thread model.
+    /// You could simply ask Alice to call her communication partner
+    /// Bob and confirm that he rotated his keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # #[macro_use] extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::TPK;
+    /// # use openpgp::parse::Parse;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// # let old = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
+    /// # let new = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy-new.pgp")).unwrap();
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// store.import("Testy McTestface", &old)?;
+    /// // later...
+    /// let binding = store.lookup("Testy McTestface")?;
+    /// let r = binding.import(&new);
+    /// assert!(r.is_err()); // Conflict!
+    /// let r = binding.rotate(&new)?;
+    /// assert_eq!(new.fingerprint(), r.fingerprint());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rotate(&self, tpk: &TPK) -> Result<TPK> {
+        let mut blob = vec![];
+        tpk.serialize(&mut blob)?;
+        let mut request = self.binding.import_request();
+        request.get().set_force(true);
+        request.get().set_key(&blob);
+        self.refresh();
+        make_request_map!(
+            self.core.borrow_mut(),
+            request,
+            |data| TPK::from_bytes(data).map_err(|e| e.into()))
+    }
+
+    /// Deletes this binding.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # #[macro_use] extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// let binding = store.add("Mister B.", &fp)?;
+    /// binding.delete()?;
+    /// let binding = store.lookup("Mister B.");
+    /// assert!(binding.is_err()); // not found
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn delete(self) -> Result<()> {
+        let request = self.binding.delete_request();
+        make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
+    }
+
+    /// Records that the key has been used.
+    ///
+    /// This updates the `Stamps` of the given kind.  Use this if the
+    /// key is used outside of Sequoia, e.g., if it is exported to
+    /// another tool, so that the usage is reflected in the
+    /// statistics.  Returns the updated statistics.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// let binding = store.add("Mister B.", &fp)?;
+    /// let stats = binding.touch(UseKind::Encryption)?;
+    /// assert_eq!(stats.encryption.count, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn touch(&self, kind: UseKind) -> Result<Stats> {
+        match kind {
+            UseKind::Encryption => self.register_encryption(),
+            UseKind::Verification => self.register_verification(),
+        }
+    }
+
+    fn register_encryption(&self) -> Result<Stats> {
+        make_stats_request!(
+            self.core.borrow_mut(),
+            self.binding.register_encryption_request())
+    }
+
+    fn register_verification(&self) -> Result<Stats> {
+        make_stats_request!(
+            self.core.borrow_mut(),
+            self.binding.register_verification_request())
+    }
+
+    /// Overwrites the stats of this binding.
+    ///
+    /// This is used to restore archived bindings.
+    fn restore_stats(&self, stats: &Stats) -> Result<Stats> {
+        let mut request = self.binding.restore_stats_request();
+        {
+            let mut s = request.get().init_stats();
+            s.set_created(archive::to_unix(stats.created));
+            s.set_updated(archive::to_unix(stats.updated));
+            s.set_encryption_count(stats.encryption.count as i64);
+            s.set_encryption_first(archive::to_unix(stats.encryption.first));
+            s.set_encryption_last(archive::to_unix(stats.encryption.last));
+            s.set_verification_count(stats.verification.count as i64);
+            s.set_verification_first(
+                archive::to_unix(stats.verification.first));
+            s.set_verification_last(
+                archive::to_unix(stats.verification.last));
+        }
+        make_stats_request!(self.core.borrow_mut(), request)
+    }
+
+    /// Lists all log entries related to this binding.
+    pub fn log(&self) -> Result<LogIter> {
+        let request = self.binding.log_request();
+        let iter = make_request!(self.core.borrow_mut(), request)?;
+        Ok(LogIter{core: self.core.clone(), iter: iter, error: None})
+    }
+
+    /// Gets this binding's label.
+    ///
+    /// If the label is not valid UTF-8, invalid sequences are
+    /// replaced.  Use `Binding::label_bytes` to get the label
+    /// verbatim.
+    pub fn label(&self) -> Result<String> {
+        if let Some(ref label) = self.label {
+            return Ok(String::from_utf8_lossy(label).into_owned());
+        }
+
+        let request = self.binding.label_request();
+        make_request_map!(self.core.borrow_mut(),
+                          request,
+                          |l: &str| Ok(l.into()))
+    }
+
+    /// Returns the fingerprint of the key this binding refers to.
+    ///
+    /// Unlike `Key::summary`, this also works if only the
+    /// fingerprint of the key is known.  The fingerprint is always
+    /// fetched from the store, so it reflects rotations done using
+    /// other `Binding` objects.
+    pub fn fingerprint(&self) -> Result<Fingerprint> {
+        let request = self.binding.fingerprint_request();
+        make_request_map!(self.core.borrow_mut(),
+                          request,
+                          |fp: &str| parse_fingerprint(fp))
+    }
+
+    /// Gets this binding's label as bytes.
+    pub fn label_bytes(&self) -> Result<Vec<u8>> {
+        if let Some(ref label) = self.label {
+            return Ok(label.clone());
+        }
+
+        let request = self.binding.label_bytes_request();
+        make_request_map!(self.core.borrow_mut(),
+                          request,
+                          |l: &[u8]| Ok(l.to_vec()))
+    }
+}
+
+/// Represents a key in a store.
+///
+/// A `Key` is a handle to a stored TPK.  We make this explicit
+/// because we associate metadata with TPKs.
+pub struct Key {
+    core: Rc<RefCell<Core>>,
+    key: node::key::Client,
+    fingerprint: Option<Fingerprint>,
+    integrity_checks: bool,
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // If we do not know the fingerprint yet, ask the backend.
+        // This is best-effort, Debug must not fail.
+        let fingerprint = self.fingerprint()
+            .map(|fp| fp.to_hex_abbreviated())
+            .unwrap_or_else(|_| "<unavailable>".into());
+        write!(f, "Key {{ fingerprint: {} }}", fingerprint)
+    }
+}
+
+impl Key {
+    fn new(core: Rc<RefCell<Core>>, fingerprint: Option<Fingerprint>,
+           key: node::key::Client, integrity_checks: bool) -> Self {
+        Key{core: core, key: key, fingerprint: fingerprint,
+            integrity_checks: integrity_checks}
+    }
+
+    /// Returns the fingerprint of this key.
+    ///
+    /// If the fingerprint is not known locally, e.g. because the
+    /// `Key` was obtained by looking up a KeyID, we ask the backend.
+    /// This also works if the backend only knows the fingerprint.
+    fn fingerprint(&self) -> Result<Fingerprint> {
+        if let Some(ref fp) = self.fingerprint {
+            return Ok(fp.clone());
+        }
+        self.stored_fingerprint()
+    }
+
+    /// Returns the fingerprint recorded by the backend.
+    ///
+    /// Unlike the fingerprint in the `KeySummary`, this is not
+    /// computed from the stored TPK.
+    fn stored_fingerprint(&self) -> Result<Fingerprint> {
+        make_request_map!(self.core.borrow_mut(),
+                          self.key.fingerprint_request(),
+                          |fp: &str| parse_fingerprint(fp))
+    }
+
+    /// Returns the TPK.
+    ///
+    /// Unless disabled using `OpenOptions::integrity_checks`, the
+    /// fingerprint of the TPK is compared to the one recorded by the
+    /// backend.  This detects corruption of, or tampering with the
+    /// database.  On mismatch, `Error::IntegrityFailure` is
+    /// returned.
+    pub fn tpk(&self) -> Result<TPK> {
+        let tpk = make_request_map!(
+            self.core.borrow_mut(),
+            self.key.tpk_request(),
+            |tpk| TPK::from_bytes(tpk).map_err(|e| e.into()))?;
+
+        if self.integrity_checks {
+            let expected = self.stored_fingerprint()?;
+            if tpk.fingerprint() != expected {
+                return Err(Error::IntegrityFailure {
+                    expected: expected,
+                    found: tpk.fingerprint(),
+                }.into());
+            }
+        }
+
+        Ok(tpk)
+    }
+
+    /// Returns the serialized TPK.
+    ///
+    /// If only the fingerprint of the key is known, the result is
+    /// empty.
+    fn tpk_bytes(&self) -> Result<Vec<u8>> {
+        make_request_map!(self.core.borrow_mut(),
+                          self.key.tpk_request(),
+                          |tpk: &[u8]| Ok(tpk.to_vec()))
+    }
+
+    /// Returns stats for this key.
+    pub fn stats(&self) -> Result<Stats> {
+        make_stats_request!(self.core.borrow_mut(),
+                            self.key.stats_request())
+    }
+
+    /// Summarizes this key's primary key.
+    ///
+    /// The summary is computed by the backend, so the key does not
+    /// need to be transferred.  If only the fingerprint of the key is
+    /// known, `Error::NotFound` is returned.
+    pub fn summary(&self) -> Result<KeySummary> {
+        make_request_map!(self.core.borrow_mut(),
+                          self.key.summary_request(),
+                          |r: node::key_summary::Reader|
+                          Ok(KeySummary {
+                              fingerprint:
+                                  parse_fingerprint(r.get_fingerprint()?)?,
+                              algo: r.get_algo().into(),
+                              bits: match r.get_bits() {
+                                  0 => None,
+                                  n => Some(n as usize),
+                              },
+                              created: Timespec::new(r.get_created(), 0),
+                              expires: from_unix(r.get_expires()),
+                              revoked: r.get_revoked(),
+                          }))
+    }
+
+    /// Returns the capabilities of the primary key and the subkeys.
+    ///
+    /// The first entry describes the primary key, followed by one
+    /// entry per subkey.  The capabilities are taken from the key
+    /// flags of the newest binding signatures, and are computed by
+    /// the backend, so the key does not need to be transferred.
+    /// This can be used to select a subkey for a given purpose.  If
+    /// only the fingerprint of the key is known, `Error::NotFound`
+    /// is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::TPK;
+    /// # use openpgp::parse::Parse;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// # let tpk = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// store.import("Testy McTestface", &tpk)?;
+    /// let key = store.lookup("Testy McTestface")?.key()?;
+    /// let encryption_subkey = key.capabilities()?.into_iter()
+    ///     .find(|c| c.flags.can_encrypt_for_transport() && ! c.revoked)
+    ///     .map(|c| c.fingerprint);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn capabilities(&self) -> Result<Vec<SubkeyCapabilities>> {
+        make_request_map!(
+            self.core.borrow_mut(),
+            self.key.capabilities_request(),
+            |list: capnp::struct_list::Reader<node::key_capabilities::Owned>| {
+                let mut capabilities = Vec::new();
+                for c in list.iter() {
+                    capabilities.push(SubkeyCapabilities {
+                        fingerprint:
+                            parse_fingerprint(c.get_fingerprint()?)?,
+                        flags: KeyFlags::default()
+                            .set_certify(c.get_certify())
+                            .set_sign(c.get_sign())
+                            .set_encrypt_for_transport(
+                                c.get_encrypt_for_transport())
+                            .set_encrypt_at_rest(c.get_encrypt_at_rest())
+                            .set_authenticate(c.get_authenticate()),
+                        revoked: c.get_revoked(),
+                    });
+                }
+                Ok(capabilities)
+            })
+    }
+
+    /// Returns a TPK containing only the given subkey.
+    ///
+    /// The returned TPK contains the primary key and its direct
+    /// self-signatures and self-revocations, and the subkey together
+    /// with its binding signatures and self-revocations.  All user
+    /// ids, user attributes, other subkeys, and third-party
+    /// certifications are stripped.  This is useful to hand out a
+    /// single subkey, e.g. for delegating decryption.
+    ///
+    /// If `subkey_fp` is not the fingerprint of one of the key's
+    /// subkeys, `Error::NotFound` is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::{Fingerprint, TPK};
+    /// # use openpgp::parse::Parse;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// # let tpk = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// store.import("Testy McTestface", &tpk)?;
+    /// let key = store.lookup("Testy McTestface")?.key()?;
+    /// let subkey = Fingerprint::from_hex(
+    ///     "01F187575BD45644046564C149E2118166C92632")?;
+    /// let stripped = key.export_subkey(&subkey)?;
+    /// assert_eq!(stripped.fingerprint(), tpk.fingerprint());
+    /// assert_eq!(stripped.userids().count(), 0);
+    /// assert_eq!(stripped.subkeys().count(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn export_subkey(&self, subkey_fp: &Fingerprint) -> Result<TPK> {
+        use openpgp::{Packet, PacketPile};
+
+        let tpk = self.tpk()?;
+        let skb = tpk.subkeys()
+            .find(|skb| skb.subkey().fingerprint() == *subkey_fp)
+            .ok_or(Error::NotFound)?;
+
+        let mut packets = vec![Packet::PublicKey(tpk.primary().clone())];
+        packets.extend(tpk.selfsigs().iter().chain(tpk.self_revocations())
+                       .cloned().map(Packet::Signature));
+        packets.push(Packet::PublicSubkey(skb.subkey().clone()));
+        packets.extend(skb.selfsigs().iter().chain(skb.self_revocations())
+                       .cloned().map(Packet::Signature));
+
+        TPK::from_packet_pile(PacketPile::from(packets))
+    }
+
+    /// Updates this stored key with the given TPK.
+    ///
+    /// If the new key `tpk` matches the current key, i.e. they have
+    /// the same fingerprint, both keys are merged and normalized.
+    /// The returned key contains all packets known to Sequoia, and
+    /// should be used instead of `tpk`.
+    ///
+    /// If the new key does not match the current key,
+    /// `Error::Conflict` is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # #[macro_use] extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use openpgp::TPK;
+    /// # use openpgp::parse::Parse;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// # let old = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
+    /// # let new = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy-new.pgp")).unwrap();
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let fp = Fingerprint::from_hex("3E8877C877274692975189F5D03F6F865226FE8B").unwrap();
+    /// let binding = store.add("Testy McTestface", &fp)?;
+    /// let key = binding.key()?;
+    /// let r = key.import(&old)?;
+    /// assert_eq!(r.fingerprint(), old.fingerprint());
+    /// let r = key.import(&new);
+    /// assert!(r.is_err()); // conflict
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import(&self, tpk: &TPK) -> Result<TPK> {
+        let mut blob = vec![];
+        tpk.serialize(&mut blob)?;
+        let mut request = self.key.import_request();
+        request.get().set_key(&blob);
+        make_request_map!(
+            self.core.borrow_mut(),
+            request,
+            |data| TPK::from_bytes(data).map_err(|e| e.into()))
+    }
+
+    /// Lists all log entries related to this key.
+    pub fn log(&self) -> Result<LogIter> {
+        let request = self.key.log_request();
+        let iter = make_request!(self.core.borrow_mut(), request)?;
+        Ok(LogIter{core: self.core.clone(), iter: iter, error: None})
+    }
+}
+
+
+/// Returns `t` as Timespec.
+fn from_unix(t: i64) -> Option<Timespec> {
+    if t == 0 {
+        None
+    } else {
+        Some(Timespec::new(t, 0))
+    }
+}
+
+/// Statistics about bindings and stored keys.
+///
+/// We collect some data about binginds and stored keys.  This
+/// information can be used to make informed decisions about key
+/// transitions.
+#[derive(Debug)]
+pub struct Stats {
+    /// Records the time this item was created.
+    pub created: Option<Timespec>,
+
+    /// Records the time this item was last updated.
+    pub updated: Option<Timespec>,
+
+    /// Records counters and timestamps of encryptions.
+    pub encryption: Stamps,
+
+    /// Records counters and timestamps of verifications.
+    pub verification: Stamps,
+
+    /// Records when the key is due to be updated next.
+    ///
+    /// This is only an estimate, updates may be delayed, e.g. if
+    /// the network policy forbids them.  See
+    /// [`Binding::set_update_interval`].
+    ///
+    ///   [`Binding::set_update_interval`]: struct.Binding.html#method.set_update_interval
+    pub next_update: Option<SystemTime>,
+}
+
+/// Kinds of key usage tracked in `Stats`.
+///
+/// See [`Binding::touch`].
+///
+///   [`Binding::touch`]: struct.Binding.html#method.touch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UseKind {
+    /// The key was used to encrypt a message.
+    Encryption,
+
+    /// The key was used to verify a signature.
+    Verification,
+}
+
+/// Summarizes the compaction of the common key pool.
+///
+/// See [`Store::compact_keys`].
+///
+///   [`Store::compact_keys`]: struct.Store.html#method.compact_keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactReport {
+    /// Number of duplicate keys merged into another key.
+    pub keys_merged: u64,
+
+    /// Number of bindings that now refer to a different key.
+    pub bindings_rewired: u64,
+
+    /// Number of bytes returned to the file system.
+    pub bytes_reclaimed: u64,
+}
+
+/// Summarizes a key in the common key pool.
+///
+/// See [`Key::summary`].
+///
+///   [`Key::summary`]: struct.Key.html#method.summary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySummary {
+    /// The fingerprint of the key.
+    pub fingerprint: Fingerprint,
+
+    /// The public key algorithm of the primary key.
+    pub algo: PublicKeyAlgorithm,
+
+    /// The size of the primary key in bits, if known.
+    pub bits: Option<usize>,
+
+    /// The creation time of the primary key.
+    pub created: Timespec,
+
+    /// The expiration time of the primary key, if any.
+    pub expires: Option<Timespec>,
+
+    /// Whether the primary key has been revoked.
+    pub revoked: bool,
+}
+
+/// Describes what a primary key or subkey may be used for.
+///
+/// See [`Key::capabilities`].
+///
+///   [`Key::capabilities`]: struct.Key.html#method.capabilities
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubkeyCapabilities {
+    /// The fingerprint of the primary key or subkey.
+    pub fingerprint: Fingerprint,
+
+    /// The key flags of the newest binding signature.
+    ///
+    /// If there is no binding signature, or it does not contain key
+    /// flags, no flags are set.
+    pub flags: KeyFlags,
+
+    /// Whether the key has been revoked.
+    pub revoked: bool,
+}
+
+/// Describes what an import added to a key.
+///
+/// See [`Binding::import_diff`].
+///
+///   [`Binding::import_diff`]: struct.Binding.html#method.import_diff
+#[derive(Debug, Clone)]
+pub struct KeyDiff {
+    /// The merged key.
+    pub tpk: TPK,
+
+    /// User IDs that were not part of the stored key.
+    pub userids: Vec<UserID>,
+
+    /// Fingerprints of subkeys that were not part of the stored key.
+    pub subkeys: Vec<Fingerprint>,
+
+    /// Signatures that were not part of the stored key.
+    ///
+    /// This includes the binding signatures of new user IDs and
+    /// subkeys.
+    pub signatures: Vec<Signature>,
+}
+
+impl KeyDiff {
+    /// Returns whether the import added anything at all.
+    pub fn is_empty(&self) -> bool {
+        self.userids.is_empty() && self.subkeys.is_empty()
+            && self.signatures.is_empty()
+    }
+}
+
+/// Summarizes what a merge added to a key.
+///
+/// Unlike `KeyDiff`, this only counts the new signatures.
+///
+/// See [`Binding::import_detailed`].
+///
+///   [`Binding::import_detailed`]: struct.Binding.html#method.import_detailed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeReport {
+    /// User IDs that were not part of the stored key.
+    pub userids: Vec<UserID>,
+
+    /// Fingerprints of subkeys that were not part of the stored key.
+    pub subkeys: Vec<Fingerprint>,
+
+    /// Number of new signatures, not counting revocations.
+    ///
+    /// This includes the binding signatures of new user IDs and
+    /// subkeys.
+    pub signatures: usize,
+
+    /// Number of new revocation signatures.
+    pub revocations: usize,
+}
+
+impl MergeReport {
+    /// Returns whether the merge added anything at all.
+    pub fn is_empty(&self) -> bool {
+        self.userids.is_empty() && self.subkeys.is_empty()
+            && self.signatures == 0 && self.revocations == 0
+    }
+}
+
+impl<'a> From<&'a KeyDiff> for MergeReport {
+    fn from(diff: &'a KeyDiff) -> Self {
+        let revocations = diff.signatures.iter()
+            .filter(|sig| match sig.sigtype() {
+                SignatureType::KeyRevocation
+                    | SignatureType::SubkeyRevocation
+                    | SignatureType::CertificateRevocation => true,
+                _ => false,
+            })
+            .count();
+
+        MergeReport {
+            userids: diff.userids.clone(),
+            subkeys: diff.subkeys.clone(),
+            signatures: diff.signatures.len() - revocations,
+            revocations: revocations,
+        }
+    }
+}
+
+/// Represents a log entry.
+#[derive(Debug)]
+pub struct Log {
+    /// Records the time of the entry.
+    pub timestamp: Timespec,
+
+    /// Relates the entry to a store.
+    pub store: Option<Store>,
+
+    /// Relates the entry to a binding.
+    pub binding: Option<Binding>,
+
+    /// Relates the entry to a key.
+    pub key: Option<Key>,
+
+    /// Relates the entry to some object.
+    ///
+    /// This is a human-readable description of what this log entry is
+    /// mainly concerned with.
+    pub slug: String,
+
+    /// Holds the result of the operation.
+    ///
+    /// This is either `Ok(Message)`, or `Err((Message, Error))`.
+    pub status: ::std::result::Result<String, (String, String)>,
+
+    /// Records where the data came from.
+    ///
+    /// For key updates, this is the URL of the keyserver the key was
+    /// retrieved from.
+    pub source: Option<String>,
+}
+
+impl Log {
+    fn new(timestamp: i64,
+           store: Option<Store>, binding: Option<Binding>, key: Option<Key>,
+           slug: &str, message: &str, error: Option<&str>,
+           source: Option<&str>)
+           -> Option<Self> {
+        let timestamp = from_unix(timestamp)?;
+
+        Some(Log{
+            timestamp: timestamp,
+            store: store,
+            binding: binding,
+            key: key,
+            slug: slug.into(),
+            status: if let Some(error) = error {
+                Err((message.into(), error.into()))
+            } else {
+                Ok(message.into())
+            },
+            source: source.map(|s| s.into()),
+        })
+    }
+
+    /// Returns the message without context.
+    pub fn short(&self) -> String {
+        match self.status {
+            Ok(ref m) => m.clone(),
+            Err((ref m, ref e)) => format!("{}: {}", m, e),
+        }
+    }
+
+    /// Returns the message with some context.
+    pub fn string(&self) -> String {
+        match self.status {
+            Ok(ref m) => format!("{}: {}", self.slug, m),
+            Err((ref m, ref e)) => format!("{}: {}: {}", self.slug, m, e),
+        }
+    }
+
+    /// Returns the message with timestamp and context.
+    pub fn full(&self) -> String {
+        let timestamp =
+            time::strftime("%F %H:%M", &time::at(self.timestamp))
+            .unwrap(); // Only parse errors can happen.
+
+        match self.status {
+            Ok(ref m) => format!(
+                "{}: {}: {}", timestamp, self.slug, m),
+            Err((ref m, ref e)) => format!(
+                "{}: {}: {}: {}", timestamp, self.slug, m, e),
+        }
+    }
+}
+
+/// Counter and timestamps.
+#[derive(Debug)]
+pub struct Stamps {
+    /// Counts how many times this has been used.
+    pub count: usize,
+
+    /// Records the time when this has been used first.
+    pub first:  Option<Timespec>,
+
+    /// Records the time when this has been used last.
+    pub last: Option<Timespec>,
+}
+
+impl Stamps {
+    fn new(count: i64, first: Option<Timespec>, last: Option<Timespec>) -> Self {
+        Stamps {
+            count: count as usize,
+            first: first,
+            last: last,
+        }
+    }
+
+    /// Returns how long ago this has been used last.
+    ///
+    /// The time is computed relative to `now`.  Returns `None` if
+    /// this has never been used.
+    pub fn since_last(&self, now: Timespec) -> Option<Duration> {
+        self.last.map(|t| now - t)
+    }
+
+    /// Returns the average time between two uses.
+    ///
+    /// Returns `None` if this has been used less than twice.
+    pub fn frequency(&self) -> Option<Duration> {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) if self.count > 1 =>
+                Some((last - first) / (self.count as i32 - 1)),
+            _ => None,
+        }
+    }
+}
+
+/* Iterators.  */
+
+/// Implements the error reporting common to all iterators.
+///
+/// The iterator must have a field `error: Option<failure::Error>`,
+/// which is set using `end_iteration`.
+macro_rules! impl_iter_error {
+    ( $iter: ident ) => {
+        impl $iter {
+            /// Returns the error that ended the iteration, if any.
+            ///
+            /// The iterator returns `None` both if all items have
+            /// been returned and if an error occurred, e.g. because
+            /// the connection to the backend was lost.  Check this
+            /// after the iteration ended to tell the two apart.
+            pub fn last_error(&self) -> Option<&failure::Error> {
+                self.error.as_ref()
+            }
+
+            /// Fails if the iteration ended prematurely.
+            ///
+            /// Like `last_error`, but takes the error so that it can
+            /// be propagated.
+            pub fn finish(&mut self) -> Result<()> {
+                self.error.take().map(Err).unwrap_or(Ok(()))
+            }
+        }
+    }
+}
+
+/// Iterates over stores.
+pub struct StoreIter {
+    core: Rc<RefCell<Core>>,
+    iter: node::store_iter::Client,
+    error: Option<failure::Error>,
+}
+
+impl_iter_error!(StoreIter);
+
+impl Iterator for StoreIter {
+    type Item = (String, String, core::NetworkPolicy, Store);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        let request = self.iter.next_request();
+        let doit = || {
+            make_request_map!(
+                self.core.borrow_mut(), request,
+                |r: node::store_iter::item::Reader| {
+                    let policy = r.get_network_policy()?.into();
+                    Ok((
+                        r.get_realm()?.into(),
+                        r.get_name()?.into(),
+                        policy,
+                        Store::new(self.core.clone(), r.get_name()?, policy,
+                                   r.get_store()?)))
+                })
+        };
+        end_iteration(doit(), &mut self.error)
+    }
+}
+
+/// Iterates over bindings in a store.
+///
+/// The bindings are ordered by label, see [`Store::iter`].
+///
+///   [`Store::iter`]: struct.Store.html#method.iter
+pub struct BindingIter {
+    core: Rc<RefCell<Core>>,
+    iter: node::binding_iter::Client,
+    error: Option<failure::Error>,
+    integrity_checks: bool,
+}
+
+impl_iter_error!(BindingIter);
+
+impl Iterator for BindingIter {
+    type Item = (String, openpgp::Fingerprint, Binding);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        let request = self.iter.next_request();
+        let doit = || {
+            make_request_map!(
+                self.core.borrow_mut(), request,
+                |r: node::binding_iter::item::Reader|
+                {
+                    let fp = parse_fingerprint(r.get_fingerprint()?)?;
+                    Ok((String::from(r.get_label()?),
+                        fp.clone(),
+                        Binding::new(self.core.clone(),
+                                     Some(r.get_label_bytes()?),
+                                     Some(fp), r.get_binding()?,
+                                     self.integrity_checks)))
+                })
+        };
+        end_iteration(doit(), &mut self.error)
+    }
+}
+
+/// Iterates over keys in the common key pool.
+///
+/// The keys are ordered by fingerprint.
+pub struct KeyIter {
+    core: Rc<RefCell<Core>>,
+    iter: node::key_iter::Client,
+    error: Option<failure::Error>,
+}
+
+impl_iter_error!(KeyIter);
+
+impl Iterator for KeyIter {
+    type Item = (openpgp::Fingerprint, Key);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        let request = self.iter.next_request();
+        let doit = || {
+            make_request_map!(
+                self.core.borrow_mut(), request,
+                |r: node::key_iter::item::Reader|
+                {
+                    let fp = parse_fingerprint(r.get_fingerprint()?)?;
+                    Ok((fp.clone(),
+                        Key::new(self.core.clone(), Some(fp), r.get_key()?,
+                                 true)))
+                })
+        };
+        end_iteration(doit(), &mut self.error)
+    }
+}
+
+/// Iterates over logs.
+pub struct LogIter {
+    core: Rc<RefCell<Core>>,
+    iter: node::log_iter::Client,
+    error: Option<failure::Error>,
+}
+
+impl_iter_error!(LogIter);
+
+impl Iterator for LogIter {
+    type Item = Log;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        let request = self.iter.next_request();
+        let doit = || {
+            make_request_map!(
+                self.core.borrow_mut(), request,
+                |r: node::log_iter::entry::Reader|
+                Log::new(r.get_timestamp(),
+                         r.get_store().ok().map(
+                             // The policy is unknown, assume the
+                             // strictest one.
+                             |cap| Store::new(self.core.clone(), &"",
+                                              core::NetworkPolicy::Offline,
+                                              cap)),
+                         r.get_binding().ok().map(
+                             |cap| Binding::new(self.core.clone(), None, None, cap,
+                                                true)),
+                         r.get_key().ok().map(
+                             |cap| Key::new(self.core.clone(), None, cap, true)),
+                         r.get_slug()?,
+                         r.get_message()?,
+                         if r.has_error() {
+                             r.get_error().ok()
+                         } else {
+                             None
+                         },
+                         if r.has_source() {
+                             r.get_source().ok()
+                         } else {
+                             None
+                         }).ok_or(Error::StoreError.into()))
+        };
+        end_iteration(doit(), &mut self.error)
+    }
+}
+
+/// Ends the iteration if `result` is an error.
+///
+/// The backend signals the end of an iteration using
+/// `Error::NotFound`.  Any other error is remembered in `error`.
+fn end_iteration<T>(result: Result<T>, error: &mut Option<failure::Error>)
+                    -> Option<T> {
+    match result {
+        Ok(item) => Some(item),
+        Err(e) => {
+            match e.downcast_ref::<Error>() {
+                Some(&Error::NotFound) => (),
+                _ => *error = Some(e),
+            }
+            None
+        },
+    }
+}
+
+/* Error handling.  */
+
+/// Results for sequoia-store.
+pub type Result<T> = ::std::result::Result<T, failure::Error>;
+
+
+// Converts from backend errors.
+impl From<node::Error> for failure::Error {
+    fn from(error: node::Error) -> Self {
+        match error {
+            node::Error::Unspecified => Error::StoreError.into(),
+            node::Error::NotFound => Error::NotFound.into(),
+            // Conflicts are reported using the conflict variant of
+            // the result, which carries the details.
+            node::Error::Conflict => Error::ProtocolError.into(),
+            node::Error::SystemError => Error::StoreError.into(),
+            node::Error::MalformedTPK => Error::MalformedTPK.into(),
+            // Fingerprints are checked before they are sent, see
+            // `check_fingerprint`.
+            node::Error::MalformedFingerprint => Error::ProtocolError.into(),
+            node::Error::TransactionInProgress =>
+                Error::TransactionInProgress.into(),
+            node::Error::NetworkPolicyViolationOffline =>
+                core::Error::NetworkPolicyViolation(core::NetworkPolicy::Offline).into(),
+            node::Error::NetworkPolicyViolationAnonymized =>
+                core::Error::NetworkPolicyViolation(core::NetworkPolicy::Anonymized).into(),
+            node::Error::NetworkPolicyViolationEncrypted =>
+                core::Error::NetworkPolicyViolation(core::NetworkPolicy::Encrypted).into(),
             node::Error::NetworkPolicyViolationInsecure =>
                 core::Error::NetworkPolicyViolation(core::NetworkPolicy::Insecure).into(),
         }
     }
-}
+}
+
+#[derive(Fail, Debug)]
+/// Errors returned from the store.
+pub enum Error {
+    /// A requested key was not found.
+    #[fail(display = "Key not found")]
+    NotFound,
+    /// The new key is in conflict with the current key.
+    #[fail(display = "New key conflicts with the current key")]
+    Conflict(ConflictInfo),
+    /// Some of the new bindings conflict with existing ones.
+    ///
+    /// Returned by `Store::add_many`.  Contains the label and the
+    /// details for every conflicting binding.
+    #[fail(display = "New bindings conflict with existing ones")]
+    Conflicts(Vec<(String, ConflictInfo)>),
+    /// The key does not match the key of the binding.
+    ///
+    /// Unlike `Error::Conflict`, which is returned by
+    /// `Binding::import` after trying to reconcile the two keys, this
+    /// is returned by `Binding::import_strict` if the fingerprints
+    /// differ.
+    #[fail(display = "Fingerprint mismatch: expected {}, found {}",
+           expected, found)]
+    FingerprintMismatch {
+        /// Fingerprint of the key the binding refers to.
+        expected: Fingerprint,
+        /// Fingerprint of the given key.
+        found: Fingerprint,
+    },
+    /// This is a catch-all for unspecified backend errors, and should
+    /// go away soon.
+    #[fail(display = "Unspecified store error")]
+    StoreError,
+    /// A protocol error occurred.
+    #[fail(display = "Unspecified protocol error")]
+    ProtocolError,
+    /// A TPK is malformed.
+    #[fail(display = "Malformed TPK")]
+    MalformedTPK,
+    /// A fingerprint is malformed.
+    ///
+    /// Contains the offending fingerprint in hexadecimal form.
+    #[fail(display = "Malformed fingerprint: {}", _0)]
+    MalformedFingerprint(String),
+    /// A store archive is malformed.
+    #[fail(display = "Malformed store archive")]
+    MalformedArchive,
+    /// Another transaction is in progress.
+    #[fail(display = "Another transaction is in progress")]
+    TransactionInProgress,
+    /// The database was created by an incompatible version.
+    ///
+    /// This happens if a database created by a newer version of this
+    /// library is opened.
+    #[fail(display = "Incompatible database version {} (supported: {})",
+           found, supported)]
+    IncompatibleVersion {
+        /// The schema version of the database.
+        found: i64,
+        /// The newest schema version supported.
+        supported: i64,
+    },
+    /// A stored key does not match its recorded fingerprint.
+    ///
+    /// This indicates that the database has been corrupted or
+    /// tampered with.
+    #[fail(display = "Integrity check failed: expected key {}, found {}",
+           expected, found)]
+    IntegrityFailure {
+        /// The fingerprint recorded for the key.
+        expected: Fingerprint,
+        /// The fingerprint of the stored key.
+        found: Fingerprint,
+    },
+    /// There is no background service at the given rendezvous point.
+    #[fail(display = "No key store service running at {:?}", _0)]
+    ServiceNotRunning(PathBuf),
+    /// The connection to the backend was lost.
+    ///
+    /// This happens if the key store service died.  Opening the
+    /// store again reconnects to, or restarts, the service.
+    #[fail(display = "Disconnected from the key store service")]
+    Disconnected,
+    /// A keyserver returned a key other than the requested one.
+    #[fail(display = "Expected key {}, but the server returned {}",
+           expected, found)]
+    UnexpectedKey {
+        /// The requested KeyID.
+        expected: KeyID,
+        /// The fingerprint of the returned key.
+        found: Fingerprint,
+    },
+    /// A `capnp::Error` occurred.
+    #[fail(display = "Internal RPC error")]
+    RpcError(capnp::Error),
+    /// An `openpgp::Error` occurred.
+    #[fail(display = "{}", _0)]
+    OpenPGPError(openpgp::Error),
+}
+
+/// Details about a conflict between two keys.
+///
+/// Returned with `Error::Conflict` if a key is imported into a
+/// binding that is bound to a different key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictInfo {
+    /// Fingerprint of the key the binding currently refers to.
+    pub existing: Fingerprint,
+    /// Fingerprint of the conflicting key.
+    pub incoming: Fingerprint,
+    /// Whether the incoming key carries a certification issued by
+    /// the existing key.
+    pub certified: bool,
+}
+
+/// Parses a fingerprint reported by the backend.
+fn parse_fingerprint(fp: &str) -> Result<Fingerprint> {
+    Fingerprint::from_hex(fp)
+        .map_err(|_| Error::MalformedFingerprint(fp.into()).into())
+}
+
+/// Checks a fingerprint before sending it to the backend.
+///
+/// The backend only accepts version 4 fingerprints.
+fn check_fingerprint(fp: &Fingerprint) -> Result<()> {
+    match fp {
+        &Fingerprint::V4(_) => Ok(()),
+        _ => Err(Error::MalformedFingerprint(fp.to_hex()).into()),
+    }
+}
+
+/// Decodes a conflict reported by the backend.
+fn conflict_info(c: node::conflict_info::Reader) -> Result<ConflictInfo> {
+    (|| -> Result<ConflictInfo> {
+        Ok(ConflictInfo {
+            existing: Fingerprint::from_hex(c.get_existing()?)?,
+            incoming: Fingerprint::from_hex(c.get_incoming()?)?,
+            certified: c.get_certified(),
+        })
+    })().map_err(|_| Error::ProtocolError.into())
+}
+
+/// Decodes a conflict reported by the backend.
+fn conflict_error(c: node::conflict_info::Reader) -> failure::Error {
+    match conflict_info(c) {
+        Ok(info) => Error::Conflict(info).into(),
+        Err(e) => e,
+    }
+}
+
+fn incompatible_version_error(v: node::version_info::Reader) -> failure::Error {
+    Error::IncompatibleVersion {
+        found: v.get_found(),
+        supported: v.get_supported(),
+    }.into()
+}
+
+impl From<capnp::Error> for Error {
+    fn from(error: capnp::Error) -> Self {
+        match error.kind {
+            capnp::ErrorKind::Disconnected => Error::Disconnected,
+            _ => Error::RpcError(error),
+        }
+    }
+}
+
+impl From<capnp::NotInSchema> for Error {
+    fn from(_: capnp::NotInSchema) -> Self {
+        Error::ProtocolError
+    }
+}
+
+impl From<openpgp::Error> for Error {
+    fn from(error: openpgp::Error) -> Self {
+        match error {
+            openpgp::Error::MalformedTPK(_) => Error::MalformedTPK,
+            e => Error::OpenPGPError(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use openpgp::parse::Parse;
+
+    macro_rules! bytes {
+        ( $x:expr ) => { include_bytes!(concat!("../../openpgp/tests/data/keys/", $x)) };
+    }
+
+    #[test]
+    fn store_network_policy_mismatch() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        // Create store.
+        Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        let ctx2 = core::Context::configure()
+            .home(ctx.home())
+            .network_policy(core::NetworkPolicy::Encrypted)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx2, REALM_CONTACTS, "default");
+        assert_match!(core::Error::NetworkPolicyViolation(_)
+                      = store.err().unwrap().downcast::<core::Error>().unwrap());
+    }
+
+    #[test]
+    fn store_policy_of() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        // Create store.
+        Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        let ctx2 = core::Context::configure()
+            .home(ctx.home())
+            .network_policy(core::NetworkPolicy::Encrypted)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        assert_eq!(Store::policy_of(&ctx2, REALM_CONTACTS, "default").unwrap(),
+                   core::NetworkPolicy::Offline);
+        assert_match!(Error::NotFound
+                      = Store::policy_of(&ctx2, REALM_CONTACTS, "other")
+                      .err().unwrap().downcast::<Error>().unwrap());
+        assert_match!(Error::NotFound
+                      = Store::policy_of(&ctx2, REALM_SOFTWARE_UPDATES, "default")
+                      .err().unwrap().downcast::<Error>().unwrap());
+    }
+
+    #[test]
+    fn import_key() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        store.import("Mr. McTestface", &tpk).unwrap();
+        let binding = store.lookup("Mr. McTestface").unwrap();
+        let tpk_retrieved = binding.tpk().unwrap();
+        assert_eq!(tpk.fingerprint(), tpk_retrieved.fingerprint());
+    }
+
+    #[test]
+    fn export_all() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let other = Store::open(&ctx, REALM_CONTACTS, "other").unwrap();
+        let testy = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        let neal = TPK::from_bytes(bytes!("neal.pgp")).unwrap();
+        store.import("Mr. McTestface", &testy).unwrap();
+        store.import("Testy", &testy).unwrap();
+        store.import("Neal", &neal).unwrap();
+        store.add("Mister B.", &Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb"))
+            .unwrap();
+        // Keys bound in other stores are not exported.
+        other.import("Bannon",
+                     &TPK::from_bytes(bytes!("bannon-base.gpg")).unwrap())
+            .unwrap();
+
+        let mut keyring = Vec::new();
+        assert_eq!(store.export_all(&mut keyring, false).unwrap(), 2);
+        let tpks = openpgp::tpk::TPKParser::from_bytes(&keyring).unwrap()
+            .map(|tpk| tpk.unwrap().fingerprint())
+            .collect::<Vec<_>>();
+        let mut expected = vec![testy.fingerprint(), neal.fingerprint()];
+        expected.sort_by_key(|fp| fp.to_hex());
+        assert_eq!(tpks, expected);
+
+        let mut armored = Vec::new();
+        assert_eq!(store.export_all(&mut armored, true).unwrap(), 2);
+        let mut reader = armor::Reader::new(&armored[..], None);
+        let mut dearmored = Vec::new();
+        reader.read_to_end(&mut dearmored).unwrap();
+        assert_eq!(dearmored, keyring);
+
+        let empty = Store::open(&ctx, REALM_CONTACTS, "empty").unwrap();
+        assert_eq!(empty.export_all(&mut Vec::new(), false).unwrap(), 0);
+    }
+
+    #[test]
+    fn binding_age() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let binding = store.add("Mister B.", &fp).unwrap();
+
+        let now = time::now_utc().to_timespec();
+        let age = binding.age(now).unwrap().unwrap();
+        assert!(age >= Duration::zero());
+        assert!(age < Duration::minutes(1));
+        assert!(binding.last_update_age(now).unwrap().is_none());
+    }
+
+    #[test]
+    fn key_not_found() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let r = store.lookup("I do not exist");
+        assert_match!(Error::NotFound
+                      = r.err().unwrap().downcast::<Error>().unwrap());
+    }
+
+    #[test]
+    fn openpgp_error_conversion() {
+        assert_match!(Error::MalformedTPK
+                      = Error::from(openpgp::Error::MalformedTPK("".into())));
+        assert_match!(Error::OpenPGPError(openpgp::Error::InvalidArgument(_))
+                      = Error::from(openpgp::Error::InvalidArgument("".into())));
+    }
+
+    #[test]
+    fn add_then_import_wrong_key() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let binding = store.add("Mister B.", &fp).unwrap();
+        let r = binding.import(&tpk);
+        match r.err().unwrap().downcast::<Error>().unwrap() {
+            Error::Conflict(info) => {
+                assert_eq!(info.existing, fp);
+                assert_eq!(info.incoming, tpk.fingerprint());
+                assert!(! info.certified);
+            },
+            e => panic!("Expected Error::Conflict, got {:?}.", e),
+        }
+    }
+
+    #[test]
+    fn import_strict() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        let binding = store.add("Testy", &tpk.fingerprint()).unwrap();
+        assert_eq!(binding.import_strict(&tpk).unwrap().fingerprint(),
+                   tpk.fingerprint());
+
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let binding = store.add("Mister B.", &fp).unwrap();
+        match binding.import_strict(&tpk)
+            .err().unwrap().downcast::<Error>().unwrap()
+        {
+            Error::FingerprintMismatch { expected, found } => {
+                assert_eq!(expected, fp);
+                assert_eq!(found, tpk.fingerprint());
+            },
+            e => panic!("Expected Error::FingerprintMismatch, got {:?}.", e),
+        }
+        // The binding has not been touched.
+        assert!(binding.tpk().is_err());
+    }
+
+    #[test]
+    fn integrity_checks() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let testy = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        let neal = TPK::from_bytes(bytes!("neal.pgp")).unwrap();
+        let binding = store.add("Testy", &testy.fingerprint()).unwrap();
+        binding.import(&testy).unwrap();
+        assert_eq!(binding.tpk().unwrap(), testy);
+
+        // Replace the stored key behind the server's back.
+        {
+            let c = rusqlite::Connection::open(store.location().unwrap())
+                .unwrap();
+            let mut blob = Vec::new();
+            neal.serialize(&mut blob).unwrap();
+            c.execute("UPDATE keys SET key = ?1", &[&blob]).unwrap();
+        }
+
+        match binding.tpk().unwrap_err().downcast::<Error>().unwrap() {
+            Error::IntegrityFailure { expected, found } => {
+                assert_eq!(expected, testy.fingerprint());
+                assert_eq!(found, neal.fingerprint());
+            },
+            e => panic!("Expected Error::IntegrityFailure, got {:?}.", e),
+        }
+        let key = Pool::lookup(&ctx, &testy.fingerprint()).unwrap();
+        assert_match!(Ok(Error::IntegrityFailure { .. })
+                      = key.tpk().unwrap_err().downcast::<Error>());
+
+        // The checks can be disabled.
+        let store = Store::open_with_options(
+            &ctx, REALM_CONTACTS, "default",
+            &OpenOptions::new().integrity_checks(false)).unwrap();
+        assert_eq!(store.lookup("Testy").unwrap().tpk().unwrap(), neal);
+    }
+
+    #[test]
+    fn add_then_add_different_key() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        store.add("Mister B.", &b).unwrap();
+        let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+        match store.add("Mister B.", &c)
+            .err().unwrap().downcast::<Error>().unwrap()
+        {
+            Error::Conflict(info) => {
+                assert_eq!(info.existing, b);
+                assert_eq!(info.incoming, c);
+                assert!(! info.certified);
+            },
+            e => panic!("Expected Error::Conflict, got {:?}.", e),
+        }
+    }
+
+    #[test]
+    fn add_existing() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let first = store.add("Mister B.", &b).unwrap();
+        let second = store.add("Mister B.", &b).unwrap();
+        assert_eq!(first.fingerprint().unwrap(), b);
+        assert_eq!(second.fingerprint().unwrap(), b);
+        assert_eq!(first.stats().unwrap().created,
+                   second.stats().unwrap().created);
+        assert_eq!(store.iter().unwrap().count(), 1);
+
+        let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+        match store.add("Mister B.", &c)
+            .err().unwrap().downcast::<Error>().unwrap()
+        {
+            Error::Conflict(info) => {
+                assert_eq!(info.existing, b);
+                assert_eq!(info.incoming, c);
+            },
+            e => panic!("Expected Error::Conflict, got {:?}.", e),
+        }
+        assert_eq!(store.lookup("Mister B.").unwrap().fingerprint().unwrap(),
+                   b);
+    }
+
+    #[test]
+    fn delete_bindings() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let labels = ["A", "B", "C", "D"];
+        for (i, label) in labels.iter().enumerate() {
+            let fp = Fingerprint::from_bytes(&[i as u8; 20]);
+            store.add(label, &fp).unwrap();
+        }
+
+        let r = store.delete_bindings(&["B", "X", "D"]).unwrap();
+        assert_eq!(r.len(), 3);
+        assert!(r[0].is_ok());
+        assert_match!(Error::NotFound
+                      = r[1].as_ref().unwrap_err().downcast_ref::<Error>()
+                      .unwrap());
+        assert!(r[2].is_ok());
+
+        let remaining = store.iter().unwrap()
+            .map(|(label, _, _)| label).collect::<Vec<_>>();
+        assert_eq!(remaining, vec!["A".to_string(), "C".to_string()]);
+
+        // Clearing the store removes the rest, but keeps the store.
+        store.clear().unwrap();
+        assert_eq!(store.iter().unwrap().count(), 0);
+        store.add("E", &Fingerprint::from_bytes(&[4; 20])).unwrap();
+        assert_eq!(store.iter().unwrap().count(), 1);
+    }
+
+    #[test]
+    fn add_many() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let a = Fingerprint::from_bytes(&[0; 20]);
+        let b = Fingerprint::from_bytes(&[1; 20]);
+        let c = Fingerprint::from_bytes(&[2; 20]);
+
+        let bindings = store.add_many(&[("B", &b), ("A", &a)]).unwrap();
+        assert_eq!(bindings.len(), 2);
+        assert_eq!(bindings[0].label().unwrap(), "B");
+        assert_eq!(bindings[1].label().unwrap(), "A");
+
+        // Adding an existing binding again is fine.
+        let bindings = store.add_many(&[("A", &a), ("C", &c)]).unwrap();
+        assert_eq!(bindings.len(), 2);
+        assert_eq!(store.iter().unwrap().count(), 3);
+
+        // A conflict aborts the whole batch.
+        let r = store.add_many(&[("D", &a), ("A", &b), ("B", &c)]);
+        match r.unwrap_err().downcast::<Error>().unwrap() {
+            Error::Conflicts(conflicts) => {
+                assert_eq!(conflicts.len(), 2);
+                assert_eq!(conflicts[0].0, "A");
+                assert_eq!(conflicts[0].1.existing, a);
+                assert_eq!(conflicts[0].1.incoming, b);
+                assert_eq!(conflicts[1].0, "B");
+            },
+            e => panic!("Unexpected error: {}", e),
+        }
+        assert!(store.lookup("D").is_err());
+        assert_eq!(store.iter().unwrap().count(), 3);
+    }
+
+    #[test]
+    fn lookup_by_fingerprint() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let other = Store::open(&ctx, REALM_CONTACTS, "other").unwrap();
+        let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+        store.add("Mister B.", &b).unwrap();
+        store.add("B", &b).unwrap();
+        store.add("Mister C.", &c).unwrap();
+        other.add("Bee", &b).unwrap();
+
+        let bindings = store.lookup_by_fingerprint(&b).unwrap();
+        assert_eq!(bindings.iter().map(|b| b.label().unwrap())
+                   .collect::<Vec<_>>(),
+                   vec!["B".to_string(), "Mister B.".to_string()]);
+        assert_eq!(bindings[0].fingerprint().unwrap(), b);
+
+        // Fingerprints are not unique in the key pool, e.g. before
+        // compacting it.  Bind "B" to a second key with the same
+        // fingerprint.
+        {
+            let c = rusqlite::Connection::open(
+                ctx.home().join("public-key-store.sqlite")).unwrap();
+            c.execute("INSERT INTO keys (fingerprint, created, update_at)
+                           VALUES (?1, 0, 0)",
+                      &[&b.to_hex()]).unwrap();
+            c.execute("UPDATE bindings SET key = ?1 WHERE label = 'B'",
+                      &[&c.last_insert_rowid()]).unwrap();
+        }
+        assert_eq!(store.lookup_by_fingerprint(&b).unwrap().iter()
+                   .map(|b| b.label().unwrap())
+                   .collect::<Vec<_>>(),
+                   vec!["B".to_string(), "Mister B.".to_string()]);
+
+        // The key is in the pool, but not bound in this store.
+        let empty = Store::open(&ctx, REALM_CONTACTS, "empty").unwrap();
+        assert_eq!(empty.lookup_by_fingerprint(&b).unwrap().len(), 0);
+
+        // The key is not in the pool.
+        let d = Fingerprint::from_bytes(b"dddddddddddddddddddd");
+        assert_match!(Error::NotFound
+                      = store.lookup_by_fingerprint(&d).unwrap_err()
+                      .downcast::<Error>().unwrap());
+    }
+
+    #[test]
+    fn update_interval() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let b = store.add("Mister B.", &fp).unwrap();
+        assert_eq!(b.update_interval().unwrap(), None);
+        assert!(b.stats().unwrap().next_update.is_some());
+
+        // A week from now, the key is due for an update.
+        let in_a_week = SystemTime::now()
+            + ::std::time::Duration::from_secs(7 * 24 * 3600);
+        let b2 = store.add("B2", &fp).unwrap();
+        b2.set_update_interval(Duration::weeks(1)).unwrap();
+        assert_eq!(b2.update_interval().unwrap(), Some(Duration::weeks(1)));
+        assert!(b2.stats().unwrap().next_update.unwrap() <= in_a_week);
+
+        // Shortening the interval reschedules the update.
+        b.set_update_interval(Duration::hours(1)).unwrap();
+        assert_eq!(b.update_interval().unwrap(), Some(Duration::hours(1)));
+        let in_an_hour = SystemTime::now()
+            + ::std::time::Duration::from_secs(3600);
+        let next = b.stats().unwrap().next_update.unwrap();
+        assert!(next <= in_an_hour);
+        assert_eq!(b.key().unwrap().stats().unwrap().next_update, Some(next));
+
+        // Restore the default.
+        b.set_update_interval(Duration::zero()).unwrap();
+        assert_eq!(b.update_interval().unwrap(), None);
+
+        // Overly long intervals are clamped.
+        b.set_update_interval(Duration::max_value()).unwrap();
+        assert_eq!(b.update_interval().unwrap(), Some(Duration::days(365)));
+    }
+
+    #[test]
+    fn delete_store_twice() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let s0 = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let s1 = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        s0.delete().unwrap();
+        s1.delete().unwrap();
+    }
+
+    #[test]
+    fn delete_store_then_use() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let s0 = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let s1 = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        s0.delete().unwrap();
+        let binding = s1.lookup("Foobarbaz");
+        assert_match!(Error::NotFound
+                      = binding.err().unwrap().downcast::<Error>().unwrap());
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let binding = s1.add("Mister B.", &fp);
+        assert_match!(Error::NotFound
+                      = binding.err().unwrap().downcast::<Error>().unwrap());
+    }
+
+    #[test]
+    fn delete_binding_twice() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let b0 = store.add("Mister B.", &fp).unwrap();
+        let b1 = store.lookup("Mister B.").unwrap();
+        b0.delete().unwrap();
+        b1.delete().unwrap();
+    }
+
+    #[test]
+    fn delete_binding_then_use() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let b0 = store.add("Mister B.", &fp).unwrap();
+        let b1 = store.lookup("Mister B.").unwrap();
+        b0.delete().unwrap();
+        assert_match!(Error::NotFound
+                      = b1.stats().err().unwrap().downcast::<Error>().unwrap());
+        assert_match!(Error::NotFound
+                      = b1.key().err().unwrap().downcast::<Error>().unwrap());
+    }
+
+    #[test]
+    fn location() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        store.add("Mister B.", &fp).unwrap();
+
+        let location = store.location().unwrap();
+        assert!(location.exists());
+        assert!(location.starts_with(ctx.home()));
+        assert!(store.disk_usage().unwrap() > 0);
+    }
+
+    fn make_some_stores() -> core::Context {
+        let ctx0 = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx0, REALM_CONTACTS, "default").unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        store.add("Mister B.", &fp).unwrap();
+        store.add("B4", &fp).unwrap();
+
+        Store::open(&ctx0, REALM_CONTACTS, "another store").unwrap();
+
+        let ctx1 = core::Context::configure()
+            .home(ctx0.home())
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store =
+            Store::open(&ctx1, REALM_SOFTWARE_UPDATES, "default").unwrap();
+        let fp = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+        store.add("Mister C.", &fp).unwrap();
+
+        ctx0
+    }
+
+    #[test]
+    fn stats() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let binding = store.add("Mister B.", &fp).unwrap();
+
+        let stats0 = binding.stats().unwrap();
+        assert_match!(Some(_) = stats0.created);
+        assert_match!(None = stats0.updated);
+        assert_eq!(stats0.encryption.count, 0);
+        assert_match!(None = stats0.encryption.first);
+        assert_match!(None = stats0.encryption.last);
+        assert_eq!(stats0.verification.count, 0);
+        assert_match!(None = stats0.verification.first);
+        assert_match!(None = stats0.verification.last);
+
+        binding.register_encryption().unwrap();
+        binding.register_encryption().unwrap();
+        binding.register_verification().unwrap();
+
+        let stats1 = binding.stats().unwrap();
+        assert_match!(Some(_) = stats1.created);
+        assert_eq!(stats0.created, stats1.created);
+        assert_match!(None = stats1.updated);
+        assert_eq!(stats1.encryption.count, 2);
+        assert_match!(Some(_) = stats1.encryption.first);
+        assert_match!(Some(_) = stats1.encryption.last);
+        assert!(stats1.encryption.first <= stats1.encryption.last);
+        assert_eq!(stats1.verification.count, 1);
+        assert_match!(Some(_) = stats1.verification.first);
+        assert_match!(Some(_) = stats1.verification.last);
+        assert_eq!(stats1.verification.first, stats1.verification.last);
+    }
+
+    #[test]
+    fn touch() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let binding = store.add("Mister B.", &fp).unwrap();
+
+        let stats = binding.touch(UseKind::Encryption).unwrap();
+        assert_eq!(stats.encryption.count, 1);
+        assert_eq!(stats.verification.count, 0);
+        assert_match!(None = stats.verification.first);
+
+        let stats = binding.touch(UseKind::Verification).unwrap();
+        assert_eq!(stats.encryption.count, 1);
+        assert_eq!(stats.verification.count, 1);
+
+        binding.touch(UseKind::Verification).unwrap();
+        let stats = binding.stats().unwrap();
+        assert_eq!(stats.encryption.count, 1);
+        assert_eq!(stats.verification.count, 2);
+        assert_match!(Some(_) = stats.encryption.last);
+        assert!(stats.verification.first <= stats.verification.last);
+    }
+
+
+    #[test]
+    fn store_iterator() {
+        let ctx = make_some_stores();
+        let mut iter = Store::list(&ctx, REALM_CONTACTS).unwrap();
+        let (realm, name, network_policy, store) = iter.next().unwrap();
+        assert_eq!(realm, REALM_CONTACTS);
+        assert_eq!(name, "default");
+        assert_eq!(network_policy, core::NetworkPolicy::Offline);
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        store.add("Mister B.", &fp).unwrap();
+        let (realm, name, network_policy, store) = iter.next().unwrap();
+        assert_eq!(realm, REALM_CONTACTS);
+        assert_eq!(name, "another store");
+        assert_eq!(network_policy, core::NetworkPolicy::Offline);
+        store.add("Mister B.", &fp).unwrap();
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn binding_iterator() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        // Bindings are ordered by label, not by insertion.
+        let mut iter = store.iter().unwrap();
+        let (label, fingerprint, binding) = iter.next().unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        assert_eq!(label, "B4");
+        assert_eq!(fingerprint, fp);
+        binding.stats().unwrap();
+        let (label, fingerprint, binding) = iter.next().unwrap();
+        assert_eq!(label, "Mister B.");
+        assert_eq!(fingerprint, fp);
+        binding.stats().unwrap();
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn key_iterator() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        store.add("Mister A.", &Fingerprint::from_bytes(b"aaaaaaaaaaaaaaaaaaaa"))
+            .unwrap();
+
+        // Keys are ordered by fingerprint, not by insertion.
+        let mut iter = Store::list_keys(&ctx).unwrap();
+        let (fingerprint, key) = iter.next().unwrap();
+        assert_eq!(fingerprint, Fingerprint::from_bytes(b"aaaaaaaaaaaaaaaaaaaa"));
+        key.stats().unwrap();
+        let (fingerprint, key) = iter.next().unwrap();
+        assert_eq!(fingerprint, Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb"));
+        key.stats().unwrap();
+        let (fingerprint, key) = iter.next().unwrap();
+        assert_eq!(fingerprint, Fingerprint::from_bytes(b"cccccccccccccccccccc"));
+        key.stats().unwrap();
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn compact_keys() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let fp = Fingerprint::from_hex(
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA").unwrap();
+        store.add("Mister A.", &fp).unwrap();
+
+        // Nothing to do.
+        let report = Store::compact_keys(&ctx).unwrap();
+        assert_eq!(report.keys_merged, 0);
+        assert_eq!(report.bindings_rewired, 0);
+
+        // Sneak in a duplicate key behind the server's back.
+        {
+            let c = rusqlite::Connection::open(store.location().unwrap())
+                .unwrap();
+            c.execute("INSERT INTO keys (fingerprint, created, update_at)
+                       VALUES (?1, 0, 0)",
+                      &[&fp.to_hex().to_lowercase()]).unwrap();
+            let key = c.last_insert_rowid();
+            c.execute("INSERT INTO bindings (store, label, key, created)
+                       SELECT store, 'A2', ?1, 0 FROM bindings",
+                      &[&key]).unwrap();
+        }
+        assert_eq!(Store::list_keys(&ctx).unwrap().count(), 2);
+
+        let report = Store::compact_keys(&ctx).unwrap();
+        assert_eq!(report.keys_merged, 1);
+        assert_eq!(report.bindings_rewired, 1);
+
+        let mut iter = Store::list_keys(&ctx).unwrap();
+        let (fingerprint, _) = iter.next().unwrap();
+        assert_eq!(fingerprint, fp);
+        assert!(iter.next().is_none());
+
+        let mut iter = store.iter().unwrap();
+        assert_eq!(iter.next().unwrap().1, fp);
+        assert_eq!(iter.next().unwrap().1, fp);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn server_log_filtered() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        // Plant some entries with known timestamps.
+        {
+            let c = rusqlite::Connection::open(store.location().unwrap())
+                .unwrap();
+            for &(timestamp, error) in &[(100, None), (200, Some("boom")),
+                                         (300, None), (400, Some("bang"))] {
+                c.execute("INSERT INTO log
+                               (timestamp, level, slug, message, error)
+                           VALUES (?1, 0, 'test', 'message', ?2)",
+                          &[&timestamp, &error]).unwrap();
+            }
+        }
+
+        let timestamps = |since, until, failures_only| {
+            Store::server_log_filtered(&ctx, since, until, failures_only)
+                .unwrap()
+                .map(|l| l.timestamp.sec)
+                .filter(|&t| t < 1000)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(timestamps(None, None, false), vec![400, 300, 200, 100]);
+        assert_eq!(timestamps(None, None, true), vec![400, 200]);
+        assert_eq!(timestamps(Some(Timespec::new(200, 0)),
+                              Some(Timespec::new(400, 0)), false),
+                   vec![300, 200]);
+        assert_eq!(timestamps(Some(Timespec::new(300, 0)), None, true),
+                   vec![400]);
+        assert_eq!(timestamps(None, Some(Timespec::new(200, 0)), true),
+                   Vec::<i64>::new());
+    }
 
-#[derive(Fail, Debug)]
-/// Errors returned from the store.
-pub enum Error {
-    /// A requested key was not found.
-    #[fail(display = "Key not found")]
-    NotFound,
-    /// The new key is in conflict with the current key.
-    #[fail(display = "New key conflicts with the current key")]
-    Conflict,
-    /// This is a catch-all for unspecified backend errors, and should
-    /// go away soon.
-    #[fail(display = "Unspecified store error")]
-    StoreError,
-    /// A protocol error occurred.
-    #[fail(display = "Unspecified protocol error")]
-    ProtocolError,
-    /// A TPK is malformed.
-    #[fail(display = "Malformed TPK")]
-    MalformedTPK,
-    /// A fingerprint is malformed.
-    #[fail(display = "Malformed fingerprint")]
-    MalformedFingerprint,
-    /// A `capnp::Error` occurred.
-    #[fail(display = "Internal RPC error")]
-    RpcError(capnp::Error),
-}
+    #[test]
+    fn import_bytes() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+
+        let binary = store.import_bytes("binary", bytes!("testy.pgp")).unwrap();
+        assert_eq!(binary.fingerprint(), tpk.fingerprint());
+
+        let mut armored = Vec::new();
+        {
+            let mut w = armor::Writer::new(&mut armored,
+                                           armor::Kind::PublicKey, &[])
+                .unwrap();
+            tpk.serialize(&mut w).unwrap();
+        }
+        let armored = store.import_bytes("armored", &armored).unwrap();
+        assert_eq!(armored.fingerprint(), tpk.fingerprint());
+        assert_eq!(store.lookup("armored").unwrap().tpk().unwrap().fingerprint(),
+                   tpk.fingerprint());
+
+        assert_match!(Error::MalformedTPK
+                      = store.import_bytes("garbage", b"not a key")
+                      .err().unwrap().downcast::<Error>().unwrap());
+        assert_match!(Error::NotFound
+                      = store.lookup("garbage")
+                      .err().unwrap().downcast::<Error>().unwrap());
+    }
+
+    #[test]
+    fn key_summary() {
+        use openpgp::tpk::TPKBuilder;
+
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        let testy = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        store.import("Testy", &testy).unwrap();
+        let summary = store.lookup("Testy").unwrap().key().unwrap()
+            .summary().unwrap();
+        assert_eq!(summary.fingerprint, testy.fingerprint());
+        assert_eq!(summary.algo, testy.primary().pk_algo());
+        assert_eq!(summary.bits, testy.primary().mpis().bits());
+        let created = testy.primary().creation_time().to_timespec();
+        assert_eq!(summary.created, created);
+        assert_eq!(summary.expires,
+                   testy.primary_key_signature().unwrap()
+                   .key_expiration_time().map(|e| created + e));
+        assert!(! summary.revoked);
+
+        let (tpk, revocation) = TPKBuilder::new()
+            .add_userid("Ephemeral")
+            .set_expiration(Duration::days(7))
+            .generate().unwrap();
+        store.import("Ephemeral", &tpk).unwrap();
+        let key = store.lookup("Ephemeral").unwrap().key().unwrap();
+        let summary = key.summary().unwrap();
+        let created = tpk.primary().creation_time().to_timespec();
+        assert_eq!(summary.expires, Some(created + Duration::days(7)));
+        assert!(! summary.revoked);
+
+        let tpk = tpk.merge_packets(vec![revocation.into()]).unwrap();
+        key.import(&tpk).unwrap();
+        assert!(key.summary().unwrap().revoked);
+
+        // We only know the fingerprint of this one.
+        let binding = store.add("Mister B.",
+                                &Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb"))
+            .unwrap();
+        assert_match!(Error::NotFound
+                      = binding.key().unwrap().summary()
+                      .err().unwrap().downcast::<Error>().unwrap());
+    }
+
+    #[test]
+    fn logger() {
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .logger(move |e| sink.lock().unwrap().push(e))
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let n = events.lock().unwrap().len();
+        assert!(n > 0);
+
+        assert!(store.lookup("I do not exist").is_err());
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), n + 1);
+        match events[n] {
+            core::LogEvent::StoreRequest {
+                ref operation, error: Some(_), ..
+            } => assert_eq!(operation, "store.lookup"),
+            ref e => panic!("unexpected event: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn import_if_newer() {
+        use openpgp::tpk::TPKBuilder;
+
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        let (tpk, revocation) = TPKBuilder::new()
+            .add_userid("Rollback")
+            .generate().unwrap();
+        let revoked = tpk.clone().merge_packets(vec![revocation.into()])
+            .unwrap();
+
+        // We only know the fingerprint, so any key is newer.
+        let binding = store.add("Rollback", &tpk.fingerprint()).unwrap();
+        assert_eq!(binding.import_if_newer(&tpk).unwrap(), Some(tpk.clone()));
+
+        // Same key again.
+        assert_eq!(binding.import_if_newer(&tpk).unwrap(), None);
+
+        // The revocation is new.
+        assert_eq!(binding.import_if_newer(&revoked).unwrap(),
+                   Some(revoked.clone()));
+
+        // A rollback is refused, and the store is not modified.
+        let stats = binding.key().unwrap().stats().unwrap();
+        assert_eq!(binding.import_if_newer(&tpk).unwrap(), None);
+        assert_eq!(binding.tpk().unwrap(), revoked);
+        assert_eq!(binding.key().unwrap().stats().unwrap().updated,
+                   stats.updated);
+    }
+
+    #[test]
+    fn export_subkey() {
+        use openpgp::tpk::{CipherSuite, TPKBuilder};
+
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        let (tpk, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_userid("Delegator")
+            .add_signing_subkey()
+            .add_encryption_subkey()
+            .generate().unwrap();
+        store.import("Delegator", &tpk).unwrap();
+        let key = store.lookup("Delegator").unwrap().key().unwrap();
+
+        for skb in tpk.subkeys() {
+            let fp = skb.subkey().fingerprint();
+            let stripped = key.export_subkey(&fp).unwrap();
+            assert_eq!(stripped.fingerprint(), tpk.fingerprint());
+            assert_eq!(stripped.userids().count(), 0);
+            assert_eq!(stripped.subkeys().count(), 1);
+
+            let s = stripped.subkeys().next().unwrap();
+            assert_eq!(s.subkey().fingerprint(), fp);
+            assert_eq!(s.binding_signature(), skb.binding_signature());
+        }
+
+        // The primary key is not a subkey.
+        match key.export_subkey(&tpk.fingerprint()).unwrap_err()
+            .downcast::<Error>().unwrap()
+        {
+            Error::NotFound => (),
+            e => panic!("Expected NotFound, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn key_capabilities() {
+        use openpgp::tpk::{CipherSuite, TPKBuilder};
+
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        let (tpk, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_userid("Capable")
+            .add_signing_subkey()
+            .add_encryption_subkey()
+            .generate().unwrap();
+        store.import("Capable", &tpk).unwrap();
+        let key = store.lookup("Capable").unwrap().key().unwrap();
+        let capabilities = key.capabilities().unwrap();
+        assert_eq!(capabilities.len(), 3);
+
+        assert_eq!(capabilities[0].fingerprint, tpk.fingerprint());
+        assert!(capabilities[0].flags.can_certify());
+        assert!(! capabilities[0].revoked);
+
+        for c in capabilities[1..].iter() {
+            let skb = tpk.subkeys()
+                .find(|skb| skb.subkey().fingerprint() == c.fingerprint)
+                .unwrap();
+            assert_eq!(c.flags,
+                       skb.binding_signature().unwrap().key_flags());
+            assert!(! c.revoked);
+        }
+        assert_eq!(capabilities.iter()
+                   .filter(|c| c.flags.can_sign()).count(), 1);
+        assert_eq!(capabilities.iter()
+                   .filter(|c| c.flags.can_encrypt_for_transport()).count(),
+                   1);
+
+        // We only know the fingerprint of this one.
+        let binding = store.add("Mister B.",
+                                &Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb"))
+            .unwrap();
+        assert_match!(Error::NotFound
+                      = binding.key().unwrap().capabilities()
+                      .err().unwrap().downcast::<Error>().unwrap());
+    }
+
+    #[test]
+    fn shared_store() {
+        use std::sync::Arc;
+        use std::thread;
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SharedStore>();
+
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Arc::new(
+            Store::open_shared(&ctx, REALM_CONTACTS, "default").unwrap());
+
+        let testy = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        store.import("Testy", &testy).unwrap();
+
+        let threads = (0..4).map(|_| {
+            let store = store.clone();
+            let fp = testy.fingerprint();
+            thread::spawn(move || {
+                for _ in 0..8 {
+                    assert_eq!(store.lookup("Testy").unwrap().fingerprint(),
+                               fp);
+                    assert_match!(Error::NotFound
+                                  = store.lookup("Mister B.")
+                                  .err().unwrap().downcast::<Error>()
+                                  .unwrap());
+                }
+            })
+        }).collect::<Vec<_>>();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        // The backing store is the same as for Store::open.
+        let s = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        assert_eq!(s.lookup("Testy").unwrap().tpk().unwrap().fingerprint(),
+                   testy.fingerprint());
+        store.delete("Testy").unwrap();
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn debug() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        let testy = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        let binding = store.import("Testy", &testy)
+            .and_then(|_| store.lookup("Testy")).unwrap();
+        let abbreviated = testy.fingerprint().to_hex_abbreviated();
+        assert_eq!(format!("{:?}", binding.key().unwrap()),
+                   format!("Key {{ fingerprint: {} }}", abbreviated));
+        assert!(format!("{:?}", binding).contains(&abbreviated));
+
+        // We only know the fingerprint of this one, so the backend
+        // cannot compute it for us.
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let binding = store.add("Mister B.", &fp).unwrap();
+        assert_eq!(format!("{:?}", binding),
+                   format!("Binding {{ label: Some(\"Mister B.\"), \
+                            fingerprint: {} }}", fp.to_hex_abbreviated()));
+        assert_eq!(format!("{:?}", binding.key().unwrap()),
+                   format!("Key {{ fingerprint: {} }}",
+                           fp.to_hex_abbreviated()));
+        let (_, _, binding) = store.iter().unwrap()
+            .find(|&(ref l, _, _)| l == "Mister B.").unwrap();
+        assert!(format!("{:?}", binding).contains(&fp.to_hex_abbreviated()));
+
+        // Without it, we fall back to asking the backend, which
+        // knows the fingerprint even without the key.
+        let binding = store.lookup("Mister B.").unwrap();
+        assert_eq!(format!("{:?}", binding),
+                   format!("Binding {{ label: Some(\"Mister B.\"), \
+                            fingerprint: {} }}", fp.to_hex_abbreviated()));
+        let key = Pool::lookup_by_keyid(&ctx, &fp.to_keyid()).unwrap();
+        assert_eq!(format!("{:?}", key),
+                   format!("Key {{ fingerprint: {} }}",
+                           fp.to_hex_abbreviated()));
+    }
+
+    #[test]
+    fn transaction() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        let fp = tpk.fingerprint();
+
+        // Errors roll back the transaction.
+        assert_match!(Error::MalformedTPK
+                      = store.transaction(|tx| -> Result<()> {
+                          tx.add("Testy", &fp)?;
+                          Err(Error::MalformedTPK.into())
+                      }).err().unwrap().downcast::<Error>().unwrap());
+        assert_match!(Error::NotFound
+                      = store.lookup("Testy").err().unwrap()
+                      .downcast::<Error>().unwrap());
+
+        // So do panics.
+        let r = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            store.transaction(|tx| -> Result<()> {
+                tx.add("Testy", &fp)?;
+                panic!("oops")
+            })
+        }));
+        assert!(r.is_err());
+        assert!(store.lookup("Testy").is_err());
+
+        // There can only be one transaction at a time.
+        store.transaction(|tx| {
+            assert_match!(Error::TransactionInProgress
+                          = store.transaction(|_| Ok(()))
+                          .err().unwrap().downcast::<Error>().unwrap());
+            // Changes not done using the transaction do not become
+            // part of it.
+            assert_match!(Error::TransactionInProgress
+                          = store.add("Outsider", &fp)
+                          .err().unwrap().downcast::<Error>().unwrap());
+            tx.add("Testy", &fp)?;
+            tx.import("Testy", &tpk)?;
+            tx.add("Mister B.",
+                   &Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb"))?;
+            tx.delete("Mister B.")
+        }).unwrap();
+        assert_eq!(store.lookup("Testy").unwrap().tpk().unwrap().fingerprint(),
+                   fp);
+        assert!(store.lookup("Mister B.").is_err());
+        assert_eq!(store.iter().unwrap().count(), 1);
+    }
+
+    #[test]
+    fn revoked() {
+        use openpgp::tpk::TPKBuilder;
+
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        let (tpk, revocation) = TPKBuilder::new()
+            .add_userid("Revoked")
+            .generate().unwrap();
+        let fp = tpk.fingerprint();
+        store.import("Not yet revoked", &tpk).unwrap();
+        store.import("Testy", &TPK::from_bytes(bytes!("testy.pgp")).unwrap())
+            .unwrap();
+        store.add("Mister B.", &Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb"))
+            .unwrap();
+        assert!(store.revoked().unwrap().is_empty());
+
+        let tpk = tpk.merge_packets(vec![revocation.into()]).unwrap();
+        store.import("Revoked", &tpk).unwrap();
+        assert_eq!(store.revoked().unwrap(),
+                   vec![("Not yet revoked".into(), fp.clone()),
+                        ("Revoked".into(), fp)]);
+    }
+
+    #[test]
+    fn iter_prefix() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        for label in ["work/alice", "work/bob", "Work/carol", "personal/bob",
+                      "50%/dave", "50x/erin", "a_b", "axb"].iter() {
+            store.add(label, &fp).unwrap();
+        }
+        store.add_bytes(b"work/\xff", &fp).unwrap();
+
+        let labels = |prefix| store.iter_prefix(prefix).unwrap()
+            .map(|(label, _, _)| label).collect::<Vec<_>>();
+        assert_eq!(labels("work/"),
+                   vec!["work/alice", "work/bob", "work/\u{FFFD}"]);
+        assert_eq!(labels("work/b"), vec!["work/bob"]);
+        assert_eq!(labels("Work/"), vec!["Work/carol"]);
+        assert_eq!(labels("50%"), vec!["50%/dave"]);
+        assert_eq!(labels("a_"), vec!["a_b"]);
+        assert_eq!(labels("nope"), Vec::<String>::new());
+        assert_eq!(labels("").len(), store.iter().unwrap().count());
+    }
+
+    #[test]
+    fn iteration_errors() {
+        // Pretend the backend fails in the middle of an iteration.
+        let mut results = vec![Ok(1), Ok(2), Err(Error::StoreError.into()),
+                               Ok(3)].into_iter();
+        let mut error = None;
+        let mut items = Vec::new();
+        while let Some(item) = end_iteration(results.next().unwrap(),
+                                             &mut error) {
+            items.push(item);
+        }
+        assert_eq!(items, vec![1, 2]);
+        assert_match!(Some(&Error::StoreError)
+                      = error.as_ref().and_then(|e| e.downcast_ref::<Error>()));
+
+        // The end of an iteration is not an error.
+        let mut error = None;
+        assert!(end_iteration::<()>(Err(Error::NotFound.into()), &mut error)
+                .is_none());
+        assert!(error.is_none());
+
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+        store.add("Mister B.", &b).unwrap();
+        store.add("Mister C.", &c).unwrap();
+        let mut iter = store.iter().unwrap();
+        assert_eq!(iter.by_ref().count(), 2);
+        assert!(iter.last_error().is_none());
+        assert!(iter.finish().is_ok());
 
-impl From<capnp::Error> for Error {
-    fn from(error: capnp::Error) -> Self {
-        Error::RpcError(error)
-    }
-}
+        // Corrupt Mister C.'s fingerprint, which is returned last.
+        rusqlite::Connection::open(
+            ctx.home().join("public-key-store.sqlite")).unwrap()
+            .execute("UPDATE keys SET fingerprint = 'ZZ' WHERE fingerprint = ?1",
+                     &[&c.to_hex()]).unwrap();
 
-impl From<capnp::NotInSchema> for Error {
-    fn from(_: capnp::NotInSchema) -> Self {
-        Error::ProtocolError
-    }
-}
+        let mut iter = store.iter().unwrap();
+        assert_eq!(iter.by_ref().map(|(label, _, _)| label)
+                   .collect::<Vec<_>>(),
+                   vec!["Mister B.".to_string()]);
+        assert_match!(Some(&Error::MalformedFingerprint(_))
+                      = iter.last_error()
+                      .and_then(|e| e.downcast_ref::<Error>()));
+        // The iterator stays exhausted.
+        assert!(iter.next().is_none());
+        assert_match!(Error::MalformedFingerprint(_)
+                      = iter.finish().unwrap_err()
+                      .downcast::<Error>().unwrap());
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use openpgp::parse::Parse;
+        let mut iter = Store::list_keys(&ctx).unwrap();
+        assert_eq!(iter.by_ref().map(|(fp, _)| fp).collect::<Vec<_>>(),
+                   vec![b]);
+        assert_match!(Error::MalformedFingerprint(_)
+                      = iter.finish().unwrap_err()
+                      .downcast::<Error>().unwrap());
+    }
 
-    macro_rules! bytes {
-        ( $x:expr ) => { include_bytes!(concat!("../../openpgp/tests/data/keys/", $x)) };
+    /// Serves `key` to the next `n` HTTP requests.
+    fn serve_key(key: &'static [u8], n: usize)
+                 -> (std::net::SocketAddr, thread::JoinHandle<()>) {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            for _ in 0..n {
+                let (s, _) = listener.accept().unwrap();
+                let mut r = BufReader::new(s);
+                let mut line = String::new();
+                while r.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let mut s = r.into_inner();
+                write!(s, "HTTP/1.1 200 OK\r\n\
+                           Content-Length: {}\r\n\
+                           Connection: close\r\n\r\n", key.len()).unwrap();
+                s.write_all(key).unwrap();
+            }
+        });
+        (addr, server)
     }
 
     #[test]
-    fn store_network_policy_mismatch() {
+    fn import_from_keyserver() {
+        let testy: &'static [u8] =
+            include_bytes!("../../openpgp/tests/data/keys/testy.asc");
+        let tpk = TPK::from_bytes(testy).unwrap();
+        let (addr, server) = serve_key(testy, 3);
+
         let ctx = core::Context::configure()
             .ephemeral()
-            .network_policy(core::NetworkPolicy::Offline)
+            .network_policy(core::NetworkPolicy::Insecure)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
-        // Create store.
-        Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let mut ks = sequoia_net::KeyServer::new(
+            &ctx, &format!("hkp://{}", addr)).unwrap();
+
+        // By primary key.
+        let imported = store.import_from_keyserver(
+            "Testy", &mut ks, &tpk.keyid()).unwrap();
+        assert_eq!(imported.fingerprint(), tpk.fingerprint());
+        assert_eq!(store.lookup("Testy").unwrap().tpk().unwrap(), imported);
+
+        // By subkey.
+        let subkey = KeyID::from_hex("49E2118166C92632").unwrap();
+        store.import_from_keyserver("Testy's subkey", &mut ks, &subkey)
+            .unwrap();
+
+        // The server returns the wrong key.
+        let other = KeyID::from_hex("AACB3243630052D9").unwrap();
+        match store.import_from_keyserver("Neal", &mut ks, &other)
+            .unwrap_err().downcast::<Error>().unwrap()
+        {
+            Error::UnexpectedKey { expected, found } => {
+                assert_eq!(expected, other);
+                assert_eq!(found, tpk.fingerprint());
+            },
+            e => panic!("Expected UnexpectedKey, got {:?}", e),
+        }
+        assert!(store.lookup("Neal").is_err());
+        server.join().unwrap();
 
-        let ctx2 = core::Context::configure()
-            .home(ctx.home())
+        // The store's network policy forbids plain http.
+        let ctx = core::Context::configure()
+            .ephemeral()
             .network_policy(core::NetworkPolicy::Encrypted)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
-        let store = Store::open(&ctx2, REALM_CONTACTS, "default");
-        assert_match!(core::Error::NetworkPolicyViolation(_)
-                      = store.err().unwrap().downcast::<core::Error>().unwrap());
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        assert!(store.import_from_keyserver("Testy", &mut ks, &tpk.keyid())
+                .is_err());
     }
 
     #[test]
-    fn import_key() {
+    fn disconnected() {
+        use std::io::Read;
+        use std::net::TcpListener;
+        use std::thread;
+
         let ctx = core::Context::configure()
             .ephemeral()
             .network_policy(core::NetworkPolicy::Offline)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
-        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
-        store.import("Mr. McTestface", &tpk).unwrap();
-        let binding = store.lookup("Mr. McTestface").unwrap();
-        let tpk_retrieved = binding.tpk().unwrap();
-        assert_eq!(tpk.fingerprint(), tpk_retrieved.fingerprint());
+
+        // Pretend there is a service that dies as soon as a client
+        // connects to it.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut cookie = [0; 32];
+            s.read_exact(&mut cookie).unwrap();
+        });
+        {
+            let mut f = fs::File::create(
+                ctx.home().join("public-key-store.cookie")).unwrap();
+            f.write_all(&[0; 32]).unwrap();
+            write!(f, "{}", addr).unwrap();
+        }
+
+        assert_match!(Error::Disconnected
+                      = Store::open(&ctx, REALM_CONTACTS, "default")
+                      .err().unwrap().downcast::<Error>().unwrap());
+        service.join().unwrap();
     }
 
     #[test]
-    fn key_not_found() {
+    fn open_resilient() {
+        use std::io::Read;
+        use std::net::TcpListener;
+        use std::thread;
+
         let ctx = core::Context::configure()
             .ephemeral()
             .network_policy(core::NetworkPolicy::Offline)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
-        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        let r = store.lookup("I do not exist");
+
+        // Pretend there is a service that dies as soon as a client
+        // connects to it.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut cookie = [0; 32];
+            s.read_exact(&mut cookie).unwrap();
+            // Make sure that we are gone for good once the client
+            // notices.
+            drop(listener);
+        });
+        {
+            let mut f = fs::File::create(
+                ctx.home().join("public-key-store.cookie")).unwrap();
+            f.write_all(&[0; 32]).unwrap();
+            write!(f, "{}", addr).unwrap();
+        }
+
+        // We reconnect to a fresh service.
+        let store = Store::open_resilient(&ctx, REALM_CONTACTS, "default")
+            .unwrap();
+        service.join().unwrap();
+
+        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        store.import("Testy", &tpk).unwrap();
+        assert_eq!(store.lookup("Testy").unwrap().tpk().unwrap().fingerprint(),
+                   tpk.fingerprint());
+        assert_eq!(store.iter().unwrap().count(), 1);
+        assert_eq!(store.with(|s| s.iter()).unwrap().count(), 1);
+
+        // Other errors are passed through.
         assert_match!(Error::NotFound
-                      = r.err().unwrap().downcast::<Error>().unwrap());
+                      = store.lookup("Mister B.")
+                      .err().unwrap().downcast::<Error>().unwrap());
     }
 
     #[test]
-    fn add_then_import_wrong_key() {
+    fn import_flooded() {
+        use openpgp::Packet;
+        use openpgp::parse::{PacketParser, PacketParserResult};
+
         let ctx = core::Context::configure()
             .ephemeral()
             .network_policy(core::NetworkPolicy::Offline)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
-        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
-        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-        let binding = store.add("Mister B.", &fp).unwrap();
-        let r = binding.import(&tpk);
-        assert_match!(Error::Conflict
-                      = r.err().unwrap().downcast::<Error>().unwrap());
+
+        // Append lots of copies of a signature to a key.  We need to
+        // bypass TPK, because it would deduplicate them.
+        let key = bytes!("testy.pgp");
+        let mut flooded = key.to_vec();
+        let mut ppr = PacketParser::from_bytes(key).unwrap();
+        let mut sig = None;
+        while let PacketParserResult::Some(pp) = ppr {
+            let (packet, ppr_) = pp.recurse().unwrap();
+            ppr = ppr_;
+            if let Packet::Signature(s) = packet {
+                sig = Some(s);
+            }
+        }
+        let sig = Packet::Signature(sig.unwrap());
+        for _ in 0..40000 {
+            sig.serialize(&mut flooded).unwrap();
+        }
+
+        let (mut core, client) = Store::connect(&ctx).unwrap();
+        let mut request = client.import_request();
+        request.get().set_key(&flooded);
+        let err = (|| -> Result<()> {
+            make_request!(&mut core, request)?;
+            Ok(())
+        })().unwrap_err();
+        assert_match!(Some(&Error::MalformedTPK) = err.downcast_ref::<Error>());
+
+        // The service is still responsive.
+        let tpk = TPK::from_bytes(key).unwrap();
+        let key = Pool::import(&ctx, &tpk).unwrap();
+        assert_eq!(key.tpk().unwrap().fingerprint(), tpk.fingerprint());
     }
 
     #[test]
-    fn add_then_add_different_key() {
+    fn binding_key_cache() {
         let ctx = core::Context::configure()
             .ephemeral()
             .network_policy(core::NetworkPolicy::Offline)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
         let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-        store.add("Mister B.", &b).unwrap();
-        let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
-        assert_match!(Error::Conflict
-                      = store.add("Mister B.", &c)
-                      .err().unwrap().downcast::<Error>().unwrap());
+        let old = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        let new = TPK::from_bytes(bytes!("testy-new.pgp")).unwrap();
+        store.import("Testy", &old).unwrap();
+
+        let b0 = store.lookup("Testy").unwrap();
+        let b1 = store.lookup("Testy").unwrap();
+        assert_eq!(b0.tpk().unwrap().fingerprint(), old.fingerprint());
+        assert_eq!(b1.tpk().unwrap().fingerprint(), old.fingerprint());
+
+        // Rotating using b1 clears b1's cache, but not b0's.
+        b1.rotate(&new).unwrap();
+        assert_eq!(b1.tpk().unwrap().fingerprint(), new.fingerprint());
+        assert_eq!(b0.tpk().unwrap().fingerprint(), old.fingerprint());
+
+        b0.refresh();
+        assert_eq!(b0.tpk().unwrap().fingerprint(), new.fingerprint());
     }
 
     #[test]
-    fn delete_store_twice() {
+    fn byte_labels() {
         let ctx = core::Context::configure()
             .ephemeral()
             .network_policy(core::NetworkPolicy::Offline)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
-        let s0 = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        let s1 = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        s0.delete().unwrap();
-        s1.delete().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let fp0 = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let fp1 = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+
+        // Not valid UTF-8.
+        let label = &b"\xc3\x28 binary"[..];
+        store.add_bytes(label, &fp0).unwrap();
+        let binding = store.lookup_bytes(label).unwrap();
+        assert_eq!(binding.label_bytes().unwrap(), label);
+        assert_eq!(binding.label().unwrap(), "\u{fffd}( binary");
+        assert_match!(Error::NotFound
+                      = store.lookup("\u{fffd}( binary").unwrap_err()
+                      .downcast::<Error>().unwrap());
+
+        // UTF-8 byte labels are interchangeable with string labels.
+        store.add("Mister C.", &fp1).unwrap();
+        assert_eq!(store.lookup_bytes(b"Mister C.").unwrap()
+                   .label_bytes().unwrap(), b"Mister C.");
+        store.add_bytes(b"Mister C.", &fp1).unwrap();
+        assert_match!(Error::Conflict(_)
+                      = store.add_bytes(b"Mister C.", &fp0).unwrap_err()
+                      .downcast::<Error>().unwrap());
+
+        let bindings: Vec<_> = store.iter().unwrap()
+            .map(|(label, fp, binding)|
+                 (label, fp, binding.label_bytes().unwrap()))
+            .collect();
+        // Labels that are not valid UTF-8 come last.
+        assert_eq!(bindings,
+                   vec![("Mister C.".into(), fp1, b"Mister C.".to_vec()),
+                        ("\u{fffd}( binary".into(), fp0, label.to_vec())]);
     }
 
     #[test]
-    fn delete_store_then_use() {
+    fn add_malformed_fingerprint() {
         let ctx = core::Context::configure()
             .ephemeral()
             .network_policy(core::NetworkPolicy::Offline)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
-        let s0 = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        let s1 = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        s0.delete().unwrap();
-        let binding = s1.lookup("Foobarbaz");
-        assert_match!(Error::NotFound
-                      = binding.err().unwrap().downcast::<Error>().unwrap());
-        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-        let binding = s1.add("Mister B.", &fp);
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        // Only version 4 fingerprints are accepted.
+        for len in &[0, 8, 16, 19, 21, 32] {
+            let fp = Fingerprint::from_bytes(&vec![0xaa; *len]);
+            match store.add("Mister B.", &fp).unwrap_err()
+                .downcast::<Error>().unwrap()
+            {
+                Error::MalformedFingerprint(hex) =>
+                    assert_eq!(hex, fp.to_hex()),
+                e => panic!("Expected MalformedFingerprint, got {:?}", e),
+            }
+            assert_match!(Error::MalformedFingerprint(_)
+                          = store.add_bytes(b"Mister B.", &fp).unwrap_err()
+                          .downcast::<Error>().unwrap());
+        }
         assert_match!(Error::NotFound
-                      = binding.err().unwrap().downcast::<Error>().unwrap());
+                      = store.lookup("Mister B.").unwrap_err()
+                      .downcast::<Error>().unwrap());
     }
 
     #[test]
-    fn delete_binding_twice() {
+    fn incompatible_version() {
         let ctx = core::Context::configure()
             .ephemeral()
             .network_policy(core::NetworkPolicy::Offline)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
-        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-        let b0 = store.add("Mister B.", &fp).unwrap();
-        let b1 = store.lookup("Mister B.").unwrap();
-        b0.delete().unwrap();
-        b1.delete().unwrap();
+
+        // Pretend a newer version created the database.
+        {
+            let c = rusqlite::Connection::open(
+                ctx.home().join("public-key-store.sqlite")).unwrap();
+            c.execute_batch(
+                "CREATE TABLE version (
+                     id INTEGER PRIMARY KEY,
+                     version INTEGER);
+                 INSERT INTO version (id, version) VALUES (1, 99);")
+                .unwrap();
+        }
+
+        match Store::open(&ctx, REALM_CONTACTS, "default").unwrap_err()
+            .downcast::<Error>()
+        {
+            Ok(Error::IncompatibleVersion { found, supported }) => {
+                assert_eq!(found, 99);
+                assert_eq!(supported, 5);
+            },
+            e => panic!("Expected IncompatibleVersion, got {:?}", e),
+        }
+        assert_match!(Error::IncompatibleVersion { .. }
+                      = Pool::import(&ctx, &TPK::from_bytes(bytes!("testy.pgp"))
+                                     .unwrap()).unwrap_err()
+                      .downcast::<Error>().unwrap());
     }
 
     #[test]
-    fn delete_binding_then_use() {
+    fn resolve_keyid() {
         let ctx = core::Context::configure()
             .ephemeral()
             .network_policy(core::NetworkPolicy::Offline)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
         let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-        let b0 = store.add("Mister B.", &fp).unwrap();
-        let b1 = store.lookup("Mister B.").unwrap();
-        b0.delete().unwrap();
-        assert_match!(Error::NotFound
-                      = b1.stats().err().unwrap().downcast::<Error>().unwrap());
-        assert_match!(Error::NotFound
-                      = b1.key().err().unwrap().downcast::<Error>().unwrap());
+
+        // Two keys sharing the short key ID 0xDEADBEEF.
+        let fp0 = Fingerprint::from_hex(
+            "0000000000000000000000000000000ADEADBEEF").unwrap();
+        let fp1 = Fingerprint::from_hex(
+            "0000000000000000000000000000000BDEADBEEF").unwrap();
+        store.add("Mister A.", &fp0).unwrap();
+        store.add("Mister B.", &fp1).unwrap();
+
+        let short = KeyID::from_hex("DEADBEEF").unwrap();
+        let mut matches = Store::resolve_keyid(&ctx, &short).unwrap();
+        matches.sort_by_key(|fp| fp.to_hex());
+        assert_eq!(matches, vec![fp0.clone(), fp1.clone()]);
+
+        assert_eq!(Store::resolve_keyid(&ctx, &fp0.to_keyid()).unwrap(),
+                   vec![fp0]);
+        assert_eq!(Store::resolve_keyid(&ctx, &fp1.to_keyid()).unwrap(),
+                   vec![fp1]);
+        assert_eq!(Store::resolve_keyid(
+            &ctx, &KeyID::from_hex("FEEBDAED").unwrap()).unwrap(),
+                   vec![]);
     }
 
-    fn make_some_stores() -> core::Context {
-        let ctx0 = core::Context::configure()
+    #[test]
+    fn dump_restore() {
+        let ctx = core::Context::configure()
             .ephemeral()
             .network_policy(core::NetworkPolicy::Offline)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
-        let store = Store::open(&ctx0, REALM_CONTACTS, "default").unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        let testy = store.add("Testy McTestface", &tpk.fingerprint()).unwrap();
+        testy.import(&tpk).unwrap();
+        testy.register_encryption().unwrap();
+        testy.register_encryption().unwrap();
+        testy.register_verification().unwrap();
         let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-        store.add("Mister B.", &fp).unwrap();
-        store.add("B4", &fp).unwrap();
+        store.add_bytes(b"\xffB", &fp).unwrap();
 
-        Store::open(&ctx0, REALM_CONTACTS, "another store").unwrap();
+        let mut archive = Vec::new();
+        assert_eq!(store.dump(&mut archive).unwrap(), 2);
 
-        let ctx1 = core::Context::configure()
-            .home(ctx0.home())
-            .network_policy(core::NetworkPolicy::Offline)
+        // Restore into a fresh context using a different policy.
+        let ctx2 = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Anonymized)
             .ipc_policy(core::IPCPolicy::Internal)
             .build().unwrap();
-        let store =
-            Store::open(&ctx1, REALM_SOFTWARE_UPDATES, "default").unwrap();
-        let fp = Fingerprint::from_bytes(b"cccccccccccccccccccc");
-        store.add("Mister C.", &fp).unwrap();
-
-        ctx0
+        let restored = Store::restore(&ctx2, REALM_CONTACTS, "restored",
+                                      &mut &archive[..]).unwrap();
+        assert_eq!(Store::policy_of(&ctx2, REALM_CONTACTS, "restored")
+                   .unwrap(), core::NetworkPolicy::Anonymized);
+
+        let testy2 = restored.lookup("Testy McTestface").unwrap();
+        assert_eq!(testy2.tpk().unwrap(), tpk);
+        let (s1, s2) = (testy.stats().unwrap(), testy2.stats().unwrap());
+        assert_eq!(s1.created, s2.created);
+        assert_eq!(s1.updated, s2.updated);
+        assert_eq!(s2.encryption.count, 2);
+        assert_eq!(s1.encryption.first, s2.encryption.first);
+        assert_eq!(s1.encryption.last, s2.encryption.last);
+        assert_eq!(s2.verification.count, 1);
+        assert_eq!(s1.verification.first, s2.verification.first);
+        assert_eq!(s1.verification.last, s2.verification.last);
+
+        let b = restored.lookup_bytes(b"\xffB").unwrap();
+        assert_eq!(b.key().unwrap().tpk_bytes().unwrap(), Vec::<u8>::new());
+        assert_eq!(restored.iter().unwrap().count(), 2);
+
+        // Truncated archives are rejected, and no store is created.
+        let r = Store::restore(&ctx2, REALM_CONTACTS, "truncated",
+                               &mut &archive[..archive.len() - 1]);
+        assert_match!(Error::MalformedArchive
+                      = r.err().unwrap().downcast::<Error>().unwrap());
+        assert_match!(Error::NotFound
+                      = Store::policy_of(&ctx2, REALM_CONTACTS, "truncated")
+                      .unwrap_err().downcast::<Error>().unwrap());
     }
 
     #[test]
-    fn stats() {
-        let ctx = make_some_stores();
+    fn import_diff() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
         let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-        let binding = store.add("Mister B.", &fp).unwrap();
-
-        let stats0 = binding.stats().unwrap();
-        assert_match!(Some(_) = stats0.created);
-        assert_match!(None = stats0.updated);
-        assert_eq!(stats0.encryption.count, 0);
-        assert_match!(None = stats0.encryption.first);
-        assert_match!(None = stats0.encryption.last);
-        assert_eq!(stats0.verification.count, 0);
-        assert_match!(None = stats0.verification.first);
-        assert_match!(None = stats0.verification.last);
-
-        binding.register_encryption().unwrap();
-        binding.register_encryption().unwrap();
-        binding.register_verification().unwrap();
-
-        let stats1 = binding.stats().unwrap();
-        assert_match!(Some(_) = stats1.created);
-        assert_eq!(stats0.created, stats1.created);
-        assert_match!(None = stats1.updated);
-        assert_eq!(stats1.encryption.count, 2);
-        assert_match!(Some(_) = stats1.encryption.first);
-        assert_match!(Some(_) = stats1.encryption.last);
-        assert!(stats1.encryption.first <= stats1.encryption.last);
-        assert_eq!(stats1.verification.count, 1);
-        assert_match!(Some(_) = stats1.verification.first);
-        assert_match!(Some(_) = stats1.verification.last);
-        assert_eq!(stats1.verification.first, stats1.verification.last);
+        let base = TPK::from_bytes(bytes!("bannon-base.gpg")).unwrap();
+        let binding = store.add("Bannon", &base.fingerprint()).unwrap();
+
+        // Only the fingerprint is known, so everything is new.
+        let diff = binding.import_diff(&base).unwrap();
+        assert_eq!(diff.tpk, base);
+        assert_eq!(diff.userids.len(), base.userids().count());
+        assert_eq!(diff.subkeys.len(), base.subkeys().count());
+        assert!(! diff.signatures.is_empty());
+
+        // Nothing changes when importing the same key again.
+        assert!(binding.import_diff(&base).unwrap().is_empty());
+
+        let tpk = TPK::from_bytes(bytes!("bannon-add-uid-1-whitehouse.gov.gpg"))
+            .unwrap();
+        let diff = binding.import_diff(&tpk).unwrap();
+        assert_eq!(diff.userids.len(), 1);
+        assert!(diff.userids[0].value().ends_with(b"@whitehouse.gov>"));
+        assert_eq!(diff.subkeys.len(), 0);
+        assert_eq!(diff.signatures.len(), 1);
+
+        let tpk = TPK::from_bytes(bytes!("bannon-add-subkey-1.gpg")).unwrap();
+        let diff = binding.import_diff(&tpk).unwrap();
+        assert_eq!(diff.userids.len(), 0);
+        assert_eq!(diff.subkeys.len(), 1);
+        assert!(tpk.subkeys().any(|b| b.subkey().fingerprint()
+                                  == diff.subkeys[0]));
+        assert_eq!(diff.signatures.len(), 1);
+
+        let tpk = TPK::from_bytes(bytes!("bannon-ivanka-signs-base.gpg"))
+            .unwrap();
+        let diff = binding.import_diff(&tpk).unwrap();
+        assert_eq!(diff.userids.len(), 0);
+        assert_eq!(diff.subkeys.len(), 0);
+        assert_eq!(diff.signatures.len(), 1);
+        assert_eq!(diff.tpk, binding.tpk().unwrap());
+
+        // A different key is a conflict.
+        let testy = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        let r = binding.import_diff(&testy);
+        assert_match!(Error::Conflict(_)
+                      = r.err().unwrap().downcast::<Error>().unwrap());
     }
 
+    #[test]
+    fn import_detailed() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let base = TPK::from_bytes(bytes!("bannon-base.gpg")).unwrap();
+        let binding = store.add("Bannon", &base.fingerprint()).unwrap();
+
+        let (tpk, report) = binding.import_detailed(&base).unwrap();
+        assert_eq!(tpk, base);
+        assert_eq!(report.userids.len(), base.userids().count());
+        assert_eq!(report.subkeys.len(), base.subkeys().count());
+        assert!(report.signatures > 0);
+        assert_eq!(report.revocations, 0);
+        assert!(binding.import_detailed(&base).unwrap().1.is_empty());
+
+        let tpk = TPK::from_bytes(bytes!("bannon-add-subkey-1.gpg")).unwrap();
+        let (merged, report) = binding.import_detailed(&tpk).unwrap();
+        assert_eq!(merged, binding.tpk().unwrap());
+        assert_eq!(report.userids.len(), 0);
+        assert_eq!(report.subkeys.len(), 1);
+        assert_eq!(report.signatures, 1);
+        assert_eq!(report.revocations, 0);
+
+        // Revocations are counted separately.
+        let (tpk, revocation) = TPKBuilder::new()
+            .add_userid("Revoked")
+            .generate().unwrap();
+        let revoked = tpk.clone().merge_packets(vec![revocation.into()])
+            .unwrap();
+        let binding = store.add("Revoked", &tpk.fingerprint()).unwrap();
+        binding.import(&tpk).unwrap();
+        let (merged, report) = binding.import_detailed(&revoked).unwrap();
+        assert_eq!(merged, revoked);
+        assert_eq!(report, MergeReport {
+            userids: vec![],
+            subkeys: vec![],
+            signatures: 0,
+            revocations: 1,
+        });
+    }
 
     #[test]
-    fn store_iterator() {
-        let ctx = make_some_stores();
-        let mut iter = Store::list(&ctx, REALM_CONTACTS).unwrap();
-        let (realm, name, network_policy, store) = iter.next().unwrap();
-        assert_eq!(realm, REALM_CONTACTS);
-        assert_eq!(name, "default");
-        assert_eq!(network_policy, core::NetworkPolicy::Offline);
-        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-        store.add("Mister B.", &fp).unwrap();
-        let (realm, name, network_policy, store) = iter.next().unwrap();
-        assert_eq!(realm, REALM_CONTACTS);
-        assert_eq!(name, "another store");
-        assert_eq!(network_policy, core::NetworkPolicy::Offline);
-        store.add("Mister B.", &fp).unwrap();
-        assert!(iter.next().is_none());
+    fn stamps() {
+        let now = Timespec::new(1000000, 0);
+        let never = Stamps::new(0, None, None);
+        assert_eq!(never.since_last(now), None);
+        assert_eq!(never.frequency(), None);
+
+        let once = Stamps::new(1, Some(Timespec::new(999000, 0)),
+                               Some(Timespec::new(999000, 0)));
+        assert_eq!(once.since_last(now), Some(Duration::seconds(1000)));
+        assert_eq!(once.frequency(), None);
+
+        let often = Stamps::new(5, Some(Timespec::new(996000, 0)),
+                                Some(Timespec::new(999600, 0)));
+        assert_eq!(often.since_last(now), Some(Duration::seconds(400)));
+        assert_eq!(often.frequency(), Some(Duration::seconds(900)));
     }
 
     #[test]
-    fn binding_iterator() {
-        let ctx = make_some_stores();
+    fn keyserver_uri() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
         let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
-        let mut iter = store.iter().unwrap();
-        let (label, fingerprint, binding) = iter.next().unwrap();
-        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-        assert_eq!(label, "Mister B.");
-        assert_eq!(fingerprint, fp);
-        binding.stats().unwrap();
-        let (label, fingerprint, binding) = iter.next().unwrap();
-        assert_eq!(label, "B4");
-        assert_eq!(fingerprint, fp);
-        binding.stats().unwrap();
-        assert!(iter.next().is_none());
+        assert_eq!(store.keyserver_uri().unwrap(), None);
+
+        let ctx2 = core::Context::configure()
+            .home(ctx.home())
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .keyserver("hkps://keys.example.org")
+            .build().unwrap();
+        let store = Store::open(&ctx2, REALM_CONTACTS, "default").unwrap();
+        assert_eq!(store.keyserver_uri().unwrap(),
+                   Some("hkps://keys.example.org".into()));
+
+        // Opening the store without configuring a keyserver does
+        // not forget it.
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        assert_eq!(store.keyserver_uri().unwrap(),
+                   Some("hkps://keys.example.org".into()));
+        let other = Store::open(&ctx, REALM_CONTACTS, "other").unwrap();
+        assert_eq!(other.keyserver_uri().unwrap(), None);
+
+        // Keyservers we cannot use are rejected.
+        let ctx3 = core::Context::configure()
+            .home(ctx.home())
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .keyserver("ftp://keys.example.org")
+            .build().unwrap();
+        assert!(Store::open(&ctx3, REALM_CONTACTS, "default").is_err());
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        assert_eq!(store.keyserver_uri().unwrap(),
+                   Some("hkps://keys.example.org".into()));
     }
 
     #[test]
-    fn key_iterator() {
-        let ctx = make_some_stores();
-        let mut iter = Store::list_keys(&ctx).unwrap();
-        let (fingerprint, key) = iter.next().unwrap();
-        assert_eq!(fingerprint, Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb"));
-        key.stats().unwrap();
-        let (fingerprint, key) = iter.next().unwrap();
-        assert_eq!(fingerprint, Fingerprint::from_bytes(b"cccccccccccccccccccc"));
-        key.stats().unwrap();
-        assert!(iter.next().is_none());
+    fn upgrade_version_1() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .keyserver("hkps://keys.example.org")
+            .build().unwrap();
+
+        // Create a database using the first version of the schema.
+        {
+            let c = rusqlite::Connection::open(
+                ctx.home().join("public-key-store.sqlite")).unwrap();
+            c.execute_batch(backend::DB_SCHEMA_1).unwrap();
+            c.execute("INSERT INTO stores (realm, network_policy, name)
+                           VALUES (?1, 0, 'default')",
+                      &[&REALM_CONTACTS]).unwrap();
+        }
+
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        assert_eq!(store.keyserver_uri().unwrap(),
+                   Some("hkps://keys.example.org".into()));
     }
 }
 
@@ -14,21 +14,31 @@ macro_rules! make_request {
     ( $core: expr, $request: expr ) => {{
         use node::result::Which;
 
+        let request = $request;
+        let operation = ::operation(&request);
         let r: std::result::Result<Result<_>, capnp::Error> = $core.run(
-            $request.send().promise
+            operation,
+            request.send().promise
                 .and_then(|response| -> Promise<Result<_>, capnp::Error> {
                     let r = pry!(pry!(pry!(response.get()).get_result()).which());
                     let r = match r {
                         /* The Result.  */
                         Which::Ok(Ok(x)) => Ok(x),
                         Which::Err(Ok(e)) => Err(failure::Error::from(e)),
+                        Which::Conflict(Ok(c)) => Err(::conflict_error(c)),
+                        Which::IncompatibleVersion(Ok(v)) =>
+                            Err(::incompatible_version_error(v)),
                         /* Protocol violations.  */
                         Which::Ok(Err(e)) => Err(failure::Error::from(e)),
                         Which::Err(Err(e)) => Err(failure::Error::from(e)),
+                        Which::Conflict(Err(e)) => Err(failure::Error::from(e)),
+                        Which::IncompatibleVersion(Err(e)) =>
+                            Err(failure::Error::from(e)),
                     };
                     Promise::ok(r)
                 }));
-        r?
+        // Map RPC errors, so that disconnects can be told apart.
+        r.map_err(::Error::from)?
     }}
 }
 
@@ -36,21 +46,31 @@ macro_rules! make_request_map {
     ( $core: expr, $request: expr, $map: expr ) => {{
         use node::result::Which;
 
+        let request = $request;
+        let operation = ::operation(&request);
         let r: std::result::Result<Result<_>, capnp::Error> = $core.run(
-            $request.send().promise
+            operation,
+            request.send().promise
                 .and_then(|response| -> Promise<Result<_>, capnp::Error> {
                     let r = pry!(pry!(pry!(response.get()).get_result()).which());
                     let r = match r {
                         /* The Result.  */
                         Which::Ok(Ok(x)) => $map(x),
                         Which::Err(Ok(e)) => Err(failure::Error::from(e)),
+                        Which::Conflict(Ok(c)) => Err(::conflict_error(c)),
+                        Which::IncompatibleVersion(Ok(v)) =>
+                            Err(::incompatible_version_error(v)),
                         /* Protocol violations.  */
                         Which::Ok(Err(e)) => Err(failure::Error::from(e)),
                         Which::Err(Err(e)) => Err(failure::Error::from(e)),
+                        Which::Conflict(Err(e)) => Err(failure::Error::from(e)),
+                        Which::IncompatibleVersion(Err(e)) =>
+                            Err(failure::Error::from(e)),
                     };
                     Promise::ok(r)
                 }));
-        r?
+        // Map RPC errors, so that disconnects can be told apart.
+        r.map_err(::Error::from)?
     }}
 }
 
@@ -74,6 +94,34 @@ macro_rules! bind_results {
             }};
         }
 
+        /// Reports a conflict to the client.
+        #[allow(unused_macros)]
+        macro_rules! conflict {
+            ( $info:expr ) => {{
+                let info: &::ConflictInfo = $info;
+                if DEBUG_BACKEND_ERRORS {
+                    eprintln!("{}:{}: {:?}", file!(), line!(), info);
+                }
+                let mut c = pry!($results.get().get_result()).init_conflict();
+                c.set_existing(&info.existing.to_hex());
+                c.set_incoming(&info.incoming.to_hex());
+                c.set_certified(info.certified);
+                return Promise::ok(());
+            }};
+        }
+
+        /// Reports an incompatible database version to the client.
+        #[allow(unused_macros)]
+        macro_rules! incompatible_version {
+            ( $found:expr, $supported:expr ) => {{
+                let mut v = pry!($results.get().get_result())
+                    .init_incompatible_version();
+                v.set_found($found);
+                v.set_supported($supported);
+                return Promise::ok(());
+            }};
+        }
+
         /// Behaves like `try!` for server functions.
         ///
         /// If the given expression evaluates to Err(_), the error is
@@ -84,6 +132,16 @@ macro_rules! bind_results {
                 match $expr {
                     Ok(x) => x,
                     Err(x) => {
+                        if let Some(info) =
+                            ::backend::ConflictDetails::conflict_info(&x)
+                        {
+                            conflict!(info);
+                        }
+                        if let Some((found, supported)) =
+                            ::backend::ConflictDetails::incompatible_version(&x)
+                        {
+                            incompatible_version!(found, supported);
+                        }
                         if DEBUG_BACKEND_ERRORS {
                             eprintln!("{}:{}: {:?}", file!(), line!(), x);
                         }
@@ -0,0 +1,55 @@
+extern crate sequoia_openpgp as openpgp;
+extern crate sequoia_core;
+extern crate sequoia_store;
+
+use std::env::current_exe;
+use std::path::PathBuf;
+
+use openpgp::Fingerprint;
+use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+use sequoia_store::{Error, Store, REALM_CONTACTS};
+
+#[test]
+fn open_at() {
+    // Start a service by opening a store.
+    let ctx = Context::configure()
+        .ephemeral()
+        .lib(current_exe().unwrap().parent().unwrap().parent().unwrap())
+        .network_policy(NetworkPolicy::Offline)
+        .ipc_policy(IPCPolicy::External)
+        .build().unwrap();
+    let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+    let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    store.add("Mister B.", &fp).unwrap();
+
+    // Connect to it from a context with a different home, which
+    // cannot start a service of its own.
+    let other = Context::configure()
+        .ephemeral()
+        .lib(PathBuf::from("/i/do/not/exist"))
+        .network_policy(NetworkPolicy::Offline)
+        .ipc_policy(IPCPolicy::External)
+        .build().unwrap();
+    assert!(other.home() != ctx.home());
+    let shared = Store::open_at(&other,
+                                ctx.home().join("public-key-store.cookie"),
+                                REALM_CONTACTS, "default").unwrap();
+    assert_eq!(shared.lookup("Mister B.").unwrap().fingerprint().unwrap(), fp);
+}
+
+#[test]
+fn open_at_missing() {
+    let ctx = Context::configure()
+        .ephemeral()
+        .lib(PathBuf::from("/i/do/not/exist"))
+        .network_policy(NetworkPolicy::Offline)
+        .ipc_policy(IPCPolicy::External)
+        .build().unwrap();
+    let rendezvous = ctx.home().join("public-key-store.cookie");
+    match Store::open_at(&ctx, &rendezvous, REALM_CONTACTS, "default")
+        .unwrap_err().downcast::<Error>()
+    {
+        Ok(Error::ServiceNotRunning(ref p)) if *p == rendezvous => (),
+        e => panic!("Expected ServiceNotRunning, got {:?}", e),
+    }
+}
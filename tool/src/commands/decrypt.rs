@@ -8,12 +8,13 @@ extern crate sequoia_openpgp as openpgp;
 use sequoia_core::Context;
 use openpgp::constants::SymmetricAlgorithm;
 use openpgp::conversions::hex;
-use openpgp::crypto::SessionKey;
+use openpgp::crypto::{Password, SessionKey};
 use openpgp::{Fingerprint, TPK, KeyID, Result};
 use openpgp::packet::{Key, key::SecretKey, Signature, PKESK, SKESK};
 use openpgp::parse::PacketParser;
 use openpgp::parse::stream::{
-    VerificationHelper, DecryptionHelper, Decryptor, MessageStructure,
+    VerificationHelper, DecryptionHelper, Decryptor, EncryptionInfo,
+    MessageStructure,
 };
 extern crate sequoia_store as store;
 
@@ -24,14 +25,18 @@ struct Helper<'a> {
     secret_keys: HashMap<KeyID, Key>,
     key_identities: HashMap<KeyID, Fingerprint>,
     key_hints: HashMap<KeyID, String>,
+    passwords: Vec<Password>,
+    session_key: Option<(SymmetricAlgorithm, SessionKey)>,
     dump_session_key: bool,
     dumper: Option<PacketDumper>,
     hex: bool,
 }
 
 impl<'a> Helper<'a> {
-    fn new(ctx: &'a Context, store: &'a mut store::Store,
+    fn new(ctx: &'a Context, store: Option<&'a mut store::Store>,
            signatures: usize, tpks: Vec<TPK>, secrets: Vec<TPK>,
+           passwords: Vec<Password>,
+           session_key: Option<(SymmetricAlgorithm, SessionKey)>,
            dump_session_key: bool, dump: bool, hex: bool)
            -> Self {
         let mut keys: HashMap<KeyID, Key> = HashMap::new();
@@ -76,6 +81,8 @@ impl<'a> Helper<'a> {
             secret_keys: keys,
             key_identities: identities,
             key_hints: hints,
+            passwords: passwords,
+            session_key: session_key,
             dump_session_key: dump_session_key,
             dumper: if dump || hex {
                 let width =
@@ -87,6 +94,34 @@ impl<'a> Helper<'a> {
             hex: hex,
         }
     }
+
+    /// Returns the IDs of the secret keys that may decrypt `pkesk`.
+    ///
+    /// If the recipient is hidden, i.e. the PKESK uses the wildcard
+    /// key ID, we have to try all keys using the same algorithm.
+    fn candidates(&self, pkesk: &PKESK) -> Vec<KeyID> {
+        let recipient = pkesk.recipient();
+        if recipient.is_wildcard() {
+            self.secret_keys.iter()
+                .filter(|&(_, key)| key.pk_algo() == pkesk.pk_algo())
+                .map(|(id, _)| id.clone())
+                .collect()
+        } else if self.secret_keys.contains_key(recipient) {
+            vec![recipient.clone()]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Prints the session key to stderr if the user asked for it.
+    ///
+    /// The format is `algo:hex`, which is also accepted by
+    /// `--session-key`.
+    fn print_session_key(&self, algo: SymmetricAlgorithm, sk: &SessionKey) {
+        if self.dump_session_key {
+            eprintln!("Session key: {}:{}", u8::from(algo), hex::encode(sk));
+        }
+    }
 }
 
 impl<'a> VerificationHelper for Helper<'a> {
@@ -107,7 +142,8 @@ impl<'a> DecryptionHelper for Helper<'a> {
         if let Some(dumper) = self.dumper.as_mut() {
             dumper.packet(&mut io::stderr(),
                           pp.recursion_depth() as usize,
-                          pp.header().clone(), pp.packet.clone(),
+                          pp.header().clone(), pp.offset(),
+                          pp.packet.clone(),
                           pp.map().map(|m| m.clone()), None)?;
         }
         Ok(())
@@ -117,19 +153,43 @@ impl<'a> DecryptionHelper for Helper<'a> {
                   mut decrypt: D) -> openpgp::Result<Option<Fingerprint>>
         where D: FnMut(SymmetricAlgorithm, &SessionKey) -> openpgp::Result<()>
     {
+        if self.dumper.is_some() {
+            // Tell the user who the message is encrypted to, so that
+            // they know what is needed to decrypt it.
+            let info = EncryptionInfo::new(pkesks, skesks);
+            eprintln!("Encrypted to key IDs: {}, password: {}.",
+                      if info.recipients().is_empty() {
+                          "none".into()
+                      } else {
+                          info.recipients().iter()
+                              .map(|id| id.to_string())
+                              .collect::<Vec<_>>().join(", ")
+                      },
+                      if info.skesks() > 0 { "yes" } else { "no" });
+        }
+
+        // If the user supplied the session key, there is no need to
+        // look at the PKESKs and SKESKs.
+        if let Some((algo, ref sk)) = self.session_key {
+            decrypt(algo, sk)
+                .context("Failed to decrypt using the given session key")?;
+            self.print_session_key(algo, sk);
+            return Ok(None);
+        }
+
         // First, we try those keys that we can use without prompting
         // for a password.
         for pkesk in pkesks {
-            let keyid = pkesk.recipient();
-            if let Some(key) = self.secret_keys.get(&keyid) {
+            for keyid in self.candidates(pkesk) {
+                let key = &self.secret_keys[&keyid];
                 if let Some(SecretKey::Unencrypted { mpis }) = key.secret() {
-                    if let Ok(sk) = pkesks[0].decrypt(key, mpis)
-                        .and_then(|(algo, sk)| { decrypt(algo, &sk)?; Ok(sk) })
+                    if let Ok((algo, sk)) = pkesk.decrypt(key, mpis)
+                        .and_then(|(algo, sk)| {
+                            decrypt(algo, &sk)?; Ok((algo, sk))
+                        })
                     {
-                        if self.dump_session_key {
-                            eprintln!("Session key: {}", hex::encode(&sk));
-                        }
-                        return Ok(self.key_identities.get(keyid)
+                        self.print_session_key(algo, &sk);
+                        return Ok(self.key_identities.get(&keyid)
                                   .map(|fp| fp.clone()));
                     }
                 }
@@ -137,15 +197,15 @@ impl<'a> DecryptionHelper for Helper<'a> {
         }
 
         // Second, we try those keys that are encrypted.
-        'pkesk_loop: for pkesk in pkesks {
+        for pkesk in pkesks {
             // Don't ask the user to decrypt a key if we don't support
             // the algorithm.
             if ! pkesk.pk_algo().is_supported() {
                 continue;
             }
 
-            let keyid = pkesk.recipient();
-            if let Some(key) = self.secret_keys.get(&keyid) {
+            'key_loop: for keyid in self.candidates(pkesk) {
+                let key = &self.secret_keys[&keyid];
                 if key.secret().map(|s| ! s.is_encrypted())
                     .unwrap_or(true)
                 {
@@ -164,22 +224,19 @@ impl<'a> DecryptionHelper for Helper<'a> {
                     {
                         match pkesk.decrypt(key, &mpis)
                             .and_then(|(algo, sk)| {
-                                decrypt(algo, &sk)?; Ok(sk)
+                                decrypt(algo, &sk)?; Ok((algo, sk))
                             })
                         {
-                            Ok(sk) => {
-                                if self.dump_session_key {
-                                    eprintln!("Session key: {}",
-                                              hex::encode(&sk));
-                                }
-                                return Ok(self.key_identities.get(keyid)
+                            Ok((algo, sk)) => {
+                                self.print_session_key(algo, &sk);
+                                return Ok(self.key_identities.get(&keyid)
                                           .map(|fp| fp.clone()));
                             },
                             Err(e) => {
                                 eprintln!("Decryption using {} failed:\n  {}",
                                           self.key_hints.get(&keyid).unwrap(),
                                           e);
-                                continue 'pkesk_loop;
+                                continue 'key_loop;
                             },
                         }
                     } else {
@@ -194,19 +251,39 @@ impl<'a> DecryptionHelper for Helper<'a> {
                 Err(failure::err_msg("No key to decrypt message"));
         }
 
-        // Finally, try to decrypt using the SKESKs.
+        // Finally, try to decrypt using the SKESKs.  First, try the
+        // passwords given on the command line.
+        for password in self.passwords.iter() {
+            for skesk in skesks {
+                if let Ok((algo, sk)) = skesk.decrypt(password)
+                    .and_then(|(algo, sk)| {
+                        decrypt(algo, &sk)?; Ok((algo, sk))
+                    })
+                {
+                    self.print_session_key(algo, &sk);
+                    return Ok(None);
+                }
+            }
+        }
+
+        // Don't prompt if the user supplied passwords non-interactively.
+        if ! self.passwords.is_empty() {
+            return
+                Err(failure::err_msg("No password decrypted the message"));
+        }
+
         loop {
             let password =
                 rpassword::read_password_from_tty(Some(
                     "Enter password to decrypt message: "))?.into();
 
             for skesk in skesks {
-                if let Ok(sk) = skesk.decrypt(&password)
-                    .and_then(|(algo, sk)| { decrypt(algo, &sk)?; Ok(sk) })
+                if let Ok((algo, sk)) = skesk.decrypt(&password)
+                    .and_then(|(algo, sk)| {
+                        decrypt(algo, &sk)?; Ok((algo, sk))
+                    })
                 {
-                    if self.dump_session_key {
-                        eprintln!("Session key: {}", hex::encode(&sk));
-                    }
+                    self.print_session_key(algo, &sk);
                     return Ok(None);
                 }
             }
@@ -216,14 +293,17 @@ impl<'a> DecryptionHelper for Helper<'a> {
     }
 }
 
-pub fn decrypt(ctx: &Context, store: &mut store::Store,
+pub fn decrypt(ctx: &Context, store: Option<&mut store::Store>,
                input: &mut io::Read, output: &mut io::Write,
                signatures: usize, tpks: Vec<TPK>, secrets: Vec<TPK>,
+               passwords: Vec<Password>,
+               session_key: Option<(SymmetricAlgorithm, SessionKey)>,
                dump_session_key: bool,
                dump: bool, hex: bool)
                -> Result<()> {
     let helper = Helper::new(ctx, store, signatures, tpks, secrets,
-                             dump_session_key, dump, hex);
+                             passwords, session_key, dump_session_key,
+                             dump, hex);
     let mut decryptor = Decryptor::from_reader(input, helper, None)
         .context("Decryption failed")?;
 
@@ -16,11 +16,11 @@ use openpgp::parse::{map::Map, Parse, PacketParserResult};
 use super::TIMEFMT;
 
 pub fn dump(input: &mut io::Read, output: &mut io::Write, mpis: bool, hex: bool,
-            sk: Option<&SessionKey>)
+            lenient: bool, sk: Option<&SessionKey>)
         -> Result<()> {
     let mut ppr
         = openpgp::parse::PacketParserBuilder::from_reader(input)?
-        .map(hex).finalize()?;
+        .map(hex).lenient(lenient).finalize()?;
     let width = termsize::get().map(|s| s.cols as usize).unwrap_or(80);
     let mut dumper = PacketDumper::new(width, mpis);
 
@@ -84,6 +84,7 @@ pub fn dump(input: &mut io::Read, output: &mut io::Write, mpis: bool, hex: bool,
         };
 
         let header = pp.header().clone();
+        let offset = pp.offset();
         let map = pp.take_map();
 
         let (packet, ppr_) = pp.recurse()?;
@@ -91,14 +92,21 @@ pub fn dump(input: &mut io::Read, output: &mut io::Write, mpis: bool, hex: bool,
         let recursion_depth = ppr.last_recursion_depth().unwrap();
 
         dumper.packet(output, recursion_depth as usize,
-                      header, packet, map, additional_fields)?;
+                      header, offset, packet, map, additional_fields)?;
     }
 
+    if let PacketParserResult::EOF(eof) = ppr {
+        dumper.end(eof.offset());
+    }
     dumper.flush(output)
 }
 
 struct Node {
     header: Header,
+    /// Offset of top-level packets.
+    offset: Option<u64>,
+    /// Total length of top-level packets, once it is known.
+    length: Option<u64>,
     packet: Packet,
     map: Option<Map>,
     additional_fields: Option<Vec<String>>,
@@ -106,10 +114,12 @@ struct Node {
 }
 
 impl Node {
-    fn new(header: Header, packet: Packet, map: Option<Map>,
-           additional_fields: Option<Vec<String>>) -> Self {
+    fn new(header: Header, offset: Option<u64>, packet: Packet,
+           map: Option<Map>, additional_fields: Option<Vec<String>>) -> Self {
         Node {
             header: header,
+            offset: offset,
+            length: None,
             packet: packet,
             map: map,
             additional_fields: additional_fields,
@@ -117,6 +127,12 @@ impl Node {
         }
     }
 
+    fn set_end(&mut self, end: u64) {
+        if let Some(offset) = self.offset {
+            self.length = Some(end - offset);
+        }
+    }
+
     fn append(&mut self, depth: usize, node: Node) {
         if depth == 0 {
             self.children.push(node);
@@ -141,17 +157,24 @@ impl PacketDumper {
         }
     }
 
+    /// Adds a packet to the dump.
+    ///
+    /// `offset` is the packet's offset in the input, which is only
+    /// known for top-level packets.
     pub fn packet(&mut self, output: &mut io::Write, depth: usize,
-                  header: Header, p: Packet, map: Option<Map>,
-                  additional_fields: Option<Vec<String>>)
+                  header: Header, offset: Option<u64>, p: Packet,
+                  map: Option<Map>, additional_fields: Option<Vec<String>>)
                   -> Result<()> {
-        let node = Node::new(header, p, map, additional_fields);
+        let node = Node::new(header, offset, p, map, additional_fields);
         if self.root.is_none() {
             assert_eq!(depth, 0);
             self.root = Some(node);
         } else {
             if depth == 0 {
-                let root = self.root.take().unwrap();
+                let mut root = self.root.take().unwrap();
+                if let Some(offset) = offset {
+                    root.set_end(offset);
+                }
                 self.dump_tree(output, "", &root)?;
                 self.root = Some(node);
             } else {
@@ -161,6 +184,13 @@ impl PacketDumper {
         Ok(())
     }
 
+    /// Records where the last top-level packet ends.
+    pub fn end(&mut self, offset: u64) {
+        if let Some(root) = self.root.as_mut() {
+            root.set_end(offset);
+        }
+    }
+
     pub fn flush(&self, output: &mut io::Write) -> Result<()> {
         if let Some(root) = self.root.as_ref() {
             self.dump_tree(output, "", &root)?;
@@ -173,7 +203,8 @@ impl PacketDumper {
         let indent_node =
             format!("{}{} ", indent,
                     if node.children.is_empty() { " " } else { "│" });
-        self.dump_packet(output, &indent_node, Some(&node.header), &node.packet,
+        self.dump_packet(output, &indent_node, Some(&node.header),
+                         node.offset, node.length, &node.packet,
                          node.map.as_ref(), node.additional_fields.as_ref())?;
         if node.children.is_empty() {
             return Ok(());
@@ -193,7 +224,8 @@ impl PacketDumper {
     }
 
     fn dump_packet(&self, output: &mut io::Write, i: &str,
-                  header: Option<&Header>, p: &Packet, map: Option<&Map>,
+                  header: Option<&Header>, offset: Option<u64>,
+                  length: Option<u64>, p: &Packet, map: Option<&Map>,
                   additional_fields: Option<&Vec<String>>)
                   -> Result<()> {
         use self::openpgp::Packet::*;
@@ -589,6 +621,14 @@ impl PacketDumper {
             },
         }
 
+        if let Some(offset) = offset {
+            write!(output, "{}  Offset: 0x{:08x}", i, offset)?;
+            if let Some(length) = length {
+                write!(output, ", total length: {} bytes", length)?;
+            }
+            writeln!(output)?;
+        }
+
         if let Some(fields) = additional_fields {
             for field in fields {
                 writeln!(output, "{}  {}", i, field)?;
@@ -597,9 +637,15 @@ impl PacketDumper {
 
         if let Some(map) = map {
             writeln!(output, "{}", i)?;
-            let mut hd = hex::Dumper::new(output, self.indentation_for_hexdump(
-                i, map.iter().map(|f| f.name.len()).max()
-                    .expect("we always have one entry")));
+            // Align the dump with the packet's position in the
+            // input.  Note that the map does not include partial body
+            // length headers.
+            let mut hd = hex::Dumper::with_offset(
+                output,
+                self.indentation_for_hexdump(
+                    i, map.iter().map(|f| f.name.len()).max()
+                        .expect("we always have one entry")),
+                offset.unwrap_or(0) as usize);
 
             for field in map.iter() {
                 hd.write(field.data, field.name)?;
@@ -714,7 +760,8 @@ impl PacketDumper {
         match s.value {
             EmbeddedSignature(ref sig) => {
                 let indent = format!("{}      ", i);
-                self.dump_packet(output, &indent, None, sig, None, None)?;
+                self.dump_packet(output, &indent, None, None, None, sig, None,
+                                 None)?;
             },
             _ => (),
         }
@@ -8,9 +8,10 @@ use rpassword;
 
 extern crate sequoia_openpgp as openpgp;
 use sequoia_core::Context;
-use openpgp::constants::DataFormat;
+use openpgp::constants::{CompressionAlgorithm, DataFormat};
 use openpgp::crypto;
-use openpgp::{TPK, KeyID, Result};
+use openpgp::{TPK, KeyID, Result, RevocationStatus};
+use openpgp::packet::KeyFlags;
 use openpgp::packet::key::SecretKey;
 use openpgp::parse::{
     Parse,
@@ -21,7 +22,8 @@ use openpgp::parse::stream::{
     MessageStructure, MessageLayer,
 };
 use openpgp::serialize::stream::{
-    Message, Signer, LiteralWriter, Encryptor, EncryptionMode,
+    Message, Signer, LiteralWriter, EncryptorBuilder, EncryptionMode,
+    Compressor,
 };
 extern crate sequoia_store as store;
 
@@ -41,6 +43,38 @@ fn tm2str(t: &time::Tm) -> String {
     time::strftime(TIMEFMT, t).expect("TIMEFMT is correct")
 }
 
+/// Renders a duration in a human-friendly way, e.g. "3 days".
+///
+/// Only the most significant unit is shown.
+fn duration2str(d: time::Duration) -> String {
+    let s = d.num_seconds();
+    let (n, unit) = if s < 60 {
+        (s, "second")
+    } else if s < 60 * 60 {
+        (d.num_minutes(), "minute")
+    } else if s < 24 * 60 * 60 {
+        (d.num_hours(), "hour")
+    } else if s < 30 * 24 * 60 * 60 {
+        (d.num_days(), "day")
+    } else if s < 365 * 24 * 60 * 60 {
+        (d.num_days() / 30, "month")
+    } else {
+        (d.num_days() / 365, "year")
+    };
+    format!("{} {}{}", n, unit, if n == 1 { "" } else { "s" })
+}
+
+/// Renders how long ago `t` was relative to `now`, e.g. "3 days ago".
+fn ago2str(now: time::Timespec, t: time::Timespec) -> String {
+    let d = now - t;
+    if d < time::Duration::minutes(1) {
+        // This includes timestamps in the future due to clock skew.
+        "just now".into()
+    } else {
+        format!("{} ago", duration2str(d))
+    }
+}
+
 /// Returns suitable signing keys from a given list of TPKs.
 fn get_signing_keys(tpks: &[openpgp::TPK]) -> Result<Vec<crypto::KeyPair>> {
     let mut keys = Vec::new();
@@ -75,14 +109,48 @@ fn get_signing_keys(tpks: &[openpgp::TPK]) -> Result<Vec<crypto::KeyPair>> {
     Ok(keys)
 }
 
-pub fn encrypt(store: &mut store::Store,
+/// Returns why `tpk` is not suitable as a recipient, if it isn't.
+fn recipient_problem(tpk: &TPK) -> Option<&'static str> {
+    if let RevocationStatus::Revoked(_) = tpk.revocation_status() {
+        Some("is revoked")
+    } else if tpk.expired() {
+        Some("is expired")
+    } else if tpk.keys_valid()
+        .key_flags(KeyFlags::default().set_encrypt_at_rest(true))
+        .next().is_none()
+    {
+        Some("has no valid encryption subkey")
+    } else {
+        None
+    }
+}
+
+pub fn encrypt(mut store: Option<&mut store::Store>,
                input: &mut io::Read, output: &mut io::Write,
                npasswords: usize, recipients: Vec<&str>,
-               mut tpks: Vec<openpgp::TPK>, signers: Vec<openpgp::TPK>)
+               mut tpks: Vec<openpgp::TPK>, signers: Vec<openpgp::TPK>,
+               compression: Option<CompressionAlgorithm>,
+               hidden_recipients: bool, force: bool)
                -> Result<()> {
     for r in recipients {
+        let store = store.as_mut().ok_or_else(|| failure::err_msg(
+            "Recipients can only be given by file when not using the store"))?;
         tpks.push(store.lookup(r).context("No such key found")?.tpk()?);
     }
+
+    // Encrypting to a key that is no longer valid produces a message
+    // that nobody may be able to read.
+    for tpk in tpks.iter() {
+        if let Some(problem) = recipient_problem(tpk) {
+            if force {
+                eprintln!("Warning: Key {} {}.", tpk.fingerprint(), problem);
+            } else {
+                return Err(failure::err_msg(
+                    format!("Key {} {}.  Use --force to encrypt anyway.",
+                            tpk.fingerprint(), problem)));
+            }
+        }
+    }
     let mut passwords = Vec::with_capacity(npasswords);
     for n in 0..npasswords {
         let nprompt = format!("Enter password {}: ", n + 1);
@@ -96,30 +164,43 @@ pub fn encrypt(store: &mut store::Store,
 
     let mut signers = get_signing_keys(&signers)?;
 
-    // Build a vector of references to hand to Encryptor.
+    // Build a vector of references to hand to the Signer.
     let recipients: Vec<&openpgp::TPK> = tpks.iter().collect();
-    let passwords_: Vec<&openpgp::crypto::Password> =
-        passwords.iter().collect();
 
     // Stream an OpenPGP message.
     let message = Message::new(output);
 
     // We want to encrypt a literal data packet.
-    let mut sink = Encryptor::new(message,
-                                  &passwords_,
-                                  &recipients,
-                                  EncryptionMode::AtRest,
-                                  None)
+    let mut encryptor = EncryptorBuilder::new(EncryptionMode::AtRest)
+        .hidden_recipients(hidden_recipients);
+    for tpk in tpks.iter() {
+        encryptor = encryptor.add_recipient(tpk);
+    }
+    for password in passwords.iter() {
+        encryptor = encryptor.add_password(password);
+    }
+    let mut sink = encryptor.build(message)
         .context("Failed to create encryptor")?;
 
-    // Optionally sign message.
+    // Optionally compress the data.  The compressed data packet goes
+    // inside the encryption container, wrapping any signatures.
+    if let Some(algo) = compression {
+        sink = Compressor::new(sink, algo)
+            .context("Failed to create compressor")?;
+    }
+
+    // Optionally sign message.  If the recipients are hidden, the
+    // signatures must not reveal them either.
     if ! signers.is_empty() {
-        sink = Signer::with_intended_recipients(
-            sink,
-            signers.iter_mut().map(|s| -> &mut dyn crypto::Signer { s })
-                .collect(),
-            &recipients,
-            None)?;
+        let signers = signers.iter_mut()
+            .map(|s| -> &mut dyn crypto::Signer { s })
+            .collect();
+        sink = if hidden_recipients {
+            Signer::new(sink, signers, None)?
+        } else {
+            Signer::with_intended_recipients(sink, signers, &recipients,
+                                             None)?
+        };
     }
 
     let mut literal_writer = LiteralWriter::new(sink, DataFormat::Binary,
@@ -138,7 +219,7 @@ pub fn encrypt(store: &mut store::Store,
 
 struct VHelper<'a> {
     ctx: &'a Context,
-    store: &'a mut store::Store,
+    store: Option<&'a mut store::Store>,
     signatures: usize,
     tpks: Option<Vec<TPK>>,
     labels: HashMap<KeyID, String>,
@@ -151,7 +232,8 @@ struct VHelper<'a> {
 }
 
 impl<'a> VHelper<'a> {
-    fn new(ctx: &'a Context, store: &'a mut store::Store, signatures: usize,
+    fn new(ctx: &'a Context, store: Option<&'a mut store::Store>,
+           signatures: usize,
            tpks: Vec<TPK>)
            -> Self {
         VHelper {
@@ -265,10 +347,16 @@ impl<'a> VerificationHelper for VHelper<'a> {
         // Explicitly provided keys are trusted.
         self.trusted = seen.clone();
 
+        // Without a store, we only use the explicitly provided keys.
+        if self.store.is_none() {
+            return Ok(tpks);
+        }
+
         // Try to get missing TPKs from the store.
         for id in ids.iter().filter(|i| !seen.contains(i)) {
             let _ =
-                self.store.lookup_by_subkeyid(id)
+                self.store.as_ref().expect("checked above")
+                .lookup_by_subkeyid(id)
                 .and_then(|binding| {
                     self.labels.insert(id.clone(), binding.label()?);
 
@@ -329,7 +417,7 @@ impl<'a> VerificationHelper for VHelper<'a> {
     }
 }
 
-pub fn verify(ctx: &Context, store: &mut store::Store,
+pub fn verify(ctx: &Context, store: Option<&mut store::Store>,
               input: &mut io::Read,
               detached: Option<&mut io::Read>,
               output: &mut io::Write,
@@ -400,7 +488,36 @@ pub fn split(input: &mut io::Read, prefix: &str)
     Ok(())
 }
 
-pub fn store_print_stats(store: &store::Store, label: &str) -> Result<()> {
+/// Copies the top-level packets in `input` to `output`.
+///
+/// Nested packets are copied as part of their container.  The
+/// packets are copied verbatim, so that joining the top-level
+/// packets produced by `split` recreates the original message.
+pub fn join(input: &mut io::Read, output: &mut io::Write)
+            -> Result<()> {
+    let mut ppr =
+        openpgp::parse::PacketParserBuilder::from_reader(input)?
+        .map(true).finalize()?;
+
+    let mut packets = 0;
+    while let PacketParserResult::Some(pp) = ppr {
+        if let Some(ref map) = pp.map() {
+            for field in map.iter() {
+                output.write_all(field.data)?;
+            }
+        }
+        packets += 1;
+        ppr = pp.next()?.1;
+    }
+
+    if packets == 0 {
+        return Err(failure::err_msg("No OpenPGP packets found"));
+    }
+    Ok(())
+}
+
+pub fn store_print_stats(store: &store::Store, label: &str, iso: bool)
+                         -> Result<()> {
     fn print_stamps(st: &store::Stamps) -> Result<()> {
         println!("{} messages using this key", st.count);
         if let Some(t) = st.first {
@@ -426,6 +543,45 @@ pub fn store_print_stats(store: &store::Store, label: &str) -> Result<()> {
         Ok(())
     }
 
+    fn print_stamps_relative(what: &str, st: &store::Stamps,
+                             now: time::Timespec) {
+        match st.last {
+            Some(last) if st.count > 0 => {
+                print!("  {}: used {} time{}, last used {}", what,
+                       st.count, if st.count == 1 { "" } else { "s" },
+                       ago2str(now, last));
+                if let Some(f) = st.frequency() {
+                    print!(", about every {}", duration2str(f));
+                }
+                println!(".");
+            },
+            _ => println!("  {}: never used.", what),
+        }
+    }
+
+    fn print_stats_relative(st: &store::Stats) {
+        let now = time::now_utc().to_timespec();
+        match st.created {
+            Some(t) => print!("  Created {}", ago2str(now, t)),
+            None => print!("  Creation time unknown"),
+        }
+        match st.updated {
+            Some(t) => println!(", updated {}.", ago2str(now, t)),
+            None => println!(", never updated."),
+        }
+        print_stamps_relative("Encryption", &st.encryption, now);
+        print_stamps_relative("Verification", &st.verification, now);
+    }
+
+    let print_stats = |st: &store::Stats| -> Result<()> {
+        if iso {
+            print_stats(st)
+        } else {
+            print_stats_relative(st);
+            Ok(())
+        }
+    };
+
     let binding = store.lookup(label)?;
     println!("Binding {:?}", label);
     print_stats(&binding.stats().context("Failed to get stats")?)?;
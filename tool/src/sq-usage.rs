@@ -9,9 +9,10 @@
 //!     sq [FLAGS] [OPTIONS] [SUBCOMMAND]
 //!
 //! FLAGS:
-//!     -f, --force      Overwrite existing files
-//!     -h, --help       Prints help information
-//!     -V, --version    Prints version information
+//!     -f, --force       Overwrite existing files
+//!     -h, --help        Prints help information
+//!         --no-store    Operates on files only, never using the key store
+//!     -V, --version     Prints version information
 //!
 //! OPTIONS:
 //!     -d, --domain <DOMAIN>            Sets the domain to use
@@ -46,16 +47,20 @@
 //!
 //! FLAGS:
 //!         --dump                Print a packet dump to stderr
-//!         --dump-session-key    Prints the session key to stderr
+//!         --dump-session-key    Prints the session key to stderr, in the format accepted by --session-key
 //!     -h, --help                Prints help information
 //!     -x, --hex                 Print a hexdump (implies --dump)
 //!     -V, --version             Prints version information
 //!
 //! OPTIONS:
-//!     -o, --output <FILE>                    Sets the output file to use
-//!         --public-key-file <TPK-FILE>...    Public key to verify with, given as a file (can be given multiple times)
-//!         --secret-key-file <TSK-FILE>...    Secret key to decrypt with, given as a file (can be given multiple times)
-//!     -n, --signatures <N>                   The number of valid signatures required.  Default: 0
+//!     -o, --output <FILE>                     Sets the output file to use
+//!         --password-file <FILE>...           Password to decrypt with, read from the first line of the file (can be given
+//!                                             multiple times)
+//!         --public-key-file <TPK-FILE>...     Public key to verify with, given as a file (can be given multiple times)
+//!         --secret-key-file <TSK-FILE>...     Secret key to decrypt with, given as a file (can be given multiple times)
+//!         --session-key <ALGO:SESSION-KEY>    Session key to decrypt with, given as the numeric symmetric algorithm
+//!                                             identifier and the hex-encoded key, separated by a colon
+//!     -n, --signatures <N>                    The number of valid signatures required.  Default: 0
 //!
 //! ARGS:
 //!     <FILE>    Sets the input file to use
@@ -70,12 +75,15 @@
 //!     sq encrypt [FLAGS] [OPTIONS] [--] [FILE]
 //!
 //! FLAGS:
-//!     -B, --binary       Don't ASCII-armor encode the OpenPGP data
-//!     -h, --help         Prints help information
-//!     -s, --symmetric    Encrypt with a password (can be given multiple times)
-//!     -V, --version      Prints version information
+//!     -B, --binary               Don't ASCII-armor encode the OpenPGP data
+//!     -h, --help                 Prints help information
+//!         --hidden-recipients    Don't reveal the recipients' key IDs
+//!     -s, --symmetric            Encrypt with a password (can be given multiple times)
+//!     -V, --version              Prints version information
 //!
 //! OPTIONS:
+//!         --compression <KIND>                  Selects compression scheme to use [default: none]  [possible values:
+//!                                               none, zip, zlib, bzip2]
 //!     -o, --output <FILE>                       Sets the output file to use
 //!     -r, --recipient <LABEL>...                Recipient to encrypt for (can be given multiple times)
 //!         --recipient-key-file <TPK-FILE>...    Recipient to encrypt for, given as a file (can be given multiple times)
@@ -144,14 +152,15 @@
 //!     -V, --version    Prints version information
 //!
 //! SUBCOMMANDS:
-//!     add       Add a key identified by fingerprint
-//!     delete    Deletes bindings or stores
-//!     export    Exports a key
-//!     help      Prints this message or the help of the given subcommand(s)
-//!     import    Imports a key
-//!     list      Lists keys in the store
-//!     log       Lists the keystore log
-//!     stats     Get stats for the given label
+//!     add        Add a key identified by fingerprint
+//!     compact    Merges duplicate keys in the common key pool
+//!     delete     Deletes bindings or stores
+//!     export     Exports a key
+//!     help       Prints this message or the help of the given subcommand(s)
+//!     import     Imports a key
+//!     list       Lists keys in the store
+//!     log        Lists the keystore log
+//!     stats      Get stats for the given label
 //! ```
 //!
 //! ### Subcommand store add
@@ -171,21 +180,35 @@
 //!     <FINGERPRINT>    Key to add
 //! ```
 //!
+//! ### Subcommand store compact
+//!
+//! ```text
+//! Merges duplicate keys in the common key pool
+//!
+//! USAGE:
+//!     sq store compact
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//! ```
+//!
 //! ### Subcommand store delete
 //!
 //! ```text
 //! Deletes bindings or stores
 //!
 //! USAGE:
-//!     sq store delete [FLAGS] [LABEL]
+//!     sq store delete [FLAGS] [LABEL]...
 //!
 //! FLAGS:
-//!     -h, --help         Prints help information
-//!         --the-store    Delete the selected store (change with --store)
-//!     -V, --version      Prints version information
+//!         --all-bindings    Delete all bindings, but keep the store
+//!     -h, --help            Prints help information
+//!         --the-store       Delete the selected store (change with --store)
+//!     -V, --version         Prints version information
 //!
 //! ARGS:
-//!     <LABEL>    Delete binding with this label
+//!     <LABEL>...    Delete bindings with these labels
 //! ```
 //!
 //! ### Subcommand store export
@@ -202,7 +225,9 @@
 //!     -V, --version    Prints version information
 //!
 //! OPTIONS:
-//!     -o, --output <FILE>    Sets the output file to use
+//!         --comment <COMMENT>...    Adds a comment to the ASCII armor header
+//!     -o, --output <FILE>           Sets the output file to use
+//!         --subkey <FINGERPRINT>    Exports only the given subkey and the primary key
 //!
 //! ARGS:
 //!     <LABEL>    Label to use
@@ -231,11 +256,15 @@
 //! Lists keys in the store
 //!
 //! USAGE:
-//!     sq store list
+//!     sq store list [FLAGS] [PREFIX]
 //!
 //! FLAGS:
-//!     -h, --help       Prints help information
-//!     -V, --version    Prints version information
+//!     -h, --help            Prints help information
+//!         --revoked-only    Only list keys that are revoked
+//!     -V, --version         Prints version information
+//!
+//! ARGS:
+//!     <PREFIX>    List only bindings whose label starts with the given prefix
 //! ```
 //!
 //! ### Subcommand store log
@@ -260,10 +289,11 @@
 //! Get stats for the given label
 //!
 //! USAGE:
-//!     sq store stats <LABEL>
+//!     sq store stats [FLAGS] <LABEL>
 //!
 //! FLAGS:
 //!     -h, --help       Prints help information
+//!         --iso        Print absolute timestamps instead of relative times
 //!     -V, --version    Prints version information
 //!
 //! ARGS:
@@ -392,11 +422,12 @@
 //! Removes ASCII Armor from a file
 //!
 //! USAGE:
-//!     sq dearmor [OPTIONS] [FILE]
+//!     sq dearmor [FLAGS] [OPTIONS] [FILE]
 //!
 //! FLAGS:
-//!     -h, --help       Prints help information
-//!     -V, --version    Prints version information
+//!     -h, --help               Prints help information
+//!         --ignore-checksum    Does not fail if the armor checksum does not match
+//!     -V, --version            Prints version information
 //!
 //! OPTIONS:
 //!     -o, --output <FILE>    Sets the output file to use
@@ -411,9 +442,10 @@
 //! Applies ASCII Armor to a file
 //!
 //! USAGE:
-//!     sq enarmor [OPTIONS] [FILE]
+//!     sq enarmor [FLAGS] [OPTIONS] [FILE]
 //!
 //! FLAGS:
+//!         --crlf       Terminates lines with CRLF instead of LF
 //!     -h, --help       Prints help information
 //!     -V, --version    Prints version information
 //!
@@ -543,11 +575,16 @@
 //! Lists the server log
 //!
 //! USAGE:
-//!     sq list log
+//!     sq list log [FLAGS] [OPTIONS]
 //!
 //! FLAGS:
-//!     -h, --help       Prints help information
-//!     -V, --version    Prints version information
+//!         --failures-only    Only list failed operations
+//!     -h, --help             Prints help information
+//!     -V, --version          Prints version information
+//!
+//! OPTIONS:
+//!         --since <DATE>    Only list entries logged on or after DATE (YYYY-MM-DD)
+//!         --until <DATE>    Only list entries logged on or before DATE (YYYY-MM-DD)
 //! ```
 //!
 //! ### Subcommand list stores
@@ -581,6 +618,7 @@
 //! SUBCOMMANDS:
 //!     dump     Lists OpenPGP packets
 //!     help     Prints this message or the help of the given subcommand(s)
+//!     join     Joins OpenPGP packets split using sq packet split
 //!     split    Splits a message into OpenPGP packets
 //! ```
 //!
@@ -595,6 +633,7 @@
 //! FLAGS:
 //!     -h, --help       Prints help information
 //!     -x, --hex        Print a hexdump
+//!         --lenient    Skip over malformed data instead of giving up
 //!         --mpis       Print MPIs
 //!     -V, --version    Prints version information
 //!
@@ -606,6 +645,25 @@
 //!     <FILE>    Sets the input file to use
 //! ```
 //!
+//! ### Subcommand packet join
+//!
+//! ```text
+//! Joins OpenPGP packets split using sq packet split
+//!
+//! USAGE:
+//!     sq packet join [OPTIONS] [FILE]...
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!     -o, --output <FILE>    Sets the output file to use
+//!
+//! ARGS:
+//!     <FILE>...    Sets the input files to use, in order
+//! ```
+//!
 //! ### Subcommand packet split
 //!
 //! ```text
@@ -9,10 +9,11 @@ extern crate rpassword;
 extern crate tempfile;
 extern crate time;
 
+use clap::Shell;
 use failure::ResultExt;
 use prettytable::{Table, Cell, Row};
 use std::fs::{File, OpenOptions};
-use std::io;
+use std::io::{self, BufRead};
 use std::path::{Path, PathBuf};
 use std::process::exit;
 
@@ -22,7 +23,9 @@ extern crate sequoia_net;
 extern crate sequoia_store;
 
 use openpgp::{armor, autocrypt, Fingerprint, TPK};
+use openpgp::constants::{CompressionAlgorithm, SymmetricAlgorithm};
 use openpgp::conversions::hex;
+use openpgp::crypto::{Password, SessionKey};
 use openpgp::parse::Parse;
 use openpgp::serialize::Serialize;
 use sequoia_core::{Context, NetworkPolicy};
@@ -73,6 +76,44 @@ fn load_tpks<'a, I>(files: I) -> openpgp::Result<Vec<TPK>>
     Ok(tpks)
 }
 
+fn load_passwords<'a, I>(files: I) -> openpgp::Result<Vec<Password>>
+    where I: Iterator<Item=&'a str>
+{
+    let mut passwords = vec![];
+    for f in files {
+        let mut password = String::new();
+        io::BufReader::new(File::open(f)
+                           .context(format!("Failed to open {:?}", f))?)
+            .read_line(&mut password)
+            .context(format!("Failed to read password from {:?}", f))?;
+        // Strip the line terminator.
+        let len = password.trim_end_matches(|c| c == '\n' || c == '\r').len();
+        password.truncate(len);
+        passwords.push(password.into());
+    }
+    Ok(passwords)
+}
+
+/// Parses a session key of the form `algo:hex`, where `algo` is the
+/// numeric symmetric algorithm identifier.
+fn parse_session_key(s: &str)
+                     -> openpgp::Result<(SymmetricAlgorithm, SessionKey)> {
+    let mut parts = s.splitn(2, ':');
+    let algo = parts.next().unwrap();
+    let key = parts.next().ok_or_else(|| failure::err_msg(
+        "Malformed session key, expected ALGO:SESSION-KEY"))?;
+    let algo: SymmetricAlgorithm = algo.parse::<u8>()
+        .context(format!("Invalid symmetric algorithm {:?}", algo))?
+        .into();
+    if ! algo.is_supported() {
+        return Err(format_err!(
+            "Unsupported symmetric algorithm {}", algo));
+    }
+    let key = hex::decode_pretty(key)
+        .context("Session key is not hex-encoded")?;
+    Ok((algo, key.into()))
+}
+
 /// Prints a warning if the user supplied "help" or "-help" to an
 /// positional argument.
 ///
@@ -88,6 +129,18 @@ fn help_warning(arg: &str) {
 fn real_main() -> Result<(), failure::Error> {
     let matches = sq_cli::build().get_matches();
 
+    // Generating completions requires neither a context nor a store.
+    if let ("completions", Some(m)) = matches.subcommand() {
+        let shell = match m.value_of("shell").expect("required") {
+            "bash" => Shell::Bash,
+            "zsh" => Shell::Zsh,
+            "fish" => Shell::Fish,
+            _ => unreachable!(),
+        };
+        sq_cli::build().gen_completions_to("sq", shell, &mut io::stdout());
+        return Ok(());
+    }
+
     let policy = match matches.value_of("policy") {
         None => NetworkPolicy::Encrypted,
         Some("offline") => NetworkPolicy::Offline,
@@ -100,6 +153,7 @@ fn real_main() -> Result<(), failure::Error> {
         },
     };
     let force = matches.is_present("force");
+    let no_store = matches.is_present("no-store");
     let (realm_name, store_name) = {
         let s = matches.value_of("store").expect("has a default value");
         if let Some(i) = s.find('/') {
@@ -115,6 +169,16 @@ fn real_main() -> Result<(), failure::Error> {
     }
     let ctx = builder.build()?;
 
+    // Opens the store, unless we were asked not to use one.
+    let open_store = || -> Result<Option<Store>, failure::Error> {
+        if no_store {
+            Ok(None)
+        } else {
+            Ok(Some(Store::open(&ctx, realm_name, store_name)
+                    .context("Failed to open the store")?))
+        }
+    };
+
     match matches.subcommand() {
         ("decrypt",  Some(m)) => {
             let mut input = open_or_stdin(m.value_of("input"))?;
@@ -127,11 +191,18 @@ fn real_main() -> Result<(), failure::Error> {
             let secrets = m.values_of("secret-key-file")
                 .map(load_tpks)
                 .unwrap_or(Ok(vec![]))?;
-            let mut store = Store::open(&ctx, realm_name, store_name)
-                .context("Failed to open the store")?;
-            commands::decrypt(&ctx, &mut store,
+            let passwords = m.values_of("password-file")
+                .map(load_passwords)
+                .unwrap_or(Ok(vec![]))?;
+            let session_key = match m.value_of("session-key") {
+                Some(sk) => Some(parse_session_key(sk)?),
+                None => None,
+            };
+            let mut store = open_store()?;
+            commands::decrypt(&ctx, store.as_mut(),
                               &mut input, &mut output,
-                              signatures, tpks, secrets,
+                              signatures, tpks, secrets, passwords,
+                              session_key,
                               m.is_present("dump-session-key"),
                               m.is_present("dump"), m.is_present("hex"))?;
         },
@@ -145,8 +216,7 @@ fn real_main() -> Result<(), failure::Error> {
             } else {
                 output
             };
-            let mut store = Store::open(&ctx, realm_name, store_name)
-                .context("Failed to open the store")?;
+            let mut store = open_store()?;
             let recipients = m.values_of("recipient")
                 .map(|r| r.collect())
                 .unwrap_or(vec![]);
@@ -156,9 +226,19 @@ fn real_main() -> Result<(), failure::Error> {
             let additional_secrets = m.values_of("signer-key-file")
                 .map(load_tpks)
                 .unwrap_or(Ok(vec![]))?;
-            commands::encrypt(&mut store, &mut input, &mut output,
+            let compression =
+                match m.value_of("compression").expect("has default") {
+                    "none" => None,
+                    "zip" => Some(CompressionAlgorithm::Zip),
+                    "zlib" => Some(CompressionAlgorithm::Zlib),
+                    "bzip2" => Some(CompressionAlgorithm::BZip2),
+                    _ => unreachable!("all possible choices are handled"),
+                };
+            commands::encrypt(store.as_mut(), &mut input, &mut output,
                               m.occurrences_of("symmetric") as usize,
-                              recipients, additional_tpks, additional_secrets)?;
+                              recipients, additional_tpks, additional_secrets,
+                              compression, m.is_present("hidden-recipients"),
+                              force)?;
         },
         ("sign",  Some(m)) => {
             let mut input = open_or_stdin(m.value_of("input"))?;
@@ -186,9 +266,8 @@ fn real_main() -> Result<(), failure::Error> {
             let tpks = m.values_of("public-key-file")
                 .map(load_tpks)
                 .unwrap_or(Ok(vec![]))?;
-            let mut store = Store::open(&ctx, realm_name, store_name)
-                .context("Failed to open the store")?;
-            commands::verify(&ctx, &mut store, &mut input,
+            let mut store = open_store()?;
+            commands::verify(&ctx, store.as_mut(), &mut input,
                              detached.as_mut().map(|r| r as &mut io::Read),
                              &mut output, signatures, tpks)?;
         },
@@ -204,13 +283,22 @@ fn real_main() -> Result<(), failure::Error> {
                 "file" => armor::Kind::File,
                 _ => unreachable!(),
             };
-            let mut filter = armor::Writer::new(&mut output, kind, &[])?;
+            let options = armor::ArmorOptions {
+                crlf: m.is_present("crlf"),
+                .. Default::default()
+            };
+            let mut filter =
+                armor::Writer::with_options(&mut output, kind, &[], options)?;
             io::copy(&mut input, &mut filter)?;
         },
         ("dearmor",  Some(m)) => {
             let mut input = open_or_stdin(m.value_of("input"))?;
             let mut output = create_or_stdout(m.value_of("output"), force)?;
-            let mut filter = armor::Reader::new(&mut input, None);
+            let mut filter = if m.is_present("ignore-checksum") {
+                armor::Reader::new_lenient(&mut input, None)
+            } else {
+                armor::Reader::new(&mut input, None)
+            };
             io::copy(&mut filter, &mut output)?;
         },
         ("autocrypt", Some(m)) => {
@@ -272,6 +360,7 @@ fn real_main() -> Result<(), failure::Error> {
                     };
                 commands::dump(&mut input, &mut output,
                                m.is_present("mpis"), m.is_present("hex"),
+                               m.is_present("lenient"),
                                session_key.as_ref())?;
             },
             ("split",  Some(m)) => {
@@ -292,6 +381,17 @@ fn real_main() -> Result<(), failure::Error> {
                             + "-");
                 commands::split(&mut input, &prefix)?;
             },
+            ("join",  Some(m)) => {
+                let mut output = create_or_stdout(m.value_of("output"), force)?;
+                if let Some(inputs) = m.values_of("input") {
+                    for name in inputs {
+                        commands::join(&mut open_or_stdin(Some(name))?,
+                                       &mut output)?;
+                    }
+                } else {
+                    commands::join(&mut io::stdin(), &mut output)?;
+                }
+            },
             _ => unreachable!(),
         },
 
@@ -299,7 +399,7 @@ fn real_main() -> Result<(), failure::Error> {
             let mut ks = if let Some(uri) = m.value_of("server") {
                 KeyServer::new(&ctx, &uri)
             } else {
-                KeyServer::sks_pool(&ctx)
+                KeyServer::from_context(&ctx)
             }.context("Malformed keyserver URI")?;
 
             match m.subcommand() {
@@ -340,17 +440,26 @@ fn real_main() -> Result<(), failure::Error> {
             }
         },
         ("store",  Some(m)) => {
-            let store = Store::open(&ctx, realm_name, store_name)
-                .context("Failed to open the store")?;
+            let store = open_store()?.ok_or_else(|| failure::err_msg(
+                "The store subcommands cannot be used with --no-store"))?;
 
             match m.subcommand() {
-                ("list",  Some(_)) => {
-                    list_bindings(&store, realm_name, store_name)?;
+                ("list",  Some(m)) => {
+                    let prefix = m.value_of("prefix").unwrap_or("");
+                    if m.is_present("revoked-only") {
+                        list_revoked_bindings(&store, realm_name, store_name,
+                                              prefix)?;
+                    } else {
+                        list_bindings(&store, realm_name, store_name, prefix)?;
+                    }
                 },
                 ("add",  Some(m)) => {
-                    let fp = Fingerprint::from_hex(m.value_of("fingerprint").unwrap())
-                        .expect("Malformed fingerprint");
-                    store.add(m.value_of("label").unwrap(), &fp)?;
+                    let fp = m.value_of("fingerprint").unwrap();
+                    let fp = Fingerprint::from_hex(fp)
+                        .map_err(|_| failure::err_msg(
+                            format!("Malformed fingerprint: {:?}", fp)))?;
+                    store.add(m.value_of("label").unwrap(), &fp)
+                        .context(format!("Failed to add fingerprint {}", fp))?;
                 },
                 ("import",  Some(m)) => {
                     let label = m.value_of("label").unwrap();
@@ -360,13 +469,29 @@ fn real_main() -> Result<(), failure::Error> {
                     store.import(label, &tpk)?;
                 },
                 ("export",  Some(m)) => {
-                    let tpk = store.lookup(m.value_of("label").unwrap())?.tpk()?;
+                    let label = m.value_of("label").unwrap();
+                    let tpk = if let Some(subkey) = m.value_of("subkey") {
+                        let subkey = Fingerprint::from_hex(subkey)
+                            .map_err(|_| failure::err_msg(
+                                format!("Malformed fingerprint: {:?}", subkey)))?;
+                        store.lookup(label)?.key()?.export_subkey(&subkey)?
+                    } else {
+                        store.lookup(label)?.tpk()?
+                    };
+
+                    // Tell the recipient what they are looking at.
+                    let fingerprint = tpk.fingerprint().to_string();
+                    let mut headers = vec![("Comment", fingerprint.as_str()),
+                                           ("Comment", label)];
+                    if let Some(comments) = m.values_of("comment") {
+                        headers.extend(comments.map(|c| ("Comment", c)));
+                    }
 
                     let mut output = create_or_stdout(m.value_of("output"), force)?;
                     let mut output = if ! m.is_present("binary") {
                         Box::new(armor::Writer::new(&mut output,
                                                     armor::Kind::PublicKey,
-                                                    &[])?)
+                                                    &headers)?)
                     } else {
                         output
                     };
@@ -374,32 +499,58 @@ fn real_main() -> Result<(), failure::Error> {
                     tpk.serialize(&mut output)?;
                 },
                 ("delete",  Some(m)) => {
-                    if m.is_present("label") == m.is_present("the-store") {
-                        eprintln!("Please specify either a label or --the-store.");
+                    if [m.is_present("label"), m.is_present("the-store"),
+                        m.is_present("all-bindings")].iter()
+                        .filter(|&&present| present).count() != 1
+                    {
+                        eprintln!("Please specify either labels, --the-store, \
+                                   or --all-bindings.");
                         exit(1);
                     }
 
                     if m.is_present("the-store") {
                         store.delete().context("Failed to delete the store")?;
+                    } else if m.is_present("all-bindings") {
+                        store.clear().context("Failed to delete the bindings")?;
                     } else {
-                        let binding = store.lookup(m.value_of("label").unwrap())
-                            .context("Failed to get key")?;
-                        binding.delete().context("Failed to delete the binding")?;
+                        let labels: Vec<&str> =
+                            m.values_of("label").unwrap().collect();
+                        let results = store.delete_bindings(&labels)
+                            .context("Failed to delete the bindings")?;
+                        let mut failed = false;
+                        for (label, r) in labels.iter().zip(results) {
+                            if let Err(e) = r {
+                                eprintln!("Failed to delete {:?}: {}", label, e);
+                                failed = true;
+                            }
+                        }
+                        if failed {
+                            exit(1);
+                        }
                     }
                 },
                 ("stats",  Some(m)) => {
                     commands::store_print_stats(&store,
-                                                m.value_of("label").unwrap())?;
+                                                m.value_of("label").unwrap(),
+                                                m.is_present("iso"))?;
                 },
                 ("log",  Some(m)) => {
                     if m.is_present("label") {
                         let binding = store.lookup(m.value_of("label").unwrap())
                             .context("No such key")?;
-                        print_log(binding.log().context("Failed to get log")?, false);
+                        print_log(binding.log().context("Failed to get log")?, false)?;
                     } else {
-                        print_log(store.log().context("Failed to get log")?, true);
+                        print_log(store.log().context("Failed to get log")?, true)?;
                     }
                 },
+                ("compact",  Some(_)) => {
+                    let report = Store::compact_keys(&ctx)
+                        .context("Failed to compact the common key pool")?;
+                    println!("Merged {} keys, rewired {} bindings, \
+                              reclaimed {} bytes.",
+                             report.keys_merged, report.bindings_rewired,
+                             report.bytes_reclaimed);
+                },
                 _ => unreachable!(),
             }
         },
@@ -410,46 +561,85 @@ fn real_main() -> Result<(), failure::Error> {
                     table.set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
                     table.set_titles(row!["realm", "name", "network policy"]);
 
-                    for (realm, name, network_policy, _)
-                        in Store::list(&ctx, m.value_of("prefix").unwrap_or(""))? {
+                    let mut iter =
+                        Store::list(&ctx, m.value_of("prefix").unwrap_or(""))?;
+                    for (realm, name, network_policy, _) in iter.by_ref() {
                             table.add_row(Row::new(vec![
                                 Cell::new(&realm),
                                 Cell::new(&name),
                                 Cell::new(&format!("{:?}", network_policy))
                             ]));
                         }
+                    iter.finish().context("Failed to list stores")?;
 
                     table.printstd();
                 },
                 ("bindings",  Some(m)) => {
-                    for (realm, name, _, store)
-                        in Store::list(&ctx, m.value_of("prefix").unwrap_or(""))? {
-                            list_bindings(&store, &realm, &name)?;
+                    let mut iter =
+                        Store::list(&ctx, m.value_of("prefix").unwrap_or(""))?;
+                    for (realm, name, _, store) in iter.by_ref() {
+                            list_bindings(&store, &realm, &name, "")?;
                         }
+                    iter.finish().context("Failed to list stores")?;
                 },
                 ("keys",  Some(_)) => {
                     let mut table = Table::new();
                     table.set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-                    table.set_titles(row!["fingerprint", "updated", "status"]);
+                    table.set_titles(row!["fingerprint", "algorithm",
+                                          "expires", "updated", "status"]);
 
-                    for (fingerprint, key) in Store::list_keys(&ctx)? {
+                    let mut iter = Store::list_keys(&ctx)?;
+                    for (fingerprint, key) in iter.by_ref() {
                             let stats = key.stats()
                                 .context("Failed to get key stats")?;
+                            // We may only know the fingerprint.
+                            let summary = key.summary().ok();
+                            let now = time::get_time();
                             table.add_row(Row::new(vec![
                                 Cell::new(&fingerprint.to_string()),
+                                Cell::new(&summary.as_ref().map(|s| match s.bits {
+                                    Some(bits) => format!("{}/{}", s.algo, bits),
+                                    None => s.algo.to_string(),
+                                }).unwrap_or_default()),
+                                if let Some(ref t) = summary.as_ref()
+                                    .and_then(|s| s.expires)
+                                {
+                                    Cell::new(&format_time(t))
+                                } else {
+                                    Cell::new("")
+                                },
                                 if let Some(ref t) = stats.updated {
                                     Cell::new(&format_time(t))
                                 } else {
                                     Cell::new("")
                                 },
-                                Cell::new("")
+                                Cell::new(match summary {
+                                    Some(ref s) if s.revoked => "revoked",
+                                    Some(ref s) if s.expires
+                                        .map(|t| t <= now).unwrap_or(false) =>
+                                        "expired",
+                                    _ => "",
+                                })
                             ]));
                         }
+                    iter.finish().context("Failed to list keys")?;
 
                     table.printstd();
                 },
-                ("log",  Some(_)) => {
-                    print_log(Store::server_log(&ctx)?, true);
+                ("log",  Some(m)) => {
+                    let since = match m.value_of("since") {
+                        Some(date) => Some(parse_date(date)?),
+                        None => None,
+                    };
+                    // Include the whole day given as upper bound.
+                    let until = match m.value_of("until") {
+                        Some(date) =>
+                            Some(parse_date(date)? + time::Duration::days(1)),
+                        None => None,
+                    };
+                    print_log(Store::server_log_filtered(
+                        &ctx, since, until, m.is_present("failures-only"))?,
+                              true)?;
                 },
                 _ => unreachable!(),
             }
@@ -464,9 +654,19 @@ fn real_main() -> Result<(), failure::Error> {
     return Ok(())
 }
 
-fn list_bindings(store: &Store, realm: &str, name: &str) -> Result<(), failure::Error> {
-    if store.iter()?.count() == 0 {
-        println!("No label-key bindings in the \"{}/{}\" store.", realm, name);
+fn list_bindings(store: &Store, realm: &str, name: &str, prefix: &str)
+                 -> Result<(), failure::Error> {
+    let mut iter = store.iter_prefix(prefix)?;
+    let count = iter.by_ref().count();
+    iter.finish().context("Failed to list bindings")?;
+    if count == 0 {
+        if prefix.is_empty() {
+            println!("No label-key bindings in the \"{}/{}\" store.",
+                     realm, name);
+        } else {
+            println!("No label-key bindings with prefix {:?} in the \
+                      \"{}/{}\" store.", prefix, realm, name);
+        }
         return Ok(());
     }
 
@@ -475,16 +675,44 @@ fn list_bindings(store: &Store, realm: &str, name: &str) -> Result<(), failure::
     let mut table = Table::new();
     table.set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
     table.set_titles(row!["label", "fingerprint"]);
-    for (label, fingerprint, _) in store.iter()? {
+    let mut iter = store.iter_prefix(prefix)?;
+    for (label, fingerprint, _) in iter.by_ref() {
         table.add_row(Row::new(vec![
             Cell::new(&label),
             Cell::new(&fingerprint.to_string())]));
     }
+    iter.finish().context("Failed to list bindings")?;
     table.printstd();
     Ok(())
 }
 
-fn print_log(iter: LogIter, with_slug: bool) {
+fn list_revoked_bindings(store: &Store, realm: &str, name: &str,
+                         prefix: &str)
+                         -> Result<(), failure::Error> {
+    let revoked = store.revoked()?.into_iter()
+        .filter(|&(ref label, _)| label.starts_with(prefix))
+        .collect::<Vec<_>>();
+    if revoked.is_empty() {
+        println!("No revoked keys in the \"{}/{}\" store.", realm, name);
+        return Ok(());
+    }
+
+    println!("Realm: {:?}, store: {:?}:", realm, name);
+
+    let mut table = Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["label", "fingerprint"]);
+    for (label, fingerprint) in revoked {
+        table.add_row(Row::new(vec![
+            Cell::new(&label),
+            Cell::new(&fingerprint.to_string())]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn print_log(mut iter: LogIter, with_slug: bool)
+             -> Result<(), failure::Error> {
     let mut table = Table::new();
     table.set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
     let mut head = row!["timestamp", "message"];
@@ -493,7 +721,7 @@ fn print_log(iter: LogIter, with_slug: bool) {
     }
     table.set_titles(head);
 
-    for entry in iter {
+    for entry in iter.by_ref() {
         let mut row = row![&format_time(&entry.timestamp),
                            &entry.short()];
         if with_slug {
@@ -501,8 +729,18 @@ fn print_log(iter: LogIter, with_slug: bool) {
         }
         table.add_row(row);
     }
+    iter.finish().context("Failed to get log")?;
 
     table.printstd();
+    Ok(())
+}
+
+/// Parses a date of the form YYYY-MM-DD.
+fn parse_date(s: &str) -> Result<time::Timespec, failure::Error> {
+    let tm = time::strptime(s, "%F")
+        .map_err(|e| failure::err_msg(
+            format!("Malformed date {:?}: {}", s, e)))?;
+    Ok(tm.to_timespec())
 }
 
 fn format_time(t: &time::Timespec) -> String {
@@ -19,6 +19,9 @@ pub fn build() -> App<'static, 'static> {
              .short("s")
              .default_value("org.sequoia-pgp.contacts/default")
              .help("Sets the realm and store to use"))
+        .arg(Arg::with_name("no-store")
+             .long("no-store")
+             .help("Operates on files only, never using the key store"))
         .arg(Arg::with_name("policy").value_name("NETWORK-POLICY")
              .long("policy")
              .short("p")
@@ -58,9 +61,26 @@ pub fn build() -> App<'static, 'static> {
                          .number_of_values(1)
                          .help("Secret key to decrypt with, given as a file \
                                 (can be given multiple times)"))
+                    .arg(Arg::with_name("password-file")
+                         .long("password-file")
+                         .multiple(true)
+                         .takes_value(true)
+                         .value_name("FILE")
+                         .number_of_values(1)
+                         .help("Password to decrypt with, read from the \
+                                first line of the file (can be given \
+                                multiple times)"))
+                    .arg(Arg::with_name("session-key")
+                         .long("session-key")
+                         .takes_value(true)
+                         .value_name("ALGO:SESSION-KEY")
+                         .help("Session key to decrypt with, given as the \
+                                numeric symmetric algorithm identifier and \
+                                the hex-encoded key, separated by a colon"))
                     .arg(Arg::with_name("dump-session-key")
                          .long("dump-session-key")
-                         .help("Prints the session key to stderr"))
+                         .help("Prints the session key to stderr, in the \
+                                format accepted by --session-key"))
                     .arg(Arg::with_name("dump")
                          .long("dump")
                          .help("Print a packet dump to stderr"))
@@ -111,7 +131,16 @@ pub fn build() -> App<'static, 'static> {
                          .short("s")
                          .multiple(true)
                          .help("Encrypt with a password \
-                                (can be given multiple times)")))
+                                (can be given multiple times)"))
+                    .arg(Arg::with_name("hidden-recipients")
+                         .long("hidden-recipients")
+                         .help("Don't reveal the recipients' key IDs"))
+                    .arg(Arg::with_name("compression")
+                         .long("compression")
+                         .value_name("KIND")
+                         .possible_values(&["none", "zip", "zlib", "bzip2"])
+                         .default_value("none")
+                         .help("Selects compression scheme to use")))
         .subcommand(SubCommand::with_name("sign")
                     .display_order(25)
                     .about("Signs a message")
@@ -188,7 +217,10 @@ pub fn build() -> App<'static, 'static> {
                          .possible_values(&["message", "publickey", "secretkey",
                                             "signature", "file"])
                          .default_value("file")
-                         .help("Selects the kind of header line to produce")))
+                         .help("Selects the kind of header line to produce"))
+                    .arg(Arg::with_name("crlf")
+                         .long("crlf")
+                         .help("Terminates lines with CRLF instead of LF")))
 
         .subcommand(SubCommand::with_name("dearmor")
                     .about("Removes ASCII Armor from a file")
@@ -197,7 +229,11 @@ pub fn build() -> App<'static, 'static> {
                     .arg(Arg::with_name("output").value_name("FILE")
                          .long("output")
                          .short("o")
-                         .help("Sets the output file to use")))
+                         .help("Sets the output file to use"))
+                    .arg(Arg::with_name("ignore-checksum")
+                         .long("ignore-checksum")
+                         .help("Does not fail if the armor checksum does \
+                                not match")))
         .subcommand(SubCommand::with_name("autocrypt")
                     .about("Autocrypt support")
                     .setting(AppSettings::ArgRequiredElseHelp)
@@ -271,7 +307,13 @@ pub fn build() -> App<'static, 'static> {
                     .about("Interacts with key stores")
                     .setting(AppSettings::ArgRequiredElseHelp)
                     .subcommand(SubCommand::with_name("list")
-                                .about("Lists keys in the store"))
+                                .about("Lists keys in the store")
+                                .arg(Arg::with_name("prefix").value_name("PREFIX")
+                                     .help("List only bindings whose label \
+                                            starts with the given prefix"))
+                                .arg(Arg::with_name("revoked-only")
+                                     .long("revoked-only")
+                                     .help("Only list keys that are revoked")))
                     .subcommand(SubCommand::with_name("add")
                                 .about("Add a key identified by fingerprint")
                                 .arg(Arg::with_name("label").value_name("LABEL")
@@ -292,6 +334,11 @@ pub fn build() -> App<'static, 'static> {
                                 .arg(Arg::with_name("label").value_name("LABEL")
                                      .required(true)
                                      .help("Label to use"))
+                                .arg(Arg::with_name("subkey")
+                                     .long("subkey")
+                                     .value_name("FINGERPRINT")
+                                     .help("Exports only the given subkey \
+                                            and the primary key"))
                                 .arg(Arg::with_name("output").value_name("FILE")
                                      .long("output")
                                      .short("o")
@@ -299,25 +346,43 @@ pub fn build() -> App<'static, 'static> {
                                 .arg(Arg::with_name("binary")
                                      .long("binary")
                                      .short("B")
-                                     .help("Don't ASCII-armor encode the OpenPGP data")))
+                                     .help("Don't ASCII-armor encode the OpenPGP data"))
+                                .arg(Arg::with_name("comment")
+                                     .long("comment")
+                                     .value_name("COMMENT")
+                                     .multiple(true)
+                                     .number_of_values(1)
+                                     .conflicts_with("binary")
+                                     .help("Adds a comment to the ASCII armor \
+                                            header")))
                     .subcommand(SubCommand::with_name("delete")
                                 .about("Deletes bindings or stores")
                                 .arg(Arg::with_name("the-store")
                                      .long("the-store")
                                      .help("Delete the selected store (change with --store)"))
+                                .arg(Arg::with_name("all-bindings")
+                                     .long("all-bindings")
+                                     .help("Delete all bindings, but keep the store"))
                                 .arg(Arg::with_name("label")
                                      .value_name("LABEL")
-                                     .help("Delete binding with this label")))
+                                     .multiple(true)
+                                     .help("Delete bindings with these labels")))
                     .subcommand(SubCommand::with_name("stats")
                                 .about("Get stats for the given label")
                                 .arg(Arg::with_name("label").value_name("LABEL")
                                      .required(true)
-                                     .help("Label to use")))
+                                     .help("Label to use"))
+                                .arg(Arg::with_name("iso")
+                                     .long("iso")
+                                     .help("Print absolute timestamps instead \
+                                            of relative times")))
                     .subcommand(SubCommand::with_name("log")
                                 .about("Lists the keystore log")
                                 .arg(Arg::with_name("label")
                                      .value_name("LABEL")
-                                     .help("List messages related to this label"))))
+                                     .help("List messages related to this label")))
+                    .subcommand(SubCommand::with_name("compact")
+                                .about("Merges duplicate keys in the common key pool")))
         .subcommand(SubCommand::with_name("list")
                     .about("Lists key stores and known keys")
                     .setting(AppSettings::ArgRequiredElseHelp)
@@ -332,7 +397,18 @@ pub fn build() -> App<'static, 'static> {
                     .subcommand(SubCommand::with_name("keys")
                                 .about("Lists all keys in the common key pool"))
                     .subcommand(SubCommand::with_name("log")
-                                .about("Lists the server log")))
+                                .about("Lists the server log")
+                                .arg(Arg::with_name("since").value_name("DATE")
+                                     .long("since")
+                                     .help("Only list entries logged on or \
+                                            after DATE (YYYY-MM-DD)"))
+                                .arg(Arg::with_name("until").value_name("DATE")
+                                     .long("until")
+                                     .help("Only list entries logged on or \
+                                            before DATE (YYYY-MM-DD)"))
+                                .arg(Arg::with_name("failures-only")
+                                     .long("failures-only")
+                                     .help("Only list failed operations"))))
         .subcommand(
             SubCommand::with_name("key")
                 .about("Manipulates keys")
@@ -414,7 +490,11 @@ pub fn build() -> App<'static, 'static> {
                                 .arg(Arg::with_name("hex")
                                      .long("hex")
                                      .short("x")
-                                     .help("Print a hexdump")))
+                                     .help("Print a hexdump"))
+                                .arg(Arg::with_name("lenient")
+                                     .long("lenient")
+                                     .help("Skip over malformed data \
+                                            instead of giving up")))
                     .subcommand(SubCommand::with_name("split")
                                 .about("Splits a message into OpenPGP packets")
                                 .arg(Arg::with_name("input").value_name("FILE")
@@ -424,5 +504,24 @@ pub fn build() -> App<'static, 'static> {
                                      .short("p")
                                      .help("Sets the prefix to use for output files \
                                             (defaults to the input filename with a dash, \
-                                            or 'output')"))))
+                                            or 'output')")))
+                    .subcommand(SubCommand::with_name("join")
+                                .about("Joins OpenPGP packets split using \
+                                        sq packet split")
+                                .arg(Arg::with_name("input").value_name("FILE")
+                                     .multiple(true)
+                                     .help("Sets the input files to use, \
+                                            in order"))
+                                .arg(Arg::with_name("output").value_name("FILE")
+                                     .long("output")
+                                     .short("o")
+                                     .help("Sets the output file to use"))))
+
+        .subcommand(SubCommand::with_name("completions")
+                    .about("Generates shell completions")
+                    .setting(AppSettings::Hidden)
+                    .arg(Arg::with_name("shell").value_name("SHELL")
+                         .required(true)
+                         .possible_values(&["bash", "zsh", "fish"])
+                         .help("The shell to generate completions for")))
 }
@@ -0,0 +1,17 @@
+extern crate assert_cli;
+use assert_cli::Assert;
+
+#[test]
+fn sq_completions() {
+    for shell in &["bash", "zsh", "fish"] {
+        Assert::cargo_binary("sq")
+            .with_args(&["completions", shell])
+            .stdout().contains("sq")
+            .unwrap();
+    }
+
+    Assert::cargo_binary("sq")
+        .with_args(&["completions", "tcsh"])
+        .fails()
+        .unwrap();
+}
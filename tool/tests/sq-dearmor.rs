@@ -0,0 +1,33 @@
+extern crate assert_cli;
+use assert_cli::Assert;
+
+#[test]
+fn sq_dearmor_ignore_checksum() {
+    let good = "-----BEGIN PGP ARMORED FILE-----
+
+SGVsbG8gd29ybGQh
+=s4Gu
+-----END PGP ARMORED FILE-----
+";
+    let bad = good.replace("=s4Gu", "=s4Gv");
+
+    Assert::cargo_binary("sq")
+        .with_args(&["dearmor"])
+        .stdin(good)
+        .stdout().is("Hello world!")
+        .unwrap();
+
+    // Broken checksums are rejected by default...
+    Assert::cargo_binary("sq")
+        .with_args(&["dearmor"])
+        .stdin(bad.clone())
+        .fails()
+        .unwrap();
+
+    // ... but ignored on request.
+    Assert::cargo_binary("sq")
+        .with_args(&["dearmor", "--ignore-checksum"])
+        .stdin(bad)
+        .stdout().is("Hello world!")
+        .unwrap();
+}
@@ -0,0 +1,161 @@
+use std::fs::{self, File};
+use std::io::Write;
+
+extern crate assert_cli;
+use assert_cli::Assert;
+extern crate tempfile;
+use tempfile::TempDir;
+
+extern crate sequoia_openpgp as openpgp;
+use openpgp::constants::DataFormat;
+use openpgp::serialize::stream::{Message, Encryptor, EncryptionMode, LiteralWriter};
+
+#[test]
+fn sq_decrypt_password_file() {
+    let tmp_dir = TempDir::new().unwrap();
+    let ciphertext = tmp_dir.path().join("ciphertext");
+    let plaintext = tmp_dir.path().join("plaintext");
+    let good = tmp_dir.path().join("good");
+    let bad = tmp_dir.path().join("bad");
+    fs::write(&good, "streng geheim\n").unwrap();
+    fs::write(&bad, "nicht geheim\n").unwrap();
+
+    // Encrypt a message using a password.
+    {
+        let sink = File::create(&ciphertext).unwrap();
+        let message = Message::new(sink);
+        let encryptor = Encryptor::new(message, &[&"streng geheim".into()],
+                                       &[], EncryptionMode::ForTransport,
+                                       None).unwrap();
+        let mut literal = LiteralWriter::new(encryptor, DataFormat::Binary,
+                                             None, None).unwrap();
+        literal.write_all(b"Hello world.").unwrap();
+        literal.finalize().unwrap();
+    }
+
+    // Wrong password.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "decrypt",
+              "--password-file",
+              &bad.to_string_lossy(),
+              "--output",
+              &plaintext.to_string_lossy(),
+              &ciphertext.to_string_lossy()])
+        .fails()
+        .unwrap();
+
+    // Any of the given passwords may be the right one.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "--force",
+              "decrypt",
+              "--password-file",
+              &bad.to_string_lossy(),
+              "--password-file",
+              &good.to_string_lossy(),
+              "--output",
+              &plaintext.to_string_lossy(),
+              &ciphertext.to_string_lossy()])
+        .unwrap();
+
+    assert_eq!(fs::read(&plaintext).unwrap(), b"Hello world.");
+}
+
+#[test]
+fn sq_decrypt_dump_recipients() {
+    let tmp_dir = TempDir::new().unwrap();
+
+    // Without the secret key, decryption fails, but we learn whom the
+    // message is encrypted to.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "decrypt",
+              "--dump",
+              "../openpgp/tests/data/messages/encrypted-to-testy.gpg"])
+        .fails()
+        .and().stderr()
+        .contains("Encrypted to key IDs: 49E2 1181 66C9 2632, password: no.")
+        .unwrap();
+}
+
+#[test]
+fn sq_decrypt_session_key() {
+    use std::sync::{Arc, Mutex};
+
+    let tmp_dir = TempDir::new().unwrap();
+    let ciphertext = tmp_dir.path().join("ciphertext");
+    let plaintext = tmp_dir.path().join("plaintext");
+    let password = tmp_dir.path().join("password");
+    fs::write(&password, "streng geheim\n").unwrap();
+
+    {
+        let sink = File::create(&ciphertext).unwrap();
+        let message = Message::new(sink);
+        let encryptor = Encryptor::new(message, &[&"streng geheim".into()],
+                                       &[], EncryptionMode::ForTransport,
+                                       None).unwrap();
+        let mut literal = LiteralWriter::new(encryptor, DataFormat::Binary,
+                                             None, None).unwrap();
+        literal.write_all(b"Hello world.").unwrap();
+        literal.finalize().unwrap();
+    }
+
+    // Recover the session key using the password.
+    let session_key = Arc::new(Mutex::new(String::new()));
+    let sk = session_key.clone();
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "decrypt",
+              "--password-file",
+              &password.to_string_lossy(),
+              "--dump-session-key",
+              &ciphertext.to_string_lossy()])
+        .stdout().is("Hello world.")
+        .stderr().satisfies(move |stderr| {
+            stderr.lines()
+                .filter_map(|l| l.trim().splitn(2, "Session key: ").nth(1))
+                .next()
+                .map(|k| *sk.lock().unwrap() = k.into())
+                .is_some()
+        }, "no session key printed")
+        .unwrap();
+    let session_key = session_key.lock().unwrap().clone();
+    assert!(session_key.starts_with("9:"));
+
+    // Now decrypt using only the session key.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "decrypt",
+              "--session-key", &session_key,
+              "--output",
+              &plaintext.to_string_lossy(),
+              &ciphertext.to_string_lossy()])
+        .unwrap();
+    assert_eq!(fs::read(&plaintext).unwrap(), b"Hello world.");
+
+    // A wrong session key does not decrypt the message.
+    let mut wrong = session_key.clone();
+    let last = if wrong.pop() == Some('0') { '1' } else { '0' };
+    wrong.push(last);
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "decrypt",
+              "--session-key", &wrong,
+              &ciphertext.to_string_lossy()])
+        .fails()
+        .and().stderr().contains("Failed to decrypt using the given session key")
+        .unwrap();
+}
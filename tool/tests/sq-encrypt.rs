@@ -0,0 +1,208 @@
+use std::fs::File;
+
+extern crate assert_cli;
+use assert_cli::Assert;
+extern crate tempfile;
+use tempfile::TempDir;
+
+extern crate sequoia_openpgp as openpgp;
+use openpgp::tpk::{TPKBuilder, CipherSuite};
+use openpgp::serialize::Serialize;
+
+#[test]
+fn sq_encrypt_refuses_revoked_key() {
+    let tmp_dir = TempDir::new().unwrap();
+    let valid = tmp_dir.path().join("valid.pgp");
+    let revoked = tmp_dir.path().join("revoked.pgp");
+
+    let (tpk, revocation) = TPKBuilder::new()
+        .set_cipher_suite(CipherSuite::Cv25519)
+        .add_userid("Alice")
+        .add_encryption_subkey()
+        .generate().unwrap();
+    tpk.serialize(&mut File::create(&valid).unwrap()).unwrap();
+    tpk.merge_packets(vec![revocation.into()]).unwrap()
+        .serialize(&mut File::create(&revoked).unwrap()).unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "encrypt",
+              "--recipient-key-file",
+              &valid.to_string_lossy()])
+        .stdin("Hello world.")
+        .stdout().contains("-----BEGIN PGP MESSAGE-----")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "encrypt",
+              "--recipient-key-file",
+              &revoked.to_string_lossy()])
+        .stdin("Hello world.")
+        .fails()
+        .and().stderr().contains("is revoked")
+        .unwrap();
+
+    // --force overrides the check.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "--force",
+              "encrypt",
+              "--recipient-key-file",
+              &revoked.to_string_lossy()])
+        .stdin("Hello world.")
+        .stdout().contains("-----BEGIN PGP MESSAGE-----")
+        .and().stderr().contains("Warning")
+        .unwrap();
+}
+
+#[test]
+fn sq_encrypt_compression() {
+    let tmp_dir = TempDir::new().unwrap();
+    let cert = tmp_dir.path().join("cert.pgp");
+    let key = tmp_dir.path().join("key.pgp");
+    let ciphertext = tmp_dir.path().join("ciphertext.pgp");
+
+    let (tpk, _) = TPKBuilder::new()
+        .set_cipher_suite(CipherSuite::Cv25519)
+        .add_userid("Alice")
+        .add_encryption_subkey()
+        .generate().unwrap();
+    tpk.serialize(&mut File::create(&cert).unwrap()).unwrap();
+    tpk.as_tsk().serialize(&mut File::create(&key).unwrap()).unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "encrypt",
+              "--compression", "zip",
+              "--recipient-key-file",
+              &cert.to_string_lossy(),
+              "--output",
+              &ciphertext.to_string_lossy()])
+        .stdin("Hello world.")
+        .unwrap();
+
+    // The plaintext is wrapped in a compressed data packet.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "decrypt",
+              "--dump",
+              "--secret-key-file",
+              &key.to_string_lossy(),
+              &ciphertext.to_string_lossy()])
+        .stdout().is("Hello world.")
+        .and().stderr().contains("Compressed Data Packet")
+        .unwrap();
+}
+
+#[test]
+fn sq_encrypt_decrypt_no_store() {
+    let tmp_dir = TempDir::new().unwrap();
+    let cert = tmp_dir.path().join("cert.pgp");
+    let key = tmp_dir.path().join("key.pgp");
+    let ciphertext = tmp_dir.path().join("ciphertext.pgp");
+
+    let (tpk, _) = TPKBuilder::new()
+        .set_cipher_suite(CipherSuite::Cv25519)
+        .add_userid("Alice")
+        .add_encryption_subkey()
+        .generate().unwrap();
+    tpk.serialize(&mut File::create(&cert).unwrap()).unwrap();
+    tpk.as_tsk().serialize(&mut File::create(&key).unwrap()).unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "--no-store",
+              "encrypt",
+              "--recipient-key-file",
+              &cert.to_string_lossy(),
+              "--output",
+              &ciphertext.to_string_lossy()])
+        .stdin("Hello world.")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "--no-store",
+              "decrypt",
+              "--secret-key-file",
+              &key.to_string_lossy(),
+              &ciphertext.to_string_lossy()])
+        .stdout().is("Hello world.")
+        .unwrap();
+
+    // Labels cannot be resolved without the store.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "--no-store",
+              "encrypt",
+              "--recipient", "Alice"])
+        .stdin("Hello world.")
+        .fails()
+        .and().stderr().contains("not using the store")
+        .unwrap();
+
+    // The store was never touched.
+    assert!(! tmp_dir.path().join("public-key-store.sqlite").exists());
+}
+
+#[test]
+fn sq_encrypt_hidden_recipients() {
+    let tmp_dir = TempDir::new().unwrap();
+    let cert = tmp_dir.path().join("cert.pgp");
+    let key = tmp_dir.path().join("key.pgp");
+    let ciphertext = tmp_dir.path().join("ciphertext.pgp");
+
+    let (tpk, _) = TPKBuilder::new()
+        .set_cipher_suite(CipherSuite::Cv25519)
+        .add_userid("Alice")
+        .add_encryption_subkey()
+        .generate().unwrap();
+    tpk.serialize(&mut File::create(&cert).unwrap()).unwrap();
+    tpk.as_tsk().serialize(&mut File::create(&key).unwrap()).unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "encrypt",
+              "--hidden-recipients",
+              "--recipient-key-file",
+              &cert.to_string_lossy(),
+              "--output",
+              &ciphertext.to_string_lossy()])
+        .stdin("Hello world.")
+        .unwrap();
+
+    // The recipient is not revealed, but we find the right key by
+    // trial.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "decrypt",
+              "--dump",
+              "--secret-key-file",
+              &key.to_string_lossy(),
+              &ciphertext.to_string_lossy()])
+        .stdout().is("Hello world.")
+        .and().stderr().contains(
+            "Encrypted to key IDs: 0000 0000 0000 0000, password: no.")
+        .unwrap();
+}
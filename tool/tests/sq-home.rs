@@ -0,0 +1,53 @@
+extern crate assert_cli;
+use assert_cli::Assert;
+extern crate tempfile;
+use tempfile::TempDir;
+
+fn p(filename: &str) -> String {
+    format!("../openpgp/tests/data/{}", filename)
+}
+
+#[test]
+fn sq_home_shared_state() {
+    let tmp_dir = TempDir::new().unwrap();
+    let other_dir = TempDir::new().unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "--store", "org.example/sandbox",
+              "store", "import", "Testy",
+              &p("keys/testy.pgp")])
+        .unwrap();
+
+    // A second invocation using the same home and store sees the
+    // binding.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "--store", "org.example/sandbox",
+              "store", "export", "Testy"])
+        .stdout().contains("Comment: Testy\n")
+        .unwrap();
+
+    // But not using a different store in the same home.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "store", "export", "Testy"])
+        .fails()
+        .unwrap();
+
+    // Nor using the same store in a different home.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &other_dir.path().to_string_lossy(),
+              "--store", "org.example/sandbox",
+              "store", "export", "Testy"])
+        .fails()
+        .unwrap();
+}
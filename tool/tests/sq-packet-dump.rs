@@ -0,0 +1,55 @@
+extern crate assert_cli;
+use assert_cli::Assert;
+
+fn p(filename: &str) -> String {
+    format!("../openpgp/tests/data/{}", filename)
+}
+
+#[test]
+fn sq_packet_dump_shows_nesting() {
+    // Packets inside containers are drawn as children of the
+    // container.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["packet", "dump",
+              &p("messages/compressed-data-algo-1.gpg")])
+        .stdout().contains("Compressed Data Packet")
+        .stdout().contains("└── New CTB, partial length, \
+                            4096 bytes in first chunk: Literal Data Packet")
+        .unwrap();
+}
+
+#[test]
+fn sq_packet_dump_shows_offsets() {
+    // The literal data packet uses partial body encoding, its length
+    // includes the chunk headers.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["packet", "dump",
+              &p("messages/signed-2-partial-body.gpg")])
+        .stdout().contains("Offset: 0x00000000, total length: 15 bytes")
+        .stdout().contains("Offset: 0x0000000f, total length: 5168 bytes")
+        .stdout().contains("Offset: 0x0000143f, total length: 310 bytes")
+        .unwrap();
+
+    // Hexdumps start at the packet's offset.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["packet", "dump", "--hex",
+              &p("messages/signed-2-partial-body.gpg")])
+        .stdout().contains("00001440  01 33")
+        .unwrap();
+}
+
+#[test]
+fn sq_packet_dump_lenient() {
+    // Some junk followed by a marker packet.
+    let corrupted = b"\x00junk\xa8\x03PGP".to_vec();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["packet", "dump", "--lenient"])
+        .stdin(corrupted)
+        .stdout().contains("Skipped 5 bytes of junk")
+        .stdout().contains("Marker Packet")
+        .unwrap();
+}
@@ -0,0 +1,49 @@
+use std::fs;
+
+extern crate assert_cli;
+use assert_cli::Assert;
+extern crate tempfile;
+use tempfile::TempDir;
+
+fn p(filename: &str) -> String {
+    format!("../openpgp/tests/data/{}", filename)
+}
+
+#[test]
+fn sq_packet_split_join() {
+    let tmp_dir = TempDir::new().unwrap();
+    let prefix = tmp_dir.path().join("msg-");
+    let joined = tmp_dir.path().join("joined");
+    let message = p("messages/encrypted-to-testy.gpg");
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["packet", "split",
+              "--prefix", &prefix.to_string_lossy(),
+              &message])
+        .unwrap();
+
+    // Joining the top-level packets recreates the message.
+    let top_level = ["msg-0--PKESK", "msg-1--SEIP"].iter()
+        .map(|f| tmp_dir.path().join(f).to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["packet", "join",
+              "--output", &joined.to_string_lossy(),
+              &top_level[0], &top_level[1]])
+        .unwrap();
+
+    assert_eq!(fs::read(&joined).unwrap(), fs::read(&message).unwrap());
+}
+
+#[test]
+fn sq_packet_join_garbage() {
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["packet", "join",
+              &p("messages/a-cypherpunks-manifesto.txt")])
+        .fails()
+        .and().stderr().contains("No OpenPGP packets found")
+        .unwrap();
+}
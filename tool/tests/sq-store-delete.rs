@@ -0,0 +1,62 @@
+extern crate assert_cli;
+use assert_cli::Assert;
+extern crate tempfile;
+use tempfile::TempDir;
+
+fn p(filename: &str) -> String {
+    format!("../openpgp/tests/data/{}", filename)
+}
+
+#[test]
+fn sq_store_delete() {
+    let tmp_dir = TempDir::new().unwrap();
+    let home = tmp_dir.path().to_string_lossy();
+
+    for &(label, key) in [("Testy", "keys/testy.pgp"),
+                          ("Neal", "keys/neal.pgp"),
+                          ("Emmelie", "keys/emmelie-dorothea-dina-samantha-awina-ed25519.pgp")]
+        .iter()
+    {
+        Assert::cargo_binary("sq")
+            .with_args(&["--home", &home, "store", "import", label, &p(key)])
+            .unwrap();
+    }
+
+    // Missing labels are reported, the others are deleted.
+    Assert::cargo_binary("sq")
+        .with_args(&["--home", &home, "store", "delete", "Testy", "Nobody",
+                     "Neal"])
+        .fails()
+        .and().stderr().contains("Failed to delete \"Nobody\"")
+        .unwrap();
+    for label in ["Testy", "Neal"].iter() {
+        Assert::cargo_binary("sq")
+            .with_args(&["--home", &home, "store", "stats", label])
+            .fails()
+            .unwrap();
+    }
+    Assert::cargo_binary("sq")
+        .with_args(&["--home", &home, "store", "stats", "Emmelie"])
+        .unwrap();
+
+    // Delete the rest.
+    Assert::cargo_binary("sq")
+        .with_args(&["--home", &home, "store", "delete", "--all-bindings"])
+        .unwrap();
+    Assert::cargo_binary("sq")
+        .with_args(&["--home", &home, "store", "stats", "Emmelie"])
+        .fails()
+        .unwrap();
+}
+
+#[test]
+fn sq_store_delete_ambiguous() {
+    let tmp_dir = TempDir::new().unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(&["--home", &tmp_dir.path().to_string_lossy(),
+                     "store", "delete", "--all-bindings", "Testy"])
+        .fails()
+        .and().stderr().contains("Please specify either labels")
+        .unwrap();
+}
@@ -0,0 +1,33 @@
+extern crate assert_cli;
+use assert_cli::Assert;
+extern crate tempfile;
+use tempfile::TempDir;
+
+fn p(filename: &str) -> String {
+    format!("../openpgp/tests/data/{}", filename)
+}
+
+#[test]
+fn sq_store_export_comments() {
+    let tmp_dir = TempDir::new().unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "store", "import", "Testy",
+              &p("keys/testy.pgp")])
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "store", "export", "Testy",
+              "--comment", "Handed over in person"])
+        .stdout().contains(
+            "Comment: 3E88 77C8 7727 4692 9751  89F5 D03F 6F86 5226 FE8B\n\
+             Comment: Testy\n\
+             Comment: Handed over in person\n")
+        .unwrap();
+}
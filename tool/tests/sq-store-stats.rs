@@ -0,0 +1,42 @@
+extern crate assert_cli;
+use assert_cli::Assert;
+extern crate tempfile;
+use tempfile::TempDir;
+
+fn p(filename: &str) -> String {
+    format!("../openpgp/tests/data/{}", filename)
+}
+
+#[test]
+fn sq_store_stats() {
+    let tmp_dir = TempDir::new().unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "store", "import", "Testy",
+              &p("keys/testy.pgp")])
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "store", "stats", "Testy"])
+        .stdout().contains(
+            "Binding \"Testy\"\n  \
+             Created just now, never updated.\n  \
+             Encryption: never used.\n  \
+             Verification: never used.\n")
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "store", "stats", "--iso", "Testy"])
+        .stdout().contains("  Created: ")
+        .stdout().contains("  Encrypted 0 messages using this key\n")
+        .unwrap();
+}